@@ -25,7 +25,23 @@ pub mod update {
     // because you cannot overwrite a running .exe).
     pub const STAGED_SUFFIX: &str = ".new";
 
+    // Binary download timeout. Override: updateRequest `downloadTimeoutSecs` / env TM_UPDATE_DOWNLOAD_TIMEOUT_SECS.
     pub const DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+
+    // Upper bound for any user-supplied download timeout override.
+    pub const DOWNLOAD_TIMEOUT_MAX_SECS: u64 = 3600;
+
+    // Native-messaging manifest written for user-local installs: host name (also the
+    // file stem) and the only extension allowed to launch the helper.
+    pub const MANIFEST_NAME: &str = "tabmail_fts";
+    pub const MANIFEST_EXTENSION_ID: &str = "thunderbird@tabmail.ai";
+
+    // Windows: the apply-update helper copy runs from this directory under the OS temp dir.
+    pub const APPLY_HELPER_TMP_DIR: &str = "tabmail-native-fts-update";
+
+    // purgeStaleFiles: leftovers younger than this are kept (an update or download may
+    // still be using them).
+    pub const STALE_FILE_MIN_AGE_SECS: u64 = 3600;
 }
 
 pub mod sqlite {
@@ -33,14 +49,159 @@ pub mod sqlite {
     pub const PRAGMA_CACHE_SIZE_KIB_NEG: i64 = -64000;
     pub const PRAGMA_MMAP_SIZE_BYTES: i64 = 268_435_456;
     pub const PRAGMA_WAL_AUTOCHECKPOINT_PAGES: i64 = 200_000;
+    // The autocheckpoint above is huge, so during bulk indexing the writer also runs a
+    // PASSIVE checkpoint every this many committed rows to bound WAL growth (and peak disk use).
+    // Overridable at init (`walCheckpointRows`); 0 disables it.
+    pub const WAL_CHECKPOINT_EVERY_ROWS: u64 = 20_000;
+    // FTS index churn (rows inserted + removed) after which the writer optimizes
+    // (or recommends optimizing, per init `autoOptimize`).
+    pub const AUTO_OPTIMIZE_EVERY_ROWS: u64 = 50_000;
+    // Idle maintenance: once no request has arrived for this long (and something was written
+    // since the last run), the writer checkpoints both WALs and optimizes the email FTS index.
+    // Overridable at init (`idleMaintenanceSecs`, 0 disables; `idleOptimize: false` skips optimize).
+    pub const IDLE_MAINTENANCE_SECS: u64 = 300;
 
     pub const FTS_PREFIXES: &str = "2 3 4";
     pub const FTS_TOKENIZE: &str = "porter unicode61 remove_diacritics 2 tokenchars '-_.@'";
 
     pub const SEARCH_DEFAULT_LIMIT: i64 = 50;
+    // searchMulti: queries accepted per call.
+    pub const SEARCH_MULTI_MAX_QUERIES: usize = 10;
+    // searchStream: results per `searchResult` notification (default / cap for `chunkSize`).
+    pub const SEARCH_STREAM_CHUNK_SIZE: i64 = 200;
+    pub const SEARCH_STREAM_MAX_CHUNK_SIZE: i64 = 1000;
+    // searchStream: streams running at once (each holds a thread and a read connection);
+    // further requests are refused with a `tooManyStreams` error.
+    pub const SEARCH_STREAM_MAX_ACTIVE: usize = 4;
+    // indexBatch `validateEncoding`: a subject/body is suspect when at least this many of its
+    // chars, and this share of them, are U+FFFD (an upstream charset decode gone wrong).
+    pub const SUSPECT_ENCODING_MIN_REPLACEMENTS: usize = 3;
+    pub const SUSPECT_ENCODING_RATIO: f64 = 0.02;
+    // Synonym expansion breadth cap: when the OR groups AND-ed together in one query segment
+    // multiply out past this many combinations, only the most specific token (smallest group,
+    // then longest) stays expanded; the rest are searched literally.
+    pub const SYNONYM_MAX_EXPANSION_BREADTH: usize = 64;
+    // Bare query tokens of at least this many chars get an implicit trailing `*`.
+    // Lower = more recall ("plan" also finds "planning"), but more surprising prefix hits
+    // ("plan" -> "planetarium"); higher = more precise, but inflected forms need an explicit `*`.
+    // Overridable at init (`autoWildcardMinLen`); per query, `autoWildcard: false` disables it.
+    pub const AUTO_WILDCARD_MIN_LEN: usize = 4;
+    // Bare query tokens dropped before building the FTS match ("the meeting" -> "meeting*"),
+    // since the tokenizer indexes them and would AND them in. Field-scoped, quoted and
+    // wildcarded tokens are kept, as is a query made only of stop words.
+    // Replaceable at init (`stopWords: [...]`, `[]` or `false` disables).
+    pub const STOP_WORDS: &[&str] = &[
+        "a", "an", "and", "are", "as", "at", "be", "by", "for", "in", "is", "of", "on", "or", "the", "to",
+        "was", "with",
+    ];
     pub const SEARCH_SNIPPET_TOKENS: i64 = 16;
+    // Max length (chars) of custom snippetOpen / snippetClose / snippetEllipsis markers.
+    pub const SNIPPET_MARKER_MAX_CHARS: usize = 16;
+    // Upper bound for the `snippetCount` search param (body match windows per result).
+    pub const SEARCH_SNIPPET_MAX_COUNT: usize = 5;
     pub const SEARCH_DEBUG_SAMPLE_LIMIT: i64 = 10;
+    // Upper bound for the debugSample `limit` param.
+    pub const SEARCH_DEBUG_SAMPLE_MAX_LIMIT: i64 = 1000;
     pub const QUERY_BY_DATE_RANGE_DEFAULT_LIMIT: i64 = 1000;
+    // Upper bound for the subjectSearch `limit` param.
+    pub const SUBJECT_SEARCH_MAX_LIMIT: i64 = 1000;
+    // memoryRead: entries returned from the ±tolerance window (default / upper bound for `limit`).
+    pub const MEMORY_READ_DEFAULT_LIMIT: i64 = 50;
+    pub const MEMORY_READ_MAX_LIMIT: i64 = 500;
+    // memorySessionDigest: upper bound for `centralTurns` (turns ranked by embedding centrality).
+    pub const MEMORY_DIGEST_MAX_CENTRAL_TURNS: usize = 20;
+    // stats_history: rows kept (oldest pruned on insert) and default statsHistory limit.
+    pub const STATS_HISTORY_MAX_ROWS: i64 = 1000;
+    pub const STATS_HISTORY_DEFAULT_LIMIT: i64 = 100;
+}
+
+pub mod lang {
+    // Only the first N chars of a body are fed to language detection (cost is linear in length).
+    pub const DETECT_MAX_CHARS: usize = 2000;
+}
+
+pub mod suggest {
+    // searchSuggestQueries looks only at the subjects of this many most recent messages.
+    pub const SAMPLE_MESSAGES: i64 = 500;
+    // A term must appear in at least this many sampled subjects to be suggested.
+    pub const MIN_DOC_FREQ: usize = 2;
+    // Subject-line filler never offered as a suggestion, on top of the search stop words
+    // (sqlite::STOP_WORDS or the init override).
+    pub const FILLER_WORDS: &[&str] = &[
+        "about", "after", "all", "but", "can", "from", "fwd", "fw", "has", "have", "how", "its", "new",
+        "not", "now", "our", "out", "please", "re", "this", "that", "update", "what", "when", "will",
+        "you", "your",
+    ];
+    pub const DEFAULT_COUNT: usize = 5;
+    pub const MAX_COUNT: usize = 20;
+}
+
+pub mod protocol {
+    // Response envelope version used when init carries no `protocolVersion` (flat errors, `rank`).
+    pub const DEFAULT_VERSION: u32 = 1;
+    // Newest envelope this host can emit (structured errors, `score`).
+    pub const MAX_VERSION: u32 = 2;
+}
+
+pub mod threads {
+    // Linux nice value for the writer thread during rebuilds (init `rebuildPriority: "low"`).
+    pub const BACKGROUND_NICE: i32 = 10;
+}
+
+pub mod dispatch {
+    // Upper bound on how long a `freshness: "latest"` read waits for earlier writes.
+    pub const FRESHNESS_WAIT_MAX_MS: u64 = 5000;
+    // Consecutive reads on which the reader's view of the email DB disagrees with the
+    // writer's before the reader reopens its connection (one-off races are tolerated).
+    pub const READER_DIVERGENCE_REOPEN_CHECKS: u64 = 3;
+    // rebuildEmbeddingsBatch / reembedAllBatch run as sub-batches of this many rows, each its
+    // own transaction; between them the batch ends early if another write is queued behind it.
+    pub const REBUILD_YIELD_CHUNK_ROWS: i64 = 50;
+    // Default time budgets (ms) for reader / writer requests. Requests over budget are logged
+    // and counted; with init `cancelOnTimeout` their SQL is interrupted and they fail with a
+    // `timeout` error. Init `methodTimeoutsMs` overrides entries (0 removes one); methods not
+    // listed have no budget.
+    pub const METHOD_TIMEOUTS_MS: &[(&str, u64)] = &[
+        ("search", 10_000),
+        ("searchMulti", 20_000),
+        ("subjectSearch", 10_000),
+        ("memorySearch", 10_000),
+        ("getMessageByMsgId", 5_000),
+        ("queryByDateRange", 10_000),
+        ("filterNewMessages", 10_000),
+        ("indexBatch", 60_000),
+        ("removeBatch", 60_000),
+        ("memoryIndexBatch", 60_000),
+        ("rebuildEmbeddingsBatch", 600_000),
+        ("reembedAllBatch", 600_000),
+    ];
+}
+
+pub mod result_cache {
+    // Search results the reader keeps for repeated identical queries (init `resultCacheSize`,
+    // 0 disables the cache).
+    pub const DEFAULT_ENTRIES: usize = 64;
+    // Cached results older than this are recomputed even without writes, so relative date
+    // filters and recency boosts don't go stale.
+    pub const TTL_SECS: u64 = 300;
+}
+
+pub mod disk {
+    // Headroom required on top of an operation's own estimate (SQLite temp files, logs).
+    pub const FREE_SPACE_MARGIN_BYTES: u64 = 16 * 1024 * 1024;
+}
+
+pub mod dump {
+    // Header `format` tag identifying an exportDump NDJSON file.
+    pub const FORMAT: &str = "tabmail-fts-dump";
+    // Header `format` tag of an exportEmbeddings file (NDJSON or binary body).
+    pub const EMBEDDINGS_FORMAT: &str = "tabmail-fts-embeddings";
+    // importDump: records indexed per batch (the whole import is still one transaction).
+    pub const IMPORT_BATCH_ROWS: usize = 500;
+    // Free-space estimate for exports: stored text plus this much per record (keys, metadata),
+    // and this many bytes per embedding value written as JSON text.
+    pub const RECORD_OVERHEAD_BYTES: u64 = 256;
+    pub const NDJSON_BYTES_PER_FLOAT: u64 = 12;
 }
 
 pub mod embedding {
@@ -51,6 +212,35 @@ pub mod embedding {
     // We pre-truncate to control what gets embedded.
     pub const MAX_TOKENS: usize = 256;
 
+    // Token cap for memory entries and memory queries. Chat turns are short, and a lower cap
+    // keeps long pasted turns from costing a full-length embedding.
+    pub const MEMORY_MAX_TOKENS: usize = 128;
+
+    // Text prepended to queries / stored documents before embedding (init `queryPrefix`,
+    // `documentPrefix`), for models trained with asymmetric "query: " / "passage: " inputs.
+    // all-MiniLM-L6-v2 was not, so both are empty by default.
+    pub const QUERY_PREFIX: &str = "";
+    pub const DOCUMENT_PREFIX: &str = "";
+
+    // Embeddings with a smaller L2 norm (the zero vector returned for empty prepared text)
+    // are not stored in messages_vec; the message stays FTS-only.
+    pub const MIN_EMBEDDING_NORM: f32 = 1e-6;
+
+    // chunkLongBodies (init): bodies longer than one chunk are split into overlapping word
+    // windows, each embedded with the headers, and the vectors averaged. Cost grows with the
+    // chunk count, hence the cap (words past the last chunk are still dropped).
+    pub const CHUNK_WORDS: usize = 150;
+    pub const CHUNK_OVERLAP_WORDS: usize = 30;
+    pub const MAX_CHUNKS: usize = 8;
+
+    // embedCc / embedBcc (init): words of cc + bcc added to the embedding input. The body
+    // gives up as many words as the recipients use, so the input still fits MAX_TOKENS.
+    pub const RECIPIENTS_MAX_WORDS: usize = 40;
+
+    // preloadEmbedCache (init): most-recent embed_cache rows held in memory for the reader
+    // (~1.5 KB each at 384 dims). On-the-fly embeddings are added until the map is full.
+    pub const PRELOAD_EMBED_CACHE_MAX_ENTRIES: usize = 10_000;
+
     // Model download URL base (lazy download on first use).
     // Hosted on CF R2 bucket (tabmail-cdn) at cdn.tabmail.ai.
     pub const MODEL_CDN_BASE: &str = "https://cdn.tabmail.ai/releases/models/all-MiniLM-L6-v2";
@@ -63,8 +253,18 @@ pub mod embedding {
     pub const CONFIG_JSON_SHA256: &str =
         "953f9c0d463486b10a6871cc2fd59f223b2c70184f49815e7efbcab5d8908b41";
 
+    // Per-file model download timeout (~87 MB weights).
+    // Override: init `modelDownloadTimeoutSecs` / env TM_MODEL_DOWNLOAD_TIMEOUT_SECS.
+    pub const MODEL_DOWNLOAD_TIMEOUT_SECS: u64 = 90;
+    // Disk space needed for a full model download (weights + tokenizer + config, rounded up).
+    pub const MODEL_DOWNLOAD_REQUIRED_BYTES: u64 = 100 * 1024 * 1024;
+
     // Local model storage directory (relative to home)
     pub const MODEL_DIR_REL: &str = ".tabmail/models/all-MiniLM-L6-v2";
+
+    // benchmarkEmbedding: passes over the fixed sample set (default / upper bound).
+    pub const BENCHMARK_DEFAULT_ITERATIONS: u64 = 5;
+    pub const BENCHMARK_MAX_ITERATIONS: u64 = 100;
 }
 
 pub mod hybrid {
@@ -77,10 +277,33 @@ pub mod hybrid {
     pub const MEMORY_TEXT_WEIGHT: f64 = 0.3;
 
     // Fetch N× candidates from each engine, merge to final limit.
+    // Per-engine overrides: `ftsCandidateLimit` / `vecCandidateLimit` search params.
     pub const CANDIDATE_MULTIPLIER: i64 = 4;
+    // Upper bound for either override (sqlite-vec rejects KNN k above 4096).
+    pub const CANDIDATE_LIMIT_MAX: i64 = 4096;
+    // vec0 KNN can't filter by date, so a `from`/`to`-scoped search loses every neighbour
+    // outside the range. Without an explicit `vecCandidateLimit`, k is widened by this
+    // factor (still capped at CANDIDATE_LIMIT_MAX) so enough in-range neighbours survive.
+    pub const DATE_FILTER_VEC_K_MULTIPLIER: i64 = 8;
+    // Over-fetch: when filters leave fewer than `limit` merged results and a candidate pool
+    // came back full, the hybrid pass is retried with that pool grown by this factor.
+    pub const OVERFETCH_GROWTH: i64 = 4;
+    // Default ceiling for over-fetch retries; `maxCandidateLimit` overrides it (still capped
+    // at CANDIDATE_LIMIT_MAX, and 0 disables retries).
+    pub const OVERFETCH_MAX_CANDIDATES: i64 = 2048;
+
+    // rerankOnFallback: max FTS candidates embedded on the fly when messages_vec is empty.
+    pub const RERANK_FALLBACK_MAX_CANDIDATES: usize = 50;
 
     // Minimum combined score to return (filters noise).
     pub const MIN_SCORE: f64 = 0.1;
+
+    // recencyWeight (search): merged scores gain recencyWeight * 0.5^(age / half-life), so
+    // newer mail edges out older mail of equal relevance. Off (0) by default; values are
+    // capped at RECENCY_WEIGHT_MAX so recency never swamps relevance. `recencyHalfLifeDays`
+    // overrides the half-life.
+    pub const RECENCY_WEIGHT_MAX: f64 = 1.0;
+    pub const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
 }
 
 
@@ -0,0 +1,68 @@
+// disk.rs — Free disk space checks before large writes.
+//
+// Model downloads, VACUUM INTO snapshots, and restores can die halfway through on a full
+// disk, leaving partial files behind. Checking up front turns that into a clear error.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::config;
+
+/// Not enough free space in `dir` for an operation needing `required` bytes.
+/// Handlers surface this as `{ "error": { "code": "insufficientDisk", ... } }`.
+#[derive(Debug)]
+pub struct InsufficientDisk {
+    pub dir: PathBuf,
+    pub available: u64,
+    pub required: u64,
+}
+
+impl fmt::Display for InsufficientDisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Insufficient disk space in {}: {} bytes available, {} bytes required",
+            self.dir.display(),
+            self.available,
+            self.required
+        )
+    }
+}
+
+impl std::error::Error for InsufficientDisk {}
+
+/// Fail with `InsufficientDisk` unless `dir` has room for `needed_bytes` plus a safety margin.
+/// `dir` must exist (pass the directory the file will be written into).
+pub fn check_free_space(dir: &Path, needed_bytes: u64) -> anyhow::Result<()> {
+    let available = fs2::available_space(dir)
+        .with_context(|| format!("failed to query free space for {}", dir.display()))?;
+    let required = needed_bytes.saturating_add(config::disk::FREE_SPACE_MARGIN_BYTES);
+    if available < required {
+        log::warn!(
+            "Insufficient disk space in {}: {} available, {} required",
+            dir.display(),
+            available,
+            required
+        );
+        return Err(InsufficientDisk { dir: dir.to_path_buf(), available, required }.into());
+    }
+    log::debug!("Free space OK in {}: {} available, {} required", dir.display(), available, required);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_free_space() {
+        let dir = std::env::temp_dir();
+        assert!(check_free_space(&dir, 0).is_ok());
+
+        let err = check_free_space(&dir, u64::MAX / 2).unwrap_err();
+        let d = err.downcast_ref::<InsufficientDisk>().expect("typed error");
+        assert!(d.available < d.required);
+    }
+}
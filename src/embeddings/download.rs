@@ -6,6 +6,8 @@
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::{bail, Context};
 use sha2::{Digest, Sha256};
@@ -18,60 +20,118 @@ pub fn model_dir() -> anyhow::Result<PathBuf> {
     Ok(home.join(config::embedding::MODEL_DIR_REL))
 }
 
+/// Held for the duration of a model download so two downloads never write the same files.
+static MODEL_DOWNLOAD_GUARD: Mutex<()> = Mutex::new(());
+
+/// Model files required by the engine, with their expected SHA256 hashes.
+const MODEL_FILES: [(&str, &str); 3] = [
+    ("model.safetensors", config::embedding::MODEL_SAFETENSORS_SHA256),
+    ("tokenizer.json", config::embedding::TOKENIZER_JSON_SHA256),
+    ("config.json", config::embedding::CONFIG_JSON_SHA256),
+];
+
 /// Check if all required model files exist locally.
 pub fn model_files_exist() -> anyhow::Result<bool> {
     let dir = model_dir()?;
-    Ok(dir.join("model.safetensors").exists()
-        && dir.join("tokenizer.json").exists()
-        && dir.join("config.json").exists())
+    Ok(MODEL_FILES.iter().all(|(name, _)| dir.join(name).exists()))
+}
+
+/// Verify cached model files against their expected SHA256 hashes.
+/// Files that don't match (e.g., truncated by an interrupted download) are deleted so
+/// they get re-downloaded. Returns the names of the files that were removed.
+pub fn verify_cached_model(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let mut removed = Vec::new();
+    for (name, expected_sha256) in MODEL_FILES {
+        let path = dir.join(name);
+        if !path.exists() {
+            continue;
+        }
+        if !file_sha256_matches(&path, expected_sha256)? {
+            log::warn!("Cached model file {} failed SHA256 verification, deleting", name);
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to delete corrupt {}", path.display()))?;
+            removed.push(name.to_string());
+        }
+    }
+    Ok(removed)
 }
 
 /// Download all model files if not already cached. Returns the model directory path.
-pub fn ensure_model_files() -> anyhow::Result<PathBuf> {
+/// Cached files are verified against their SHA256 hashes; corrupt files are re-downloaded.
+/// `timeout` applies per file.
+pub fn ensure_model_files(timeout: Duration) -> anyhow::Result<PathBuf> {
     let dir = model_dir()?;
+    let Ok(_guard) = MODEL_DOWNLOAD_GUARD.try_lock() else {
+        bail!("Model download already in progress");
+    };
+
+    let corrupt = verify_cached_model(&dir)?;
+    if !corrupt.is_empty() {
+        log::warn!("Corrupt model cache detected ({}), repairing", corrupt.join(", "));
+    }
 
     if model_files_exist()? {
-        log::info!("Model files already cached at {}", dir.display());
+        log::info!("Model files already cached at {} (SHA256 verified)", dir.display());
         return Ok(dir);
     }
 
-    log::info!("Downloading embedding model to {}", dir.display());
+    log::info!(
+        "Downloading embedding model to {} (per-file timeout {}s)",
+        dir.display(),
+        timeout.as_secs()
+    );
     fs::create_dir_all(&dir)
         .with_context(|| format!("failed to create model dir {}", dir.display()))?;
+    crate::disk::check_free_space(&dir, config::embedding::MODEL_DOWNLOAD_REQUIRED_BYTES)?;
 
     let base = config::embedding::MODEL_CDN_BASE;
 
-    download_and_verify(
-        &format!("{base}/model.safetensors"),
-        &dir.join("model.safetensors"),
-        config::embedding::MODEL_SAFETENSORS_SHA256,
-    )?;
-
-    download_and_verify(
-        &format!("{base}/tokenizer.json"),
-        &dir.join("tokenizer.json"),
-        config::embedding::TOKENIZER_JSON_SHA256,
-    )?;
-
-    download_and_verify(
-        &format!("{base}/config.json"),
-        &dir.join("config.json"),
-        config::embedding::CONFIG_JSON_SHA256,
-    )?;
-
-    log::info!("Model download complete");
+    for (name, expected_sha256) in MODEL_FILES {
+        let dest = dir.join(name);
+        if dest.exists() {
+            continue;
+        }
+        download_and_verify(&format!("{base}/{name}"), &dest, expected_sha256, timeout)?;
+    }
+
+    if corrupt.is_empty() {
+        log::info!("Model download complete");
+    } else {
+        log::info!("Model download complete (corrupt cache repaired)");
+    }
     Ok(dir)
 }
 
+/// Delete `.tmp` files left in the model directory by interrupted downloads. Skipped while
+/// a download is running, since its `.tmp` files are live. Returns (path, size) per file.
+pub fn purge_stale_tmp_files(min_age: Duration) -> anyhow::Result<Vec<(PathBuf, u64)>> {
+    let Ok(_guard) = MODEL_DOWNLOAD_GUARD.try_lock() else {
+        log::info!("Model download in progress, leaving its .tmp files alone");
+        return Ok(vec![]);
+    };
+    let dir = model_dir()?;
+    Ok(crate::self_update::purge_matching(&dir, min_age, |p| {
+        p.extension().is_some_and(|e| e == "tmp")
+    }))
+}
+
+/// Stream a file through SHA256 and compare against the expected hex digest.
+fn file_sha256_matches(path: &Path, expected_sha256: &str) -> anyhow::Result<bool> {
+    let mut file = fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).with_context(|| format!("failed to hash {}", path.display()))?;
+    Ok(hex::encode(hasher.finalize()) == expected_sha256)
+}
+
 /// Download a file from URL and verify its SHA256 hash.
-fn download_and_verify(url: &str, dest: &Path, expected_sha256: &str) -> anyhow::Result<()> {
+fn download_and_verify(url: &str, dest: &Path, expected_sha256: &str, timeout: Duration) -> anyhow::Result<()> {
     let filename = dest.file_name().unwrap_or_default().to_string_lossy();
-    log::info!("Downloading {} from {}", filename, url);
+    log::info!("Downloading {} from {} (timeout {}s)", filename, url, timeout.as_secs());
 
     let resp = ureq::get(url)
-        .timeout(std::time::Duration::from_secs(config::update::DOWNLOAD_TIMEOUT_SECS * 3))
+        .timeout(timeout)
         .call()
-        .with_context(|| format!("failed to download {url}"))?;
+        .with_context(|| format!("failed to download {url} (timeout {}s)", timeout.as_secs()))?;
 
     let status = resp.status();
     if status != 200 {
@@ -122,3 +182,36 @@ fn dirs_home() -> anyhow::Result<PathBuf> {
         .map(PathBuf::from)
         .context("cannot determine home directory (neither HOME nor USERPROFILE is set)")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_sha256_matches() {
+        let dir = std::env::temp_dir().join(format!("tabmail_sha_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sample.bin");
+        fs::write(&path, b"abc").unwrap();
+
+        let abc_sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(file_sha256_matches(&path, abc_sha256).unwrap());
+        assert!(!file_sha256_matches(&path, config::embedding::CONFIG_JSON_SHA256).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_cached_model_removes_corrupt_files() {
+        let dir = std::env::temp_dir().join(format!("tabmail_model_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Truncated weights from an interrupted download; tokenizer/config missing.
+        fs::write(dir.join("model.safetensors"), b"truncated").unwrap();
+
+        let removed = verify_cached_model(&dir).unwrap();
+        assert_eq!(removed, vec!["model.safetensors".to_string()]);
+        assert!(!dir.join("model.safetensors").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -5,7 +5,9 @@
 //
 // See SEMANTIC_SEARCH_UPGRADE.md §14 Risk: "Must implement attention-mask-aware mean pooling".
 
+use std::borrow::Cow;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use anyhow::{bail, Context};
 use candle_core::{DType, Device, Tensor};
@@ -15,11 +17,62 @@ use tokenizers::Tokenizer;
 
 use crate::config;
 
+/// Fixed sample texts for `benchmark` (mix of short queries and email-sized passages).
+const BENCHMARK_SAMPLES: &[&str] = &[
+    "quarterly budget review",
+    "Subject: Team offsite planning\nFrom: alice@example.com\n\nHi all, please vote on the dates for next month's offsite and let me know about dietary restrictions.",
+    "Subject: Invoice #4521 overdue\nFrom: billing@vendor.com\n\nOur records show the attached invoice is 30 days past due. Please arrange payment at your earliest convenience or contact us if there is an issue.",
+    "flight booking confirmation for the conference in Berlin",
+];
+
+/// What a text is embedded as; selects the prefix prepended to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedRole {
+    /// A search query (email or memory).
+    Query,
+    /// Stored text: an email's prepared input or a memory entry.
+    Document,
+}
+
+/// The query / document prefixes, and the model input they turn a text into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Prefixes {
+    query: String,
+    document: String,
+}
+
+impl Default for Prefixes {
+    fn default() -> Self {
+        Self { query: config::embedding::QUERY_PREFIX.to_string(), document: config::embedding::DOCUMENT_PREFIX.to_string() }
+    }
+}
+
+impl Prefixes {
+    fn for_role(&self, role: EmbedRole) -> &str {
+        match role {
+            EmbedRole::Query => &self.query,
+            EmbedRole::Document => &self.document,
+        }
+    }
+
+    /// The text the model sees for `text` embedded as `role`, or None for blank input
+    /// (embedded as the zero vector whatever the prefix).
+    fn model_input<'a>(&self, text: &'a str, role: EmbedRole) -> Option<Cow<'a, str>> {
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(with_prefix(text, self.for_role(role)))
+    }
+}
+
 /// The embedding engine holds the loaded model and tokenizer.
 pub struct EmbeddingEngine {
     model: BertModel,
     tokenizer: Tokenizer,
     device: Device,
+    // Non-empty texts embedded since load (reported by runtimeStats).
+    embed_count: AtomicU64,
+    prefixes: Prefixes,
 }
 
 impl EmbeddingEngine {
@@ -36,7 +89,8 @@ impl EmbeddingEngine {
             .with_context(|| format!("parse {}", config_path.display()))?;
 
         log::info!(
-            "Loading embedding model: hidden_size={}, layers={}, heads={}",
+            "Loading embedding model {}: hidden_size={}, layers={}, heads={}",
+            crate::config::embedding::EMBEDDING_MODEL_NAME,
             config.hidden_size,
             config.num_hidden_layers,
             config.num_attention_heads,
@@ -45,7 +99,7 @@ impl EmbeddingEngine {
         // Load model weights from safetensors
         let weights_path = model_dir.join("model.safetensors");
         let vb = unsafe {
-            VarBuilder::from_mmaped_safetensors(&[weights_path.clone()], DType::F32, &device)
+            VarBuilder::from_mmaped_safetensors(std::slice::from_ref(&weights_path), DType::F32, &device)
                 .with_context(|| format!("load weights from {}", weights_path.display()))?
         };
 
@@ -62,31 +116,48 @@ impl EmbeddingEngine {
             model,
             tokenizer,
             device,
+            embed_count: AtomicU64::new(0),
+            prefixes: Prefixes::default(),
         })
     }
 
+    /// Override the query / document prefixes (init `queryPrefix`, `documentPrefix`).
+    pub fn set_prefixes(&mut self, query: Option<&str>, document: Option<&str>) {
+        if let Some(q) = query {
+            self.prefixes.query = q.to_string();
+        }
+        if let Some(d) = document {
+            self.prefixes.document = d.to_string();
+        }
+    }
+
+    /// Prefix prepended to texts embedded as `role`.
+    pub fn prefix(&self, role: EmbedRole) -> &str {
+        self.prefixes.for_role(role)
+    }
+
     /// Generate a sentence embedding for the given text.
     /// Returns a Vec<f32> of `EMBEDDING_DIMS` dimensions.
-    pub fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
-        if text.trim().is_empty() {
+    pub fn embed(&self, text: &str, role: EmbedRole) -> anyhow::Result<Vec<f32>> {
+        self.embed_with_max_tokens(text, role, None)
+    }
+
+    /// `embed` with the input cut to `max_tokens` word-pieces (clamped to the model's
+    /// `MAX_TOKENS`; `None` uses the full limit). Shorter caps embed faster.
+    pub fn embed_with_max_tokens(&self, text: &str, role: EmbedRole, max_tokens: Option<usize>) -> anyhow::Result<Vec<f32>> {
+        let Some(text) = self.prefixes.model_input(text, role) else {
             // Return zero vector for empty input
             return Ok(vec![0.0; config::embedding::EMBEDDING_DIMS]);
-        }
+        };
 
-        // Tokenize with truncation to MAX_TOKENS
+        // Tokenize, then truncate to the token cap (the prefix counts against it)
         let encoding = self
             .tokenizer
-            .encode(text, true)
+            .encode(text.as_ref(), true)
             .map_err(|e| anyhow::anyhow!("tokenize: {e}"))?;
 
-        let token_ids = encoding.get_ids();
-        let attention_mask = encoding.get_attention_mask();
-
-        // Truncate to MAX_TOKENS if needed
-        let max_len = config::embedding::MAX_TOKENS;
-        let len = token_ids.len().min(max_len);
-        let token_ids = &token_ids[..len];
-        let attention_mask = &attention_mask[..len];
+        let (token_ids, attention_mask) =
+            truncate_tokens(encoding.get_ids(), encoding.get_attention_mask(), max_tokens);
 
         // Create tensors [1, seq_len]
         let token_ids_t = Tensor::new(
@@ -126,17 +197,81 @@ impl EmbeddingEngine {
             );
         }
 
+        self.embed_count.fetch_add(1, Ordering::Relaxed);
         Ok(emb_vec)
     }
 
+    /// Number of embeddings computed since the engine was loaded.
+    pub fn embed_count(&self) -> u64 {
+        self.embed_count.load(Ordering::Relaxed)
+    }
+
+    /// Short name of the inference device ("cpu", "cuda", "metal").
+    pub fn device_name(&self) -> &'static str {
+        match self.device {
+            Device::Cpu => "cpu",
+            Device::Cuda(_) => "cuda",
+            Device::Metal(_) => "metal",
+        }
+    }
+
+    /// Embed the fixed sample set `iterations` times (pure inference, no DB access).
+    /// Returns (embeddings computed, total elapsed milliseconds).
+    pub fn benchmark(&self, iterations: u64) -> anyhow::Result<(u64, f64)> {
+        let start = std::time::Instant::now();
+        let mut count: u64 = 0;
+        for _ in 0..iterations {
+            for text in BENCHMARK_SAMPLES {
+                self.embed(text, EmbedRole::Document)?;
+                count += 1;
+            }
+        }
+        Ok((count, start.elapsed().as_secs_f64() * 1000.0))
+    }
+
+    /// Embed an email (see `text_prep::prepare_email_texts`). Chunked bodies are embedded
+    /// chunk by chunk and the vectors averaged into one, so storage stays one row per message.
+    pub fn embed_email(&self, subject: &str, from: &str, to: &str, cc: &str, bcc: &str, body: &str) -> anyhow::Result<Vec<f32>> {
+        let texts = crate::embeddings::text_prep::prepare_email_texts(subject, from, to, cc, bcc, body);
+        self.embed_prepared(&texts)
+    }
+
+    /// Embed the output of `prepare_email_texts` as documents: one text as-is, chunks averaged.
+    pub fn embed_prepared(&self, texts: &[String]) -> anyhow::Result<Vec<f32>> {
+        if let [text] = texts {
+            return self.embed(text, EmbedRole::Document);
+        }
+        let vectors = texts.iter().map(|t| self.embed(t, EmbedRole::Document)).collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(mean_normalized(&vectors))
+    }
+
     /// Batch embed multiple texts. Returns one embedding per text.
-    pub fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+    /// `max_tokens` is applied to each text as in `embed_with_max_tokens`.
+    pub fn embed_batch(&self, texts: &[String], role: EmbedRole, max_tokens: Option<usize>) -> anyhow::Result<Vec<Vec<f32>>> {
         // For simplicity, process one at a time (candle batch support is tricky with variable lengths).
         // At ~5-15ms per embedding, this is fast enough for our batch sizes (50 messages).
-        texts.iter().map(|t| self.embed(t)).collect()
+        texts.iter().map(|t| self.embed_with_max_tokens(t, role, max_tokens)).collect()
+    }
+}
+
+fn with_prefix<'a>(text: &'a str, prefix: &str) -> Cow<'a, str> {
+    if prefix.is_empty() {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(format!("{prefix}{text}"))
     }
 }
 
+/// Cut token ids and attention mask to the same length, at most `max_tokens` (clamped to
+/// 1..=MAX_TOKENS). Slicing both together keeps mean pooling over exactly the kept tokens.
+fn truncate_tokens<'a>(ids: &'a [u32], mask: &'a [u32], max_tokens: Option<usize>) -> (&'a [u32], &'a [u32]) {
+    let cap = max_tokens
+        .unwrap_or(config::embedding::MAX_TOKENS)
+        .clamp(1, config::embedding::MAX_TOKENS);
+    let len = ids.len().min(mask.len()).min(cap);
+    (&ids[..len], &mask[..len])
+}
+
 /// Attention-mask-aware mean pooling.
 ///
 /// For each position, multiply the hidden state by the attention mask (0 or 1),
@@ -161,6 +296,19 @@ fn mean_pooling(input_embeds: &Tensor, attention_mask: &Tensor) -> anyhow::Resul
     Ok((sum_embeddings / sum_mask)?)
 }
 
+/// Element-wise mean of equal-length vectors, L2-normalized like a single embedding.
+fn mean_normalized(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let dims = vectors.first().map_or(0, |v| v.len());
+    let mut mean = vec![0.0f32; dims];
+    for v in vectors {
+        for (m, x) in mean.iter_mut().zip(v) {
+            *m += x / vectors.len() as f32;
+        }
+    }
+    let norm = mean.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-12);
+    mean.iter().map(|x| x / norm).collect()
+}
+
 /// L2 normalize a tensor along the last dimension.
 fn l2_normalize(tensor: &Tensor) -> anyhow::Result<Tensor> {
     let norm = tensor.sqr()?.sum_keepdim(1)?.sqrt()?;
@@ -180,4 +328,62 @@ mod tests {
         let zeros = vec![0.0f32; config::embedding::EMBEDDING_DIMS];
         assert_eq!(zeros.len(), 384);
     }
+
+    #[test]
+    fn test_truncate_tokens() {
+        let ids: Vec<u32> = (0..300).collect();
+        let mask = vec![1u32; 300];
+        let (t, m) = truncate_tokens(&ids, &mask, Some(16));
+        assert_eq!((t.len(), m.len()), (16, 16));
+        assert_eq!(t, &ids[..16]);
+        // Default and over-large caps stop at the model limit; zero still keeps one token.
+        assert_eq!(truncate_tokens(&ids, &mask, None).0.len(), config::embedding::MAX_TOKENS);
+        assert_eq!(truncate_tokens(&ids, &mask, Some(10_000)).0.len(), config::embedding::MAX_TOKENS);
+        assert_eq!(truncate_tokens(&ids, &mask, Some(0)).0.len(), 1);
+        // Short inputs are untouched.
+        assert_eq!(truncate_tokens(&ids[..5], &mask[..5], Some(16)).1.len(), 5);
+    }
+
+    #[test]
+    fn test_prefixes_select_model_input() {
+        assert_eq!(with_prefix("budget", ""), "budget");
+        assert_eq!(with_prefix("budget", "query: "), "query: budget");
+
+        let mut prefixes = Prefixes::default();
+        assert_eq!(prefixes.for_role(EmbedRole::Query), config::embedding::QUERY_PREFIX);
+        assert_eq!(prefixes.for_role(EmbedRole::Document), config::embedding::DOCUMENT_PREFIX);
+        prefixes.query = "query: ".to_string();
+        prefixes.document = "passage: ".to_string();
+        assert_eq!(prefixes.model_input("budget", EmbedRole::Query).as_deref(), Some("query: budget"));
+        assert_eq!(prefixes.model_input("budget", EmbedRole::Document).as_deref(), Some("passage: budget"));
+        // Blank input skips the model (zero vector), prefix or not.
+        assert_eq!(prefixes.model_input(" \n", EmbedRole::Query), None);
+    }
+
+    #[test]
+    #[ignore = "needs the model files (downloaded by a first init)"]
+    fn test_prefixes_shift_embeddings() {
+        let dir = crate::embeddings::download::model_dir().unwrap();
+        let mut engine = EmbeddingEngine::load(&dir).expect("model files present");
+        let text = "quarterly budget review";
+        let plain = engine.embed(text, EmbedRole::Query).unwrap();
+        assert_eq!(plain, engine.embed(text, EmbedRole::Document).unwrap());
+
+        engine.set_prefixes(Some("query: "), Some("passage: "));
+        let query = engine.embed(text, EmbedRole::Query).unwrap();
+        let document = engine.embed(text, EmbedRole::Document).unwrap();
+        let cosine = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+        assert!(cosine(&plain, &query) < 0.999, "query prefix did not change the embedding");
+        assert!(cosine(&query, &document) < 0.999, "query and document prefixes embed alike");
+        // Empty input is still the zero vector, prefix or not.
+        assert!(engine.embed(" ", EmbedRole::Query).unwrap().iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn test_mean_normalized() {
+        let mean = mean_normalized(&[vec![1.0, 0.0], vec![0.0, 1.0]]);
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((mean[0] - expected).abs() < 1e-6 && (mean[1] - expected).abs() < 1e-6);
+        assert_eq!(mean_normalized(&[vec![3.0, 4.0]]), vec![0.6, 0.8]);
+    }
 }
@@ -1,26 +1,59 @@
 // text_prep.rs — Text preparation for embedding generation.
 //
 // Constructs embedding input text from structured fields (email headers, body, etc.).
-// Truncates to fit the model's context window (256 word-piece tokens for all-MiniLM-L6-v2).
+// Truncates to fit the model's context window (256 word-piece tokens for all-MiniLM-L6-v2),
+// or, with chunking enabled, splits long bodies into several windows that each fit.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config;
+
+// Process-wide chunking switch (init `chunkLongBodies`, off by default).
+static CHUNK_LONG_BODIES: AtomicBool = AtomicBool::new(false);
+// Process-wide cc / bcc switches (init `embedCc` / `embedBcc`, off by default).
+static EMBED_CC: AtomicBool = AtomicBool::new(false);
+static EMBED_BCC: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable chunked embedding of long email bodies for this process.
+pub fn set_chunk_long_bodies(enabled: bool) {
+    CHUNK_LONG_BODIES.store(enabled, Ordering::Relaxed);
+}
+
+/// Choose whether cc / bcc go into the embedding input for this process. Changing this
+/// changes every email's embedding; existing vectors keep the old input until re-embedded.
+pub fn set_embed_recipients(cc: bool, bcc: bool) {
+    EMBED_CC.store(cc, Ordering::Relaxed);
+    EMBED_BCC.store(bcc, Ordering::Relaxed);
+}
 
 /// Prepare embedding text for an email message.
 ///
 /// Strategy:
 /// - Subject repeated for emphasis (mirrors BM25 5.0x column weight)
 /// - From/To headers included for sender/recipient context
+/// - Cc/Bcc included when enabled (`embedCc` / `embedBcc`), taking words from the body
 /// - Body truncated to fit within model context window
 ///
 /// The total text is kept to ~200 words to stay within the 256 token limit
 /// after word-piece tokenization (which expands words into subwords).
-pub fn prepare_email_text(subject: &str, from: &str, to: &str, body: &str) -> String {
+pub fn prepare_email_text(subject: &str, from: &str, to: &str, cc: &str, bcc: &str, body: &str) -> String {
+    let (header, body_words) = email_header(subject, from, to, cc, bcc);
+
+    // Body: take first ~150 words to leave room for headers.
+    // Word-piece tokenization typically expands by ~1.3x, so 150 words ≈ 195 tokens.
+    let body_truncated = truncate_words(body.trim(), body_words);
+    join_header_body(&header, body_truncated)
+}
+
+/// Header block of the embedding input, and how many body words fit after it.
+fn email_header(subject: &str, from: &str, to: &str, cc: &str, bcc: &str) -> (String, usize) {
     let subject = subject.trim();
     let from = from.trim();
     let to = to.trim();
-    let body = body.trim();
 
     // Header portion: subject (repeated) + from + to
     // This takes ~20-40 tokens, leaving ~200 tokens for body.
-    let mut parts = Vec::with_capacity(4);
+    let mut parts = Vec::with_capacity(6);
     if !subject.is_empty() {
         parts.push(format!("Subject: {subject}"));
         parts.push(format!("Subject: {subject}"));
@@ -32,19 +65,71 @@ pub fn prepare_email_text(subject: &str, from: &str, to: &str, body: &str) -> St
         parts.push(format!("To: {to}"));
     }
 
-    let header = parts.join("\n");
+    // Cc before bcc, sharing one word budget; whatever they use comes off the body.
+    let mut budget = config::embedding::RECIPIENTS_MAX_WORDS;
+    for (label, value, enabled) in [("Cc", cc, &EMBED_CC), ("Bcc", bcc, &EMBED_BCC)] {
+        if !enabled.load(Ordering::Relaxed) || budget == 0 {
+            continue;
+        }
+        let value = truncate_words(value.trim(), budget);
+        if value.is_empty() {
+            continue;
+        }
+        budget -= value.split_whitespace().count();
+        parts.push(format!("{label}: {value}"));
+    }
+    let recipient_words = config::embedding::RECIPIENTS_MAX_WORDS - budget;
 
-    // Body: take first ~150 words to leave room for headers.
-    // Word-piece tokenization typically expands by ~1.3x, so 150 words ≈ 195 tokens.
-    let body_truncated = truncate_words(body, 150);
+    (parts.join("\n"), config::embedding::CHUNK_WORDS - recipient_words)
+}
+
+/// Embedding inputs for an email: one text, or with `chunkLongBodies` enabled and a body
+/// longer than one chunk, one text per overlapping body window (headers repeated in each).
+/// The caller embeds each and averages (see `EmbeddingEngine::embed_email`).
+pub fn prepare_email_texts(subject: &str, from: &str, to: &str, cc: &str, bcc: &str, body: &str) -> Vec<String> {
+    if !CHUNK_LONG_BODIES.load(Ordering::Relaxed) {
+        return vec![prepare_email_text(subject, from, to, cc, bcc, body)];
+    }
+    let (header, body_words) = email_header(subject, from, to, cc, bcc);
+    let chunks = chunk_words(
+        body,
+        body_words,
+        config::embedding::CHUNK_OVERLAP_WORDS,
+        config::embedding::MAX_CHUNKS,
+    );
+    if chunks.len() <= 1 {
+        return vec![prepare_email_text(subject, from, to, cc, bcc, body)];
+    }
+    // Headers go with every chunk so each vector stays anchored to the message.
+    chunks.into_iter().map(|chunk| join_header_body(&header, chunk)).collect()
+}
 
-    if body_truncated.is_empty() {
-        header
+fn join_header_body(header: &str, body: String) -> String {
+    if body.is_empty() {
+        header.to_string()
     } else if header.is_empty() {
-        body_truncated
+        body
     } else {
-        format!("{header}\n\n{body_truncated}")
+        format!("{header}\n\n{body}")
+    }
+}
+
+/// Split text into windows of `size` words, each starting `size - overlap` words after the
+/// previous one, keeping at most `max_chunks`.
+fn chunk_words(text: &str, size: usize, overlap: usize, max_chunks: usize) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let step = size.saturating_sub(overlap).max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() && chunks.len() < max_chunks {
+        let end = (start + size).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            break;
+        }
+        start += step;
     }
+    chunks
 }
 
 /// Prepare embedding text for a memory/chat entry.
@@ -89,7 +174,7 @@ mod tests {
 
     #[test]
     fn test_prepare_email_text_basic() {
-        let text = prepare_email_text("Budget Review", "alice@example.com", "bob@example.com", "Please review the attached budget.");
+        let text = prepare_email_text("Budget Review", "alice@example.com", "bob@example.com", "", "", "Please review the attached budget.");
         assert!(text.contains("Subject: Budget Review"));
         assert!(text.contains("From: alice@example.com"));
         assert!(text.contains("To: bob@example.com"));
@@ -100,16 +185,81 @@ mod tests {
 
     #[test]
     fn test_prepare_email_text_empty_fields() {
-        let text = prepare_email_text("", "", "", "Just a body");
+        let text = prepare_email_text("", "", "", "", "", "Just a body");
         assert_eq!(text, "Just a body");
     }
 
+    #[test]
+    fn test_prepare_email_text_recipients() {
+        let body: Vec<String> = (0..200).map(|i| format!("w{i}")).collect();
+        let body = body.join(" ");
+        let cc: Vec<String> = (0..50).map(|i| format!("cc{i}@example.com")).collect();
+        let cc = cc.join(" ");
+
+        // The switches are process-wide, so every state is checked in this one test.
+        set_embed_recipients(false, false);
+        let plain = prepare_email_text("Budget", "a@b.com", "c@d.com", "bob@example.com", "eve@example.com", &body);
+        assert!(!plain.contains("Cc:") && !plain.contains("Bcc:"));
+
+        set_embed_recipients(true, false);
+        let text = prepare_email_text("Budget", "a@b.com", "c@d.com", "bob@example.com", "eve@example.com", &body);
+        assert!(text.contains("\nCc: bob@example.com\n") && !text.contains("Bcc:"));
+        assert_ne!(text, plain);
+        // The body gives up the word the cc line took.
+        assert!(text.ends_with(" w148") && plain.ends_with(" w149"));
+
+        set_embed_recipients(true, true);
+        let text = prepare_email_text("Budget", "", "", "bob@example.com", "eve@example.com", "hi");
+        assert!(text.contains("Cc: bob@example.com\nBcc: eve@example.com"));
+        // A long cc list is capped and bcc gets what is left of the budget.
+        let text = prepare_email_text("Budget", "", "", &cc, "eve@example.com", &body);
+        assert!(text.contains(" cc39@example.com\n") && !text.contains("cc40@") && !text.contains("Bcc:"));
+        assert!(text.ends_with(" w109"));
+        set_embed_recipients(false, false);
+    }
+
     #[test]
     fn test_prepare_memory_text() {
         let text = prepare_memory_text("user", "What's the weather like?");
         assert_eq!(text, "user: What's the weather like?");
     }
 
+    #[test]
+    fn test_chunk_words_overlap_and_cap() {
+        let body: Vec<String> = (0..390).map(|i| format!("w{i}")).collect();
+        let body = body.join(" ");
+
+        let chunks = chunk_words(&body, 150, 30, 8);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("w0 ") && chunks[0].ends_with(" w149"));
+        // Next window starts 120 words later, overlapping the previous one by 30.
+        assert!(chunks[1].starts_with("w120 ") && chunks[1].ends_with(" w269"));
+        assert!(chunks[2].starts_with("w240 ") && chunks[2].ends_with(" w389"));
+
+        assert_eq!(chunk_words(&body, 150, 30, 2).len(), 2);
+        assert_eq!(chunk_words("short body", 150, 30, 8), vec!["short body"]);
+        assert!(chunk_words("", 150, 30, 8).is_empty());
+    }
+
+    #[test]
+    fn test_prepare_email_texts_chunks_long_bodies() {
+        let body: Vec<String> = (0..390).map(|i| format!("w{i}")).collect();
+        let body = body.join(" ");
+
+        // The flag is process-wide, so both states are checked in this one test.
+        set_chunk_long_bodies(false);
+        assert_eq!(prepare_email_texts("Report", "a@b.com", "", "", "", &body).len(), 1);
+
+        set_chunk_long_bodies(true);
+        let texts = prepare_email_texts("Report", "a@b.com", "", "", "", &body);
+        assert_eq!(texts.len(), 3);
+        assert!(texts.iter().all(|t| t.starts_with("Subject: Report\nSubject: Report\nFrom: a@b.com\n\n")));
+        assert!(texts[2].ends_with("w389"));
+        // Short bodies are unaffected.
+        assert_eq!(prepare_email_texts("Hi", "", "", "", "", "see you"), vec![prepare_email_text("Hi", "", "", "", "", "see you")]);
+        set_chunk_long_bodies(false);
+    }
+
     #[test]
     fn test_truncate_words() {
         let text = "one two three four five six seven eight nine ten";
@@ -0,0 +1,116 @@
+// address.rs — Email address extraction from raw From/To header strings.
+//
+// Headers arrive as display strings ("Alice Smith <alice@example.com>", "\"Smith, Alice\"
+// <a@x>, bob@y", "carol@z (Carol)"). The indexer stores just the addresses, lowercased, so
+// `fromAddr` / `toAddr` filters match regardless of display-name formatting.
+
+/// Normalized addresses in a header value, in order, without duplicates. Entries without
+/// an `@` (group names, "undisclosed-recipients:;") are skipped.
+pub fn parse_addresses(header: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for part in split_mailboxes(header) {
+        if let Some(addr) = mailbox_address(&part) {
+            if !out.contains(&addr) {
+                out.push(addr);
+            }
+        }
+    }
+    out
+}
+
+/// Split a header on commas outside quotes, comments and angle brackets. Group syntax
+/// (`Team: a@x, b@y;`) splits like a plain list.
+fn split_mailboxes(header: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let (mut in_quotes, mut angle, mut paren, mut escaped) = (false, 0usize, 0usize, false);
+    for c in header.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => angle += 1,
+            '>' if !in_quotes => angle = angle.saturating_sub(1),
+            '(' if !in_quotes => paren += 1,
+            ')' if !in_quotes => paren = paren.saturating_sub(1),
+            ',' | ';' if !in_quotes && angle == 0 && paren == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current);
+    parts
+}
+
+/// The address of one mailbox: the `<...>` part when present, else the bare word with an `@`.
+fn mailbox_address(mailbox: &str) -> Option<String> {
+    let candidate = match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(open), Some(close)) if open < close => mailbox[open + 1..close].to_string(),
+        _ => {
+            let without_comments = strip_comments(mailbox);
+            without_comments.split_whitespace().find(|w| w.contains('@'))?.to_string()
+        }
+    };
+    // A group name glued to its first member ("Team:a@x").
+    let candidate = match (candidate.find(':'), candidate.find('@')) {
+        (Some(colon), Some(at)) if colon < at => candidate[colon + 1..].to_string(),
+        _ => candidate,
+    };
+    let addr = candidate
+        .trim()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '<' || c == '>')
+        .trim_start_matches("mailto:")
+        .to_lowercase();
+    let (local, domain) = addr.split_once('@')?;
+    (!local.is_empty() && !domain.is_empty() && !addr.contains(char::is_whitespace)).then_some(addr)
+}
+
+fn strip_comments(s: &str) -> String {
+    let mut depth = 0usize;
+    s.chars()
+        .filter(|&c| match c {
+            '(' => {
+                depth += 1;
+                false
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                false
+            }
+            _ => depth == 0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address_formats() {
+        assert_eq!(parse_addresses("Alice Smith <Alice@Example.com>"), vec!["alice@example.com"]);
+        assert_eq!(parse_addresses("alice@example.com"), vec!["alice@example.com"]);
+        assert_eq!(parse_addresses("alice@example.com (Alice Smith)"), vec!["alice@example.com"]);
+        assert_eq!(
+            parse_addresses("\"Smith, Alice\" <alice@example.com>, bob@example.org"),
+            vec!["alice@example.com", "bob@example.org"]
+        );
+        assert_eq!(
+            parse_addresses("\"Quote \\\" inside, still\" <q@x.io>; <r@x.io>"),
+            vec!["q@x.io", "r@x.io"]
+        );
+        assert_eq!(parse_addresses("Team: a@x.io, B@X.IO;"), vec!["a@x.io", "b@x.io"]);
+        assert_eq!(parse_addresses("Team:a@x.io"), vec!["a@x.io"]);
+        assert_eq!(parse_addresses("=?UTF-8?Q?J=C3=BCrgen?= <j@x.de>, j@x.de"), vec!["j@x.de"]);
+        assert!(parse_addresses("undisclosed-recipients:;").is_empty());
+        assert!(parse_addresses("Alice Smith").is_empty());
+        assert!(parse_addresses("").is_empty());
+    }
+}
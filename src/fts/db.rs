@@ -2,22 +2,106 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, bail};
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, Datelike, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
-use crate::{config, embeddings::engine::EmbeddingEngine, fts::query::build_fts_match, fts::synonyms::SynonymLookup};
+use crate::fts::hybrid::{distance_metric, AccountWeights, DistanceMetric, RecencyBoost};
+use crate::{config, embeddings::engine::{EmbedRole, EmbeddingEngine}, fts::query::{auto_wildcard_min_len, build_fts_match}, fts::synonyms::SynonymLookup};
 
 pub struct DbState {
     // Email FTS database
     pub db_path: Option<PathBuf>,
     pub conn: Option<Connection>,
     pub synonyms: SynonymLookup,
+    // Custom synonym file (init `synonymsPath`), re-read by `reloadSynonyms`
+    pub synonyms_path: Option<PathBuf>,
     // Memory database (separate from email FTS)
     pub memory_db_path: Option<PathBuf>,
     pub memory_conn: Option<Connection>,
     // Embedding engine (None if model not available — falls back to FTS-only)
     pub embedding_engine: Option<EmbeddingEngine>,
+    // Tag indexed messages with a detected language (init `detectLanguage: true`)
+    pub detect_language: bool,
+    // Rows indexed between writer PASSIVE checkpoints (init `walCheckpointRows`, 0 = off)
+    pub wal_checkpoint_rows: u64,
+    // Writer runs rebuild requests at lowered thread priority (init `rebuildPriority: "low"`, opt-in)
+    pub low_priority_rebuilds: bool,
+    // What the writer does once AUTO_OPTIMIZE_EVERY_ROWS rows were written (init `autoOptimize`)
+    pub auto_optimize: AutoOptimize,
+    // Quiet period before idle maintenance (init `idleMaintenanceSecs`, 0 = off)
+    pub idle_maintenance_secs: u64,
+    // Idle maintenance also runs FTS optimize (init `idleOptimize`, default true)
+    pub idle_optimize: bool,
+    // Reader / writer request budgets (init `methodTimeoutsMs`, `cancelOnTimeout`)
+    pub method_timeouts: crate::methods::MethodTimeouts,
+    // Checkpoint the writer runs on shutdown (init `walShutdownMode`)
+    pub wal_shutdown_mode: WalShutdownMode,
+    // Reader result cache entries (init `resultCacheSize`, 0 = off)
+    pub result_cache_size: usize,
+}
+
+/// Writer reaction to FTS index churn (rows inserted + removed since the last `optimize`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoOptimize {
+    /// Run `optimize` itself after the batch that crosses the threshold (default).
+    Auto,
+    /// Only flag `optimizeRecommended: true` in indexBatch / removeBatch results.
+    Advisory,
+    Off,
+}
+
+impl AutoOptimize {
+    pub fn from_param(v: Option<&Value>) -> anyhow::Result<Self> {
+        match v.and_then(|v| v.as_str()) {
+            None | Some("auto") => Ok(Self::Auto),
+            Some("advisory") => Ok(Self::Advisory),
+            Some("off") => Ok(Self::Off),
+            Some(other) => bail!("Invalid autoOptimize '{}' (expected \"auto\", \"advisory\" or \"off\")", other),
+        }
+    }
+}
+
+/// WAL checkpoint run by the writer as the helper shuts down. More work at exit means less
+/// WAL for the next startup to read back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalShutdownMode {
+    /// Copy every frame and reset the WAL to zero bytes; waits for readers.
+    Truncate,
+    /// Copy what can be copied without waiting; the WAL file keeps its size (default).
+    Passive,
+    /// Leave the WAL as it is.
+    None,
+}
+
+impl WalShutdownMode {
+    pub fn from_param(v: Option<&Value>) -> anyhow::Result<Self> {
+        match v.and_then(|v| v.as_str()) {
+            None | Some("passive") => Ok(Self::Passive),
+            Some("truncate") => Ok(Self::Truncate),
+            Some("none") => Ok(Self::None),
+            Some(other) => bail!("Invalid walShutdownMode '{}' (expected \"truncate\", \"passive\" or \"none\")", other),
+        }
+    }
+}
+
+/// Run the shutdown checkpoint on a writer connection that is about to be closed. For
+/// `passive` and `none`, SQLite's own checkpoint-on-close is turned off too; otherwise the
+/// last connection to close would run a full checkpoint regardless of the mode.
+pub fn shutdown_checkpoint(conn: &Connection, mode: WalShutdownMode) -> anyhow::Result<Option<(i64, i64, i64)>> {
+    use rusqlite::config::DbConfig;
+    let result = match mode {
+        WalShutdownMode::Truncate => {
+            Some(conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?)
+        }
+        WalShutdownMode::Passive => Some(wal_checkpoint_passive(conn)?),
+        WalShutdownMode::None => None,
+    };
+    if mode != WalShutdownMode::Truncate {
+        conn.set_db_config(DbConfig::SQLITE_DBCONFIG_NO_CKPT_ON_CLOSE, true)?;
+    }
+    Ok(result)
 }
 
 impl DbState {
@@ -26,9 +110,19 @@ impl DbState {
             db_path: None,
             conn: None,
             synonyms: SynonymLookup::new(),
+            synonyms_path: None,
             memory_db_path: None,
             memory_conn: None,
             embedding_engine: None,
+            detect_language: false,
+            wal_checkpoint_rows: config::sqlite::WAL_CHECKPOINT_EVERY_ROWS,
+            low_priority_rebuilds: false,
+            auto_optimize: AutoOptimize::Auto,
+            idle_maintenance_secs: config::sqlite::IDLE_MAINTENANCE_SECS,
+            idle_optimize: true,
+            method_timeouts: crate::methods::MethodTimeouts::default(),
+            wal_shutdown_mode: WalShutdownMode::Passive,
+            result_cache_size: config::result_cache::DEFAULT_ENTRIES,
         }
     }
 }
@@ -36,6 +130,53 @@ impl DbState {
 pub fn init_database(conn: &Connection) -> anyhow::Result<()> {
     log::info!("Initializing database schema (matching old WASM implementation)");
 
+    apply_pragmas(conn)?;
+
+    // Create schema.
+    create_messages_fts(conn)?;
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS message_meta (
+            rowid INTEGER PRIMARY KEY,
+            dateMs INTEGER NOT NULL,
+            hasAttachments INTEGER NOT NULL,
+            parsedIcsAttachments TEXT,
+            lang TEXT,
+            tags TEXT NOT NULL DEFAULT '',
+            contentHash TEXT,
+            bodyTruncated INTEGER NOT NULL DEFAULT 0,
+            originalBodyLength INTEGER,
+            headerMessageId TEXT,
+            isRead INTEGER,
+            isStarred INTEGER,
+            fromAddr TEXT,
+            toAddr TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS message_ids (
+            msgId TEXT PRIMARY KEY
+        );
+        "#,
+    )?;
+    ensure_meta_content_hash_column(conn)?;
+    ensure_meta_header_message_id_column(conn)?;
+    ensure_meta_address_columns(conn)?;
+    create_message_content_table(conn)?;
+    ensure_db_meta(conn)?;
+    record_distance_metric(conn)?;
+    ensure_stats_history(conn)?;
+
+    // Vector tables for semantic search (sqlite-vec).
+    // messages_vec rowids match messages_fts rowids for joining.
+    create_vec_table(conn, "messages_vec")?;
+    create_embed_cache_table(conn)?;
+
+    log::info!("Database schema initialized (8 tables: messages_fts, message_meta, message_ids, message_content, db_meta, stats_history, messages_vec, embed_cache)");
+    Ok(())
+}
+
+/// Apply the writer-connection PRAGMAs (WAL, cache, mmap, busy timeout, autocheckpoint).
+pub(crate) fn apply_pragmas(conn: &Connection) -> anyhow::Result<()> {
     // IMPORTANT:
     // SQLite PRAGMA statements do NOT reliably accept parameters, so we must interpolate.
     // Numeric values still come from config constants (repo rule: no scattered magic numbers).
@@ -54,27 +195,358 @@ PRAGMA wal_autocheckpoint = {wal_autocheckpoint};\n\
         busy_timeout = config::sqlite::PRAGMA_BUSY_TIMEOUT_MS,
         wal_autocheckpoint = config::sqlite::PRAGMA_WAL_AUTOCHECKPOINT_PAGES,
     ))?;
+    Ok(())
+}
 
-    // Create schema.
+/// Run `PRAGMA wal_checkpoint(PASSIVE)`: copies what it can without waiting on readers.
+/// Returns (busy, WAL frames, frames checkpointed) as reported by SQLite.
+pub fn wal_checkpoint_passive(conn: &Connection) -> anyhow::Result<(i64, i64, i64)> {
+    let r = conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+    Ok(r)
+}
+
+/// Create the db_meta key/value table and record the schema version if absent.
+/// Databases created before db_meta existed are stamped with the current version,
+/// which they match (SCHEMA_VERSION has not been bumped since).
+pub(crate) fn ensure_db_meta(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS db_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        "#,
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO db_meta (key, value) VALUES ('schemaVersion', ?1)",
+        params![config::SCHEMA_VERSION.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Add message_meta.lang (ISO 639-3 code from language detection) to older databases.
+pub(crate) fn ensure_meta_lang_column(conn: &Connection) -> anyhow::Result<()> {
+    let has_lang = conn
+        .prepare("SELECT name FROM pragma_table_info('message_meta') WHERE name = 'lang'")?
+        .exists([])?;
+    if !has_lang {
+        log::info!("Migrating: adding message_meta.lang column");
+        conn.execute_batch("ALTER TABLE message_meta ADD COLUMN lang TEXT;")?;
+    }
+    Ok(())
+}
+
+/// Add message_meta.contentHash (see `content_hash`) and its lookup index to older databases.
+/// Rows indexed before the migration keep a NULL hash and never match as content duplicates.
+pub(crate) fn ensure_meta_content_hash_column(conn: &Connection) -> anyhow::Result<()> {
+    let has_hash = conn
+        .prepare("SELECT name FROM pragma_table_info('message_meta') WHERE name = 'contentHash'")?
+        .exists([])?;
+    if !has_hash {
+        log::info!("Migrating: adding message_meta.contentHash column");
+        conn.execute_batch("ALTER TABLE message_meta ADD COLUMN contentHash TEXT;")?;
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_meta_content_hash ON message_meta(contentHash);")?;
+    Ok(())
+}
+
+/// Add message_meta.bodyTruncated / originalBodyLength to older databases. Existing rows
+/// read as not truncated, length unknown.
+pub(crate) fn ensure_meta_truncation_columns(conn: &Connection) -> anyhow::Result<()> {
+    let has_flag = conn
+        .prepare("SELECT name FROM pragma_table_info('message_meta') WHERE name = 'bodyTruncated'")?
+        .exists([])?;
+    if !has_flag {
+        log::info!("Migrating: adding message_meta.bodyTruncated / originalBodyLength columns");
+        conn.execute_batch(
+            "ALTER TABLE message_meta ADD COLUMN bodyTruncated INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE message_meta ADD COLUMN originalBodyLength INTEGER;",
+        )?;
+    }
+    Ok(())
+}
+
+/// Add message_meta.headerMessageId (the RFC822 Message-ID, normalized) and its lookup index
+/// to older databases, backfilled from the msgId keys (`accountId:folderPath:headerMessageId`).
+pub(crate) fn ensure_meta_header_message_id_column(conn: &Connection) -> anyhow::Result<()> {
+    let has_column = conn
+        .prepare("SELECT name FROM pragma_table_info('message_meta') WHERE name = 'headerMessageId'")?
+        .exists([])?;
+    if !has_column {
+        log::info!("Migrating: adding message_meta.headerMessageId column");
+        conn.execute_batch("ALTER TABLE message_meta ADD COLUMN headerMessageId TEXT;")?;
+        let keys: Vec<(i64, String)> = {
+            let mut stmt = conn.prepare("SELECT rowid, msgId FROM message_ids")?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut update = tx.prepare("UPDATE message_meta SET headerMessageId = ?1 WHERE rowid = ?2")?;
+            for (rowid, msg_id) in &keys {
+                update.execute(params![header_message_id_of(msg_id, None), rowid])?;
+            }
+        }
+        tx.commit()?;
+        log::info!("Backfilled headerMessageId for {} messages", keys.len());
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_meta_header_message_id ON message_meta(headerMessageId);")?;
+    Ok(())
+}
+
+/// Add message_meta.isRead / isStarred (message flags, synced via indexBatch and updateMeta)
+/// to older databases. Existing rows read as NULL (unknown) and match neither flag filter.
+pub(crate) fn ensure_meta_flag_columns(conn: &Connection) -> anyhow::Result<()> {
+    let has_flags = conn
+        .prepare("SELECT name FROM pragma_table_info('message_meta') WHERE name = 'isRead'")?
+        .exists([])?;
+    if !has_flags {
+        log::info!("Migrating: adding message_meta.isRead / isStarred columns");
+        conn.execute_batch(
+            "ALTER TABLE message_meta ADD COLUMN isRead INTEGER;
+             ALTER TABLE message_meta ADD COLUMN isStarred INTEGER;",
+        )?;
+    }
+    Ok(())
+}
+
+/// Add message_meta.fromAddr / toAddr (addresses parsed from the From / To headers, see
+/// `fts::address`), the sender index and the message_to_addrs recipient lookup to older
+/// databases, backfilled from messages_fts in one transaction. fromAddr holds the first
+/// sender address; toAddr the space-separated recipient list, also stored one row per
+/// address in message_to_addrs so the `toAddr` filter is an index lookup.
+pub(crate) fn ensure_meta_address_columns(conn: &Connection) -> anyhow::Result<()> {
+    let has_columns = conn
+        .prepare("SELECT name FROM pragma_table_info('message_meta') WHERE name = 'fromAddr'")?
+        .exists([])?;
+    let has_lookup = table_exists(conn, "message_to_addrs")?;
+    if has_columns && has_lookup {
+        return Ok(());
+    }
+    let tx = conn.unchecked_transaction()?;
+    if !has_columns {
+        log::info!("Migrating: adding message_meta.fromAddr / toAddr columns");
+        tx.execute_batch(
+            "ALTER TABLE message_meta ADD COLUMN fromAddr TEXT;
+             ALTER TABLE message_meta ADD COLUMN toAddr TEXT;",
+        )?;
+        let headers: Vec<(i64, String, String)> = {
+            let mut stmt = tx.prepare("SELECT rowid, from_, to_ FROM messages_fts")?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        let mut update = tx.prepare("UPDATE message_meta SET fromAddr = ?1, toAddr = ?2 WHERE rowid = ?3")?;
+        for (rowid, from_, to_) in &headers {
+            let (from_addr, to_addr) = header_addresses(from_, to_);
+            update.execute(params![from_addr, to_addr, rowid])?;
+        }
+        log::info!("Backfilled fromAddr / toAddr for {} messages", headers.len());
+    }
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_meta_from_addr ON message_meta(fromAddr);
+         CREATE TABLE IF NOT EXISTS message_to_addrs (
+             addr TEXT NOT NULL,
+             msgRowid INTEGER NOT NULL,
+             PRIMARY KEY (addr, msgRowid)
+         ) WITHOUT ROWID;
+         CREATE INDEX IF NOT EXISTS idx_to_addrs_row ON message_to_addrs(msgRowid);",
+    )?;
+    if !has_lookup {
+        let lists: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare("SELECT rowid, toAddr FROM message_meta WHERE toAddr IS NOT NULL")?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        for (rowid, to_addr) in &lists {
+            insert_to_addrs(&tx, *rowid, Some(to_addr))?;
+        }
+        log::info!("Backfilled message_to_addrs for {} messages", lists.len());
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// One message_to_addrs row per address of a toAddr list.
+fn insert_to_addrs(conn: &Connection, rowid: i64, to_addr: Option<&str>) -> anyhow::Result<()> {
+    let mut insert = conn.prepare_cached("INSERT OR IGNORE INTO message_to_addrs (addr, msgRowid) VALUES (?1, ?2)")?;
+    for addr in to_addr.unwrap_or("").split_whitespace() {
+        insert.execute(params![addr, rowid])?;
+    }
+    Ok(())
+}
+
+/// (fromAddr, toAddr) column values for a message's From / To headers (NULL when empty).
+fn header_addresses(from_: &str, to_: &str) -> (Option<String>, Option<String>) {
+    let from_addr = crate::fts::address::parse_addresses(from_).into_iter().next();
+    let to_addr = Some(crate::fts::address::parse_addresses(to_).join(" ")).filter(|s| !s.is_empty());
+    (from_addr, to_addr)
+}
+
+/// Normalized RFC822 Message-ID for a message: the row's `headerMessageId` when sent, else
+/// the last segment of a msgId key shaped `accountId:/folderPath:headerMessageId` (any other
+/// msgId is taken whole). Angle brackets and whitespace are stripped, so the raw header value
+/// (`<abc@host>`) and Thunderbird's form (`abc@host`) compare equal.
+pub(crate) fn header_message_id_of(msg_id: &str, explicit: Option<&str>) -> String {
+    let from_key = || {
+        let (_, rest) = msg_id.split_once(':')?;
+        let (_, key) = rest.strip_prefix('/')?.rsplit_once(':')?;
+        Some(key).filter(|k| !k.is_empty())
+    };
+    let raw = explicit.or_else(from_key).unwrap_or(msg_id);
+    normalize_message_id(raw)
+}
+
+pub(crate) fn normalize_message_id(raw: &str) -> String {
+    raw.trim().trim_start_matches('<').trim_end_matches('>').trim().to_string()
+}
+
+/// Truncation marker for an indexed row: the extension may cut long bodies before sending
+/// them, passing `isTruncated` and/or `originalBodyLength` (chars of the full body).
+/// A known length longer than the stored body implies truncation.
+fn body_truncation(row: &Value, body: &str) -> (bool, Option<i64>) {
+    let original_len = row.get("originalBodyLength").and_then(|v| v.as_i64()).filter(|&n| n >= 0);
+    let flagged = row.get("isTruncated").and_then(|v| v.as_bool()).unwrap_or(false);
+    let shorter = original_len.is_some_and(|n| n > body.chars().count() as i64);
+    (flagged || shorter, original_len)
+}
+
+/// SHA256 (hex) of subject, headers and body, lowercased with whitespace collapsed, so the
+/// same email delivered to two folders hashes equal even if the client re-wrapped it. Every
+/// field that can go into the embedding input is included: a twin's vector is reused as-is.
+pub(crate) fn content_hash(subject: &str, from: &str, to: &str, cc: &str, bcc: &str, body: &str) -> String {
+    let normalized: Vec<String> = [subject, from, to, cc, bcc, body]
+        .iter()
+        .enumerate()
+        .flat_map(|(i, field)| (i > 0).then_some("\n").into_iter().chain(field.split_whitespace()))
+        .map(|w| w.to_lowercase())
+        .collect();
+    let digest = Sha256::digest(normalized.join(" ").as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Add the `tags` column (space-separated tag names) to message_meta, message_content and
+/// messages_fts in older databases. FTS5 tables can't ALTER ADD COLUMN, so messages_fts is
+/// rebuilt from message_content once (run after `ensure_message_content_table`).
+fn ensure_tags_columns(conn: &Connection) -> anyhow::Result<()> {
+    let has_column = |table: &str| -> anyhow::Result<bool> {
+        Ok(conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{table}') WHERE name = 'tags'"))?
+            .exists([])?)
+    };
+    for table in ["message_meta", "message_content"] {
+        if !has_column(table)? {
+            log::info!("Migrating: adding {}.tags column", table);
+            conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN tags TEXT NOT NULL DEFAULT '';"))?;
+        }
+    }
+    if !has_column("messages_fts")? {
+        log::info!("Migrating: rebuilding messages_fts with a tags column");
+        let tx = conn.unchecked_transaction()?;
+        let reinserted = rebuild_messages_fts(&tx)?;
+        tx.commit()?;
+        log::info!("messages_fts rebuilt with tags column: {} messages", reinserted);
+    }
+    Ok(())
+}
+
+/// msgIds of indexBatch rows whose subject or body look mis-decoded: enough U+FFFD
+/// replacement characters that the text was probably decoded with the wrong charset.
+pub fn suspect_encoding_ids(rows: &[Value]) -> Vec<String> {
+    let suspect = |text: &str| {
+        let replacements = text.chars().filter(|&c| c == '\u{FFFD}').count();
+        replacements >= config::sqlite::SUSPECT_ENCODING_MIN_REPLACEMENTS
+            && replacements as f64 >= text.chars().count() as f64 * config::sqlite::SUSPECT_ENCODING_RATIO
+    };
+    rows.iter()
+        .filter(|row| {
+            ["subject", "body"].iter().any(|f| row.get(*f).and_then(|v| v.as_str()).is_some_and(suspect))
+        })
+        .filter_map(|row| row.get("msgId").and_then(|v| v.as_str()).map(str::to_string))
+        .collect()
+}
+
+/// Normalize an indexBatch `tags` field (array of names, or a space-separated string)
+/// to the stored form: space-separated, de-duplicated, order kept.
+fn normalize_tags(v: Option<&Value>) -> String {
+    let names: Vec<&str> = match v {
+        Some(Value::Array(arr)) => arr.iter().filter_map(|t| t.as_str()).flat_map(str::split_whitespace).collect(),
+        Some(Value::String(s)) => s.split_whitespace().collect(),
+        _ => vec![],
+    };
+    let mut tags: Vec<&str> = Vec::with_capacity(names.len());
+    for t in names {
+        if !tags.contains(&t) {
+            tags.push(t);
+        }
+    }
+    tags.join(" ")
+}
+
+/// Stored tag list -> result array.
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(str::to_string).collect()
+}
+
+/// Detect the dominant language of a message (body, or subject when the body is empty).
+/// Returns an ISO 639-3 code (e.g. "fra") only when whatlang considers the guess reliable.
+fn detect_language(subject: &str, body: &str) -> Option<&'static str> {
+    let source = if body.trim().is_empty() { subject } else { body };
+    let text: String = source.chars().take(config::lang::DETECT_MAX_CHARS).collect();
+    let info = whatlang::detect(&text)?;
+    info.is_reliable().then(|| info.lang().code())
+}
+
+/// Create the stats_history table (periodic size snapshots, see `record_stats`).
+fn ensure_stats_history(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS stats_history (
+            timestampMs INTEGER PRIMARY KEY,
+            docs INTEGER NOT NULL,
+            vecDocs INTEGER NOT NULL,
+            dbBytes INTEGER NOT NULL,
+            walBytes INTEGER NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Read a db_meta value (None if the table or key is missing).
+pub(crate) fn read_db_meta(conn: &Connection, key: &str) -> anyhow::Result<Option<String>> {
+    let has_meta: Option<String> = conn
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name='db_meta'",
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+    if has_meta.is_none() {
+        return Ok(None);
+    }
+    Ok(conn
+        .query_row("SELECT value FROM db_meta WHERE key = ?1", params![key], |r| r.get(0))
+        .optional()?)
+}
+
+/// Read the schema version recorded in db_meta (None if the table or key is missing).
+fn read_schema_version(conn: &Connection) -> anyhow::Result<Option<u32>> {
+    let value = read_db_meta(conn, "schemaVersion")?;
+    match value {
+        Some(v) => Ok(Some(v.parse().with_context(|| format!("invalid schemaVersion '{v}' in db_meta"))?)),
+        None => Ok(None),
+    }
+}
+/// Create the messages_fts virtual table with the current tokenizer config and merge settings.
+fn create_messages_fts(conn: &Connection) -> anyhow::Result<()> {
     conn.execute_batch(&format!(
         r#"
         CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
             msgId,
-            subject, from_, to_, cc, bcc, body,
+            subject, from_, to_, cc, bcc, body, tags,
             tokenize = "{tokenize}",
             prefix = '{prefix}'
         );
-
-        CREATE TABLE IF NOT EXISTS message_meta (
-            rowid INTEGER PRIMARY KEY,
-            dateMs INTEGER NOT NULL,
-            hasAttachments INTEGER NOT NULL,
-            parsedIcsAttachments TEXT
-        );
-
-        CREATE TABLE IF NOT EXISTS message_ids (
-            msgId TEXT PRIMARY KEY
-        );
         "#,
         tokenize = config::sqlite::FTS_TOKENIZE,
         prefix = config::sqlite::FTS_PREFIXES
@@ -89,26 +561,54 @@ PRAGMA wal_autocheckpoint = {wal_autocheckpoint};\n\
         "INSERT INTO messages_fts(messages_fts, rank) VALUES('usermerge', 2)",
         [],
     )?;
+    Ok(())
+}
 
-    // Vector tables for semantic search (sqlite-vec).
-    // messages_vec rowids match messages_fts rowids for joining.
-    conn.execute_batch(&format!(
+/// message_content mirrors the FTS text columns so messages_fts can be rebuilt
+/// locally (tokenizer/schema change) without the extension re-sending every message.
+fn create_message_content_table(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
         r#"
-        CREATE VIRTUAL TABLE IF NOT EXISTS messages_vec USING vec0(
-            embedding FLOAT[{dims}] distance_metric=cosine
-        );
-
-        CREATE TABLE IF NOT EXISTS embed_cache (
-            content_hash TEXT PRIMARY KEY,
-            embedding BLOB NOT NULL,
-            model TEXT NOT NULL,
-            created_at INTEGER NOT NULL
+        CREATE TABLE IF NOT EXISTS message_content (
+            rowid INTEGER PRIMARY KEY,
+            msgId TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            from_ TEXT NOT NULL,
+            to_ TEXT NOT NULL,
+            cc TEXT NOT NULL,
+            bcc TEXT NOT NULL,
+            body TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT ''
         );
         "#,
-        dims = config::embedding::EMBEDDING_DIMS,
-    ))?;
+    )?;
+    Ok(())
+}
+
+/// Add message_content to an existing database and backfill it from messages_fts
+/// (migration for databases created before content was stored outside FTS).
+fn ensure_message_content_table(conn: &Connection) -> anyhow::Result<()> {
+    let exists: Option<String> = conn
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name='message_content'",
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+    if exists.is_some() {
+        return Ok(());
+    }
 
-    log::info!("Database schema initialized (5 tables: messages_fts, message_meta, message_ids, messages_vec, embed_cache)");
+    log::info!("Migrating email DB: adding message_content table");
+    let tx = conn.unchecked_transaction()?;
+    create_message_content_table(&tx)?;
+    let copied = tx.execute(
+        "INSERT INTO message_content (rowid, msgId, subject, from_, to_, cc, bcc, body)
+         SELECT rowid, msgId, subject, from_, to_, cc, bcc, body FROM messages_fts",
+        [],
+    )?;
+    tx.commit()?;
+    log::info!("message_content backfilled with {} messages", copied);
     Ok(())
 }
 
@@ -173,6 +673,16 @@ pub fn open_or_create_db(profile_dir: &Path) -> anyhow::Result<(PathBuf, Connect
         log::info!("Using existing FTS database schema");
         // Migrate: add vector tables if missing (pre-v0.7.0 databases)
         ensure_vector_tables(&conn)?;
+        ensure_message_content_table(&conn)?;
+        ensure_db_meta(&conn)?;
+        ensure_stats_history(&conn)?;
+        ensure_meta_lang_column(&conn)?;
+        ensure_tags_columns(&conn)?;
+        ensure_meta_content_hash_column(&conn)?;
+        ensure_meta_truncation_columns(&conn)?;
+        ensure_meta_header_message_id_column(&conn)?;
+        ensure_meta_flag_columns(&conn)?;
+        ensure_meta_address_columns(&conn)?;
     }
 
     let count: i64 = conn.query_row("SELECT COUNT(*) FROM messages_fts", [], |r| r.get(0))?;
@@ -194,25 +704,19 @@ fn ensure_vector_tables(conn: &Connection) -> anyhow::Result<()> {
 
     if vec_exists.is_none() {
         log::info!("Migrating email DB: adding vector tables (messages_vec, embed_cache)");
-        conn.execute_batch(&format!(
-            r#"
-            CREATE VIRTUAL TABLE IF NOT EXISTS messages_vec USING vec0(
-                embedding FLOAT[{dims}] distance_metric=cosine
-            );
-            CREATE TABLE IF NOT EXISTS embed_cache (
-                content_hash TEXT PRIMARY KEY,
-                embedding BLOB NOT NULL,
-                model TEXT NOT NULL,
-                created_at INTEGER NOT NULL
-            );
-            "#,
-            dims = config::embedding::EMBEDDING_DIMS,
-        ))?;
+        create_vec_table(conn, "messages_vec")?;
+        create_embed_cache_table(conn)?;
         log::info!("Vector tables added to email database");
     } else {
         // Check if existing vec0 table uses cosine distance (v0.7.0-dev had L2 by mistake).
         // The table SQL in sqlite_master contains the distance_metric if set.
-        let needs_cosine_migration = needs_vec_cosine_migration(conn, "messages_vec")?;
+        // Recreating it needs the vec0 module; without it the table is unusable anyway.
+        // Only tables from before `distanceMetric` existed qualify: an L2 table stamped in
+        // db_meta (or requested at init) was chosen on purpose.
+        let needs_cosine_migration = needs_vec_cosine_migration(conn, "messages_vec")?
+            && read_db_meta(conn, "distanceMetric")?.is_none()
+            && distance_metric() == DistanceMetric::Cosine
+            && vec_available(conn);
         if needs_cosine_migration {
             log::info!("Migrating messages_vec: L2 → cosine distance metric (dropping and recreating)");
             conn.execute_batch(&format!(
@@ -233,6 +737,103 @@ fn ensure_vector_tables(conn: &Connection) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Whether sqlite-vec is loaded on this connection. It is registered as an auto-extension at
+/// startup; if that failed (bad build, platform issue) the helper runs FTS-only.
+pub fn vec_available(conn: &Connection) -> bool {
+    conn.query_row("SELECT vec_version()", [], |r| r.get::<_, String>(0)).is_ok()
+}
+
+/// Whether `name` exists in the schema. Vector tables are missing in FTS-only mode, so
+/// statements on them that run without an engine check first.
+pub(crate) fn table_exists(conn: &Connection, name: &str) -> anyhow::Result<bool> {
+    let found = conn
+        .query_row("SELECT 1 FROM sqlite_master WHERE name = ?1", params![name], |_| Ok(()))
+        .optional()?;
+    Ok(found.is_some())
+}
+
+/// Create a vec0 table with the process distance metric, or skip it (loudly) when sqlite-vec
+/// is unavailable so the FTS tables still come up. Searches treat the missing table like an
+/// empty one.
+pub(crate) fn create_vec_table(conn: &Connection, table: &str) -> anyhow::Result<()> {
+    if !vec_available(conn) {
+        log::error!("sqlite-vec extension is NOT available: skipping {table}, semantic search disabled (FTS-only mode)");
+        return Ok(());
+    }
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {table} USING vec0(embedding FLOAT[{dims}] distance_metric={metric});",
+        dims = config::embedding::EMBEDDING_DIMS,
+        metric = distance_metric().as_str(),
+    ))?;
+    Ok(())
+}
+
+/// Stamp a freshly created database with the process distance metric.
+pub(crate) fn record_distance_metric(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO db_meta (key, value) VALUES ('distanceMetric', ?1)",
+        params![distance_metric().as_str()],
+    )?;
+    Ok(())
+}
+
+/// Settle the distance metric of an opened database against the one requested at init.
+/// The stored metric wins when nothing was requested; databases from before the setting are
+/// read from their vec0 DDL (no `cosine` = L2) and stamped. A conflicting request is refused:
+/// vectors in the table were ranked under the stored metric and mixing would corrupt scores.
+pub(crate) fn resolve_distance_metric(
+    conn: &Connection,
+    vec_table: &str,
+    requested: Option<DistanceMetric>,
+) -> anyhow::Result<DistanceMetric> {
+    let stored = match read_db_meta(conn, "distanceMetric")? {
+        Some(s) => Some(DistanceMetric::parse(&s)?),
+        None => {
+            let sql: Option<String> = conn
+                .query_row("SELECT sql FROM sqlite_master WHERE name = ?1", params![vec_table], |r| r.get(0))
+                .optional()?;
+            sql.map(|sql| {
+                if sql.to_lowercase().contains("cosine") {
+                    DistanceMetric::Cosine
+                } else {
+                    DistanceMetric::L2
+                }
+            })
+        }
+    };
+    if let (Some(stored), Some(requested)) = (stored, requested) {
+        if stored != requested {
+            bail!(
+                "{} uses {} distance but init requested {}; clear the index to switch metrics (or omit distanceMetric)",
+                vec_table,
+                stored.as_str(),
+                requested.as_str()
+            );
+        }
+    }
+    let metric = stored.or(requested).unwrap_or_default();
+    conn.execute(
+        "INSERT OR REPLACE INTO db_meta (key, value) VALUES ('distanceMetric', ?1)",
+        params![metric.as_str()],
+    )?;
+    Ok(metric)
+}
+
+/// Embedding cache keyed on prepared-text hash (plain table, no extension needed).
+pub(crate) fn create_embed_cache_table(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS embed_cache (
+            content_hash TEXT PRIMARY KEY,
+            embedding BLOB NOT NULL,
+            model TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
 /// Check if a vec0 table needs migration from L2 to cosine distance.
 /// Returns true if the table exists but was created WITHOUT distance_metric=cosine.
 /// sqlite-vec stores the full CREATE statement in sqlite_master.sql.
@@ -283,111 +884,174 @@ PRAGMA busy_timeout = {busy_timeout};\n\
     Ok(conn)
 }
 
-pub fn db_count(conn: &Connection) -> anyhow::Result<i64> {
-    Ok(conn.query_row("SELECT COUNT(*) FROM messages_fts", [], |r| r.get(0))?)
-}
+/// Tables scanned by `warm_cache`: the FTS5 index b-trees plus the meta table every search
+/// joins, each with a column read from every row.
+const WARM_CACHE_TABLES: &[(&str, &str)] =
+    &[("messages_fts_data", "block"), ("messages_fts_idx", "term"), ("message_meta", "tags")];
 
-/// Count rows in the vector embedding table (0 if table missing or query fails).
-pub fn vec_count(conn: &Connection) -> i64 {
-    conn.query_row("SELECT COUNT(*) FROM messages_vec", [], |r| r.get(0)).unwrap_or(0)
+/// A bare count(*) would be answered from the smallest index (message_meta's
+/// idx_meta_from_addr), so read a column with NOT INDEXED to walk the table b-tree itself.
+fn warm_cache_sql(table: &str, column: &str) -> String {
+    format!("SELECT count(*), sum(length({column})) FROM {table} NOT INDEXED")
 }
 
-pub fn index_batch(conn: &mut Connection, rows: &[Value], engine: Option<&EmbeddingEngine>) -> anyhow::Result<(i64, i64)> {
-    log::info!("Indexing batch of {} messages (embeddings={})", rows.len(), engine.is_some());
-
-    let tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
+/// Full-scan the hot tables so their pages land in the OS cache (the connections mmap the
+/// file, so the reader's first searches hit memory instead of disk). Returns rows per table.
+pub fn warm_cache(conn: &Connection) -> anyhow::Result<Value> {
+    let mut tables = serde_json::Map::new();
+    for (table, column) in WARM_CACHE_TABLES {
+        let rows: i64 = conn.query_row(&warm_cache_sql(table, column), [], |r| r.get(0))?;
+        tables.insert(table.to_string(), Value::from(rows));
+    }
+    Ok(Value::Object(tables))
+}
 
-    let mut inserted: i64 = 0;
-    let mut skipped_duplicates: i64 = 0;
-    let mut embedded: i64 = 0;
+/// Describe the on-disk layout for `getSchema`: columns per table (FTS5/vec0 shadow tables
+/// omitted) plus the tokenizer, prefixes and distance metric the tables were actually created
+/// with, which can differ from the current config until a migration rebuilds them.
+pub fn get_schema(conn: &Connection) -> anyhow::Result<Value> {
+    let objects: Vec<(String, String)> = {
+        let mut stmt = conn.prepare(
+            "SELECT name, COALESCE(sql, '') FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    let virtual_tables: Vec<&str> = objects
+        .iter()
+        .filter(|(_, sql)| sql.to_uppercase().starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(name, _)| name.as_str())
+        .collect();
 
-    for row in rows {
-        let Some(msg_id_val) = row.get("msgId").and_then(|v| v.as_str()) else { continue };
-        if msg_id_val.is_empty() {
+    let mut tables = serde_json::Map::new();
+    for (name, _) in &objects {
+        let is_shadow = virtual_tables.iter().any(|vt| name.starts_with(&format!("{vt}_")));
+        if is_shadow {
             continue;
         }
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?;
+        let columns = stmt.query_map(params![name], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        tables.insert(name.clone(), serde_json::json!(columns));
+    }
 
-        let changed = tx.execute(
-            "INSERT OR IGNORE INTO message_ids (msgId) VALUES (?1)",
-            params![msg_id_val],
-        )?;
-        if changed == 0 {
-            skipped_duplicates += 1;
-            log::debug!("Skipping duplicate msgId: {}...", truncate_for_log(msg_id_val));
-            continue;
+    let table_sql = |table: &str| objects.iter().find(|(name, _)| name == table).map(|(_, sql)| sql.as_str());
+    let fts_sql = table_sql("messages_fts").unwrap_or("");
+    let distance_metric = table_sql("messages_vec").map(|sql| {
+        if sql.to_lowercase().contains("distance_metric=cosine") {
+            "cosine"
+        } else {
+            "l2"
         }
+    });
 
-        let row_id: i64 = tx.query_row(
-            "SELECT rowid FROM message_ids WHERE msgId = ?1",
-            params![msg_id_val],
-            |r| r.get(0),
-        )?;
+    Ok(serde_json::json!({
+        "tables": tables,
+        "tokenizer": fts5_option(fts_sql, "tokenize"),
+        "ftsPrefixes": fts5_option(fts_sql, "prefix"),
+        "embeddingDims": config::embedding::EMBEDDING_DIMS,
+        "embeddingModel": config::embedding::EMBEDDING_MODEL_NAME,
+        "distanceMetric": distance_metric,
+        "schemaVersion": read_schema_version(conn)?,
+    }))
+}
 
-        let subject = row.get("subject").and_then(|v| v.as_str()).unwrap_or("");
-        let from_ = row
-            .get("from_")
-            .and_then(|v| v.as_str())
-            .or_else(|| row.get("from").and_then(|v| v.as_str()))
-            .or_else(|| row.get("author").and_then(|v| v.as_str()))
-            .unwrap_or("");
-        let to_ = row
-            .get("to_")
-            .and_then(|v| v.as_str())
-            .or_else(|| row.get("to").and_then(|v| v.as_str()))
-            .unwrap_or("");
-        let cc = row.get("cc").and_then(|v| v.as_str()).unwrap_or("");
-        let bcc = row.get("bcc").and_then(|v| v.as_str()).unwrap_or("");
-        let body = row.get("body").and_then(|v| v.as_str()).unwrap_or("");
+/// Value of a quoted `key = "..."` / `key = '...'` option in an FTS5 CREATE statement.
+fn fts5_option(create_sql: &str, key: &str) -> Option<String> {
+    let start = create_sql.find(&format!("{key} ="))? + key.len() + 2;
+    let rest = create_sql[start..].trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &rest[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
 
-        tx.execute(
-            r#"
-            INSERT INTO messages_fts (rowid, msgId, subject, from_, to_, cc, bcc, body)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-            "#,
-            params![row_id, msg_id_val, subject, from_, to_, cc, bcc, body],
-        )?;
+pub fn db_count(conn: &Connection) -> anyhow::Result<i64> {
+    Ok(conn.query_row("SELECT COUNT(*) FROM messages_fts", [], |r| r.get(0))?)
+}
 
-        let date_ms = row.get("dateMs").and_then(|v| v.as_i64()).unwrap_or(0);
-        let has_attachments = row
-            .get("hasAttachments")
-            .and_then(|v| v.as_bool())
-            .map(|b| if b { 1 } else { 0 })
-            .unwrap_or(0);
-        let parsed_ics = row
-            .get("parsedIcsAttachments")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
+/// Cheap snapshot of the email DB's contents (highest message rowid, 0 when empty), used
+/// to check the reader connection sees what the writer committed.
+pub fn high_water_mark(conn: &Connection) -> anyhow::Result<i64> {
+    Ok(conn.query_row("SELECT COALESCE(MAX(rowid), 0) FROM message_ids", [], |r| r.get(0))?)
+}
 
-        tx.execute(
-            r#"
-            INSERT INTO message_meta (rowid, dateMs, hasAttachments, parsedIcsAttachments)
-            VALUES (?1, ?2, ?3, ?4)
-            "#,
-            params![row_id, date_ms, has_attachments, parsed_ics],
-        )?;
+/// Count rows in the vector embedding table (0 if table missing or query fails).
+pub fn vec_count(conn: &Connection) -> i64 {
+    conn.query_row("SELECT COUNT(*) FROM messages_vec", [], |r| r.get(0)).unwrap_or(0)
+}
 
-        // Generate and store embedding if engine is available
-        if let Some(engine) = engine {
-            let embed_text = crate::embeddings::text_prep::prepare_email_text(subject, from_, to_, body);
-            match engine.embed(&embed_text) {
-                Ok(embedding) => {
-                    let blob = f32_vec_to_blob(&embedding);
-                    tx.execute(
-                        "INSERT INTO messages_vec (rowid, embedding) VALUES (?1, ?2)",
-                        params![row_id, blob],
-                    )?;
-                    embedded += 1;
-                }
-                Err(e) => {
-                    log::warn!("Failed to embed message {}: {}", truncate_for_log(msg_id_val), e);
-                }
-            }
+/// Index rows, skipping msgIds already present. With `dedupe_content`, a row whose
+/// content hash matches an existing message copies that message's embedding instead of
+/// computing a new one (it is still indexed under its own msgId).
+/// Each row runs in its own savepoint: a row whose insert fails is rolled back and listed
+/// in `failed` as `{ msgId, reason }`, and the rest of the batch is still committed.
+/// Returns (inserted, skipped duplicate msgIds, deduped by content, failed rows).
+pub fn index_batch(
+    conn: &mut Connection,
+    rows: &[Value],
+    engine: Option<&EmbeddingEngine>,
+    detect_lang: bool,
+    dedupe_content: bool,
+) -> anyhow::Result<(i64, i64, i64, Vec<Value>)> {
+    log::info!(
+        "Indexing batch of {} messages (embeddings={}, detectLanguage={}, dedupeContent={})",
+        rows.len(),
+        engine.is_some(),
+        detect_lang,
+        dedupe_content
+    );
+
+    let mut tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
+    let counts = index_rows(&mut tx, rows, engine, detect_lang, dedupe_content)?;
+    tx.commit()?;
+    Ok(counts)
+}
+
+/// The body of `index_batch`, inside a transaction the caller commits (`importDump` runs a
+/// whole dump in one).
+pub(crate) fn index_rows(
+    tx: &mut rusqlite::Transaction,
+    rows: &[Value],
+    engine: Option<&EmbeddingEngine>,
+    detect_lang: bool,
+    dedupe_content: bool,
+) -> anyhow::Result<(i64, i64, i64, Vec<Value>)> {
+    let mut inserted: i64 = 0;
+    let mut skipped_duplicates: i64 = 0;
+    let mut deduped_by_content: i64 = 0;
+    let mut embedded: i64 = 0;
+    let mut failed: Vec<Value> = Vec::new();
+
+    for row in rows {
+        let Some(msg_id_val) = row.get("msgId").and_then(|v| v.as_str()) else { continue };
+        if msg_id_val.is_empty() {
+            continue;
         }
 
-        inserted += 1;
+        // Dropping the savepoint without committing rolls back just this row.
+        let sp = tx.savepoint()?;
+        match index_row(&sp, row, msg_id_val, engine, detect_lang, dedupe_content) {
+            Ok(RowOutcome::Duplicate) => skipped_duplicates += 1,
+            Ok(RowOutcome::Inserted { deduped, embedded: row_embedded }) => {
+                sp.commit()?;
+                inserted += 1;
+                deduped_by_content += deduped as i64;
+                embedded += row_embedded as i64;
+            }
+            Err(e) if is_row_error(&e) => {
+                log::warn!("Failed to index message {}: {:#}", truncate_for_log(msg_id_val), e);
+                failed.push(serde_json::json!({ "msgId": msg_id_val, "reason": format!("{:#}", e) }));
+            }
+            // Disk full, I/O, interrupt, corruption: the whole batch rolls back.
+            Err(e) => return Err(e.context(format!("indexing {}", truncate_for_log(msg_id_val)))),
+        }
     }
 
-    tx.commit()?;
+    if deduped_by_content > 0 {
+        log::info!("{} indexed messages were content duplicates of existing ones", deduped_by_content);
+    }
+    if !failed.is_empty() {
+        log::warn!("{} rows of the batch failed to index and were skipped", failed.len());
+    }
     if engine.is_some() {
         log::info!(
             "Indexed {} messages ({} embedded), {} duplicates skipped",
@@ -403,7 +1067,179 @@ pub fn index_batch(conn: &mut Connection, rows: &[Value], engine: Option<&Embedd
         log::info!("Indexed {} messages successfully", inserted);
     }
 
-    Ok((inserted, skipped_duplicates))
+    Ok((inserted, skipped_duplicates, deduped_by_content, failed))
+}
+
+enum RowOutcome {
+    Duplicate,
+    Inserted { deduped: bool, embedded: bool },
+}
+
+/// Whether an `index_row` error is about the row itself (it's skipped and the batch goes on)
+/// rather than the database. Only SQLite failures other than constraint and data errors are
+/// database-level; conversion errors and embedding failures stay per-row.
+fn is_row_error(e: &anyhow::Error) -> bool {
+    use rusqlite::ErrorCode;
+    match e.chain().find_map(|c| c.downcast_ref::<rusqlite::Error>()) {
+        Some(rusqlite::Error::SqliteFailure(err, _)) => matches!(
+            err.code,
+            ErrorCode::ConstraintViolation | ErrorCode::TypeMismatch | ErrorCode::TooBig | ErrorCode::ParameterOutOfRange
+        ),
+        _ => true,
+    }
+}
+
+/// Insert one `index_batch` row into every message table.
+fn index_row(
+    tx: &Connection,
+    row: &Value,
+    msg_id_val: &str,
+    engine: Option<&EmbeddingEngine>,
+    detect_lang: bool,
+    dedupe_content: bool,
+) -> anyhow::Result<RowOutcome> {
+    let changed = tx.execute(
+        "INSERT OR IGNORE INTO message_ids (msgId) VALUES (?1)",
+        params![msg_id_val],
+    )?;
+    if changed == 0 {
+        log::debug!("Skipping duplicate msgId: {}...", truncate_for_log(msg_id_val));
+        return Ok(RowOutcome::Duplicate);
+    }
+
+    let row_id: i64 = tx.query_row(
+        "SELECT rowid FROM message_ids WHERE msgId = ?1",
+        params![msg_id_val],
+        |r| r.get(0),
+    )?;
+
+    let subject = row.get("subject").and_then(|v| v.as_str()).unwrap_or("");
+    let from_ = row
+        .get("from_")
+        .and_then(|v| v.as_str())
+        .or_else(|| row.get("from").and_then(|v| v.as_str()))
+        .or_else(|| row.get("author").and_then(|v| v.as_str()))
+        .unwrap_or("");
+    let to_ = row
+        .get("to_")
+        .and_then(|v| v.as_str())
+        .or_else(|| row.get("to").and_then(|v| v.as_str()))
+        .unwrap_or("");
+    let cc = row.get("cc").and_then(|v| v.as_str()).unwrap_or("");
+    let bcc = row.get("bcc").and_then(|v| v.as_str()).unwrap_or("");
+    let body = row.get("body").and_then(|v| v.as_str()).unwrap_or("");
+    let tags = normalize_tags(row.get("tags"));
+
+    tx.execute(
+        r#"
+        INSERT INTO messages_fts (rowid, msgId, subject, from_, to_, cc, bcc, body, tags)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![row_id, msg_id_val, subject, from_, to_, cc, bcc, body, tags],
+    )?;
+    tx.execute(
+        r#"
+        INSERT INTO message_content (rowid, msgId, subject, from_, to_, cc, bcc, body, tags)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![row_id, msg_id_val, subject, from_, to_, cc, bcc, body, tags],
+    )?;
+
+    let date_ms = row.get("dateMs").and_then(|v| v.as_i64()).unwrap_or(0);
+    let has_attachments = row
+        .get("hasAttachments")
+        .and_then(|v| v.as_bool())
+        .map(|b| if b { 1 } else { 0 })
+        .unwrap_or(0);
+    let parsed_ics = row
+        .get("parsedIcsAttachments")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let lang = if detect_lang { detect_language(subject, body) } else { None };
+    let hash = content_hash(subject, from_, to_, cc, bcc, body);
+    let (body_truncated, original_body_len) = body_truncation(row, body);
+    let header_message_id =
+        header_message_id_of(msg_id_val, row.get("headerMessageId").and_then(|v| v.as_str()));
+    // Absent flags stay NULL (unknown) rather than reading as unread / unstarred.
+    let is_read = row.get("isRead").and_then(|v| v.as_bool());
+    let is_starred = row.get("isStarred").and_then(|v| v.as_bool());
+    let (from_addr, to_addr) = header_addresses(from_, to_);
+
+    // Earlier message with identical content (e.g. same email in another folder)
+    let content_twin: Option<i64> = if dedupe_content {
+        tx.query_row(
+            "SELECT rowid FROM message_meta WHERE contentHash = ?1 LIMIT 1",
+            params![hash],
+            |r| r.get(0),
+        )
+        .optional()?
+    } else {
+        None
+    };
+
+    tx.execute(
+        r#"
+        INSERT INTO message_meta
+            (rowid, dateMs, hasAttachments, parsedIcsAttachments, lang, tags, contentHash, bodyTruncated,
+             originalBodyLength, headerMessageId, isRead, isStarred, fromAddr, toAddr)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        "#,
+        params![
+            row_id,
+            date_ms,
+            has_attachments,
+            parsed_ics,
+            lang,
+            tags,
+            hash,
+            body_truncated,
+            original_body_len,
+            header_message_id,
+            is_read,
+            is_starred,
+            from_addr,
+            to_addr
+        ],
+    )?;
+    insert_to_addrs(tx, row_id, to_addr.as_deref())?;
+
+    if let Some(twin) = content_twin {
+        log::debug!("Content duplicate of rowid {}: {}...", twin, truncate_for_log(msg_id_val));
+    }
+
+    // Reuse the twin's embedding when it has one; otherwise embed as usual.
+    let reused_embedding = match (engine, content_twin) {
+        (Some(_), Some(twin)) => {
+            tx.execute(
+                "INSERT INTO messages_vec (rowid, embedding) SELECT ?1, embedding FROM messages_vec WHERE rowid = ?2",
+                params![row_id, twin],
+            )? > 0
+        }
+        _ => false,
+    };
+
+    // Generate and store embedding if engine is available
+    let mut embedded = reused_embedding;
+    if let (false, Some(engine)) = (reused_embedding, engine) {
+        match engine.embed_email(subject, from_, to_, cc, bcc, body) {
+            Ok(embedding) if is_degenerate_embedding(&embedding) => {
+                log::info!("No text to embed for {}, indexed FTS-only", truncate_for_log(msg_id_val));
+            }
+            Ok(embedding) => {
+                let blob = f32_vec_to_blob(&embedding);
+                tx.execute(
+                    "INSERT INTO messages_vec (rowid, embedding) VALUES (?1, ?2)",
+                    params![row_id, blob],
+                )?;
+                embedded = true;
+            }
+            Err(e) => {
+                log::warn!("Failed to embed message {}: {}", truncate_for_log(msg_id_val), e);
+            }
+        }
+    }
+
+    Ok(RowOutcome::Inserted { deduped: content_twin.is_some(), embedded })
 }
 
 /// Convert a Vec<f32> to a little-endian byte blob for sqlite-vec.
@@ -411,7 +1247,25 @@ pub(crate) fn f32_vec_to_blob(v: &[f32]) -> Vec<u8> {
     v.iter().flat_map(|f| f.to_le_bytes()).collect()
 }
 
+/// An embedding too close to zero to rank by: the engine's result for empty text. Every
+/// distance to it is degenerate, so it would match all queries equally (and weakly).
+pub(crate) fn is_degenerate_embedding(v: &[f32]) -> bool {
+    v.iter().map(|x| x * x).sum::<f32>().sqrt() < config::embedding::MIN_EMBEDDING_NORM
+}
+
+/// Inverse of `f32_vec_to_blob`.
+pub(crate) fn blob_to_f32_vec(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+/// Date param in the machine's local zone (see `parse_date_param_tz`).
 pub fn parse_date_param(v: &Value) -> anyhow::Result<Option<i64>> {
+    parse_date_param_tz(v, DateTz::Local)
+}
+
+/// Epoch ms from a number, numeric string, RFC 3339 string, or a date / datetime without an
+/// offset ("2024-03-10", "2024-03-10T08:00:00"), which is read in `tz`.
+pub(crate) fn parse_date_param_tz(v: &Value, tz: DateTz) -> anyhow::Result<Option<i64>> {
     if v.is_null() {
         return Ok(None);
     }
@@ -429,10 +1283,9 @@ pub fn parse_date_param(v: &Value) -> anyhow::Result<Option<i64>> {
         return Ok(None);
     }
 
-    let s = if s.ends_with('Z') {
-        format!("{}+00:00", &s[..(s.len() - 1)])
-    } else {
-        s.to_string()
+    let s = match s.strip_suffix('Z') {
+        Some(stripped) => format!("{stripped}+00:00"),
+        None => s.to_string(),
     };
 
     // Try ISO first.
@@ -445,9 +1298,68 @@ pub fn parse_date_param(v: &Value) -> anyhow::Result<Option<i64>> {
         return Ok(Some(f as i64));
     }
 
+    let naive = NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)));
+    if let Some(naive) = naive {
+        return Ok(Some(match tz {
+            DateTz::Utc => naive.and_utc().timestamp_millis(),
+            DateTz::Local => resolve_local_time(Local.from_local_datetime(&naive), naive)?,
+        }));
+    }
+
     bail!("Invalid date format: '{}'", v);
 }
 
+/// Timezone for dates without an offset in params and for formatted `dateStr` values
+/// (`tz` param): "local" (default, the machine's zone) or "utc".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum DateTz {
+    #[default]
+    Local,
+    Utc,
+}
+
+impl DateTz {
+    pub(crate) fn from_params(params: &Value) -> anyhow::Result<Self> {
+        match params.get("tz").and_then(|v| v.as_str()) {
+            None | Some("local") => Ok(Self::Local),
+            Some("utc") => Ok(Self::Utc),
+            Some(other) => bail!("Invalid tz '{}' (expected \"local\" or \"utc\")", other),
+        }
+    }
+}
+
+/// Epoch ms of a local wall-clock time. A time repeated by a DST change resolves to its
+/// first occurrence; a time skipped by one is an error rather than a silent shift.
+fn resolve_local_time<Tz: TimeZone>(local: LocalResult<DateTime<Tz>>, naive: NaiveDateTime) -> anyhow::Result<i64> {
+    match local {
+        LocalResult::Single(dt) => Ok(dt.timestamp_millis()),
+        LocalResult::Ambiguous(earliest, _) => Ok(earliest.timestamp_millis()),
+        LocalResult::None => bail!(
+            "Date '{}' does not exist in the local timezone (skipped by a DST change); add an offset or pass tz \"utc\"",
+            naive
+        ),
+    }
+}
+
+/// `timezoneInfo` for `health`: what the helper takes as local time, so a wrong clock or
+/// an unexpected zone can be spotted when `dateStr` values or date filters look off.
+pub fn timezone_info() -> Value {
+    let now = Local::now();
+    let offset_minutes = |dt: Option<DateTime<Local>>| dt.map(|d| d.offset().local_minus_utc() / 60);
+    // Offsets in January and July differ in zones that observe DST.
+    let jan = offset_minutes(Local.with_ymd_and_hms(now.year(), 1, 1, 12, 0, 0).earliest());
+    let jul = offset_minutes(Local.with_ymd_and_hms(now.year(), 7, 1, 12, 0, 0).earliest());
+    serde_json::json!({
+        "utcOffsetMinutes": now.offset().local_minus_utc() / 60,
+        "localTime": now.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        "utcTime": Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        "observesDst": jan.is_some() && jul.is_some() && jan != jul
+    })
+}
+
 // Internal struct for FTS candidate data during hybrid merge.
 struct FtsCandidate {
     rowid: i64,
@@ -457,6 +1369,9 @@ struct FtsCandidate {
     date_ms: i64,
     has_attachments: bool,
     snippet: String,
+    snippets: Vec<String>,
+    recipients: Recipients,
+    tags: Vec<String>,
     rank: f64,
 }
 
@@ -467,209 +1382,1121 @@ struct MessageMeta {
     subject: String,
     date_ms: i64,
     has_attachments: bool,
+    recipients: Recipients,
+    lang: Option<String>,
+    tags: Vec<String>,
+    body: String,
+    is_read: Option<bool>,
+    is_starred: Option<bool>,
+    from_addr: Option<String>,
+    to_addr: Option<String>,
 }
 
+/// Which ranking path a search actually took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Fts,
+    Hybrid,
+}
+
+impl SearchMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchMode::Fts => "fts",
+            SearchMode::Hybrid => "hybrid",
+        }
+    }
+}
+
+/// Per-search result shaping: snippet highlight markers passed to FTS5 `snippet()` (search
+/// params `snippetOpen` / `snippetClose` / `snippetEllipsis`; defaults `[`, `]`, `…`),
+/// `snippetCount` (when > 1, results also carry a `snippets` array of body match windows),
+/// `includeRecipients` (adds `to_` / `cc` / `bcc`; off by default to keep payloads small),
+/// and `explain` (adds the raw SQLite `bm25Rank` and vector `distance`, each null when the
+/// result didn't come from that engine, the `distanceMetric` that distance is in, plus the
+/// normalized 0..1 `score` it was ranked by).
+struct ResultOptions {
+    open: String,
+    close: String,
+    ellipsis: String,
+    count: usize,
+    include_recipients: bool,
+    explain: bool,
+}
+
+impl ResultOptions {
+    fn from_params(params: &Value) -> anyhow::Result<Self> {
+        let get = |key: &str, default: &str| -> anyhow::Result<String> {
+            let Some(v) = params.get(key).filter(|v| !v.is_null()) else {
+                return Ok(default.to_string());
+            };
+            let s = v.as_str().with_context(|| format!("{key} must be a string"))?;
+            if s.chars().count() > config::sqlite::SNIPPET_MARKER_MAX_CHARS {
+                bail!(
+                    "{} must be at most {} characters",
+                    key,
+                    config::sqlite::SNIPPET_MARKER_MAX_CHARS
+                );
+            }
+            Ok(s.to_string())
+        };
+        let count = match params.get("snippetCount").filter(|v| !v.is_null()) {
+            Some(v) => {
+                let n = v.as_u64().filter(|n| *n >= 1).context("snippetCount must be a positive integer")?;
+                (n as usize).min(config::sqlite::SEARCH_SNIPPET_MAX_COUNT)
+            }
+            None => 1,
+        };
+        Ok(Self {
+            open: get("snippetOpen", "[")?,
+            close: get("snippetClose", "]")?,
+            ellipsis: get("snippetEllipsis", "…")?,
+            count,
+            include_recipients: params.get("includeRecipients").and_then(|v| v.as_bool()).unwrap_or(false),
+            explain: params.get("explain").and_then(|v| v.as_bool()).unwrap_or(false),
+        })
+    }
+
+    fn multi(&self) -> bool {
+        self.count > 1
+    }
+
+    /// Add the opt-in keys (`snippets`, `to_` / `cc` / `bcc`, the `explain` ones) to a result object.
+    fn decorate(&self, result: &mut Value, snippets: &[String], recipients: &Recipients, ranking: RankDetails) {
+        if self.multi() {
+            result["snippets"] = serde_json::json!(snippets);
+        }
+        if self.include_recipients {
+            result["to_"] = serde_json::json!(recipients.to_);
+            result["cc"] = serde_json::json!(recipients.cc);
+            result["bcc"] = serde_json::json!(recipients.bcc);
+        }
+        if self.explain {
+            result["bm25Rank"] = serde_json::json!(ranking.bm25_rank);
+            result["distance"] = serde_json::json!(ranking.distance);
+            result["distanceMetric"] = serde_json::json!(distance_metric().as_str());
+            result["score"] = serde_json::json!(ranking.score);
+        }
+    }
+}
+
+/// Where a result's rank came from, reported with `explain`.
+struct RankDetails {
+    bm25_rank: Option<f64>,
+    distance: Option<f64>,
+    score: f64,
+}
+
+impl RankDetails {
+    /// FTS-only results are ranked by BM25 alone.
+    fn fts(bm25_rank: f64) -> Self {
+        let score = crate::fts::hybrid::bm25_rank_to_score(bm25_rank);
+        Self { bm25_rank: Some(bm25_rank), distance: None, score }
+    }
+}
+
+/// Recipient columns, only read from the index when `includeRecipients` is set.
+#[derive(Default)]
+struct Recipients {
+    to_: String,
+    cc: String,
+    bcc: String,
+}
+
+impl Recipients {
+    /// Read from three consecutive (nullable) columns starting at `idx`.
+    fn from_row(r: &rusqlite::Row, idx: usize) -> rusqlite::Result<Self> {
+        let col = |i: usize| -> rusqlite::Result<String> { Ok(r.get::<_, Option<String>>(idx + i)?.unwrap_or_default()) };
+        Ok(Self { to_: col(0)?, cc: col(1)?, bcc: col(2)? })
+    }
+}
+
+/// Context for a result no keyword matched (a vector-only hit): the first
+/// `SEARCH_SNIPPET_TOKENS` words of `text`, followed by `ellipsis` if cut short.
+pub(crate) fn preview_snippet(text: &str, ellipsis: &str) -> String {
+    let tokens = config::sqlite::SEARCH_SNIPPET_TOKENS.max(1) as usize;
+    let mut words = text.split_whitespace();
+    let mut s = words.by_ref().take(tokens).collect::<Vec<_>>().join(" ");
+    if words.next().is_some() {
+        s.push_str(ellipsis);
+    }
+    s
+}
+
+/// Cut up to `opts.count` non-overlapping windows of about `SEARCH_SNIPPET_TOKENS` words
+/// out of a `highlight()`ed body, one per match, in document order.
+fn extract_snippets(highlighted: &str, opts: &ResultOptions) -> Vec<String> {
+    let tokens = config::sqlite::SEARCH_SNIPPET_TOKENS.max(1) as usize;
+    let words: Vec<(usize, &str)> = highlighted
+        .split_whitespace()
+        .map(|w| (w.as_ptr() as usize - highlighted.as_ptr() as usize, w))
+        .collect();
+    // Index of the word containing byte offset `pos`.
+    let word_at = |pos: usize| words.iter().rposition(|(start, _)| *start <= pos).unwrap_or(0);
+
+    let mut out = Vec::new();
+    let mut covered_until = 0usize; // word index after the last emitted window
+    for (open_pos, _) in highlighted.match_indices(opts.open.as_str()) {
+        if out.len() >= opts.count {
+            break;
+        }
+        let first = word_at(open_pos);
+        if !out.is_empty() && first < covered_until {
+            continue;
+        }
+        // Never cut a highlighted span in half: the window extends to its close marker.
+        let last = highlighted[open_pos + opts.open.len()..]
+            .find(opts.close.as_str())
+            .map(|off| word_at(open_pos + opts.open.len() + off))
+            .unwrap_or(first);
+        let start = first.saturating_sub(tokens / 4).max(covered_until);
+        let end = (start + tokens).max(last + 1).min(words.len());
+
+        let mut s = String::new();
+        if start > 0 {
+            s.push_str(&opts.ellipsis);
+        }
+        s.push_str(&words[start..end].iter().map(|(_, w)| *w).collect::<Vec<_>>().join(" "));
+        if end < words.len() {
+            s.push_str(&opts.ellipsis);
+        }
+        out.push(s);
+        covered_until = end;
+    }
+    out
+}
+
+/// Search emails. Also reports the mode used (None for an empty query, where no search runs).
 pub fn search(
     conn: &Connection,
     q: &str,
     params: &Value,
     synonyms: &SynonymLookup,
     engine: Option<&EmbeddingEngine>,
-) -> anyhow::Result<Vec<Value>> {
+) -> anyhow::Result<(Vec<Value>, Option<SearchMode>)> {
     let query = q.trim();
     if query.is_empty() {
-        return Ok(vec![]);
+        return Ok((vec![], None));
     }
 
+    let fields = parse_fields_param(params)?;
+    let (results, mode) = search_balanced(conn, query, params, synonyms, engine)?;
+    let results = crate::fts::hybrid::dedup_results(results, "uniqueId");
+    match fields {
+        Some(fields) => Ok((project_fields(results, &fields), mode)),
+        None => Ok((results, mode)),
+    }
+}
+
+/// Run several related queries in one call (`searchMulti`): each goes through the normal
+/// `search` path (embedded once, same params). With `dedupe`, a uniqueId found by several
+/// queries is kept once, where it ranked best. With `merge`, returns one list sorted by rank
+/// (each result tagged with the `query` that found it) and cut to `limit`; otherwise one
+/// `{ q, results }` entry per query.
+pub fn search_multi(
+    conn: &Connection,
+    queries: &[String],
+    params: &Value,
+    synonyms: &SynonymLookup,
+    engine: Option<&EmbeddingEngine>,
+) -> anyhow::Result<(Value, Option<SearchMode>)> {
+    if queries.len() > config::sqlite::SEARCH_MULTI_MAX_QUERIES {
+        bail!("searchMulti accepts at most {} queries", config::sqlite::SEARCH_MULTI_MAX_QUERIES);
+    }
+    let merge = params.get("merge").and_then(|v| v.as_bool()).unwrap_or(false);
+    let dedupe = params.get("dedupe").and_then(|v| v.as_bool()).unwrap_or(true);
     let limit = params
         .get("limit")
         .and_then(|v| v.as_i64())
         .unwrap_or(config::sqlite::SEARCH_DEFAULT_LIMIT);
+    // Dedupe needs uniqueId and rank, so `fields` is applied after combining.
+    let fields = parse_fields_param(params)?;
+    let mut inner = params.clone();
+    if let Some(obj) = inner.as_object_mut() {
+        obj.remove("fields");
+    }
 
-    // Fall back to FTS-only when no embedding engine
-    let engine = match engine {
-        Some(e) => e,
-        None => return search_fts_only(conn, query, params, synonyms, limit),
-    };
+    let rank = |v: &Value| v.get("rank").and_then(|r| r.as_f64()).unwrap_or(f64::INFINITY);
+    let mut mode = None;
+    let mut per_query: Vec<(String, Vec<Value>)> = Vec::with_capacity(queries.len());
+    for q in queries {
+        let (results, m) = search(conn, q, &inner, synonyms, engine)?;
+        mode = mode.or(m);
+        per_query.push((q.clone(), results));
+    }
 
-    let ignore_date = params.get("ignoreDate").and_then(|v| v.as_bool()).unwrap_or(false);
-    let from_ts = if !ignore_date {
-        params.get("from").and_then(|v| parse_date_param(v).ok().flatten())
-    } else {
-        None
+    if dedupe {
+        // (query index, result index) of each uniqueId's best-ranked occurrence
+        let mut best: HashMap<String, (usize, usize)> = HashMap::new();
+        for (qi, (_, results)) in per_query.iter().enumerate() {
+            for (ri, r) in results.iter().enumerate() {
+                let Some(id) = r.get("uniqueId").and_then(|v| v.as_str()) else { continue };
+                match best.get(id) {
+                    Some(&(bq, br)) if rank(&per_query[bq].1[br]) <= rank(r) => {}
+                    _ => {
+                        best.insert(id.to_string(), (qi, ri));
+                    }
+                }
+            }
+        }
+        for (qi, (_, results)) in per_query.iter_mut().enumerate() {
+            let mut ri = 0;
+            results.retain(|r| {
+                let keep = match r.get("uniqueId").and_then(|v| v.as_str()) {
+                    Some(id) => best.get(id) == Some(&(qi, ri)),
+                    None => true,
+                };
+                ri += 1;
+                keep
+            });
+        }
+    }
+
+    let project = |results: Vec<Value>| match &fields {
+        Some(fields) => project_fields(results, fields),
+        None => results,
     };
-    let to_ts = if !ignore_date {
-        params.get("to").and_then(|v| parse_date_param(v).ok().flatten())
-    } else {
-        None
+    if merge {
+        let mut merged: Vec<(String, Value)> = Vec::new();
+        for (q, results) in per_query {
+            merged.extend(results.into_iter().map(|r| (q.clone(), r)));
+        }
+        merged.sort_by(|a, b| rank(&a.1).total_cmp(&rank(&b.1)));
+        merged.truncate(limit.max(0) as usize);
+        let (found_by, results): (Vec<String>, Vec<Value>) = merged.into_iter().unzip();
+        let results: Vec<Value> = project(results)
+            .into_iter()
+            .zip(found_by)
+            .map(|(mut r, q)| {
+                r["query"] = Value::from(q);
+                r
+            })
+            .collect();
+        return Ok((serde_json::json!({ "results": results }), mode));
+    }
+    let lists: Vec<Value> = per_query
+        .into_iter()
+        .map(|(q, results)| serde_json::json!({ "q": q, "results": project(results) }))
+        .collect();
+    Ok((serde_json::json!({ "perQuery": lists }), mode))
+}
+
+/// Keys a search result object can carry (the default projection).
+/// `snippets` is only present when `snippetCount` > 1; `to_` / `cc` / `bcc` with `includeRecipients`;
+/// `bm25Rank` / `distance` / `distanceMetric` / `score` with `explain`; `snippetKind: "preview"`
+/// on vector-only results, whose snippet is the start of the body rather than a match window.
+const SEARCH_RESULT_FIELDS: [&str; 17] = [
+    "uniqueId", "author", "subject", "dateMs", "hasAttachments", "tags", "snippet", "snippetKind", "snippets", "rank",
+    "to_", "cc", "bcc", "bm25Rank", "distance", "distanceMetric", "score",
+];
+
+/// Parse the optional `fields` projection. None = all fields.
+fn parse_fields_param(params: &Value) -> anyhow::Result<Option<Vec<String>>> {
+    let Some(v) = params.get("fields").filter(|v| !v.is_null()) else {
+        return Ok(None);
     };
+    let arr = v.as_array().context("fields must be an array of strings")?;
+    let mut fields = Vec::with_capacity(arr.len());
+    for f in arr {
+        let name = f.as_str().context("fields must be an array of strings")?;
+        if !SEARCH_RESULT_FIELDS.contains(&name) {
+            bail!("Unknown field '{}' (valid: {})", name, SEARCH_RESULT_FIELDS.join(", "));
+        }
+        fields.push(name.to_string());
+    }
+    Ok(Some(fields))
+}
 
-    let candidate_limit = limit * config::hybrid::CANDIDATE_MULTIPLIER;
+/// Keep only the requested keys in each result object.
+fn project_fields(results: Vec<Value>, fields: &[String]) -> Vec<Value> {
+    results
+        .into_iter()
+        .map(|mut r| {
+            if let Some(obj) = r.as_object_mut() {
+                obj.retain(|k, _| fields.iter().any(|f| f == k));
+            }
+            r
+        })
+        .collect()
+}
 
-    // --- FTS5 candidates ---
-    let fts_query = build_fts_match(Some(query), true, synonyms);
-    log::info!(
-        "Hybrid search: \"{}\" -> FTS \"{}\"",
+/// Ranked search with optional per-account balancing.
+fn search_balanced(
+    conn: &Connection,
+    query: &str,
+    params: &Value,
+    synonyms: &SynonymLookup,
+    engine: Option<&EmbeddingEngine>,
+) -> anyhow::Result<(Vec<Value>, Option<SearchMode>)> {
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(config::sqlite::SEARCH_DEFAULT_LIMIT);
+
+    // perAccountLimit: rank a wider pool, then cap each account and fill round-robin.
+    let per_account_limit = params
+        .get("perAccountLimit")
+        .and_then(|v| v.as_u64())
+        .filter(|&n| n > 0);
+    let Some(per_account_limit) = per_account_limit else {
+        let (results, mode) = search_ranked(conn, query, params, synonyms, engine, limit)?;
+        return Ok((results, Some(mode)));
+    };
+    let (pool, mode) = search_ranked(
+        conn,
         query,
-        fts_query
+        params,
+        synonyms,
+        engine,
+        limit * config::hybrid::CANDIDATE_MULTIPLIER,
+    )?;
+    let balanced = balance_per_account(pool, per_account_limit as usize, limit.max(0) as usize);
+    log::info!(
+        "Per-account balancing: {} results (perAccountLimit={})",
+        balanced.len(),
+        per_account_limit
     );
-    let fts_candidates = if !fts_query.is_empty() {
-        search_fts_candidates(conn, &fts_query, from_ts, to_ts, candidate_limit)?
-    } else {
-        vec![]
+    Ok((balanced, Some(mode)))
+}
+
+/// Account id of a msgId key (`accountId:folderPath:headerMessageId`).
+fn account_of(msg_id: &str) -> &str {
+    msg_id.split(':').next().unwrap_or("")
+}
+
+/// Keep at most `per_account` results per account, taking one result from each
+/// account in turn (accounts ordered by their best-ranked hit) until `limit` is reached.
+fn balance_per_account(results: Vec<Value>, per_account: usize, limit: usize) -> Vec<Value> {
+    let mut groups: Vec<(String, std::collections::VecDeque<Value>)> = Vec::new();
+    for r in results {
+        let account = account_of(r.get("uniqueId").and_then(|v| v.as_str()).unwrap_or("")).to_string();
+        match groups.iter_mut().find(|(a, _)| *a == account) {
+            Some((_, g)) => g.push_back(r),
+            None => groups.push((account, std::collections::VecDeque::from([r]))),
+        }
+    }
+    for (_, g) in groups.iter_mut() {
+        g.truncate(per_account);
+    }
+
+    let mut out = Vec::new();
+    while out.len() < limit {
+        let mut took_any = false;
+        for (_, g) in groups.iter_mut() {
+            if out.len() >= limit {
+                break;
+            }
+            if let Some(r) = g.pop_front() {
+                out.push(r);
+                took_any = true;
+            }
+        }
+        if !took_any {
+            break;
+        }
+    }
+    out
+}
+
+/// Ranked search (hybrid, or FTS-only fallback) returning at most `limit` results.
+/// Hybrid candidate counts (FTS, vec). Each defaults to `limit × CANDIDATE_MULTIPLIER`;
+/// `ftsCandidateLimit` / `vecCandidateLimit` trade recall against latency per engine.
+pub(crate) fn candidate_limits(params: &Value, limit: i64) -> (i64, i64) {
+    let default = limit * config::hybrid::CANDIDATE_MULTIPLIER;
+    let get = |key: &str| {
+        params
+            .get(key)
+            .and_then(|v| v.as_i64())
+            .filter(|&n| n > 0)
+            .map_or(default, |n| n.min(config::hybrid::CANDIDATE_LIMIT_MAX))
     };
+    (get("ftsCandidateLimit"), get("vecCandidateLimit"))
+}
 
-    // --- Vector candidates ---
-    let query_embedding = engine.embed(query)?;
-    let query_blob = f32_vec_to_blob(&query_embedding);
-    let vec_candidates = search_vec_candidates(conn, "messages_vec", &query_blob, candidate_limit)
-        .unwrap_or_default(); // empty vec table during rebuild → graceful empty
-
-    // Fall back to FTS-only when vec table is empty (e.g., during embedding rebuild).
-    // Without this, hybrid weights (text_weight=0.3) penalize text-only results below MIN_SCORE.
-    if vec_candidates.is_empty() {
-        log::info!("No vector candidates (vec table may be empty), falling back to FTS-only search");
-        return search_fts_only(conn, query, params, synonyms, limit);
-    }
-
-    // --- Merge ---
-    let text_pairs: Vec<(i64, f64)> = fts_candidates.iter().map(|c| (c.rowid, c.rank)).collect();
-    let merged = crate::fts::hybrid::merge_results(
-        &text_pairs,
-        &vec_candidates,
-        config::hybrid::EMAIL_VECTOR_WEIGHT,
-        config::hybrid::EMAIL_TEXT_WEIGHT,
-        limit as usize,
-    );
+/// KNN `k` for a search that may be date-scoped: widened by `DATE_FILTER_VEC_K_MULTIPLIER`
+/// when a date bound is active, unless the caller pinned `vecCandidateLimit`.
+pub(crate) fn date_scoped_vec_k(params: &Value, k: i64, date_filtered: bool) -> i64 {
+    let explicit = params.get("vecCandidateLimit").and_then(|v| v.as_i64()).is_some_and(|n| n > 0);
+    if !date_filtered || explicit {
+        return k;
+    }
+    k.saturating_mul(config::hybrid::DATE_FILTER_VEC_K_MULTIPLIER)
+        .min(config::hybrid::CANDIDATE_LIMIT_MAX)
+}
 
-    // --- Assemble results ---
-    let mut fts_map: HashMap<i64, FtsCandidate> =
-        fts_candidates.into_iter().map(|c| (c.rowid, c)).collect();
-    let mut results = Vec::with_capacity(merged.len());
-
-    for hr in &merged {
-        if let Some(fts_c) = fts_map.remove(&hr.rowid) {
-            // FTS result — has snippet
-            results.push(serde_json::json!({
-                "uniqueId": fts_c.msg_id,
-                "author": fts_c.from_,
-                "subject": fts_c.subject,
-                "dateMs": fts_c.date_ms,
-                "hasAttachments": fts_c.has_attachments,
-                "snippet": fts_c.snippet,
-                "rank": -hr.final_score
-            }));
+/// Drop vector candidates whose `meta_table.dateMs` is outside the range, before merging,
+/// so out-of-range neighbours don't take merged slots only to be filtered out afterwards.
+pub(crate) fn retain_in_date_range(
+    conn: &Connection,
+    meta_table: &str,
+    candidates: &mut Vec<(i64, f64)>,
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+    inclusive: DateInclusivity,
+) -> anyhow::Result<()> {
+    if from_ts.is_none() && to_ts.is_none() {
+        return Ok(());
+    }
+    let before = candidates.len();
+    let mut stmt = conn.prepare(&format!("SELECT dateMs FROM {meta_table} WHERE rowid = ?1"))?;
+    let mut keep = Vec::with_capacity(candidates.len());
+    for &(rowid, distance) in candidates.iter() {
+        let date_ms: Option<i64> = stmt.query_row(params![rowid], |r| r.get(0)).optional()?;
+        if date_ms.is_some_and(|d| inclusive.admits(d, from_ts, to_ts)) {
+            keep.push((rowid, distance));
+        }
+    }
+    *candidates = keep;
+    log::info!("Date filter kept {}/{} vector candidates", candidates.len(), before);
+    Ok(())
+}
+
+/// Ceiling for over-fetch retries: `maxCandidateLimit` when given (0 disables retries),
+/// else `OVERFETCH_MAX_CANDIDATES`; never above vec0's k limit.
+pub(crate) fn overfetch_max(params: &Value) -> i64 {
+    params
+        .get("maxCandidateLimit")
+        .and_then(|v| v.as_i64())
+        .filter(|&n| n >= 0)
+        .unwrap_or(config::hybrid::OVERFETCH_MAX_CANDIDATES)
+        .min(config::hybrid::CANDIDATE_LIMIT_MAX)
+}
+
+/// One hybrid pass: its output plus how many results it produced and how many
+/// candidates each engine returned before filtering.
+pub(crate) struct HybridPass<T> {
+    pub value: T,
+    pub found: usize,
+    pub fts_fetched: usize,
+    pub vec_fetched: usize,
+}
+
+/// Candidate counts for the next pass after one that produced `pass.found` of `limit`
+/// results with `(fts_k, vec_k)`, or `None` when another pass can't help. Only an engine
+/// whose pool came back full grows (by `OVERFETCH_GROWTH`, up to `max`); a pool that
+/// returned fewer rows than asked for already holds every match.
+pub(crate) fn grow_candidate_limits<T>(
+    pass: &HybridPass<T>,
+    limit: i64,
+    (fts_k, vec_k): (i64, i64),
+    max: i64,
+) -> Option<(i64, i64)> {
+    if pass.found as i64 >= limit {
+        return None;
+    }
+    let grow = |k: i64, fetched: usize| {
+        if fetched as i64 >= k && k < max {
+            k.saturating_mul(config::hybrid::OVERFETCH_GROWTH).min(max)
         } else {
-            // Vector-only result — fetch metadata, apply date filter
-            if let Some(meta) = fetch_message_meta(conn, hr.rowid)? {
-                if let Some(from) = from_ts {
-                    if meta.date_ms < from {
-                        continue;
-                    }
-                }
-                if let Some(to) = to_ts {
-                    if meta.date_ms > to {
-                        continue;
-                    }
-                }
-                results.push(serde_json::json!({
-                    "uniqueId": meta.msg_id,
-                    "author": meta.from_,
-                    "subject": meta.subject,
-                    "dateMs": meta.date_ms,
-                    "hasAttachments": meta.has_attachments,
-                    "snippet": "",
-                    "rank": -hr.final_score
-                }));
+            k
+        }
+    };
+    let next = (grow(fts_k, pass.fts_fetched), grow(vec_k, pass.vec_fetched));
+    (next != (fts_k, vec_k)).then_some(next)
+}
+
+/// Run `pass` with `initial` (FTS, vec) candidate counts, re-running it with larger pools
+/// while filters leave it short of `limit` (see `grow_candidate_limits`).
+pub(crate) fn with_overfetch<T>(
+    limit: i64,
+    initial: (i64, i64),
+    max: i64,
+    mut pass: impl FnMut(i64, i64) -> anyhow::Result<HybridPass<T>>,
+) -> anyhow::Result<T> {
+    let mut ks = initial;
+    loop {
+        let outcome = pass(ks.0, ks.1)?;
+        match grow_candidate_limits(&outcome, limit, ks, max) {
+            Some(next) => {
+                log::info!(
+                    "Over-fetch: {}/{} results with candidates {:?}, retrying with {:?}",
+                    outcome.found,
+                    limit,
+                    ks,
+                    next
+                );
+                ks = next;
             }
+            None => return Ok(outcome.value),
         }
     }
+}
 
-    log::info!(
-        "Hybrid search completed: {} results (FTS cands: {}, Vec cands: {})",
-        results.len(),
-        text_pairs.len(),
-        vec_candidates.len()
-    );
-    Ok(results)
+fn search_ranked(
+    conn: &Connection,
+    query: &str,
+    params: &Value,
+    synonyms: &SynonymLookup,
+    engine: Option<&EmbeddingEngine>,
+    limit: i64,
+) -> anyhow::Result<(Vec<Value>, SearchMode)> {
+    // Fall back to FTS-only when no embedding engine
+    let engine = match engine {
+        Some(e) => e,
+        None => {
+            let opts = ResultOptions::from_params(params)?;
+            return Ok((search_fts_only(conn, query, params, synonyms, &opts, limit)?, SearchMode::Fts));
+        }
+    };
+    let query_embedding = engine.embed(query, EmbedRole::Query)?;
+    search_hybrid(conn, query, params, synonyms, &query_embedding, Some(engine), limit)
 }
 
-/// Original FTS-only search (used when embedding engine is not available).
-fn search_fts_only(
+/// The hybrid pass of `search_ranked` for an already embedded query. `rerank_engine` is
+/// only used for `rerankOnFallback`; without it an empty vec table falls back to FTS-only.
+fn search_hybrid(
     conn: &Connection,
     query: &str,
     params: &Value,
     synonyms: &SynonymLookup,
+    query_embedding: &[f32],
+    rerank_engine: Option<&EmbeddingEngine>,
     limit: i64,
-) -> anyhow::Result<Vec<Value>> {
-    let fts_query = build_fts_match(Some(query), true, synonyms);
+) -> anyhow::Result<(Vec<Value>, SearchMode)> {
+    let opts = ResultOptions::from_params(params)?;
+    let filters = SearchFilters::lenient(params)?;
+    let recency = RecencyBoost::from_params(params, chrono::Utc::now().timestamp_millis())?;
+    let (fts_candidate_limit, vec_candidate_limit) = candidate_limits(params, limit);
+    let vec_candidate_limit = date_scoped_vec_k(params, vec_candidate_limit, filters.has_date_bounds());
+
+    let fts_query = build_fts_match(Some(query), true, synonyms, auto_wildcard_min_len(params));
     log::info!(
-        "Query transformation (with synonyms): \"{}\" -> \"{}\"",
+        "Hybrid search: \"{}\" -> FTS \"{}\"",
         query,
         fts_query
     );
-    if fts_query.is_empty() {
-        log::info!("Empty FTS query after normalization (e.g. only stop words or wildcards provided)");
-        return Ok(vec![]);
-    }
+    let query_blob = f32_vec_to_blob(query_embedding);
+
+    // Filters (dates on both engines, tags/flags/addresses on vector-only hits) can leave
+    // fewer than `limit` results; `with_overfetch` re-runs the pass with larger pools if so.
+    let fts_only = |conn: &Connection| -> anyhow::Result<HybridPass<(Vec<Value>, SearchMode)>> {
+        let results = search_fts_only(conn, query, params, synonyms, &opts, limit)?;
+        let found = results.len();
+        Ok(HybridPass { value: (results, SearchMode::Fts), found, fts_fetched: 0, vec_fetched: 0 })
+    };
+    let pass = |fts_candidate_limit: i64, vec_candidate_limit: i64| {
+        // --- FTS5 candidates ---
+        let fts_candidates = if !fts_query.is_empty() {
+            search_fts_candidates(conn, &fts_query, &filters, &opts, fts_candidate_limit)?
+        } else {
+            vec![]
+        };
+
+        // --- Vector candidates ---
+        let mut vec_candidates = search_vec_candidates(conn, "messages_vec", &query_blob, vec_candidate_limit)
+            .unwrap_or_default(); // empty vec table during rebuild → graceful empty
+        let vec_fetched = vec_candidates.len();
+        retain_in_date_range(conn, "message_meta", &mut vec_candidates, filters.from_ts, filters.to_ts, filters.inclusive)?;
+        let mut mode = SearchMode::Hybrid;
+
+        // Fall back to FTS-only when vec table is empty (e.g., during embedding rebuild).
+        // Without this, hybrid weights (text_weight=0.3) penalize text-only results below MIN_SCORE.
+        // With rerankOnFallback, the top FTS candidates are embedded on the fly instead.
+        if vec_fetched == 0 {
+            let rerank = params.get("rerankOnFallback").and_then(|v| v.as_bool()).unwrap_or(false);
+            let engine = match rerank_engine {
+                Some(engine) if rerank && !fts_candidates.is_empty() => engine,
+                _ => {
+                    log::info!("No vector candidates (vec table may be empty), falling back to FTS-only search");
+                    return fts_only(conn);
+                }
+            };
+            vec_candidates = rerank_vec_candidates(conn, engine, query_embedding, &fts_candidates)?;
+            mode = SearchMode::Fts;
+        }
+        let fts_fetched = fts_candidates.len();
+
+        // --- Merge ---
+        let text_pairs: Vec<(i64, f64)> = fts_candidates.iter().map(|c| (c.rowid, c.rank)).collect();
+        let default_weights = (config::hybrid::EMAIL_VECTOR_WEIGHT, config::hybrid::EMAIL_TEXT_WEIGHT);
+        let account_weights = AccountWeights::from_params(params, default_weights)?;
+        // Weights depend on each candidate's account: FTS candidates carry their msgId,
+        // vector-only ones cost a message_ids lookup each.
+        let mut accounts: HashMap<i64, String> = HashMap::new();
+        if account_weights.is_some() {
+            accounts.extend(fts_candidates.iter().map(|c| (c.rowid, account_of(&c.msg_id).to_string())));
+            for &(rowid, _) in &vec_candidates {
+                if let std::collections::hash_map::Entry::Vacant(e) = accounts.entry(rowid) {
+                    let msg_id: Option<String> = conn
+                        .query_row("SELECT msgId FROM message_ids WHERE rowid = ?1", params![rowid], |r| r.get(0))
+                        .optional()?;
+                    e.insert(account_of(msg_id.as_deref().unwrap_or("")).to_string());
+                }
+            }
+        }
+        let weights_for = |rowid: i64| {
+            account_weights
+                .as_ref()
+                .and_then(|w| accounts.get(&rowid).and_then(|a| w.get(a)))
+                .unwrap_or(default_weights)
+        };
+        // Same split for dates when the recency boost is on.
+        let mut dates: HashMap<i64, i64> = HashMap::new();
+        if recency.is_some() {
+            dates.extend(fts_candidates.iter().map(|c| (c.rowid, c.date_ms)));
+            for &(rowid, _) in &vec_candidates {
+                if let std::collections::hash_map::Entry::Vacant(e) = dates.entry(rowid) {
+                    let date_ms: Option<i64> = conn
+                        .query_row("SELECT dateMs FROM message_meta WHERE rowid = ?1", params![rowid], |r| r.get(0))
+                        .optional()?;
+                    e.insert(date_ms.unwrap_or(0));
+                }
+            }
+        }
+        let bonus = |rowid: i64| recency.map_or(0.0, |r| r.bonus(dates.get(&rowid).copied().unwrap_or(0)));
+        // Uncapped: vector-only hits are filtered below, so the page is cut after that.
+        let merged = crate::fts::hybrid::merge_results_boosted(
+            &text_pairs,
+            &vec_candidates,
+            weights_for,
+            bonus,
+            usize::MAX,
+        );
+
+        // --- Assemble results ---
+        let mut fts_map: HashMap<i64, FtsCandidate> =
+            fts_candidates.into_iter().map(|c| (c.rowid, c)).collect();
+        let mut results = Vec::with_capacity(merged.len().min(limit as usize));
+
+        for hr in &merged {
+            if results.len() >= limit as usize {
+                break;
+            }
+            if let Some(fts_c) = fts_map.remove(&hr.rowid) {
+                // FTS result — has snippet
+                let mut result = serde_json::json!({
+                    "uniqueId": fts_c.msg_id,
+                    "author": fts_c.from_,
+                    "subject": fts_c.subject,
+                    "dateMs": fts_c.date_ms,
+                    "hasAttachments": fts_c.has_attachments,
+                    "tags": fts_c.tags,
+                    "snippet": fts_c.snippet,
+                    "rank": -hr.final_score
+                });
+                let ranking =
+                    RankDetails { bm25_rank: Some(fts_c.rank), distance: hr.distance, score: hr.final_score };
+                opts.decorate(&mut result, &fts_c.snippets, &fts_c.recipients, ranking);
+                results.push(result);
+            } else {
+                // Vector-only result — fetch metadata, apply the same filters as the FTS query
+                if let Some(meta) = fetch_message_meta(conn, hr.rowid)? {
+                    if !filters.admits(&meta) {
+                        continue;
+                    }
+                    let mut result = serde_json::json!({
+                        "uniqueId": meta.msg_id,
+                        "author": meta.from_,
+                        "subject": meta.subject,
+                        "dateMs": meta.date_ms,
+                        "hasAttachments": meta.has_attachments,
+                        "tags": meta.tags,
+                        "snippet": preview_snippet(&meta.body, &opts.ellipsis),
+                        "snippetKind": "preview",
+                        "rank": -hr.final_score
+                    });
+                    let ranking = RankDetails { bm25_rank: None, distance: hr.distance, score: hr.final_score };
+                    opts.decorate(&mut result, &[], &meta.recipients, ranking);
+                    results.push(result);
+                }
+            }
+        }
+
+        log::info!(
+            "Hybrid search completed: {} results (FTS cands: {}, Vec cands: {})",
+            results.len(),
+            text_pairs.len(),
+            vec_candidates.len()
+        );
+        let found = results.len();
+        // A rerank pass embeds the FTS pool itself; only its FTS side can grow.
+        let vec_fetched = if mode == SearchMode::Hybrid { vec_fetched } else { 0 };
+        Ok(HybridPass { value: (results, mode), found, fts_fetched, vec_fetched })
+    };
 
-    let ignore_date = params.get("ignoreDate").and_then(|v| v.as_bool()).unwrap_or(false);
+    with_overfetch(limit, (fts_candidate_limit, vec_candidate_limit), overfetch_max(params), pass)
+}
 
+// ORDER BY clauses for the two FTS query shapes.
+const FTS_ONLY_ORDER: &str = "meta.dateMs DESC, rank ASC";
+const FTS_CANDIDATE_ORDER: &str = "rank ASC";
+const FTS_STREAM_ORDER: &str = "fts.rowid ASC";
+
+/// Build the FTS5 search SQL and bind values. Shared by the search paths and
+/// `explain_search_plan`, so the explained plan is the plan a real search gets.
+/// Columns: rowid, msgId, from_, subject, dateMs, hasAttachments, snippet, rank, body_hl
+/// (the highlighted body when `snippetCount` > 1, else NULL), to_, cc, bcc (NULL unless
+/// `includeRecipients`), tags.
+fn build_fts_search_sql(
+    fts_query: &str,
+    filters: &SearchFilters,
+    opts: &ResultOptions,
+    order_by: &str,
+    limit: i64,
+) -> (String, Vec<rusqlite::types::Value>) {
     let mut sql = format!(
         r#"
         SELECT
+            fts.rowid,
             fts.msgId, fts.from_, fts.subject, meta.dateMs, meta.hasAttachments,
-            snippet(messages_fts, -1, '[', ']', '…', {snippet_tokens}) AS snippet,
-            bm25(messages_fts, 0.0, 5.0, 3.0, 2.0, 1.0, 1.0, 1.0) AS rank
+            snippet(messages_fts, -1, ?2, ?3, ?4, {snippet_tokens}) AS snippet,
+            bm25(messages_fts, 0.0, 5.0, 3.0, 2.0, 1.0, 1.0, 1.0, 1.0) AS rank,
+            {body_hl} AS body_hl,
+            {recipients},
+            meta.tags
         FROM messages_fts fts
         JOIN message_meta meta ON fts.rowid = meta.rowid
         WHERE messages_fts MATCH ?1
         "#,
-        snippet_tokens = config::sqlite::SEARCH_SNIPPET_TOKENS
+        snippet_tokens = config::sqlite::SEARCH_SNIPPET_TOKENS,
+        // Column 6 = body
+        body_hl = if opts.multi() { "highlight(messages_fts, 6, ?2, ?3)" } else { "NULL" },
+        recipients = if opts.include_recipients { "fts.to_, fts.cc, fts.bcc" } else { "NULL, NULL, NULL" }
     );
 
-    let mut bind: Vec<rusqlite::types::Value> = vec![rusqlite::types::Value::from(fts_query.clone())];
+    // ?1 = MATCH, ?2..?4 = snippet markers; date filters and limit follow as ?5, ?6, ...
+    let mut bind: Vec<rusqlite::types::Value> = vec![
+        rusqlite::types::Value::from(fts_query.to_string()),
+        rusqlite::types::Value::from(opts.open.clone()),
+        rusqlite::types::Value::from(opts.close.clone()),
+        rusqlite::types::Value::from(opts.ellipsis.clone()),
+    ];
+
+    if let Some(from) = filters.from_ts {
+        sql.push_str(&format!(" AND meta.dateMs {} ?", filters.inclusive.lower_op()));
+        bind.push(rusqlite::types::Value::from(from));
+    }
+    if let Some(to) = filters.to_ts {
+        sql.push_str(&format!(" AND meta.dateMs {} ?", filters.inclusive.upper_op()));
+        bind.push(rusqlite::types::Value::from(to));
+    }
+    if let Some((start, end)) = filters.exclude {
+        sql.push_str(" AND NOT (meta.dateMs BETWEEN ? AND ?)");
+        bind.push(rusqlite::types::Value::from(start));
+        bind.push(rusqlite::types::Value::from(end));
+    }
+    if let Some(lang) = &filters.lang {
+        sql.push_str(" AND meta.lang = ?");
+        bind.push(rusqlite::types::Value::from(lang.clone()));
+    }
+    // Every requested tag must be present (whole-word match in the space-separated list).
+    for tag in &filters.tags {
+        sql.push_str(r" AND (' ' || meta.tags || ' ') LIKE ? ESCAPE '\'");
+        bind.push(rusqlite::types::Value::from(format!("% {} %", escape_like(tag))));
+    }
+    for (column, want) in [("isRead", filters.is_read), ("isStarred", filters.is_starred)] {
+        if let Some(want) = want {
+            sql.push_str(&format!(" AND meta.{column} = ?"));
+            bind.push(rusqlite::types::Value::from(want));
+        }
+    }
+    if let Some(addr) = &filters.from_addr {
+        sql.push_str(" AND meta.fromAddr = ?");
+        bind.push(rusqlite::types::Value::from(addr.clone()));
+    }
+    if let Some(addr) = &filters.to_addr {
+        sql.push_str(" AND meta.rowid IN (SELECT msgRowid FROM message_to_addrs WHERE addr = ?)");
+        bind.push(rusqlite::types::Value::from(addr.clone()));
+    }
+    if let Some(after) = filters.after_rowid {
+        sql.push_str(" AND fts.rowid > ?");
+        bind.push(rusqlite::types::Value::from(after));
+    }
+
+    sql.push_str(&format!(" ORDER BY {order_by} LIMIT ?"));
+    bind.push(rusqlite::types::Value::from(limit));
+    (sql, bind)
+}
+
+/// Row filters shared by the FTS query and the post-merge check on vector-only hits:
+/// date bounds, an excluded date window, `lang` (ISO 639-3 code from language detection),
+/// `tags` (all required), the `isRead` / `isStarred` flags and the `fromAddr` / `toAddr`
+/// addresses.
+struct SearchFilters {
+    from_ts: Option<i64>,
+    to_ts: Option<i64>,
+    inclusive: DateInclusivity,
+    /// `excludeFrom` / `excludeTo`: dates in `[start, end]` are dropped (a missing end is open).
+    exclude: Option<(i64, i64)>,
+    lang: Option<String>,
+    tags: Vec<String>,
+    /// `isRead` / `isStarred`: only messages whose flag is known and equal.
+    is_read: Option<bool>,
+    is_starred: Option<bool>,
+    /// `fromAddr`: the sender's address; `toAddr`: one of the To addresses (normalized).
+    from_addr: Option<String>,
+    to_addr: Option<String>,
+    /// searchStream keyset cursor: only rows after this rowid (SQL only).
+    after_rowid: Option<i64>,
+}
+
+/// Which ends of a from/to date range match (`dateRangeInclusive` param):
+/// "both" (default, `[from, to]`), "fromOnly" (`[from, to)`, for paging by day without
+/// double-counting boundary timestamps) or "neither" (`(from, to)`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum DateInclusivity {
+    #[default]
+    Both,
+    FromOnly,
+    Neither,
+}
+
+impl DateInclusivity {
+    pub(crate) fn from_params(params: &Value) -> anyhow::Result<Self> {
+        match params.get("dateRangeInclusive").and_then(|v| v.as_str()) {
+            None | Some("both") => Ok(Self::Both),
+            Some("fromOnly") => Ok(Self::FromOnly),
+            Some("neither") => Ok(Self::Neither),
+            Some(other) => bail!(
+                "Invalid dateRangeInclusive '{}' (expected \"both\", \"fromOnly\" or \"neither\")",
+                other
+            ),
+        }
+    }
+
+    /// SQL comparison operator for the lower bound (`dateMs {op} from`).
+    pub(crate) fn lower_op(self) -> &'static str {
+        if self == Self::Neither { ">" } else { ">=" }
+    }
+
+    /// SQL comparison operator for the upper bound (`dateMs {op} to`).
+    pub(crate) fn upper_op(self) -> &'static str {
+        if self == Self::Both { "<=" } else { "<" }
+    }
+
+    /// Same test as the SQL operators, for rows filtered in Rust.
+    pub(crate) fn admits(self, date_ms: i64, from_ts: Option<i64>, to_ts: Option<i64>) -> bool {
+        let after_from = match from_ts {
+            None => true,
+            Some(from) if self == Self::Neither => date_ms > from,
+            Some(from) => date_ms >= from,
+        };
+        let before_to = match to_ts {
+            None => true,
+            Some(to) if self == Self::Both => date_ms <= to,
+            Some(to) => date_ms < to,
+        };
+        after_from && before_to
+    }
+}
 
-    if !ignore_date {
-        if let Some(from_v) = params.get("from") {
-            if let Some(ts) = parse_date_param(from_v)? {
-                sql.push_str(" AND meta.dateMs >= ?");
-                bind.push(rusqlite::types::Value::from(ts));
+impl SearchFilters {
+    fn has_date_bounds(&self) -> bool {
+        self.from_ts.is_some() || self.to_ts.is_some() || self.exclude.is_some()
+    }
+
+    /// FTS-only path: unparseable dates are an error.
+    fn strict(params: &Value) -> anyhow::Result<Self> {
+        for key in ["fromAddr", "toAddr"] {
+            let raw = params.get(key).and_then(|v| v.as_str()).map(str::trim).unwrap_or("");
+            if !raw.is_empty() && crate::fts::address::parse_addresses(raw).is_empty() {
+                bail!("Invalid {} '{}' (expected an email address)", key, raw);
             }
         }
-        if let Some(to_v) = params.get("to") {
-            if let Some(ts) = parse_date_param(to_v)? {
-                sql.push_str(" AND meta.dateMs <= ?");
-                bind.push(rusqlite::types::Value::from(ts));
+        let tz = DateTz::from_params(params)?;
+        let (from_ts, to_ts) = strict_date_bounds(params, tz)?;
+        let inclusive = DateInclusivity::from_params(params)?;
+        let mut filters = Self::with_dates(params, from_ts, to_ts, inclusive);
+        filters.exclude = if params.get("ignoreDate").and_then(|v| v.as_bool()).unwrap_or(false) {
+            None
+        } else {
+            let bound = |key: &str| params.get(key).map(|v| parse_date_param_tz(v, tz)).transpose().map(Option::flatten);
+            exclusion_window(bound("excludeFrom")?, bound("excludeTo")?)
+        };
+        Ok(filters)
+    }
+
+    /// Hybrid path: unparseable dates are ignored rather than failing the search. An invalid
+    /// `dateRangeInclusive` is still an error, as on the FTS path.
+    fn lenient(params: &Value) -> anyhow::Result<Self> {
+        let ignore_date = params.get("ignoreDate").and_then(|v| v.as_bool()).unwrap_or(false);
+        let tz = DateTz::from_params(params).unwrap_or_else(|e| {
+            log::warn!("{e}; using local time");
+            DateTz::default()
+        });
+        let bound = |key: &str| {
+            if ignore_date {
+                None
+            } else {
+                params.get(key).and_then(|v| parse_date_param_tz(v, tz).ok().flatten())
             }
+        };
+        let inclusive = DateInclusivity::from_params(params)?;
+        let mut filters = Self::with_dates(params, bound("from"), bound("to"), inclusive);
+        filters.exclude = exclusion_window(bound("excludeFrom"), bound("excludeTo"));
+        Ok(filters)
+    }
+
+    fn with_dates(params: &Value, from_ts: Option<i64>, to_ts: Option<i64>, inclusive: DateInclusivity) -> Self {
+        let lang = params
+            .get("lang")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        let tags = normalize_tags(params.get("tags"))
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let flag = |key: &str| params.get(key).and_then(|v| v.as_bool());
+        // Accepts a bare address or a full "Name <addr>" form (`strict` rejects anything else).
+        let address = |key: &str| {
+            let raw = params.get(key).and_then(|v| v.as_str())?;
+            let addr = crate::fts::address::parse_addresses(raw).into_iter().next();
+            if addr.is_none() && !raw.trim().is_empty() {
+                log::warn!("Ignoring unparseable {} '{}'", key, raw);
+            }
+            addr
+        };
+        Self {
+            from_ts,
+            to_ts,
+            inclusive,
+            exclude: None,
+            lang,
+            tags,
+            is_read: flag("isRead"),
+            is_starred: flag("isStarred"),
+            from_addr: address("fromAddr"),
+            to_addr: address("toAddr"),
+            after_rowid: None,
         }
     }
 
-    sql.push_str(" ORDER BY meta.dateMs DESC, rank ASC LIMIT ?");
-    bind.push(rusqlite::types::Value::from(limit));
+    /// Same checks as the SQL filters, for rows fetched outside the FTS query.
+    fn admits(&self, meta: &MessageMeta) -> bool {
+        if !self.inclusive.admits(meta.date_ms, self.from_ts, self.to_ts) {
+            return false;
+        }
+        if self.exclude.is_some_and(|(start, end)| (start..=end).contains(&meta.date_ms)) {
+            return false;
+        }
+        if self.lang.is_some() && meta.lang != self.lang {
+            return false;
+        }
+        if self.is_read.is_some_and(|want| meta.is_read != Some(want))
+            || self.is_starred.is_some_and(|want| meta.is_starred != Some(want))
+        {
+            return false;
+        }
+        if self.from_addr.is_some() && meta.from_addr != self.from_addr {
+            return false;
+        }
+        if let Some(want) = &self.to_addr {
+            if !meta.to_addr.as_deref().unwrap_or("").split(' ').any(|have| have == want) {
+                return false;
+            }
+        }
+        self.tags
+            .iter()
+            .all(|want| meta.tags.iter().any(|have| have.eq_ignore_ascii_case(want)))
+    }
+}
+
+/// `[excludeFrom, excludeTo]` as a closed window; either end may be omitted (open-ended).
+fn exclusion_window(start: Option<i64>, end: Option<i64>) -> Option<(i64, i64)> {
+    if start.is_none() && end.is_none() {
+        return None;
+    }
+    Some((start.unwrap_or(i64::MIN), end.unwrap_or(i64::MAX)))
+}
+
+/// Escape `%`, `_` and `\` for a LIKE pattern using `ESCAPE '\'`.
+fn escape_like(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Date bounds for the FTS-only path: honours `ignoreDate`, rejects unparseable dates.
+fn strict_date_bounds(params: &Value, tz: DateTz) -> anyhow::Result<(Option<i64>, Option<i64>)> {
+    if params.get("ignoreDate").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return Ok((None, None));
+    }
+    let from_ts = match params.get("from") {
+        Some(v) => parse_date_param_tz(v, tz)?,
+        None => None,
+    };
+    let to_ts = match params.get("to") {
+        Some(v) => parse_date_param_tz(v, tz)?,
+        None => None,
+    };
+    Ok((from_ts, to_ts))
+}
+
+/// Approximate vector candidates for the rerank-on-fallback path: embed the stored text of
+/// the top FTS candidates (already in bm25 order) and compute their distance in Rust
+/// (in the active distance metric).
+fn rerank_vec_candidates(
+    conn: &Connection,
+    engine: &EmbeddingEngine,
+    query_embedding: &[f32],
+    fts_candidates: &[FtsCandidate],
+) -> anyhow::Result<Vec<(i64, f64)>> {
+    let start = std::time::Instant::now();
+    let mut stmt = conn.prepare(
+        "SELECT subject, from_, to_, cc, bcc, body FROM message_content WHERE rowid = ?1",
+    )?;
+    let mut out = Vec::new();
+    for c in fts_candidates.iter().take(config::hybrid::RERANK_FALLBACK_MAX_CANDIDATES) {
+        let text: Option<EmailText> = stmt.query_row(params![c.rowid], |r| EmailText::read(r, 0)).optional()?;
+        let Some(text) = text else { continue };
+        let texts = text.prepare();
+        let key = crate::fts::embed_cache::prepared_text_hash(&texts, engine.prefix(EmbedRole::Document));
+        if let Some(embedding) = crate::fts::embed_cache::get(&key) {
+            out.push((c.rowid, distance_metric().distance(query_embedding, &embedding)));
+            continue;
+        }
+        match engine.embed_prepared(&texts) {
+            Ok(embedding) if is_degenerate_embedding(&embedding) => {}
+            Ok(embedding) => {
+                out.push((c.rowid, distance_metric().distance(query_embedding, &embedding)));
+                crate::fts::embed_cache::insert(key, embedding);
+            }
+            Err(e) => log::warn!("Rerank: failed to embed rowid {}: {}", c.rowid, e),
+        }
+    }
+    log::info!(
+        "Vec table empty — reranked {} FTS candidates with on-the-fly embeddings in {}ms",
+        out.len(),
+        start.elapsed().as_millis()
+    );
+    Ok(out)
+}
+
+/// Original FTS-only search (used when embedding engine is not available).
+fn search_fts_only(
+    conn: &Connection,
+    query: &str,
+    params: &Value,
+    synonyms: &SynonymLookup,
+    opts: &ResultOptions,
+    limit: i64,
+) -> anyhow::Result<Vec<Value>> {
+    let fts_query = build_fts_match(Some(query), true, synonyms, auto_wildcard_min_len(params));
+    log::info!(
+        "Query transformation (with synonyms): \"{}\" -> \"{}\"",
+        query,
+        fts_query
+    );
+    if fts_query.is_empty() {
+        log::info!("Empty FTS query after normalization (e.g. only stop words or wildcards provided)");
+        return Ok(vec![]);
+    }
+
+    let filters = SearchFilters::strict(params)?;
+    let (sql, bind) = build_fts_search_sql(&fts_query, &filters, opts, FTS_ONLY_ORDER, limit);
 
     log::info!("Search SQL: {}", sql);
     log::info!("Search params: {:?}", bind);
 
     let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt.query_map(rusqlite::params_from_iter(bind.iter()), |r| {
-        let unique_id: String = r.get(0)?;
-        let author: String = r.get(1)?;
-        let subject: String = r.get(2)?;
-        let date_ms: i64 = r.get(3)?;
-        let has_attachments: i64 = r.get(4)?;
-        let snippet: String = r.get(5)?;
-        let rank: f64 = r.get(6)?;
-        Ok(serde_json::json!({
-            "uniqueId": unique_id,
-            "author": author,
-            "subject": subject,
-            "dateMs": date_ms,
-            "hasAttachments": has_attachments != 0,
-            "snippet": snippet,
-            "rank": rank
-        }))
-    })?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(bind.iter()), |r| fts_only_result(r, opts))?;
 
     let mut results: Vec<Value> = vec![];
     for r in rows {
@@ -680,42 +2507,84 @@ fn search_fts_only(
     Ok(results)
 }
 
+/// Result object for one `build_fts_search_sql` row, ranked by BM25 alone.
+fn fts_only_result(r: &rusqlite::Row, opts: &ResultOptions) -> rusqlite::Result<Value> {
+    let unique_id: String = r.get(1)?;
+    let author: String = r.get(2)?;
+    let subject: String = r.get(3)?;
+    let date_ms: i64 = r.get(4)?;
+    let has_attachments: i64 = r.get(5)?;
+    let snippet: String = r.get(6)?;
+    let rank: f64 = r.get(7)?;
+    let body_hl: Option<String> = r.get(8)?;
+    let tags: String = r.get(12)?;
+    let mut result = serde_json::json!({
+        "uniqueId": unique_id,
+        "author": author,
+        "subject": subject,
+        "dateMs": date_ms,
+        "hasAttachments": has_attachments != 0,
+        "tags": split_tags(&tags),
+        "snippet": snippet,
+        "rank": rank
+    });
+    let snippets = body_hl.map(|hl| extract_snippets(&hl, opts)).unwrap_or_default();
+    opts.decorate(&mut result, &snippets, &Recipients::from_row(r, 9)?, RankDetails::fts(rank));
+    Ok(result)
+}
+
+/// `searchStream`: every FTS match for a query, in pages. Keyset pagination on rowid, so
+/// each page is one indexed query and rows indexed mid-stream are picked up if they sort
+/// after the cursor. Results have the FTS-only shape (BM25 `rank`); there is no hybrid
+/// ranking across pages.
+pub struct SearchStream {
+    fts_query: String,
+    filters: SearchFilters,
+    opts: ResultOptions,
+    fields: Option<Vec<String>>,
+}
+
+impl SearchStream {
+    /// Validate the request (same params as an FTS-only `search`). None when the query
+    /// matches nothing by construction (empty, or only stop words).
+    pub fn new(q: &str, params: &Value, synonyms: &SynonymLookup) -> anyhow::Result<Option<Self>> {
+        let fts_query = build_fts_match(Some(q.trim()), true, synonyms, auto_wildcard_min_len(params));
+        let filters = SearchFilters::strict(params)?;
+        let opts = ResultOptions::from_params(params)?;
+        let fields = parse_fields_param(params)?;
+        log::info!("Search stream: \"{}\" -> FTS \"{}\"", q, fts_query);
+        if fts_query.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self { fts_query, filters, opts, fields }))
+    }
+
+    /// Next page of at most `size` results; empty once exhausted.
+    pub fn next_page(&mut self, conn: &Connection, size: i64) -> anyhow::Result<Vec<Value>> {
+        let (sql, bind) = build_fts_search_sql(&self.fts_query, &self.filters, &self.opts, FTS_STREAM_ORDER, size);
+        let mut stmt = conn.prepare(&sql)?;
+        let mut results = Vec::new();
+        let mut rows = stmt.query(rusqlite::params_from_iter(bind.iter()))?;
+        while let Some(r) = rows.next()? {
+            self.filters.after_rowid = Some(r.get(0)?);
+            results.push(fts_only_result(r, &self.opts)?);
+        }
+        Ok(match &self.fields {
+            Some(fields) => project_fields(results, fields),
+            None => results,
+        })
+    }
+}
+
 /// Get FTS5 candidates with full metadata for hybrid merge.
 fn search_fts_candidates(
     conn: &Connection,
     fts_query: &str,
-    from_ts: Option<i64>,
-    to_ts: Option<i64>,
+    filters: &SearchFilters,
+    opts: &ResultOptions,
     limit: i64,
 ) -> anyhow::Result<Vec<FtsCandidate>> {
-    let mut sql = format!(
-        r#"
-        SELECT
-            fts.rowid,
-            fts.msgId, fts.from_, fts.subject, meta.dateMs, meta.hasAttachments,
-            snippet(messages_fts, -1, '[', ']', '…', {snippet_tokens}) AS snippet,
-            bm25(messages_fts, 0.0, 5.0, 3.0, 2.0, 1.0, 1.0, 1.0) AS rank
-        FROM messages_fts fts
-        JOIN message_meta meta ON fts.rowid = meta.rowid
-        WHERE messages_fts MATCH ?1
-        "#,
-        snippet_tokens = config::sqlite::SEARCH_SNIPPET_TOKENS
-    );
-
-    let mut bind: Vec<rusqlite::types::Value> =
-        vec![rusqlite::types::Value::from(fts_query.to_string())];
-
-    if let Some(from) = from_ts {
-        sql.push_str(" AND meta.dateMs >= ?");
-        bind.push(rusqlite::types::Value::from(from));
-    }
-    if let Some(to) = to_ts {
-        sql.push_str(" AND meta.dateMs <= ?");
-        bind.push(rusqlite::types::Value::from(to));
-    }
-
-    sql.push_str(" ORDER BY rank ASC LIMIT ?");
-    bind.push(rusqlite::types::Value::from(limit));
+    let (sql, bind) = build_fts_search_sql(fts_query, filters, opts, FTS_CANDIDATE_ORDER, limit);
 
     let mut stmt = conn.prepare(&sql)?;
     let rows = stmt.query_map(rusqlite::params_from_iter(bind.iter()), |r| {
@@ -727,26 +2596,109 @@ fn search_fts_candidates(
             date_ms: r.get(4)?,
             has_attachments: r.get::<_, i64>(5)? != 0,
             snippet: r.get(6)?,
+            snippets: r
+                .get::<_, Option<String>>(8)?
+                .map(|hl| extract_snippets(&hl, opts))
+                .unwrap_or_default(),
+            recipients: Recipients::from_row(r, 9)?,
+            tags: split_tags(&r.get::<_, String>(12)?),
             rank: r.get(7)?,
         })
     })?;
 
-    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Get vector similarity candidates from a vec0 table.
+pub(crate) fn search_vec_candidates(
+    conn: &Connection,
+    table: &str,
+    query_blob: &[u8],
+    limit: i64,
+) -> anyhow::Result<Vec<(i64, f64)>> {
+    let sql = vec_knn_sql(table);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![query_blob, limit], |r| {
+        Ok((r.get::<_, i64>(0)?, r.get::<_, f64>(1)?))
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+fn vec_knn_sql(table: &str) -> String {
+    format!("SELECT rowid, distance FROM {table} WHERE embedding MATCH ?1 AND k = ?2")
+}
+
+/// Run `EXPLAIN QUERY PLAN` on the SQL a real `search` would execute for `q`/`params`.
+/// In hybrid mode this is the FTS candidate query plus the vec0 KNN step; otherwise
+/// the FTS-only query. Nothing is embedded and no rows are read.
+pub fn explain_search_plan(
+    conn: &Connection,
+    q: &str,
+    params: &Value,
+    synonyms: &SynonymLookup,
+    hybrid: bool,
+) -> anyhow::Result<Value> {
+    let query = q.trim();
+    let fts_query = build_fts_match(Some(query), true, synonyms, auto_wildcard_min_len(params));
+    if fts_query.is_empty() {
+        return Ok(serde_json::json!({ "q": query, "ftsQuery": "", "plan": [], "note": "Empty FTS query; search returns no FTS results" }));
+    }
+
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(config::sqlite::SEARCH_DEFAULT_LIMIT);
+    let opts = ResultOptions::from_params(params)?;
+
+    let (sql, bind) = if hybrid {
+        // Hybrid search ignores unparseable dates rather than failing.
+        let (fts_candidate_limit, _) = candidate_limits(params, limit);
+        build_fts_search_sql(&fts_query, &SearchFilters::lenient(params)?, &opts, FTS_CANDIDATE_ORDER, fts_candidate_limit)
+    } else {
+        build_fts_search_sql(&fts_query, &SearchFilters::strict(params)?, &opts, FTS_ONLY_ORDER, limit)
+    };
+    let plan = query_plan_rows(conn, &sql, rusqlite::params_from_iter(bind.iter()))?;
+
+    let mut result = serde_json::json!({
+        "q": query,
+        "ftsQuery": fts_query,
+        "mode": if hybrid { "hybrid" } else { "fts" },
+        "sql": sql.split_whitespace().collect::<Vec<_>>().join(" "),
+        "plan": plan
+    });
+
+    if hybrid {
+        let vec_sql = vec_knn_sql("messages_vec");
+        let zero_blob = f32_vec_to_blob(&vec![0.0; config::embedding::EMBEDDING_DIMS]);
+        let (_, k) = candidate_limits(params, limit);
+        let k = date_scoped_vec_k(params, k, SearchFilters::lenient(params)?.has_date_bounds());
+        let vec_docs = vec_count(conn);
+        let vec_plan = query_plan_rows(conn, &vec_sql, params![zero_blob, k])
+            .unwrap_or_else(|e| vec![serde_json::json!({ "detail": format!("unavailable: {e}") })]);
+        result["vecStep"] = serde_json::json!({
+            "sql": vec_sql,
+            "k": k,
+            "vecDocs": vec_docs,
+            "plan": vec_plan,
+            "note": if vec_docs == 0 {
+                "messages_vec is empty: search falls back to the FTS-only query"
+            } else {
+                "vec0 KNN is a full scan of messages_vec inside the virtual table; date filters are applied to vector-only hits after the merge"
+            }
+        });
+    }
+    Ok(result)
 }
 
-/// Get vector similarity candidates from a vec0 table.
-pub(crate) fn search_vec_candidates(
-    conn: &Connection,
-    table: &str,
-    query_blob: &[u8],
-    limit: i64,
-) -> anyhow::Result<Vec<(i64, f64)>> {
-    let sql = format!(
-        "SELECT rowid, distance FROM {table} WHERE embedding MATCH ?1 AND k = ?2"
-    );
-    let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt.query_map(params![query_blob, limit], |r| {
-        Ok((r.get::<_, i64>(0)?, r.get::<_, f64>(1)?))
+/// Rows of `EXPLAIN QUERY PLAN <sql>` as `{ id, parent, detail }` objects.
+fn query_plan_rows<P: rusqlite::Params>(conn: &Connection, sql: &str, bind: P) -> anyhow::Result<Vec<Value>> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}"))?;
+    let rows = stmt.query_map(bind, |r| {
+        Ok(serde_json::json!({
+            "id": r.get::<_, i64>(0)?,
+            "parent": r.get::<_, i64>(1)?,
+            "detail": r.get::<_, String>(3)?
+        }))
     })?;
     rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
 }
@@ -755,7 +2707,9 @@ pub(crate) fn search_vec_candidates(
 fn fetch_message_meta(conn: &Connection, rowid: i64) -> anyhow::Result<Option<MessageMeta>> {
     conn.query_row(
         r#"
-        SELECT fts.msgId, fts.from_, fts.subject, meta.dateMs, meta.hasAttachments
+        SELECT fts.msgId, fts.from_, fts.subject, meta.dateMs, meta.hasAttachments,
+               fts.to_, fts.cc, fts.bcc, meta.lang, meta.tags, fts.body, meta.isRead, meta.isStarred,
+               meta.fromAddr, meta.toAddr
         FROM messages_fts fts
         JOIN message_meta meta ON fts.rowid = meta.rowid
         WHERE fts.rowid = ?1
@@ -768,6 +2722,14 @@ fn fetch_message_meta(conn: &Connection, rowid: i64) -> anyhow::Result<Option<Me
                 subject: r.get(2)?,
                 date_ms: r.get(3)?,
                 has_attachments: r.get::<_, i64>(4)? != 0,
+                recipients: Recipients::from_row(r, 5)?,
+                lang: r.get(8)?,
+                tags: split_tags(&r.get::<_, String>(9)?),
+                body: r.get(10)?,
+                is_read: r.get(11)?,
+                is_starred: r.get(12)?,
+                from_addr: r.get(13)?,
+                to_addr: r.get(14)?,
             })
         },
     )
@@ -775,11 +2737,49 @@ fn fetch_message_meta(conn: &Connection, rowid: i64) -> anyhow::Result<Option<Me
     .map_err(Into::into)
 }
 
+/// Stored fields of a message that feed its embedding (read back for re-embedding).
+struct EmailText {
+    subject: String,
+    from_: String,
+    to_: String,
+    cc: String,
+    bcc: String,
+    body: String,
+}
+
+impl EmailText {
+    /// Read `subject, from_, to_, cc, bcc, body` starting at column `first`.
+    fn read(r: &rusqlite::Row, first: usize) -> rusqlite::Result<Self> {
+        Ok(Self {
+            subject: r.get(first)?,
+            from_: r.get(first + 1)?,
+            to_: r.get(first + 2)?,
+            cc: r.get(first + 3)?,
+            bcc: r.get(first + 4)?,
+            body: r.get(first + 5)?,
+        })
+    }
+
+    /// Embedding inputs (see `text_prep::prepare_email_texts`).
+    fn prepare(&self) -> Vec<String> {
+        crate::embeddings::text_prep::prepare_email_texts(
+            &self.subject,
+            &self.from_,
+            &self.to_,
+            &self.cc,
+            &self.bcc,
+            &self.body,
+        )
+    }
+}
+
 /// Start rebuilding vector embeddings: clear vec tables and return total count.
 /// Call this once, then call `rebuild_embeddings_batch` repeatedly until done.
 pub fn rebuild_embeddings_start(conn: &mut Connection) -> anyhow::Result<i64> {
     log::info!("Starting email embedding rebuild — clearing vector tables");
-    conn.execute("DELETE FROM messages_vec", [])?;
+    if table_exists(conn, "messages_vec")? {
+        conn.execute("DELETE FROM messages_vec", [])?;
+    }
     conn.execute("DELETE FROM embed_cache", [])?;
     let total: i64 = conn.query_row("SELECT COUNT(*) FROM messages_fts", [], |r| r.get(0))?;
     log::info!("Cleared messages_vec and embed_cache, {} documents to embed", total);
@@ -794,13 +2794,11 @@ pub fn rebuild_embeddings_batch(
     last_rowid: i64,
     batch_size: i64,
 ) -> anyhow::Result<(i64, i64, i64, bool)> {
-    let batch: Vec<(i64, String, String, String, String)> = {
+    let batch: Vec<(i64, EmailText)> = {
         let mut stmt = conn.prepare(
-            "SELECT rowid, subject, from_, to_, body FROM messages_fts WHERE rowid > ?1 ORDER BY rowid ASC LIMIT ?2"
+            "SELECT rowid, subject, from_, to_, cc, bcc, body FROM messages_fts WHERE rowid > ?1 ORDER BY rowid ASC LIMIT ?2"
         )?;
-        let rows = stmt.query_map(params![last_rowid, batch_size], |r| {
-            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
-        })?;
+        let rows = stmt.query_map(params![last_rowid, batch_size], |r| Ok((r.get(0)?, EmailText::read(r, 1)?)))?;
         rows.collect::<Result<Vec<_>, _>>()?
     };
 
@@ -814,9 +2812,12 @@ pub fn rebuild_embeddings_batch(
     let done = (batch.len() as i64) < batch_size;
 
     let tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
-    for (rowid, subject, from_, to_, body) in &batch {
-        let embed_text = crate::embeddings::text_prep::prepare_email_text(subject, from_, to_, body);
-        match engine.embed(&embed_text) {
+    for (rowid, text) in &batch {
+        match engine.embed_prepared(&text.prepare()) {
+            Ok(embedding) if is_degenerate_embedding(&embedding) => {
+                log::info!("No text to embed for rowid {}, left FTS-only", rowid);
+                tx.execute("DELETE FROM messages_vec WHERE rowid = ?1", params![rowid])?;
+            }
             Ok(embedding) => {
                 let blob = f32_vec_to_blob(&embedding);
                 // vec0 virtual tables don't support INSERT OR REPLACE,
@@ -839,6 +2840,190 @@ pub fn rebuild_embeddings_batch(
     Ok((new_last_rowid, processed, embedded, done))
 }
 
+/// Start re-embedding every message with the current text prep (`reembedAll`). Unlike
+/// `rebuild_embeddings_start` nothing is cleared: the old vectors keep serving searches
+/// until each row's replacement lands. Returns the number of messages to process.
+pub fn reembed_all_start(conn: &Connection) -> anyhow::Result<i64> {
+    let total: i64 = conn.query_row("SELECT COUNT(*) FROM messages_fts", [], |r| r.get(0))?;
+    log::info!("Starting re-embed of {} messages with current text prep", total);
+    Ok(total)
+}
+
+/// Progress of one `reembedAllBatch` call.
+pub struct ReembedProgress {
+    pub last_rowid: i64,
+    pub processed: i64,
+    pub embedded: i64,
+    /// Rows whose prepared text was already in embed_cache (not re-run through the model).
+    pub cache_hits: i64,
+    /// Rows the model failed to embed; their old vector is left in place.
+    pub failed: i64,
+    /// Messages after `last_rowid` still to do.
+    pub remaining: i64,
+    pub done: bool,
+}
+
+/// One batch of `reembedAll`: re-run text prep for the messages after `last_rowid` and
+/// replace their messages_vec rows. Embeddings are cached in embed_cache keyed on the
+/// prepared text, so messages whose prep output didn't change cost a lookup, not a model run.
+pub fn reembed_all_batch(
+    conn: &mut Connection,
+    engine: &EmbeddingEngine,
+    last_rowid: i64,
+    batch_size: i64,
+) -> anyhow::Result<ReembedProgress> {
+    let prefix = engine.prefix(EmbedRole::Document);
+    reembed_rows(conn, prefix, |texts| engine.embed_prepared(texts), last_rowid, batch_size)
+}
+
+/// `reembed_all_batch` with the model call passed in as `embed` (`prefix` is the document
+/// prefix it applies, part of the cache key).
+fn reembed_rows(
+    conn: &mut Connection,
+    prefix: &str,
+    embed: impl Fn(&[String]) -> anyhow::Result<Vec<f32>>,
+    last_rowid: i64,
+    batch_size: i64,
+) -> anyhow::Result<ReembedProgress> {
+    let batch: Vec<(i64, EmailText)> = {
+        let mut stmt = conn.prepare(
+            "SELECT rowid, subject, from_, to_, cc, bcc, body FROM messages_fts WHERE rowid > ?1 ORDER BY rowid ASC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![last_rowid, batch_size], |r| Ok((r.get(0)?, EmailText::read(r, 1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut progress = ReembedProgress {
+        last_rowid,
+        processed: batch.len() as i64,
+        embedded: 0,
+        cache_hits: 0,
+        failed: 0,
+        remaining: 0,
+        done: (batch.len() as i64) < batch_size,
+    };
+    let model = config::embedding::EMBEDDING_MODEL_NAME;
+    let now_ms = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
+    for (rowid, text) in &batch {
+        progress.last_rowid = *rowid;
+        let texts = text.prepare();
+        let key = crate::fts::embed_cache::prepared_text_hash(&texts, prefix);
+        let cached: Option<Vec<u8>> = tx
+            .query_row(
+                "SELECT embedding FROM embed_cache WHERE content_hash = ?1 AND model = ?2",
+                params![key, model],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let blob = match cached {
+            Some(blob) => {
+                progress.cache_hits += 1;
+                blob
+            }
+            None => match embed(&texts) {
+                Ok(embedding) if is_degenerate_embedding(&embedding) => f32_vec_to_blob(&embedding),
+                Ok(embedding) => {
+                    let blob = f32_vec_to_blob(&embedding);
+                    tx.execute(
+                        "INSERT OR REPLACE INTO embed_cache (content_hash, embedding, model, created_at) VALUES (?1, ?2, ?3, ?4)",
+                        params![key, blob, model, now_ms],
+                    )?;
+                    blob
+                }
+                Err(e) => {
+                    log::warn!("Re-embed: failed to embed rowid {}: {}", rowid, e);
+                    progress.failed += 1;
+                    continue;
+                }
+            },
+        };
+        // vec0 virtual tables don't support INSERT OR REPLACE.
+        tx.execute("DELETE FROM messages_vec WHERE rowid = ?1", params![rowid])?;
+        if is_degenerate_embedding(&blob_to_f32_vec(&blob)) {
+            log::info!("Re-embed: no text to embed for rowid {}, left FTS-only", rowid);
+            continue;
+        }
+        tx.execute("INSERT INTO messages_vec (rowid, embedding) VALUES (?1, ?2)", params![rowid, blob])?;
+        progress.embedded += 1;
+    }
+    tx.commit()?;
+
+    progress.remaining =
+        conn.query_row("SELECT COUNT(*) FROM messages_fts WHERE rowid > ?1", params![progress.last_rowid], |r| r.get(0))?;
+    log::info!(
+        "Re-embed batch: {} processed, {} embedded ({} from cache), {} failed, {} remaining",
+        progress.processed,
+        progress.embedded,
+        progress.cache_hits,
+        progress.failed,
+        progress.remaining
+    );
+    Ok(progress)
+}
+
+/// Regenerate the embedding for a single message from its stored fields,
+/// replacing its `messages_vec` row. Returns the message's rowid.
+pub fn reembed_message(conn: &mut Connection, engine: &EmbeddingEngine, msg_id: &str) -> anyhow::Result<i64> {
+    let stored: Option<(i64, EmailText)> = conn
+        .query_row(
+            r#"
+            SELECT f.rowid, f.subject, f.from_, f.to_, f.cc, f.bcc, f.body
+            FROM messages_fts f
+            JOIN message_ids i ON f.rowid = i.rowid
+            WHERE i.msgId = ?1
+            "#,
+            params![msg_id],
+            |r| Ok((r.get(0)?, EmailText::read(r, 1)?)),
+        )
+        .optional()?;
+    let Some((rowid, text)) = stored else {
+        bail!("Message not found: {}", msg_id);
+    };
+
+    log::info!("Re-embedding message {} (rowid {})", truncate_for_log(msg_id), rowid);
+    let embedding = engine.embed_prepared(&text.prepare())?;
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    // vec0 virtual tables don't support INSERT OR REPLACE.
+    tx.execute("DELETE FROM messages_vec WHERE rowid = ?1", params![rowid])?;
+    if is_degenerate_embedding(&embedding) {
+        log::info!("No text to embed for {}, left FTS-only", truncate_for_log(msg_id));
+    } else {
+        tx.execute(
+            "INSERT INTO messages_vec (rowid, embedding) VALUES (?1, ?2)",
+            params![rowid, f32_vec_to_blob(&embedding)],
+        )?;
+    }
+    tx.commit()?;
+    Ok(rowid)
+}
+
+/// Drop and recreate messages_fts from message_content, leaving message_meta and
+/// messages_vec untouched. Returns the number of messages re-inserted.
+pub fn reindex_fts_from_content(conn: &mut Connection) -> anyhow::Result<i64> {
+    log::info!("Rebuilding messages_fts from message_content (embeddings preserved)");
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let reinserted = rebuild_messages_fts(&tx)?;
+    tx.commit()?;
+    log::info!("messages_fts rebuilt: {} messages re-indexed", reinserted);
+    Ok(reinserted)
+}
+
+/// Drop messages_fts, recreate it with the current schema and refill it from message_content.
+/// Runs inside the caller's transaction.
+fn rebuild_messages_fts(conn: &Connection) -> anyhow::Result<i64> {
+    conn.execute_batch("DROP TABLE IF EXISTS messages_fts;")?;
+    create_messages_fts(conn)?;
+    let reinserted = conn.execute(
+        "INSERT INTO messages_fts (rowid, msgId, subject, from_, to_, cc, bcc, body, tags)
+         SELECT rowid, msgId, subject, from_, to_, cc, bcc, body, tags FROM message_content ORDER BY rowid",
+        [],
+    )?;
+    Ok(reinserted as i64)
+}
+
 /// Clear and rebuild the email FTS database.
 /// Takes ownership of the connection to close it, returns a new connection after rebuild.
 /// Caller must signal the reader thread to reopen its read-only connection.
@@ -859,6 +3044,108 @@ pub fn clear_rebuild_standalone(db_path: &Path, conn: Connection) -> anyhow::Res
     Ok(new_conn)
 }
 
+/// Check that `backup_path` is an intact SQLite DB containing `required_tables` and a
+/// schema version this binary understands (legacy backups without db_meta are accepted).
+pub fn validate_backup(backup_path: &Path, required_tables: &[&str]) -> anyhow::Result<()> {
+    if !backup_path.is_file() {
+        bail!("Backup file not found: {}", backup_path.display());
+    }
+    let conn = Connection::open_with_flags(backup_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("open backup {}", backup_path.display()))?;
+
+    let check: String = conn
+        .query_row("PRAGMA quick_check", [], |r| r.get(0))
+        .with_context(|| format!("{} is not a readable SQLite database", backup_path.display()))?;
+    if check != "ok" {
+        bail!("Backup failed integrity check: {}", check);
+    }
+
+    for table in required_tables {
+        let found: Option<String> = conn
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE name = ?1",
+                params![table],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if found.is_none() {
+            bail!("Backup is missing required table '{}'", table);
+        }
+    }
+
+    if let Some(version) = read_schema_version(&conn)? {
+        if version != config::SCHEMA_VERSION {
+            bail!(
+                "Backup schema version {} is incompatible with this host (expects {})",
+                version,
+                config::SCHEMA_VERSION
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replace the database at `db_path` with a copy of `backup_path`.
+/// The backup is staged next to the live DB first, so a failed copy leaves the live DB intact.
+/// Takes ownership of the live connection; the caller reopens (and re-runs migrations) afterwards
+/// and must signal the reader thread to reopen its read-only connection.
+pub fn replace_db_file(db_path: &Path, conn: Connection, backup_path: &Path) -> anyhow::Result<()> {
+    let staged = PathBuf::from(format!("{}.restoring", db_path.display()));
+    if let Some(parent) = db_path.parent() {
+        let backup_bytes = std::fs::metadata(backup_path)?.len();
+        crate::disk::check_free_space(parent, backup_bytes)?;
+    }
+    std::fs::copy(backup_path, &staged)
+        .with_context(|| format!("copy {} -> {}", backup_path.display(), staged.display()))?;
+
+    drop(conn);
+    log::info!("Database connection closed for restore");
+
+    delete_file_if_exists(&PathBuf::from(format!("{}-wal", db_path.display())))?;
+    delete_file_if_exists(&PathBuf::from(format!("{}-shm", db_path.display())))?;
+    std::fs::rename(&staged, db_path)
+        .with_context(|| format!("rename {} -> {}", staged.display(), db_path.display()))?;
+    log::info!("Restored {} from {}", db_path.display(), backup_path.display());
+    Ok(())
+}
+
+/// Restore the email DB from a backup produced by `vacuum_into`, pointing `conn` at the
+/// restored file. The live connection is only given up once the backup has validated, and
+/// is reopened on the live file if the swap itself fails. Returns the restored document count.
+pub fn restore_backup(db_path: &Path, conn: &mut Connection, backup_path: &Path) -> anyhow::Result<i64> {
+    validate_backup(backup_path, &["messages_fts", "message_meta", "message_ids"])?;
+    let old_conn = std::mem::replace(conn, Connection::open_in_memory()?);
+    let result = replace_db_file(db_path, old_conn, backup_path);
+
+    // Reopen even if the swap failed, so the writer always has a live connection.
+    *conn = reopen_db(db_path)?;
+    result?;
+
+    let docs = db_count(conn)?;
+    log::info!("Restore complete: {} documents", docs);
+    Ok(docs)
+}
+
+/// Open a fresh writer connection to an existing email DB (pragmas + migrations applied).
+/// Used after the file was replaced underneath us (restore, `reopenConnections`).
+pub fn reopen_db(db_path: &Path) -> anyhow::Result<Connection> {
+    let conn = Connection::open(db_path).with_context(|| format!("reopen db {}", db_path.display()))?;
+    ensure_fts5_available(&conn)?;
+    apply_pragmas(&conn)?;
+    ensure_vector_tables(&conn)?;
+    ensure_message_content_table(&conn)?;
+    ensure_db_meta(&conn)?;
+    ensure_stats_history(&conn)?;
+    ensure_meta_lang_column(&conn)?;
+    ensure_tags_columns(&conn)?;
+    ensure_meta_content_hash_column(&conn)?;
+    ensure_meta_truncation_columns(&conn)?;
+    ensure_meta_header_message_id_column(&conn)?;
+    ensure_meta_flag_columns(&conn)?;
+    ensure_meta_address_columns(&conn)?;
+    Ok(conn)
+}
+
 fn delete_file_if_exists(p: &Path) -> anyhow::Result<()> {
     if p.exists() {
         match std::fs::remove_file(p) {
@@ -869,25 +3156,136 @@ fn delete_file_if_exists(p: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Write a consistent, defragmented snapshot of the database behind `conn` to `dest`
+/// using `VACUUM INTO`. Works on read-only connections and while WAL writes continue.
+/// Works for either the email or the memory DB. Returns the snapshot size in bytes.
+pub fn vacuum_into(conn: &Connection, dest: &Path) -> anyhow::Result<u64> {
+    if !dest.is_absolute() {
+        bail!("Backup path must be absolute: {}", dest.display());
+    }
+    if dest.exists() {
+        bail!("Backup path already exists: {}", dest.display());
+    }
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create backup dir {}", parent.display()))?;
+        // VACUUM INTO writes only live pages, so the snapshot is at most this large.
+        let live_bytes: i64 = conn.query_row(
+            "SELECT (page_count - freelist_count) * page_size FROM pragma_page_count, pragma_freelist_count, pragma_page_size",
+            [],
+            |r| r.get(0),
+        )?;
+        crate::disk::check_free_space(parent, live_bytes.max(0) as u64)?;
+    }
+
+    log::info!("Writing database snapshot to {}", dest.display());
+    let dest_str = dest.to_str().context("Backup path is not valid UTF-8")?;
+    conn.execute("VACUUM INTO ?1", params![dest_str])
+        .with_context(|| format!("VACUUM INTO {}", dest.display()))?;
+
+    let bytes = std::fs::metadata(dest)?.len();
+    log::info!("Snapshot written: {} ({} bytes)", dest.display(), bytes);
+    Ok(bytes)
+}
+
+/// Size of the `-wal` file next to `db_path` (0 if absent).
+pub fn wal_bytes(db_path: &Path) -> i64 {
+    let wal = PathBuf::from(format!("{}-wal", db_path.display()));
+    std::fs::metadata(wal).map(|m| m.len() as i64).unwrap_or(0)
+}
+
+/// Run `PRAGMA integrity_check`; returns the problems found (empty when the DB is intact).
+pub fn integrity_check(conn: &Connection) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let rows = stmt.query_map([], |r| r.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+    Ok(rows.into_iter().filter(|r| r != "ok").collect())
+}
+
+/// Append a `{ timestampMs, docs, vecDocs, dbBytes, walBytes }` snapshot to stats_history,
+/// pruning the oldest rows beyond `STATS_HISTORY_MAX_ROWS`. Returns the snapshot.
+pub fn record_stats(conn: &Connection, db_path: &Path) -> anyhow::Result<Value> {
+    let docs = db_count(conn)?;
+    let vec_docs = vec_count(conn);
+    let db_bytes = std::fs::metadata(db_path).map(|m| m.len() as i64).unwrap_or(0);
+    let wal_bytes = wal_bytes(db_path);
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+
+    // timestampMs is the key; two snapshots in the same millisecond keep the later one.
+    conn.execute(
+        "INSERT OR REPLACE INTO stats_history (timestampMs, docs, vecDocs, dbBytes, walBytes) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![now_ms, docs, vec_docs, db_bytes, wal_bytes],
+    )?;
+    conn.execute(
+        "DELETE FROM stats_history WHERE timestampMs NOT IN (SELECT timestampMs FROM stats_history ORDER BY timestampMs DESC LIMIT ?1)",
+        params![config::sqlite::STATS_HISTORY_MAX_ROWS],
+    )?;
+    log::info!(
+        "Recorded stats snapshot: docs={} vecDocs={} dbBytes={} walBytes={}",
+        docs,
+        vec_docs,
+        db_bytes,
+        wal_bytes
+    );
+    Ok(serde_json::json!({
+        "timestampMs": now_ms,
+        "docs": docs,
+        "vecDocs": vec_docs,
+        "dbBytes": db_bytes,
+        "walBytes": wal_bytes
+    }))
+}
+
+/// The most recent `limit` stats_history snapshots, oldest first.
+pub fn stats_history(conn: &Connection, limit: i64) -> anyhow::Result<Vec<Value>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT timestampMs, docs, vecDocs, dbBytes, walBytes FROM (
+            SELECT * FROM stats_history ORDER BY timestampMs DESC LIMIT ?1
+        ) ORDER BY timestampMs ASC
+        "#,
+    )?;
+    let rows = stmt.query_map(params![limit], |r| {
+        Ok(serde_json::json!({
+            "timestampMs": r.get::<_, i64>(0)?,
+            "docs": r.get::<_, i64>(1)?,
+            "vecDocs": r.get::<_, i64>(2)?,
+            "dbBytes": r.get::<_, i64>(3)?,
+            "walBytes": r.get::<_, i64>(4)?
+        }))
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
 pub fn optimize(conn: &Connection) -> anyhow::Result<()> {
     log::info!("Optimizing FTS index");
     conn.execute("INSERT INTO messages_fts(messages_fts) VALUES('optimize')", [])?;
     Ok(())
 }
 
-pub fn filter_new_messages(conn: &Connection, rows: &[Value]) -> anyhow::Result<Value> {
+/// Split `rows` into msgIds not yet indexed (`newMsgIds`) and already-indexed ones.
+/// With `echo_rows`, the new input rows themselves are returned as `newRows`, ready to be
+/// sent on as an `indexBatch`.
+pub fn filter_new_messages(conn: &Connection, rows: &[Value], echo_rows: bool) -> anyhow::Result<Value> {
     if rows.is_empty() {
-        return Ok(serde_json::json!({
+        let mut result = serde_json::json!({
             "ok": true,
             "newMsgIds": [],
             "totalChecked": 0,
             "newCount": 0,
             "skippedCount": 0
-        }));
+        });
+        if echo_rows {
+            result["newRows"] = serde_json::json!([]);
+        }
+        return Ok(result);
     }
 
     log::info!("Filtering {} messages to find new ones", rows.len());
     let mut new_msg_ids: Vec<String> = vec![];
+    let mut new_rows: Vec<&Value> = vec![];
     let mut skipped: i64 = 0;
 
     for row in rows {
@@ -906,6 +3304,9 @@ pub fn filter_new_messages(conn: &Connection, rows: &[Value]) -> anyhow::Result<
 
         if exists.is_none() {
             new_msg_ids.push(msg_id_val.to_string());
+            if echo_rows {
+                new_rows.push(row);
+            }
         } else {
             skipped += 1;
         }
@@ -918,18 +3319,23 @@ pub fn filter_new_messages(conn: &Connection, rows: &[Value]) -> anyhow::Result<
         skipped
     );
 
-    Ok(serde_json::json!({
+    let mut result = serde_json::json!({
         "ok": true,
         "newMsgIds": new_msg_ids,
         "totalChecked": rows.len(),
         "newCount": new_msg_ids.len(),
         "skippedCount": skipped
-    }))
+    });
+    if echo_rows {
+        result["newRows"] = serde_json::json!(new_rows);
+    }
+    Ok(result)
 }
 
-pub fn remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Result<i64> {
+/// Remove messages by msgId. Returns (removed ids, requested ids that weren't indexed).
+pub fn remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Result<(Vec<String>, Vec<String>)> {
     if ids.is_empty() {
-        return Ok(0);
+        return Ok((vec![], vec![]));
     }
 
     let ids: Vec<String> = ids
@@ -940,7 +3346,9 @@ pub fn remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Result<i64>
     log::info!("Removing {} messages from index", ids.len());
 
     let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
-    let mut removed: i64 = 0;
+    let has_vec = table_exists(&tx, "messages_vec")?;
+    let mut removed: Vec<String> = vec![];
+    let mut not_found: Vec<String> = vec![];
 
     for msg_id_val in ids {
         if msg_id_val.is_empty() {
@@ -956,15 +3364,92 @@ pub fn remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Result<i64>
         if let Some(row_id) = row_id {
             tx.execute("DELETE FROM messages_fts WHERE rowid = ?1", params![row_id])?;
             tx.execute("DELETE FROM message_meta WHERE rowid = ?1", params![row_id])?;
-            tx.execute("DELETE FROM messages_vec WHERE rowid = ?1", params![row_id])?;
+            tx.execute("DELETE FROM message_content WHERE rowid = ?1", params![row_id])?;
+            tx.execute("DELETE FROM message_to_addrs WHERE msgRowid = ?1", params![row_id])?;
+            if has_vec {
+                tx.execute("DELETE FROM messages_vec WHERE rowid = ?1", params![row_id])?;
+            }
             tx.execute("DELETE FROM message_ids WHERE msgId = ?1", params![msg_id_val])?;
-            removed += 1;
+            removed.push(msg_id_val);
+        } else {
+            not_found.push(msg_id_val);
         }
     }
 
     tx.commit()?;
-    log::info!("Removed {} messages", removed);
-    Ok(removed)
+    log::info!("Removed {} messages ({} not found)", removed.len(), not_found.len());
+    Ok((removed, not_found))
+}
+
+/// Update only the message_meta row of an indexed message (messages_fts and messages_vec
+/// are untouched). Fields absent from `params` keep their current value.
+/// Returns false if the msgId is not indexed.
+pub fn update_meta(conn: &Connection, msg_id: &str, params: &Value) -> anyhow::Result<bool> {
+    let mut sets: Vec<&str> = vec![];
+    let mut bind: Vec<rusqlite::types::Value> = vec![];
+
+    if let Some(v) = params.get("dateMs").filter(|v| !v.is_null()) {
+        let date_ms = v.as_i64().context("dateMs must be an integer")?;
+        sets.push("dateMs = ?");
+        bind.push(rusqlite::types::Value::from(date_ms));
+    }
+    if let Some(v) = params.get("hasAttachments").filter(|v| !v.is_null()) {
+        let has_attachments = v.as_bool().context("hasAttachments must be a boolean")?;
+        sets.push("hasAttachments = ?");
+        bind.push(rusqlite::types::Value::from(if has_attachments { 1 } else { 0 }));
+    }
+    if let Some(v) = params.get("parsedIcsAttachments").filter(|v| !v.is_null()) {
+        let parsed_ics = v.as_str().context("parsedIcsAttachments must be a string")?;
+        sets.push("parsedIcsAttachments = ?");
+        bind.push(rusqlite::types::Value::from(parsed_ics.to_string()));
+    }
+    // Flags change often (reading, starring): sync them here instead of reindexing the message.
+    for (key, set) in [("isRead", "isRead = ?"), ("isStarred", "isStarred = ?")] {
+        if let Some(v) = params.get(key).filter(|v| !v.is_null()) {
+            let flag = v.as_bool().with_context(|| format!("{key} must be a boolean"))?;
+            sets.push(set);
+            bind.push(rusqlite::types::Value::from(flag));
+        }
+    }
+    if params.get("folderId").is_some() {
+        // The folder is encoded in the msgId key (accountId:folderPath:headerMessageId); a move
+        // is a remove + index of the new key, not a metadata change.
+        log::warn!("updateMeta: ignoring folderId for {} (not stored in message_meta)", truncate_for_log(msg_id));
+    }
+    if sets.is_empty() {
+        bail!("No metadata fields to update (expected dateMs, hasAttachments, parsedIcsAttachments, isRead, or isStarred)");
+    }
+
+    let row_id: Option<i64> = conn
+        .query_row("SELECT rowid FROM message_ids WHERE msgId = ?1", params![msg_id], |r| r.get(0))
+        .optional()?;
+    let Some(row_id) = row_id else {
+        log::info!("updateMeta: message not found: {}", truncate_for_log(msg_id));
+        return Ok(false);
+    };
+
+    let sql = format!("UPDATE message_meta SET {} WHERE rowid = ?", sets.join(", "));
+    bind.push(rusqlite::types::Value::from(row_id));
+    let changed = conn.execute(&sql, rusqlite::params_from_iter(bind.iter()))?;
+    log::info!("updateMeta: updated {} field(s) for {}", sets.len(), truncate_for_log(msg_id));
+    Ok(changed > 0)
+}
+
+/// Stored ICS text of a message (None if the msgId is unknown; empty if it has none).
+pub fn get_parsed_ics(conn: &Connection, msg_id: &str) -> anyhow::Result<Option<String>> {
+    let ics: Option<Option<String>> = conn
+        .query_row(
+            r#"
+            SELECT m.parsedIcsAttachments
+            FROM message_ids i
+            JOIN message_meta m ON i.rowid = m.rowid
+            WHERE i.msgId = ?1
+            "#,
+            params![msg_id],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(ics.map(Option::unwrap_or_default))
 }
 
 pub fn get_message_by_msgid(conn: &Connection, msg_id: &str) -> anyhow::Result<Option<Value>> {
@@ -972,7 +3457,8 @@ pub fn get_message_by_msgid(conn: &Connection, msg_id: &str) -> anyhow::Result<O
         r#"
         SELECT
             f.msgId, f.body, f.subject, f.from_, f.to_, f.cc, f.bcc,
-            m.hasAttachments, m.parsedIcsAttachments, m.dateMs
+            m.hasAttachments, m.parsedIcsAttachments, m.dateMs, m.bodyTruncated, m.originalBodyLength,
+            m.isRead, m.isStarred, m.fromAddr, m.toAddr
         FROM messages_fts f
         JOIN message_meta m ON f.rowid = m.rowid
         WHERE f.msgId = ?1
@@ -992,6 +3478,12 @@ pub fn get_message_by_msgid(conn: &Connection, msg_id: &str) -> anyhow::Result<O
             let has_attachments: i64 = r.get(7)?;
             let parsed_ics: Option<String> = r.get(8)?;
             let date_ms: i64 = r.get(9)?;
+            let body_truncated: bool = r.get(10)?;
+            let original_body_len: Option<i64> = r.get(11)?;
+            let is_read: Option<bool> = r.get(12)?;
+            let is_starred: Option<bool> = r.get(13)?;
+            let from_addr: Option<String> = r.get(14)?;
+            let to_addr: Option<String> = r.get(15)?;
 
             Ok(serde_json::json!({
                 "msgId": msg_id,
@@ -1003,7 +3495,13 @@ pub fn get_message_by_msgid(conn: &Connection, msg_id: &str) -> anyhow::Result<O
                 "bcc": bcc,
                 "hasAttachments": has_attachments,
                 "parsedIcsAttachments": parsed_ics.unwrap_or_default(),
-                "dateMs": date_ms
+                "dateMs": date_ms,
+                "isTruncated": body_truncated,
+                "originalBodyLength": original_body_len,
+                "isRead": is_read,
+                "isStarred": is_starred,
+                "fromAddr": from_addr,
+                "toAddr": to_addr.map(|a| a.split(' ').map(str::to_string).collect::<Vec<_>>()).unwrap_or_default()
             }))
         })
         .optional()?;
@@ -1011,48 +3509,64 @@ pub fn get_message_by_msgid(conn: &Connection, msg_id: &str) -> anyhow::Result<O
     Ok(row)
 }
 
-/// Find all FTS entries matching a specific headerMessageId within an account.
-/// Used by incremental indexer when the exact folder path is unknown (deletion events
-/// sometimes have stale/wrong folder info from Gmail virtual folders).
+/// Find all FTS entries for a Message-ID, within one account or (`account_id` None) across
+/// all of them. Used by incremental indexer when the exact folder path is unknown (deletion
+/// events sometimes have stale/wrong folder info from Gmail virtual folders), and for threads
+/// that span accounts. `header_message_id` may be the raw header (`<abc@host>`).
 /// Returns list of matching msgId keys (format: accountId:folderPath:headerMessageId).
-pub fn find_by_header_message_id(conn: &Connection, account_id: &str, header_message_id: &str) -> anyhow::Result<Vec<String>> {
-    // Key format is accountId:folderPath:headerMessageId
-    // We search for entries that start with accountId: and end with :headerMessageId
-    let pattern = format!("{}:%:{}", account_id, header_message_id);
-    log::info!("Finding FTS entries matching pattern: {}", pattern);
+pub fn find_by_header_message_id(
+    conn: &Connection,
+    account_id: Option<&str>,
+    header_message_id: &str,
+) -> anyhow::Result<Vec<String>> {
+    let wanted = normalize_message_id(header_message_id);
+    log::info!("Finding FTS entries for headerMessageId {} (account={:?})", wanted, account_id);
 
+    // Key format is accountId:folderPath:headerMessageId
+    let account_prefix = account_id.map(|a| format!("{}:%", escape_like(a)));
     let mut stmt = conn.prepare(
-        "SELECT msgId FROM messages_fts WHERE msgId LIKE ?1"
+        r"SELECT i.msgId FROM message_meta m JOIN message_ids i ON i.rowid = m.rowid
+          WHERE m.headerMessageId = ?1 AND (?2 IS NULL OR i.msgId LIKE ?2 ESCAPE '\')
+          ORDER BY i.msgId",
     )?;
+    let rows = stmt.query_map(params![wanted, account_prefix], |r| r.get::<_, String>(0))?;
+    let results = rows.collect::<Result<Vec<String>, _>>()?;
 
-    let rows = stmt.query_map(params![pattern], |r| r.get::<_, String>(0))?;
-    let results: Vec<String> = rows.filter_map(|r| r.ok()).collect();
-
-    log::info!("Found {} entries matching headerMessageId {}", results.len(), header_message_id);
+    log::info!("Found {} entries matching headerMessageId {}", results.len(), wanted);
     Ok(results)
 }
 
-pub fn query_by_date_range(conn: &Connection, from_v: &Value, to_v: &Value, limit: i64) -> anyhow::Result<Vec<Value>> {
-    let Some(from_ts) = parse_date_param(from_v)? else { bail!("from and to parameters are required") };
-    let Some(to_ts) = parse_date_param(to_v)? else { bail!("from and to parameters are required") };
+pub fn query_by_date_range(
+    conn: &Connection,
+    from_v: &Value,
+    to_v: &Value,
+    limit: i64,
+    inclusive: DateInclusivity,
+    tz: DateTz,
+) -> anyhow::Result<Vec<Value>> {
+    let Some(from_ts) = parse_date_param_tz(from_v, tz)? else { bail!("from and to parameters are required") };
+    let Some(to_ts) = parse_date_param_tz(to_v, tz)? else { bail!("from and to parameters are required") };
 
     log::info!(
-        "Querying messages from {} to {}, limit {}",
+        "Querying messages from {} to {} ({:?}), limit {}",
         from_ts,
         to_ts,
+        inclusive,
         limit
     );
 
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         r#"
         SELECT f.msgId, f.subject, m.dateMs
         FROM messages_fts f
         JOIN message_meta m ON f.rowid = m.rowid
-        WHERE m.dateMs >= ?1 AND m.dateMs <= ?2
+        WHERE m.dateMs {lower_op} ?1 AND m.dateMs {upper_op} ?2
         ORDER BY m.dateMs DESC
         LIMIT ?3
         "#,
-    )?;
+        lower_op = inclusive.lower_op(),
+        upper_op = inclusive.upper_op(),
+    ))?;
 
     let mut rows = stmt.query(params![from_ts, to_ts, limit])?;
     let mut out: Vec<Value> = vec![];
@@ -1060,7 +3574,7 @@ pub fn query_by_date_range(conn: &Connection, from_v: &Value, to_v: &Value, limi
         let msg_id: String = r.get(0)?;
         let subject: String = r.get(1)?;
         let date_ms: i64 = r.get(2)?;
-        let date_str = format_date_iso_like_python(date_ms);
+        let date_str = format_date_iso_like_python(date_ms, tz);
         out.push(serde_json::json!({
             "msgId": msg_id,
             "subject": subject,
@@ -1073,19 +3587,126 @@ pub fn query_by_date_range(conn: &Connection, from_v: &Value, to_v: &Value, limi
     Ok(out)
 }
 
-pub fn debug_sample(conn: &Connection) -> anyhow::Result<Vec<Value>> {
-    log::info!("Getting debug sample");
+/// Subject-line filter box: `query` matched against the subject column only, newest first.
+/// No synonyms, snippets or vectors — just `{ uniqueId, subject, dateMs }` per hit.
+pub fn subject_search(conn: &Connection, query: &str, params: &Value, limit: i64) -> anyhow::Result<Vec<Value>> {
+    let fts_query = build_fts_match(Some(query), false, &SynonymLookup::new(), auto_wildcard_min_len(params));
+    if fts_query.is_empty() {
+        return Ok(vec![]);
+    }
+    let match_expr = format!("subject : ({fts_query})");
+    log::info!("Subject search: \"{}\" -> \"{}\"", query, match_expr);
+
     let mut stmt = conn.prepare(
         r#"
         SELECT f.msgId, f.subject, m.dateMs
         FROM messages_fts f
         JOIN message_meta m ON f.rowid = m.rowid
+        WHERE messages_fts MATCH ?1
+        ORDER BY m.dateMs DESC
+        LIMIT ?2
+        "#,
+    )?;
+    let rows = stmt.query_map(params![match_expr, limit], |r| {
+        Ok(serde_json::json!({
+            "uniqueId": r.get::<_, String>(0)?,
+            "subject": r.get::<_, String>(1)?,
+            "dateMs": r.get::<_, i64>(2)?,
+        }))
+    })?;
+    let out = rows.collect::<Result<Vec<_>, _>>()?;
+    log::info!("Subject search found {} messages", out.len());
+    Ok(out)
+}
+
+/// "Try searching for…" prompts: the terms and adjacent term pairs occurring in the most
+/// recent subjects (bounded by `SAMPLE_MESSAGES`). Pairs count double, being more specific;
+/// a single term already covered by a chosen pair is dropped.
+pub fn suggest_queries(conn: &Connection, count: usize) -> anyhow::Result<Vec<Value>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT c.subject
+        FROM message_content c
+        JOIN message_meta m ON c.rowid = m.rowid
         ORDER BY m.dateMs DESC
         LIMIT ?1
         "#,
     )?;
+    let subjects = stmt
+        .query_map(params![config::suggest::SAMPLE_MESSAGES], |r| r.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Document frequency per term / term pair (counted once per subject)
+    let mut doc_freq: HashMap<String, usize> = HashMap::new();
+    for subject in &subjects {
+        let terms: Vec<String> = subject
+            .split(|c: char| !c.is_alphanumeric())
+            .map(|w| w.to_lowercase())
+            .filter(|w| w.chars().count() >= 3 && !w.chars().all(|c| c.is_ascii_digit()))
+            .filter(|w| !crate::fts::query::is_stop_word(w) && !config::suggest::FILLER_WORDS.contains(&w.as_str()))
+            .collect();
+        let mut seen: Vec<String> = terms.clone();
+        seen.extend(terms.windows(2).filter(|p| p[0] != p[1]).map(|p| format!("{} {}", p[0], p[1])));
+        seen.sort();
+        seen.dedup();
+        for key in seen {
+            *doc_freq.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    let mut ranked: Vec<(String, usize)> = doc_freq
+        .into_iter()
+        .filter(|(_, n)| *n >= config::suggest::MIN_DOC_FREQ)
+        .collect();
+    let score = |(q, n): &(String, usize)| if q.contains(' ') { n * 2 } else { *n };
+    ranked.sort_by(|a, b| score(b).cmp(&score(a)).then_with(|| a.0.cmp(&b.0)));
+
+    let mut chosen: Vec<(String, usize)> = Vec::new();
+    for (query, n) in ranked {
+        if chosen.len() >= count {
+            break;
+        }
+        let covered = !query.contains(' ')
+            && chosen.iter().any(|(c, _)| c.split(' ').any(|w| w == query));
+        if !covered {
+            chosen.push((query, n));
+        }
+    }
+    log::info!("Suggested {} queries from {} recent subjects", chosen.len(), subjects.len());
+    Ok(chosen
+        .into_iter()
+        .map(|(query, n)| serde_json::json!({ "query": query, "messages": n }))
+        .collect())
+}
+
+/// Sample of indexed messages: `order` is "newest" (default), "oldest" or "random"
+/// (`ORDER BY RANDOM()`, spread across the whole index); `limit` is capped at
+/// SEARCH_DEBUG_SAMPLE_MAX_LIMIT.
+pub fn debug_sample(conn: &Connection, params: &Value) -> anyhow::Result<Vec<Value>> {
+    let order = params.get("order").and_then(|v| v.as_str()).unwrap_or("newest");
+    let order_by = match order {
+        "newest" => "m.dateMs DESC",
+        "oldest" => "m.dateMs ASC",
+        "random" => "RANDOM()",
+        other => bail!("Invalid order '{}' (expected \"newest\", \"oldest\" or \"random\")", other),
+    };
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(config::sqlite::SEARCH_DEBUG_SAMPLE_LIMIT)
+        .clamp(1, config::sqlite::SEARCH_DEBUG_SAMPLE_MAX_LIMIT);
+    log::info!("Getting debug sample (order={}, limit={})", order, limit);
+    let mut stmt = conn.prepare(&format!(
+        r#"
+        SELECT f.msgId, f.subject, m.dateMs
+        FROM messages_fts f
+        JOIN message_meta m ON f.rowid = m.rowid
+        ORDER BY {order_by}
+        LIMIT ?1
+        "#
+    ))?;
 
-    let mut rows = stmt.query(params![config::sqlite::SEARCH_DEBUG_SAMPLE_LIMIT])?;
+    let mut rows = stmt.query(params![limit])?;
     let mut out: Vec<Value> = vec![];
     while let Some(r) = rows.next()? {
         let msg_id: String = r.get(0)?;
@@ -1100,7 +3721,7 @@ pub fn debug_sample(conn: &Connection) -> anyhow::Result<Vec<Value>> {
     Ok(out)
 }
 
-fn format_date_iso_like_python(date_ms: i64) -> String {
+fn format_date_iso_like_python(date_ms: i64, tz: DateTz) -> String {
     if date_ms == 0 {
         return String::new();
     }
@@ -1109,10 +3730,12 @@ fn format_date_iso_like_python(date_ms: i64) -> String {
     let frac = secs - (whole as f64);
     let micros = (frac * 1_000_000.0).round() as u32;
 
-    let dt: DateTime<Local> = Local.timestamp_opt(whole, micros * 1000).single().unwrap_or_else(|| {
-        // If local conversion fails, fall back to epoch-based safe value.
-        Local.timestamp_opt(0, 0).single().unwrap()
-    });
+    let dt: NaiveDateTime = match tz {
+        DateTz::Local => Local.timestamp_opt(whole, micros * 1000).single().map(|d| d.naive_local()),
+        DateTz::Utc => Utc.timestamp_opt(whole, micros * 1000).single().map(|d| d.naive_utc()),
+    }
+    // If conversion fails, fall back to epoch-based safe value.
+    .unwrap_or_default();
 
     // Python's datetime.isoformat() for naive local datetime includes microseconds if non-zero.
     if micros == 0 {
@@ -1144,7 +3767,7 @@ mod tests {
         conn.execute_batch(r#"
             CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
                 msgId,
-                subject, from_, to_, cc, bcc, body,
+                subject, from_, to_, cc, bcc, body, tags,
                 tokenize = "unicode61"
             );
 
@@ -1152,15 +3775,58 @@ mod tests {
                 rowid INTEGER PRIMARY KEY,
                 dateMs INTEGER NOT NULL,
                 hasAttachments INTEGER NOT NULL,
-                parsedIcsAttachments TEXT
+                parsedIcsAttachments TEXT,
+                tags TEXT NOT NULL DEFAULT ''
             );
 
             CREATE TABLE IF NOT EXISTS message_ids (
                 msgId TEXT PRIMARY KEY
             );
         "#).unwrap();
+        create_message_content_table(&conn).unwrap();
+        ensure_meta_lang_column(&conn).unwrap();
+        ensure_meta_content_hash_column(&conn).unwrap();
+        ensure_meta_truncation_columns(&conn).unwrap();
+        ensure_meta_header_message_id_column(&conn).unwrap();
+        ensure_meta_flag_columns(&conn).unwrap();
+        ensure_meta_address_columns(&conn).unwrap();
+
+        conn
+    }
+
+    /// `setup_test_db` plus sqlite-vec, loaded into this connection only (registering it as an
+    /// auto-extension would also reach the tests that run without it).
+    fn setup_vec_test_db() -> Connection {
+        type VecInitFn = unsafe extern "C" fn(
+            *mut rusqlite::ffi::sqlite3,
+            *mut *mut std::os::raw::c_char,
+            *const rusqlite::ffi::sqlite3_api_routines,
+        ) -> std::os::raw::c_int;
+        let conn = setup_test_db();
+        unsafe {
+            let init = std::mem::transmute::<*const (), VecInitFn>(sqlite_vec::sqlite3_vec_init as *const ());
+            assert_eq!(init(conn.handle(), std::ptr::null_mut(), std::ptr::null()), rusqlite::ffi::SQLITE_OK);
+        }
+        create_vec_table(&conn, "messages_vec").unwrap();
+        conn
+    }
+
+    /// Unit embedding at angle `t` (radians) from the first axis, toward the second.
+    fn unit_vector(t: f32) -> Vec<f32> {
+        let mut v = vec![0.0f32; config::embedding::EMBEDDING_DIMS];
+        v[0] = t.cos();
+        v[1] = t.sin();
+        v
+    }
 
-        conn
+    /// Store `unit_vector(t)` as the embedding of an indexed message.
+    fn insert_test_vector(conn: &Connection, msg_id: &str, t: f32) {
+        let rowid: i64 = conn.query_row("SELECT rowid FROM message_ids WHERE msgId = ?1", params![msg_id], |r| r.get(0)).unwrap();
+        conn.execute(
+            "INSERT INTO messages_vec (rowid, embedding) VALUES (?1, ?2)",
+            params![rowid, f32_vec_to_blob(&unit_vector(t))],
+        )
+        .unwrap();
     }
 
     /// Insert a test message into the database.
@@ -1185,11 +3851,353 @@ mod tests {
 
         // Insert into meta table
         conn.execute(
-            "INSERT INTO message_meta (rowid, dateMs, hasAttachments, parsedIcsAttachments) VALUES (?1, ?2, 0, '')",
-            params![row_id, date_ms],
+            "INSERT INTO message_meta (rowid, dateMs, hasAttachments, parsedIcsAttachments, headerMessageId) VALUES (?1, ?2, 0, '', ?3)",
+            params![row_id, date_ms, header_message_id_of(msg_id, None)],
         ).unwrap();
     }
 
+    #[test]
+    fn test_dump_roundtrip_keeps_text_meta_and_vectors() {
+        let mut src = setup_vec_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget", "from": "Ann <ann@example.com>", "to": "bob@example.com", "body": "numbers", "dateMs": 1000, "tags": ["work"], "isRead": true, "headerMessageId": "<budget@example.com>" }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Lunch", "body": "tacos", "dateMs": 2000, "isStarred": false }),
+        ];
+        index_batch(&mut src, &rows, None, false, false).unwrap();
+        insert_test_vector(&src, "a:/INBOX:1", 0.25);
+        insert_test_vector(&src, "a:/INBOX:2", 0.5);
+
+        let dir = std::env::temp_dir().join(format!("tabmail_dump_roundtrip_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dump.ndjson");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(crate::fts::dump::export_dump(&src, &path).unwrap(), 2);
+        assert!(!dir.join("dump.ndjson.tmp").exists());
+
+        let mut dst = setup_vec_test_db();
+        let (imported, _, embeddings, failed) = crate::fts::dump::import_dump(&mut dst, &path, false, false).unwrap();
+        assert_eq!((imported, embeddings, failed), (2, 2, vec![]));
+        for id in ["a:/INBOX:1", "a:/INBOX:2"] {
+            let (before, after) = (get_message_by_msgid(&src, id).unwrap().unwrap(), get_message_by_msgid(&dst, id).unwrap().unwrap());
+            for key in ["subject", "from_", "to_", "body", "dateMs", "hasAttachments", "isRead", "isStarred", "isTruncated", "fromAddr", "toAddr"] {
+                assert_eq!(before[key], after[key], "{id} {key}");
+            }
+            let vector = |conn: &Connection| -> Vec<u8> {
+                conn.query_row(
+                    "SELECT v.embedding FROM messages_vec v JOIN message_ids i ON i.rowid = v.rowid WHERE i.msgId = ?1",
+                    params![id],
+                    |r| r.get(0),
+                )
+                .unwrap()
+            };
+            assert_eq!(vector(&src), vector(&dst), "{id} embedding");
+        }
+        let found = find_by_header_message_id(&dst, Some("a"), "budget@example.com").unwrap();
+        assert_eq!(found, vec!["a:/INBOX:1".to_string()]);
+
+        // A dump that fails part-way imports nothing.
+        let mut text = std::fs::read_to_string(&path).unwrap();
+        text.push_str(&serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Bad", "dateMs": 3000, "embedding": [0.5] }).to_string());
+        let bad_path = dir.join("bad.ndjson");
+        std::fs::write(&bad_path, text).unwrap();
+        let mut partial = setup_vec_test_db();
+        assert!(crate::fts::dump::import_dump(&mut partial, &bad_path, false, false).is_err());
+        assert_eq!(db_count(&partial).unwrap(), 0);
+        assert_eq!(vec_count(&partial), 0);
+
+        // A zero vector (a message with no text) is imported FTS-only.
+        let mut text = std::fs::read_to_string(&path).unwrap();
+        let zeros = vec![0.0; config::embedding::EMBEDDING_DIMS];
+        text.push_str(&serde_json::json!({ "msgId": "a:/INBOX:4", "subject": "", "dateMs": 4000, "embedding": zeros }).to_string());
+        let empty_path = dir.join("empty.ndjson");
+        std::fs::write(&empty_path, text).unwrap();
+        let mut with_empty = setup_vec_test_db();
+        let (imported, _, embeddings, _) = crate::fts::dump::import_dump(&mut with_empty, &empty_path, false, false).unwrap();
+        assert_eq!((imported, embeddings), (3, 2));
+        assert_eq!(vec_count(&with_empty), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dump_export_import_roundtrip() {
+        let mut src = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget", "from": "ann@example.com", "body": "numbers", "dateMs": 1000, "tags": ["work"], "hasAttachments": true }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Lunch", "body": "tacos", "dateMs": 2000 }),
+        ];
+        index_batch(&mut src, &rows, None, false, false).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("tabmail_dump_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dump.ndjson");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(crate::fts::dump::export_dump(&src, &path).unwrap(), 2);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let header = crate::fts::dump::DumpHeader::parse(text.lines().next().unwrap()).unwrap();
+        assert!(!header.has_embeddings);
+        assert!(crate::fts::dump::export_dump(&src, &path).is_err()); // never overwrites
+
+        let mut dst = setup_test_db();
+        insert_test_message(&dst, "a:/INBOX:2", "Lunch", 2000);
+        let (imported, skipped, embeddings, failed) = crate::fts::dump::import_dump(&mut dst, &path, false, false).unwrap();
+        assert_eq!((imported, skipped, embeddings, failed), (1, 1, 0, vec![]));
+        let msg = get_message_by_msgid(&dst, "a:/INBOX:1").unwrap().unwrap();
+        assert_eq!(msg["subject"], "Budget");
+        assert_eq!(msg["hasAttachments"], 1);
+
+        // A dump whose header is from a newer schema is refused before any record is read.
+        let lines: Vec<&str> = text.lines().collect();
+        let mut newer = crate::fts::dump::DumpHeader::parse(lines[0]).unwrap();
+        newer.schema_version = config::SCHEMA_VERSION + 1;
+        let newer_path = dir.join("newer.ndjson");
+        std::fs::write(&newer_path, format!("{}\n{}\n", serde_json::to_string(&newer).unwrap(), lines[1])).unwrap();
+        let mut dst = setup_test_db();
+        assert!(crate::fts::dump::import_dump(&mut dst, &newer_path, true, false).is_err());
+        assert_eq!(db_count(&dst).unwrap(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_multi() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget review", "body": "budget budget budget", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Offsite", "body": "budget for the offsite", "dateMs": 2000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Lunch", "body": "tacos", "dateMs": 3000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let queries = vec!["budget".to_string(), "offsite".to_string(), "tacos".to_string()];
+        let ids = |list: &Value| -> Vec<String> {
+            list.as_array().unwrap().iter().map(|r| r["uniqueId"].as_str().unwrap().to_string()).collect()
+        };
+
+        // Per-query lists without dedupe: message 2 matches both of the first two queries.
+        let params = serde_json::json!({ "ignoreDate": true, "dedupe": false });
+        let (res, _) = search_multi(&conn, &queries, &params, &synonyms, None).unwrap();
+        let lists = res["perQuery"].as_array().unwrap();
+        assert_eq!(lists.len(), 3);
+        assert_eq!(lists[0]["q"], "budget");
+        assert_eq!(ids(&lists[0]["results"]).len(), 2);
+        assert!(ids(&lists[1]["results"]).contains(&"a:/INBOX:2".to_string()));
+
+        // Deduped (default): each message appears once across all lists.
+        let params = serde_json::json!({ "ignoreDate": true });
+        let (res, _) = search_multi(&conn, &queries, &params, &synonyms, None).unwrap();
+        let mut all: Vec<String> = res["perQuery"].as_array().unwrap().iter().flat_map(|l| ids(&l["results"])).collect();
+        all.sort();
+        assert_eq!(all, vec!["a:/INBOX:1", "a:/INBOX:2", "a:/INBOX:3"]);
+
+        // Merged, sorted by rank, tagged with the finding query, projected and cut to limit.
+        let params = serde_json::json!({ "ignoreDate": true, "merge": true, "limit": 2, "fields": ["uniqueId"] });
+        let (res, _) = search_multi(&conn, &queries, &params, &synonyms, None).unwrap();
+        let merged = res["results"].as_array().unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].as_object().unwrap().len(), 2);
+        assert!(merged[0]["query"].is_string());
+
+        let too_many: Vec<String> = (0..=config::sqlite::SEARCH_MULTI_MAX_QUERIES).map(|i| format!("q{i}")).collect();
+        assert!(search_multi(&conn, &too_many, &params, &synonyms, None).is_err());
+    }
+
+    #[test]
+    fn test_auto_optimize_from_param() {
+        assert_eq!(AutoOptimize::from_param(None).unwrap(), AutoOptimize::Auto);
+        assert_eq!(AutoOptimize::from_param(Some(&serde_json::json!("advisory"))).unwrap(), AutoOptimize::Advisory);
+        assert_eq!(AutoOptimize::from_param(Some(&serde_json::json!("off"))).unwrap(), AutoOptimize::Off);
+        assert!(AutoOptimize::from_param(Some(&serde_json::json!("sometimes"))).is_err());
+    }
+
+    #[test]
+    fn test_shutdown_checkpoint_modes() {
+        assert_eq!(WalShutdownMode::from_param(None).unwrap(), WalShutdownMode::Passive);
+        assert_eq!(WalShutdownMode::from_param(Some(&serde_json::json!("truncate"))).unwrap(), WalShutdownMode::Truncate);
+        assert!(WalShutdownMode::from_param(Some(&serde_json::json!("full"))).is_err());
+
+        let dir = std::env::temp_dir().join(format!("tm_wal_shutdown_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let wal_len = |db: &Path| std::fs::metadata(db.with_extension("db-wal")).map(|m| m.len()).ok();
+        for (name, mode) in [("t", WalShutdownMode::Truncate), ("p", WalShutdownMode::Passive), ("n", WalShutdownMode::None)] {
+            let db = dir.join(format!("{name}.db"));
+            let conn = Connection::open(&db).unwrap();
+            conn.execute_batch("PRAGMA journal_mode=WAL; CREATE TABLE t (x); INSERT INTO t VALUES (zeroblob(10000));")
+                .unwrap();
+            let r = shutdown_checkpoint(&conn, mode).unwrap();
+            drop(conn);
+            match mode {
+                // Fully checkpointed, and the close removes the empty WAL.
+                WalShutdownMode::Truncate => {
+                    assert_eq!(r.unwrap().0, 0);
+                    assert_eq!(wal_len(&db), None);
+                }
+                _ => assert!(wal_len(&db).unwrap() > 0, "{mode:?} should keep the WAL"),
+            }
+            let reopened = Connection::open(&db).unwrap();
+            assert_eq!(reopened.query_row("SELECT count(*) FROM t", [], |r| r.get::<_, i64>(0)).unwrap(), 1);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_body_truncation_marker() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Long", "body": "first part", "originalBodyLength": 50_000, "dateMs": 1 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Cut", "body": "head", "isTruncated": true, "dateMs": 2 }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Whole", "body": "all", "originalBodyLength": 3, "dateMs": 3 }),
+            serde_json::json!({ "msgId": "a:/INBOX:4", "subject": "Plain", "body": "no marker", "dateMs": 4 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let get = |id: &str| get_message_by_msgid(&conn, id).unwrap().unwrap();
+        let long = get("a:/INBOX:1");
+        assert_eq!(long["isTruncated"], true);
+        assert_eq!(long["originalBodyLength"], 50_000);
+        assert_eq!(get("a:/INBOX:2")["isTruncated"], true);
+        assert!(get("a:/INBOX:2")["originalBodyLength"].is_null());
+        assert_eq!(get("a:/INBOX:3")["isTruncated"], false);
+        assert_eq!(get("a:/INBOX:4")["isTruncated"], false);
+    }
+
+    #[test]
+    fn test_suspect_encoding_ids() {
+        let garbled = "Gr\u{FFFD}\u{FFFD}e aus M\u{FFFD}nchen, sch\u{FFFD}ne W\u{FFFD}che";
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Hello", "body": "Plain ASCII body" }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Hi", "body": garbled }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": garbled, "body": "" }),
+            // One stray replacement char in a long body is noise, not a charset bug.
+            serde_json::json!({ "msgId": "a:/INBOX:4", "subject": "x", "body": format!("{} \u{FFFD}", "word ".repeat(200)) }),
+        ];
+        assert_eq!(suspect_encoding_ids(&rows), vec!["a:/INBOX:2", "a:/INBOX:3"]);
+    }
+
+    #[test]
+    fn test_get_schema() {
+        let conn = setup_test_db();
+        let schema = get_schema(&conn).unwrap();
+
+        let tables = schema["tables"].as_object().unwrap();
+        assert_eq!(tables["messages_fts"][0], "msgId");
+        assert!(tables["message_meta"].as_array().unwrap().iter().any(|c| c == "dateMs"));
+        assert!(!tables.contains_key("messages_fts_data"), "shadow tables are omitted");
+        // Reported as created (the test table uses plain unicode61, no prefixes).
+        assert_eq!(schema["tokenizer"], "unicode61");
+        assert!(schema["ftsPrefixes"].is_null());
+        assert_eq!(schema["embeddingDims"], config::embedding::EMBEDDING_DIMS);
+        // No vec0 in unit tests.
+        assert!(schema["distanceMetric"].is_null());
+
+        // Options are read back from the table create_messages_fts actually makes.
+        let prod = Connection::open_in_memory().unwrap();
+        create_messages_fts(&prod).unwrap();
+        let prod_sql: String =
+            prod.query_row("SELECT sql FROM sqlite_master WHERE name = 'messages_fts'", [], |r| r.get(0)).unwrap();
+        assert_eq!(fts5_option(&prod_sql, "tokenize").as_deref(), Some(config::sqlite::FTS_TOKENIZE));
+        assert_eq!(fts5_option(&prod_sql, "prefix").as_deref(), Some(config::sqlite::FTS_PREFIXES));
+    }
+
+    #[test]
+    fn test_init_database_without_sqlite_vec() {
+        // sqlite-vec isn't registered in unit tests, like a host whose extension failed to load.
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(!vec_available(&conn));
+        init_database(&conn).unwrap();
+
+        assert!(!table_exists(&conn, "messages_vec").unwrap());
+        assert!(table_exists(&conn, "embed_cache").unwrap() && table_exists(&conn, "messages_fts").unwrap());
+        assert_eq!(vec_count(&conn), 0);
+
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "quarterly budget", "dateMs": 1 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "budget follow-up", "dateMs": 2 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let (results, _) = search(&conn, "budget", &serde_json::json!({}), &SynonymLookup::new(), None).unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Writes that touch vectors without an engine skip the missing table.
+        let (removed, _) = remove_batch(&mut conn, &[serde_json::json!("a:/INBOX:1")]).unwrap();
+        assert_eq!(removed, vec!["a:/INBOX:1"]);
+        assert_eq!(rebuild_embeddings_start(&mut conn).unwrap(), 1);
+
+        let mut memory = Connection::open_in_memory().unwrap();
+        crate::fts::memory_db::init_memory_database(&memory).unwrap();
+        assert!(!table_exists(&memory, "memory_vec").unwrap());
+        let entries: Vec<Value> = (0..3)
+            .map(|i| serde_json::json!({ "memId": format!("m{i}"), "role": "user", "content": format!("note {i}"), "sessionId": if i < 2 { "s1" } else { "s2" }, "dateMs": i, "turnIndex": i }))
+            .collect();
+        crate::fts::memory_db::memory_index_batch(&mut memory, &entries, None).unwrap();
+        let (removed, _) = crate::fts::memory_db::memory_remove_batch(&mut memory, &[serde_json::json!("m0")]).unwrap();
+        assert_eq!(removed, vec!["m0"]);
+        assert_eq!(crate::fts::memory_db::memory_clear_session(&mut memory, "s1").unwrap(), 1);
+        assert!(crate::fts::memory_db::memory_session_digest(&memory, "s2", 2).is_ok());
+        assert_eq!(crate::fts::memory_db::rebuild_memory_embeddings_start(&mut memory).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_resolve_distance_metric() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        // New databases are stamped with the process metric (cosine unless init asked otherwise).
+        assert_eq!(read_db_meta(&conn, "distanceMetric").unwrap().as_deref(), Some("cosine"));
+        assert_eq!(resolve_distance_metric(&conn, "messages_vec", None).unwrap(), DistanceMetric::Cosine);
+        let err = resolve_distance_metric(&conn, "messages_vec", Some(DistanceMetric::L2)).unwrap_err();
+        assert!(err.to_string().contains("uses cosine distance"), "{err}");
+
+        // Pre-setting databases: read from the vec table DDL (no distance_metric = L2).
+        let legacy = Connection::open_in_memory().unwrap();
+        ensure_db_meta(&legacy).unwrap();
+        legacy.execute_batch("CREATE TABLE messages_vec (embedding BLOB)").unwrap();
+        assert_eq!(resolve_distance_metric(&legacy, "messages_vec", None).unwrap(), DistanceMetric::L2);
+        assert_eq!(read_db_meta(&legacy, "distanceMetric").unwrap().as_deref(), Some("l2"));
+    }
+
+    #[test]
+    fn test_warm_cache() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "a:/INBOX:1", "Budget review", 1_000);
+        let tables = warm_cache(&conn).unwrap();
+        assert_eq!(tables["message_meta"], 1);
+        assert!(tables["messages_fts_data"].as_i64().unwrap() > 0);
+        assert!(tables.get("messages_fts_idx").is_some());
+        // Every scan reads the table itself, not a covering index.
+        for (table, column) in WARM_CACHE_TABLES {
+            let plan: String = conn
+                .query_row(&format!("EXPLAIN QUERY PLAN {}", warm_cache_sql(table, column)), [], |r| r.get(3))
+                .unwrap();
+            assert!(!plan.contains("INDEX"), "{table}: {plan}");
+        }
+    }
+
+    #[test]
+    fn test_subject_search() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "a:/INBOX:1", "Budget review", 1_000);
+        insert_test_message(&conn, "a:/INBOX:2", "Quarterly budget", 2_000);
+        insert_test_message(&conn, "a:/INBOX:3", "Lunch", 3_000);
+        conn.execute("UPDATE messages_fts SET body = 'budget attached' WHERE msgId = 'a:/INBOX:3'", []).unwrap();
+
+        let results = subject_search(&conn, "budget", &serde_json::json!({}), 10).unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r["uniqueId"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["a:/INBOX:2", "a:/INBOX:1"]);
+        assert_eq!(results[0], serde_json::json!({ "uniqueId": "a:/INBOX:2", "subject": "Quarterly budget", "dateMs": 2_000 }));
+
+        assert_eq!(subject_search(&conn, "budget OR lunch", &serde_json::json!({}), 1).unwrap().len(), 1);
+        assert!(subject_search(&conn, "  ", &serde_json::json!({}), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_field_aliases_target_fts_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_messages_fts(&conn).unwrap();
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('messages_fts')").unwrap();
+        let columns: Vec<String> = stmt.query_map([], |r| r.get(0)).unwrap().map(|c| c.unwrap()).collect();
+        for (alias, column) in crate::fts::query::FIELD_ALIASES {
+            assert!(columns.iter().any(|c| c == column), "alias {alias}: -> missing column {column}");
+        }
+    }
+
     #[test]
     fn test_find_by_header_message_id_basic() {
         let conn = setup_test_db();
@@ -1200,7 +4208,7 @@ mod tests {
         insert_test_message(&conn, "account1:/[Gmail]/All Mail:msg123", "Test Subject", 1002);
 
         // Search for msg123 in account1
-        let results = find_by_header_message_id(&conn, "account1", "msg123").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "msg123").unwrap();
 
         assert_eq!(results.len(), 3);
         assert!(results.contains(&"account1:/INBOX:msg123".to_string()));
@@ -1218,7 +4226,7 @@ mod tests {
         insert_test_message(&conn, "account3:/INBOX:msg123", "Test Subject 3", 1002);
 
         // Search should only return entries for the specified account
-        let results = find_by_header_message_id(&conn, "account1", "msg123").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "msg123").unwrap();
 
         assert_eq!(results.len(), 1);
         assert!(results.contains(&"account1:/INBOX:msg123".to_string()));
@@ -1234,7 +4242,7 @@ mod tests {
         insert_test_message(&conn, "account1:/INBOX:msg789", "Test Subject 3", 1002);
 
         // Search should only return entries with matching headerMessageId
-        let results = find_by_header_message_id(&conn, "account1", "msg456").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "msg456").unwrap();
 
         assert_eq!(results.len(), 1);
         assert!(results.contains(&"account1:/INBOX:msg456".to_string()));
@@ -1248,11 +4256,11 @@ mod tests {
         insert_test_message(&conn, "account1:/INBOX:msg123", "Test Subject", 1000);
 
         // Search for non-existent headerMessageId
-        let results = find_by_header_message_id(&conn, "account1", "nonexistent").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "nonexistent").unwrap();
         assert_eq!(results.len(), 0);
 
         // Search for non-existent account
-        let results = find_by_header_message_id(&conn, "nonexistent", "msg123").unwrap();
+        let results = find_by_header_message_id(&conn, Some("nonexistent"), "msg123").unwrap();
         assert_eq!(results.len(), 0);
     }
 
@@ -1261,7 +4269,7 @@ mod tests {
         let conn = setup_test_db();
 
         // Search in empty database
-        let results = find_by_header_message_id(&conn, "account1", "msg123").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "msg123").unwrap();
         assert_eq!(results.len(), 0);
     }
 
@@ -1274,7 +4282,7 @@ mod tests {
         insert_test_message(&conn, "account1:/Folder With Spaces:msg123", "Test 2", 1001);
         insert_test_message(&conn, "account1:/Folder/With/Slashes:msg123", "Test 3", 1002);
 
-        let results = find_by_header_message_id(&conn, "account1", "msg123").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "msg123").unwrap();
 
         assert_eq!(results.len(), 3);
         assert!(results.contains(&"account1:/[Gmail]/All Mail:msg123".to_string()));
@@ -1295,11 +4303,1180 @@ mod tests {
 
         // When a message is deleted, we might only know the headerMessageId
         // This function should find all occurrences regardless of folder
-        let results = find_by_header_message_id(&conn, "account1", "test@example.com").unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "test@example.com").unwrap();
 
         assert_eq!(results.len(), 5);
     }
 
+    #[test]
+    fn test_find_by_header_message_id_any_account() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "account1:/INBOX:msg123", "Test Subject 1", 1000);
+        insert_test_message(&conn, "account2:/Sent:msg123", "Test Subject 2", 1001);
+        insert_test_message(&conn, "account2:/INBOX:msg456", "Other", 1002);
+
+        let results = find_by_header_message_id(&conn, None, "msg123").unwrap();
+        assert_eq!(results, vec!["account1:/INBOX:msg123".to_string(), "account2:/Sent:msg123".to_string()]);
+
+        // The raw header form matches too.
+        let results = find_by_header_message_id(&conn, None, " <msg123> ").unwrap();
+        assert_eq!(results.len(), 2);
+        // LIKE wildcards in the account id are literal.
+        assert!(find_by_header_message_id(&conn, Some("account_"), "msg123").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_header_message_id_backfill() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "account1:/INBOX:abc@host", "Test", 1000);
+        // Simulate a database from before the column existed.
+        conn.execute_batch(
+            "DROP INDEX idx_meta_header_message_id; ALTER TABLE message_meta DROP COLUMN headerMessageId;",
+        )
+        .unwrap();
+        ensure_meta_header_message_id_column(&conn).unwrap();
+        let results = find_by_header_message_id(&conn, Some("account1"), "<abc@host>").unwrap();
+        assert_eq!(results, vec!["account1:/INBOX:abc@host".to_string()]);
+        assert_eq!(header_message_id_of("a:/INBOX:x", Some("<y@h>")), "y@h");
+        // Only `account:/folder:key` keys are split; anything else is the Message-ID itself.
+        assert_eq!(header_message_id_of("a:/[Gmail]/All Mail:<k@h>", None), "k@h");
+        assert_eq!(header_message_id_of("urn:uuid:1234@host", None), "urn:uuid:1234@host");
+        assert_eq!(header_message_id_of("plain@host", None), "plain@host");
+        assert_eq!(header_message_id_of("a:/INBOX:", None), "a:/INBOX:");
+    }
+
+    #[test]
+    fn test_search_stream_pages() {
+        let conn = setup_test_db();
+        for i in 0..5 {
+            insert_test_message(&conn, &format!("account1:/INBOX:m{i}"), &format!("Budget {i}"), 1000 + i);
+        }
+        insert_test_message(&conn, "account1:/INBOX:other", "Lunch", 2000);
+
+        let params = serde_json::json!({ "ignoreDate": true, "fields": ["uniqueId"] });
+        let mut stream = SearchStream::new("budget", &params, &SynonymLookup::new()).unwrap().unwrap();
+        let mut seen = Vec::new();
+        let mut sizes = Vec::new();
+        loop {
+            let page = stream.next_page(&conn, 2).unwrap();
+            sizes.push(page.len());
+            if page.is_empty() {
+                break;
+            }
+            for r in page {
+                assert_eq!(r.as_object().unwrap().len(), 1);
+                seen.push(r["uniqueId"].as_str().unwrap().to_string());
+            }
+        }
+        assert_eq!(sizes, vec![2, 2, 1, 0]);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5);
+
+        assert!(SearchStream::new("  ", &params, &SynonymLookup::new()).unwrap().is_none());
+        assert!(SearchStream::new("budget", &serde_json::json!({ "from": "garbage" }), &SynonymLookup::new()).is_err());
+    }
+
+    #[test]
+    fn test_search_from_field_with_from_date_param() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget", "from": "alice@x.com", "body": "numbers", "dateMs": 2000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Budget", "from": "alice@x.com", "body": "numbers", "dateMs": 9000 }),
+            // Mentions alice only in the body: must not match a from: filter.
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Fwd", "from": "bob@x.com", "body": "ask alice@x.com", "dateMs": 2000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let ids = |q: &str, params: serde_json::Value| -> Vec<String> {
+            let (results, _) = search(&conn, q, &params, &synonyms, None).unwrap();
+            let mut ids: Vec<String> = results.iter().map(|r| r["uniqueId"].as_str().unwrap().to_string()).collect();
+            ids.sort();
+            ids
+        };
+
+        // `from` / `to` params are date bounds; `from:` in q is the sender column.
+        let in_range = serde_json::json!({ "from": 1000, "to": 3000 });
+        assert_eq!(ids("from:alice@x.com", in_range.clone()), vec!["a:/INBOX:1"]);
+        assert_eq!(ids("from:alice@x.com", serde_json::json!({ "ignoreDate": true })), vec!["a:/INBOX:1", "a:/INBOX:2"]);
+        assert_eq!(ids("alice@x.com", in_range.clone()), vec!["a:/INBOX:1", "a:/INBOX:3"]);
+        // A date-like value in q is never read as a date bound.
+        assert!(ids("from:1000", serde_json::json!({ "ignoreDate": true })).is_empty());
+        // A dangling prefix is a plain word, not a syntax error.
+        assert!(ids("budget from:", in_range).is_empty());
+    }
+
+    #[test]
+    fn test_search_per_account_limit() {
+        let conn = setup_test_db();
+
+        // account1 dominates by recency; account2/account3 have fewer hits.
+        for i in 0..8 {
+            insert_test_message(&conn, &format!("account1:/INBOX:a{i}"), "budget report", 2000 + i);
+        }
+        for i in 0..3 {
+            insert_test_message(&conn, &format!("account2:/INBOX:b{i}"), "budget report", 1000 + i);
+        }
+        for i in 0..3 {
+            insert_test_message(&conn, &format!("account3:/INBOX:c{i}"), "budget report", 500 + i);
+        }
+
+        let synonyms = SynonymLookup::new();
+        let params = serde_json::json!({ "limit": 6, "perAccountLimit": 2, "ignoreDate": true });
+        let results = search(&conn, "budget", &params, &synonyms, None).unwrap().0;
+
+        assert_eq!(results.len(), 6);
+        for account in ["account1", "account2", "account3"] {
+            let n = results
+                .iter()
+                .filter(|r| account_of(r["uniqueId"].as_str().unwrap()) == account)
+                .count();
+            assert_eq!(n, 2, "{account} should have exactly 2 results");
+        }
+        // Round-robin: the first three results come from three different accounts.
+        let first: std::collections::HashSet<&str> = results[..3]
+            .iter()
+            .map(|r| account_of(r["uniqueId"].as_str().unwrap()))
+            .collect();
+        assert_eq!(first.len(), 3);
+    }
+
+    #[test]
+    fn test_dedupe_content_across_folders() {
+        let mut conn = setup_test_db();
+        let first = vec![serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Team offsite", "body": "Agenda  attached", "dateMs": 1000 })];
+        assert_eq!(index_batch(&mut conn, &first, None, false, true).unwrap(), (1, 0, 0, vec![]));
+
+        // Same email in another folder (whitespace/case differ); an unrelated one doesn't match.
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/Archive:1", "subject": "team offsite", "body": "Agenda attached\n", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Team offsite", "body": "Moved to Friday", "dateMs": 2000 }),
+        ];
+        assert_eq!(index_batch(&mut conn, &rows, None, false, true).unwrap(), (2, 0, 1, vec![]));
+        // Both msgIds stay individually indexed.
+        assert_eq!(db_count(&conn).unwrap(), 3);
+
+        // Off by default: hashes are stored but not consulted.
+        let again = vec![serde_json::json!({ "msgId": "a:/Trash:1", "subject": "Team offsite", "body": "Agenda attached", "dateMs": 1000 })];
+        assert_eq!(index_batch(&mut conn, &again, None, false, false).unwrap(), (1, 0, 0, vec![]));
+        assert_eq!(content_hash("A  b", "", "", "", "", "C"), content_hash("a b", "", "", "", "", "c\n"));
+    }
+
+    #[test]
+    fn test_dedupe_content_requires_matching_headers() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Invoice", "from": "billing@vendor.com", "body": "Payment due", "dateMs": 1000 }),
+            // Same subject and body from another sender: its embedding input differs, so no twin.
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Invoice", "from": "billing@other.com", "body": "Payment due", "dateMs": 2000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Invoice", "from": "billing@vendor.com", "to": "me@example.com", "body": "Payment due", "dateMs": 3000 }),
+        ];
+        assert_eq!(index_batch(&mut conn, &rows, None, false, true).unwrap(), (3, 0, 0, vec![]));
+        // Moving a field between headers changes the hash too.
+        assert_ne!(content_hash("s", "a@x.io", "", "", "", "b"), content_hash("s", "", "a@x.io", "", "", "b"));
+    }
+
+    #[test]
+    fn test_index_batch_skips_failed_rows() {
+        let mut conn = setup_test_db();
+        // Stand-in for a row the schema rejects.
+        conn.execute_batch(
+            "CREATE TRIGGER reject_row BEFORE INSERT ON message_meta WHEN NEW.dateMs = 666
+             BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+        )
+        .unwrap();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Kept", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Bad", "dateMs": 666 }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Also kept", "dateMs": 2000 }),
+        ];
+        let (inserted, skipped, _, failed) = index_batch(&mut conn, &rows, None, false, false).unwrap();
+        assert_eq!((inserted, skipped), (2, 0));
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["msgId"], "a:/INBOX:2");
+        assert!(failed[0]["reason"].as_str().unwrap().contains("rejected"));
+        // The failed row left nothing behind, so it can be indexed once fixed.
+        assert_eq!(db_count(&conn).unwrap(), 2);
+        let ids: i64 = conn.query_row("SELECT COUNT(*) FROM message_ids", [], |r| r.get(0)).unwrap();
+        assert_eq!(ids, 2);
+    }
+
+    #[test]
+    fn test_index_batch_fails_whole_batch_on_database_errors() {
+        let sqlite_error = |code| anyhow::Error::new(rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(code), None));
+        assert!(is_row_error(&sqlite_error(rusqlite::ffi::SQLITE_CONSTRAINT)));
+        assert!(is_row_error(&sqlite_error(rusqlite::ffi::SQLITE_TOOBIG)));
+        assert!(is_row_error(&anyhow::anyhow!("embedding failed")));
+        for code in [
+            rusqlite::ffi::SQLITE_FULL,
+            rusqlite::ffi::SQLITE_IOERR,
+            rusqlite::ffi::SQLITE_INTERRUPT,
+            rusqlite::ffi::SQLITE_CORRUPT,
+        ] {
+            assert!(!is_row_error(&sqlite_error(code).context("while indexing")), "code {code}");
+        }
+
+        // A read-only database is not a problem with any one row.
+        let mut conn = setup_test_db();
+        conn.execute_batch("PRAGMA query_only = 1").unwrap();
+        let rows = vec![serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Kept", "dateMs": 1000 })];
+        assert!(index_batch(&mut conn, &rows, None, false, false).is_err());
+    }
+
+    #[test]
+    fn test_empty_message_is_not_embedded() {
+        assert!(is_degenerate_embedding(&[0.0; config::embedding::EMBEDDING_DIMS]));
+        assert!(is_degenerate_embedding(&[1e-9, -1e-9]));
+        assert!(!is_degenerate_embedding(&[0.6, 0.8]));
+        // All-empty fields prepare to no text, which the engine embeds as the zero vector.
+        let texts = crate::embeddings::text_prep::prepare_email_texts("", "", "", "", "", "");
+        assert!(texts.iter().all(|t| t.trim().is_empty()), "{texts:?}");
+    }
+
+    #[test]
+    #[ignore = "needs the model files (downloaded by a first init)"]
+    fn test_empty_message_is_not_embedded_end_to_end() {
+        let dir = crate::embeddings::download::model_dir().unwrap();
+        let engine = EmbeddingEngine::load(&dir).expect("model files present");
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "", "from_": "", "to_": "", "body": "", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Budget", "body": "numbers", "dateMs": 2000 }),
+        ];
+        let (inserted, _, _, failed) = index_batch(&mut conn, &rows, Some(&engine), false, false).unwrap();
+        assert_eq!(inserted, 2);
+        assert!(failed.is_empty());
+        // Both are searchable as text; only the one with content has a vector.
+        assert_eq!(db_count(&conn).unwrap(), 2);
+        assert_eq!(vec_count(&conn), 1);
+        let vec_rowid: i64 = conn.query_row("SELECT rowid FROM messages_vec", [], |r| r.get(0)).unwrap();
+        let empty_rowid: i64 =
+            conn.query_row("SELECT rowid FROM message_ids WHERE msgId = 'a:/INBOX:1'", [], |r| r.get(0)).unwrap();
+        assert_ne!(vec_rowid, empty_rowid);
+    }
+
+    #[test]
+    fn test_suggest_queries() {
+        let mut conn = setup_test_db();
+        let subjects = [
+            "Quarterly budget review",
+            "Re: Quarterly budget review",
+            "Budget approval for the offsite",
+            "Offsite hotel options",
+            "Lunch?",
+        ];
+        let rows: Vec<Value> = subjects
+            .iter()
+            .enumerate()
+            .map(|(i, s)| serde_json::json!({ "msgId": format!("a:/INBOX:{i}"), "subject": s, "dateMs": 1000 + i as i64 }))
+            .collect();
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+
+        let got: Vec<String> = suggest_queries(&conn, 5)
+            .unwrap()
+            .iter()
+            .map(|v| v["query"].as_str().unwrap().to_string())
+            .collect();
+        // Pairs in 2 subjects outrank "budget" (3), which they then cover, as they do
+        // "quarterly" / "review"; terms seen in only one subject are omitted.
+        assert_eq!(got, vec!["budget review", "quarterly budget", "offsite"]);
+        assert_eq!(suggest_queries(&conn, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get_parsed_ics() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Invite", "dateMs": 1000, "parsedIcsAttachments": "BEGIN:VCALENDAR\nEND:VCALENDAR\n" }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Plain", "dateMs": 2000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        assert_eq!(get_parsed_ics(&conn, "a:/INBOX:1").unwrap().as_deref(), Some("BEGIN:VCALENDAR\nEND:VCALENDAR\n"));
+        assert_eq!(get_parsed_ics(&conn, "a:/INBOX:2").unwrap().as_deref(), Some(""));
+        assert_eq!(get_parsed_ics(&conn, "a:/INBOX:3").unwrap(), None);
+    }
+
+    #[test]
+    fn test_debug_sample_order_and_limit() {
+        let mut conn = setup_test_db();
+        let rows: Vec<Value> = (0..5)
+            .map(|i| serde_json::json!({ "msgId": format!("a:/INBOX:{i}"), "subject": format!("s{i}"), "dateMs": 1000 + i }))
+            .collect();
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let ids = |params: Value| -> Vec<String> {
+            debug_sample(&conn, &params)
+                .unwrap()
+                .iter()
+                .map(|r| r["msgId"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        assert_eq!(ids(serde_json::json!({ "limit": 2 })), vec!["a:/INBOX:4", "a:/INBOX:3"]);
+        assert_eq!(ids(serde_json::json!({ "order": "oldest", "limit": 2 })), vec!["a:/INBOX:0", "a:/INBOX:1"]);
+        assert_eq!(ids(serde_json::json!({ "order": "random", "limit": 100 })).len(), 5);
+        assert!(debug_sample(&conn, &serde_json::json!({ "order": "sideways" })).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_msgid_rows_collapse_in_search() {
+        let mut conn = setup_test_db();
+        let rows = vec![serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Invoice overdue", "dateMs": 1000 })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        // A second FTS/meta row with the same msgId under another rowid (e.g. a buggy reindex).
+        conn.execute_batch(
+            "INSERT INTO messages_fts (rowid, msgId, subject, from_, to_, cc, bcc, body, tags)
+                 VALUES (99, 'a:/INBOX:1', 'Invoice overdue', '', '', '', '', '', '');
+             INSERT INTO message_meta (rowid, dateMs, hasAttachments, tags) VALUES (99, 1000, 0, '');",
+        )
+        .unwrap();
+
+        let synonyms = SynonymLookup::new();
+        let params = serde_json::json!({ "ignoreDate": true });
+        let results = search(&conn, "invoice", &params, &synonyms, None).unwrap().0;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["uniqueId"], "a:/INBOX:1");
+    }
+
+    #[test]
+    fn test_exclude_date_window() {
+        let mut conn = setup_test_db();
+        let rows: Vec<Value> = [1000, 1500, 2000, 2500]
+            .iter()
+            .map(|d| serde_json::json!({ "msgId": format!("a:/INBOX:{d}"), "subject": "standup notes", "dateMs": d }))
+            .collect();
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let dates = |params: Value| -> Vec<i64> {
+            let mut d: Vec<i64> = search(&conn, "standup", &params, &synonyms, None)
+                .unwrap()
+                .0
+                .iter()
+                .map(|r| r["dateMs"].as_i64().unwrap())
+                .collect();
+            d.sort();
+            d
+        };
+        // 1500 matches the query but sits inside the (inclusive) exclusion window.
+        assert_eq!(dates(serde_json::json!({ "excludeFrom": 1500, "excludeTo": 2000 })), vec![1000, 2500]);
+        // Composes with from/to, and an open end excludes everything after excludeFrom.
+        assert_eq!(dates(serde_json::json!({ "from": 1000, "to": 2500, "excludeFrom": 1200, "excludeTo": 1800 })), vec![1000, 2000, 2500]);
+        assert_eq!(dates(serde_json::json!({ "excludeFrom": 2000 })), vec![1000, 1500]);
+        assert_eq!(dates(serde_json::json!({ "excludeFrom": 1500, "ignoreDate": true })), vec![1000, 1500, 2000, 2500]);
+        assert!(search(&conn, "standup", &serde_json::json!({ "excludeTo": "not a date" }), &synonyms, None).is_err());
+
+        // Vector-only hits go through the same check in Rust.
+        let filters = SearchFilters::lenient(&serde_json::json!({ "excludeFrom": 1500, "excludeTo": 2000 })).unwrap();
+        assert!(filters.has_date_bounds());
+        let rowid = |d: i64| -> i64 {
+            conn.query_row("SELECT rowid FROM message_ids WHERE msgId = ?1", params![format!("a:/INBOX:{d}")], |r| r.get(0)).unwrap()
+        };
+        assert!(!filters.admits(&fetch_message_meta(&conn, rowid(1500)).unwrap().unwrap()));
+        assert!(filters.admits(&fetch_message_meta(&conn, rowid(2500)).unwrap().unwrap()));
+    }
+
+    #[test]
+    fn test_date_range_inclusivity_at_boundaries() {
+        let mut conn = setup_test_db();
+        let rows: Vec<Value> = [1000, 1500, 2000]
+            .iter()
+            .map(|d| serde_json::json!({ "msgId": format!("a:/INBOX:{d}"), "subject": "standup notes", "dateMs": d }))
+            .collect();
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let dates = |inclusive: &str| -> Vec<i64> {
+            let params = serde_json::json!({ "from": 1000, "to": 2000, "dateRangeInclusive": inclusive });
+            let mut d: Vec<i64> = search(&conn, "standup", &params, &synonyms, None)
+                .unwrap()
+                .0
+                .iter()
+                .map(|r| r["dateMs"].as_i64().unwrap())
+                .collect();
+            d.sort();
+            d
+        };
+        assert_eq!(dates("both"), vec![1000, 1500, 2000]);
+        assert_eq!(dates("fromOnly"), vec![1000, 1500]);
+        assert_eq!(dates("neither"), vec![1500]);
+        let bad = serde_json::json!({ "from": 1000, "dateRangeInclusive": "open" });
+        assert!(search(&conn, "standup", &bad, &synonyms, None).is_err());
+        assert!(SearchFilters::lenient(&bad).is_err(), "hybrid search rejects it too");
+
+        let range = |inclusive| query_by_date_range(&conn, &serde_json::json!(1000), &serde_json::json!(2000), 10, inclusive, DateTz::Local).unwrap().len();
+        assert_eq!(range(DateInclusivity::Both), 3);
+        assert_eq!(range(DateInclusivity::FromOnly), 2);
+        assert_eq!(range(DateInclusivity::Neither), 1);
+
+        // The Rust-side check (vector-only hits) agrees with the SQL operators.
+        assert!(DateInclusivity::FromOnly.admits(1000, Some(1000), Some(2000)));
+        assert!(!DateInclusivity::FromOnly.admits(2000, Some(1000), Some(2000)));
+        assert!(!DateInclusivity::Neither.admits(1000, Some(1000), None));
+        assert!(DateInclusivity::Both.admits(2000, None, Some(2000)));
+    }
+
+    #[test]
+    fn test_dates_at_dst_boundaries() {
+        // US spring-forward (2024-03-10 07:00Z) and fall-back (2024-11-03 06:00Z) instants
+        // read back the same in UTC mode whatever the machine's zone.
+        let spring = 1_710_054_000_000;
+        let fall = 1_730_613_600_000;
+        assert_eq!(format_date_iso_like_python(spring, DateTz::Utc), "2024-03-10T07:00:00");
+        assert_eq!(format_date_iso_like_python(fall + 1, DateTz::Utc), "2024-11-03T06:00:00.001000");
+        let utc = |s: &str| parse_date_param_tz(&serde_json::json!(s), DateTz::Utc).unwrap();
+        assert_eq!(utc("2024-03-10T07:00:00"), Some(spring));
+        assert_eq!(utc("2024-11-03 06:00:00.000"), Some(fall));
+        assert_eq!(utc("2024-03-10"), Some(spring - 7 * 3_600_000));
+        // Explicit offsets ignore tz.
+        let offset = serde_json::json!("2024-03-10T03:00:00-04:00");
+        assert_eq!(parse_date_param_tz(&offset, DateTz::Local).unwrap(), Some(spring));
+
+        // Wall-clock times around a DST change (US Eastern, as offsets).
+        let edt = chrono::FixedOffset::west_opt(4 * 3600).unwrap();
+        let est = chrono::FixedOffset::west_opt(5 * 3600).unwrap();
+        let naive = NaiveDate::from_ymd_opt(2024, 11, 3).unwrap().and_hms_opt(1, 30, 0).unwrap();
+        let repeated = LocalResult::Ambiguous(
+            edt.from_local_datetime(&naive).unwrap(),
+            est.from_local_datetime(&naive).unwrap(),
+        );
+        assert_eq!(resolve_local_time(repeated, naive).unwrap(), fall - 30 * 60_000);
+        let skipped = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap().and_hms_opt(2, 30, 0).unwrap();
+        let gap: LocalResult<DateTime<chrono::FixedOffset>> = LocalResult::None;
+        assert!(resolve_local_time(gap, skipped).is_err());
+
+        assert!(DateTz::from_params(&serde_json::json!({ "tz": "UTC+2" })).is_err());
+        let info = timezone_info();
+        assert!(info["utcOffsetMinutes"].is_i64() && info["observesDst"].is_boolean());
+    }
+
+    #[test]
+    fn test_integrity_check() {
+        let conn = setup_test_db();
+        assert!(integrity_check(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_candidate_limits() {
+        let multiplied = 10 * config::hybrid::CANDIDATE_MULTIPLIER;
+        assert_eq!(candidate_limits(&serde_json::json!({}), 10), (multiplied, multiplied));
+        let params = serde_json::json!({ "ftsCandidateLimit": 15, "vecCandidateLimit": 200 });
+        assert_eq!(candidate_limits(&params, 10), (15, 200));
+        // Non-positive falls back to the default; oversize is capped at vec0's k limit.
+        let params = serde_json::json!({ "ftsCandidateLimit": 0, "vecCandidateLimit": 1_000_000 });
+        assert_eq!(candidate_limits(&params, 10), (multiplied, config::hybrid::CANDIDATE_LIMIT_MAX));
+    }
+
+    #[test]
+    fn test_date_scoped_vec_candidates() {
+        let k = 10 * config::hybrid::CANDIDATE_MULTIPLIER;
+        let widened = k * config::hybrid::DATE_FILTER_VEC_K_MULTIPLIER;
+        assert_eq!(date_scoped_vec_k(&serde_json::json!({}), k, false), k);
+        assert_eq!(date_scoped_vec_k(&serde_json::json!({ "from": "2024-01-01" }), k, true), widened);
+        assert_eq!(date_scoped_vec_k(&serde_json::json!({ "vecCandidateLimit": 25 }), 25, true), 25);
+        assert_eq!(date_scoped_vec_k(&serde_json::json!({}), 4000, true), config::hybrid::CANDIDATE_LIMIT_MAX);
+
+        // KNN ranking for a semantic query: the nearest neighbours are all old mail;
+        // the in-range matches sit deeper in the ranking.
+        let conn = setup_test_db();
+        for i in 1..=6 {
+            let date_ms = if i <= 4 { 1_000 + i } else { 50_000 + i };
+            insert_test_message(&conn, &format!("a:/INBOX:{i}"), "Offsite planning", date_ms);
+        }
+        let rowid = |i: i64| -> i64 {
+            conn.query_row("SELECT rowid FROM message_ids WHERE msgId = ?1", params![format!("a:/INBOX:{i}")], |r| r.get(0))
+                .unwrap()
+        };
+        let mut knn: Vec<(i64, f64)> = (1..=6).map(|i| (rowid(i), i as f64 / 10.0)).collect();
+        retain_in_date_range(&conn, "message_meta", &mut knn, Some(50_000), None, DateInclusivity::Both).unwrap();
+        assert_eq!(knn, vec![(rowid(5), 0.5), (rowid(6), 0.6)]);
+
+        // In-range neighbours now fill the merged top slots instead of being dropped after the merge.
+        let merged = crate::fts::hybrid::merge_results(&[], &knn, 0.7, 0.3, 2);
+        assert_eq!(merged.iter().map(|m| m.rowid).collect::<Vec<_>>(), vec![rowid(5), rowid(6)]);
+
+        let mut knn = vec![(rowid(1), 0.1)];
+        retain_in_date_range(&conn, "message_meta", &mut knn, None, None, DateInclusivity::Both).unwrap();
+        assert_eq!(knn.len(), 1);
+    }
+
+    #[test]
+    fn test_tag_filtered_semantic_search_fills_the_page() {
+        // 40 messages on one topic; the 30 nearest neighbours lack the tag the query asks
+        // for, and no subject matches the query text, so every hit is vector-only.
+        let mut conn = setup_vec_test_db();
+        let rows: Vec<Value> = (1..=40)
+            .map(|i| {
+                let tags: Vec<&str> = if i > 30 { vec!["travel"] } else { vec![] };
+                serde_json::json!({ "msgId": format!("a:/INBOX:{i}"), "subject": "Offsite planning", "dateMs": i, "tags": tags })
+            })
+            .collect();
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        for i in 1..=40 {
+            insert_test_vector(&conn, &format!("a:/INBOX:{i}"), i as f32 / 100.0);
+        }
+
+        let query = unit_vector(0.0);
+        let params = serde_json::json!({ "ignoreDate": true, "tags": ["travel"], "vecCandidateLimit": 8 });
+        let (results, mode) = search_hybrid(&conn, "retreat", &params, &SynonymLookup::new(), &query, None, 5).unwrap();
+        assert_eq!(mode, SearchMode::Hybrid);
+        let ids: Vec<&str> = results.iter().map(|r| r["uniqueId"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["a:/INBOX:31", "a:/INBOX:32", "a:/INBOX:33", "a:/INBOX:34", "a:/INBOX:35"]);
+
+        // With over-fetch disabled the k=8 pool holds no tagged message at all.
+        let params = serde_json::json!({ "ignoreDate": true, "tags": ["travel"], "vecCandidateLimit": 8, "maxCandidateLimit": 0 });
+        let (results, _) = search_hybrid(&conn, "retreat", &params, &SynonymLookup::new(), &query, None, 5).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_date_filtered_semantic_search_fills_the_page() {
+        // The 30 nearest neighbours are older than the requested range; only vector hits
+        // (no subject matches the query) dated inside it may fill the page.
+        let mut conn = setup_vec_test_db();
+        let rows: Vec<Value> = (1..=40)
+            .map(|i| serde_json::json!({ "msgId": format!("a:/INBOX:{i}"), "subject": "Offsite planning", "dateMs": i * 1000 }))
+            .collect();
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        for i in 1..=40 {
+            insert_test_vector(&conn, &format!("a:/INBOX:{i}"), i as f32 / 100.0);
+        }
+
+        let query = unit_vector(0.0);
+        let params = serde_json::json!({ "from": 31_000, "to": 40_000 });
+        let (results, mode) = search_hybrid(&conn, "retreat", &params, &SynonymLookup::new(), &query, None, 5).unwrap();
+        assert_eq!(mode, SearchMode::Hybrid);
+        let ids: Vec<&str> = results.iter().map(|r| r["uniqueId"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["a:/INBOX:31", "a:/INBOX:32", "a:/INBOX:33", "a:/INBOX:34", "a:/INBOX:35"]);
+        assert!(results.iter().all(|r| (31_000..=40_000).contains(&r["dateMs"].as_i64().unwrap())));
+    }
+
+    #[test]
+    fn test_reembed_rows_replaces_vectors_and_counts_failures() {
+        let mut conn = setup_vec_test_db();
+        create_embed_cache_table(&conn).unwrap();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget", "body": "numbers", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "", "body": "", "dateMs": 2000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Broken", "body": "model error", "dateMs": 3000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        for (i, id) in ["a:/INBOX:1", "a:/INBOX:2", "a:/INBOX:3"].iter().enumerate() {
+            insert_test_vector(&conn, id, i as f32 / 10.0);
+        }
+        // Stand-in model: blank input is the zero vector, "Broken" fails, the rest embed alike.
+        let embed = |texts: &[String]| -> anyhow::Result<Vec<f32>> {
+            let joined = texts.join(" ");
+            if joined.contains("Broken") {
+                bail!("model error");
+            }
+            Ok(if joined.trim().is_empty() { vec![0.0; config::embedding::EMBEDDING_DIMS] } else { unit_vector(1.0) })
+        };
+        let vector_of = |conn: &Connection, id: &str| -> Option<Vec<u8>> {
+            conn.query_row(
+                "SELECT v.embedding FROM messages_vec v JOIN message_ids i ON i.rowid = v.rowid WHERE i.msgId = ?1",
+                params![id],
+                |r| r.get(0),
+            )
+            .optional()
+            .unwrap()
+        };
+
+        let p = reembed_rows(&mut conn, "", embed, 0, 10).unwrap();
+        assert_eq!((p.processed, p.embedded, p.cache_hits, p.failed, p.remaining, p.done), (3, 1, 0, 1, 0, true));
+        assert_eq!(vector_of(&conn, "a:/INBOX:1"), Some(f32_vec_to_blob(&unit_vector(1.0))));
+        // The blank message loses its vector; the failed one keeps its old one.
+        assert_eq!(vector_of(&conn, "a:/INBOX:2"), None);
+        assert_eq!(vector_of(&conn, "a:/INBOX:3"), Some(f32_vec_to_blob(&unit_vector(0.2))));
+        // Only the real embedding is cached, and a second pass is served from it.
+        let cached: i64 = conn.query_row("SELECT COUNT(*) FROM embed_cache", [], |r| r.get(0)).unwrap();
+        assert_eq!(cached, 1);
+        let p = reembed_rows(&mut conn, "", embed, 0, 2).unwrap();
+        assert_eq!((p.processed, p.embedded, p.cache_hits, p.remaining, p.done), (2, 1, 1, 1, false));
+    }
+
+    #[test]
+    fn test_grow_candidate_limits() {
+        let pass = |found, fts_fetched, vec_fetched| HybridPass { value: (), found, fts_fetched, vec_fetched };
+        let growth = config::hybrid::OVERFETCH_GROWTH;
+        // Enough results: stop.
+        assert_eq!(grow_candidate_limits(&pass(10, 40, 40), 10, (40, 40), 2048), None);
+        // Short, both pools full: both grow.
+        assert_eq!(grow_candidate_limits(&pass(3, 40, 40), 10, (40, 40), 2048), Some((40 * growth, 40 * growth)));
+        // Only the full pool grows; a short pool already holds every match.
+        assert_eq!(grow_candidate_limits(&pass(3, 12, 40), 10, (40, 40), 2048), Some((40, 40 * growth)));
+        assert_eq!(grow_candidate_limits(&pass(3, 12, 25), 10, (40, 40), 2048), None);
+        // Bounded by the ceiling.
+        assert_eq!(grow_candidate_limits(&pass(3, 40, 1000), 10, (40, 1000), 2048), Some((160, 2048)));
+        assert_eq!(grow_candidate_limits(&pass(3, 40, 2048), 10, (40, 2048), 40), None);
+
+        assert_eq!(overfetch_max(&serde_json::json!({})), config::hybrid::OVERFETCH_MAX_CANDIDATES);
+        assert_eq!(overfetch_max(&serde_json::json!({ "maxCandidateLimit": 0 })), 0);
+        assert_eq!(
+            overfetch_max(&serde_json::json!({ "maxCandidateLimit": 1_000_000 })),
+            config::hybrid::CANDIDATE_LIMIT_MAX
+        );
+    }
+
+    #[test]
+    fn test_reindex_fts_from_content() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "account1:/INBOX:m1", "subject": "Quarterly budget", "body": "numbers attached", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "account1:/INBOX:m2", "subject": "Lunch plans", "body": "tacos on friday", "dateMs": 2000 }),
+        ];
+        let (inserted, _, _, _) = index_batch(&mut conn, &rows, None, false, false).unwrap();
+        assert_eq!(inserted, 2);
+
+        let reinserted = reindex_fts_from_content(&mut conn).unwrap();
+        assert_eq!(reinserted, 2);
+        assert_eq!(db_count(&conn).unwrap(), 2);
+
+        // Rowids are preserved, so meta joins (and vec joins) still line up.
+        let synonyms = SynonymLookup::new();
+        let params = serde_json::json!({ "ignoreDate": true });
+        let results = search(&conn, "tacos", &params, &synonyms, None).unwrap().0;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["uniqueId"], "account1:/INBOX:m2");
+        assert_eq!(results[0]["dateMs"], 2000);
+    }
+
+    #[test]
+    fn test_vacuum_into_snapshot() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "account1:/INBOX:msg1", "Snapshot me", 1000);
+
+        let dir = std::env::temp_dir().join(format!("tabmail-fts-backup-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dest = dir.join("fts-backup.db");
+
+        let bytes = vacuum_into(&conn, &dest).unwrap();
+        assert!(bytes > 0);
+        let copy = Connection::open(&dest).unwrap();
+        assert_eq!(db_count(&copy).unwrap(), 1);
+
+        // Refuses to overwrite an existing file.
+        assert!(vacuum_into(&conn, &dest).is_err());
+        drop(copy);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate_backup_checks_tables_and_version() {
+        let dir = std::env::temp_dir().join(format!("tabmail-fts-restore-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let required = ["messages_fts", "message_meta", "message_ids"];
+
+        // Valid: full schema + current version.
+        let good = dir.join("good.db");
+        let conn = setup_test_db();
+        ensure_db_meta(&conn).unwrap();
+        vacuum_into(&conn, &good).unwrap();
+        assert!(validate_backup(&good, &required).is_ok());
+
+        // Incompatible schema version.
+        let newer = dir.join("newer.db");
+        conn.execute(
+            "UPDATE db_meta SET value = ?1 WHERE key = 'schemaVersion'",
+            params![(config::SCHEMA_VERSION + 1).to_string()],
+        ).unwrap();
+        vacuum_into(&conn, &newer).unwrap();
+        let err = validate_backup(&newer, &required).unwrap_err();
+        assert!(err.to_string().contains("incompatible"));
+
+        // Missing tables / not a database.
+        let empty = dir.join("empty.db");
+        vacuum_into(&Connection::open_in_memory().unwrap(), &empty).unwrap();
+        assert!(validate_backup(&empty, &required).is_err());
+        let junk = dir.join("junk.db");
+        std::fs::write(&junk, b"definitely not sqlite").unwrap();
+        assert!(validate_backup(&junk, &required).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failed_restore_keeps_live_db_writable() {
+        let dir = std::env::temp_dir().join(format!("tabmail-fts-failed-restore-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (db_path, mut conn) = open_or_create_db(&dir).unwrap();
+        let row = |i: i64| serde_json::json!({ "msgId": format!("a:/INBOX:{i}"), "subject": "Kept", "dateMs": i });
+        index_batch(&mut conn, &[row(1)], None, false, false).unwrap();
+
+        let junk = dir.join("junk.db");
+        std::fs::write(&junk, b"definitely not sqlite").unwrap();
+        assert!(restore_backup(&db_path, &mut conn, &dir.join("missing.db")).is_err());
+        assert!(restore_backup(&db_path, &mut conn, &junk).is_err());
+
+        // Still the file-backed DB: later writes land on disk, next to the earlier ones.
+        index_batch(&mut conn, &[row(2)], None, false, false).unwrap();
+        assert_eq!(db_count(&Connection::open(&db_path).unwrap()).unwrap(), 2);
+        drop(conn);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_search_custom_snippet_markers() {
+        let mut conn = setup_test_db();
+        let rows = vec![serde_json::json!({
+            "msgId": "account1:/INBOX:m1", "subject": "Status", "body": "the [draft] contract is ready", "dateMs": 1000
+        })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+
+        let params = serde_json::json!({ "ignoreDate": true, "snippetOpen": "<mark>", "snippetClose": "</mark>" });
+        let (results, _) = search(&conn, "contract", &params, &synonyms, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0]["snippet"].as_str().unwrap().contains("<mark>contract</mark>"));
+
+        let too_long = "x".repeat(config::sqlite::SNIPPET_MARKER_MAX_CHARS + 1);
+        let params = serde_json::json!({ "ignoreDate": true, "snippetOpen": too_long });
+        assert!(search(&conn, "contract", &params, &synonyms, None).is_err());
+    }
+
+    #[test]
+    fn test_preview_snippet_for_vector_only_hit() {
+        let mut conn = setup_test_db();
+        let body = format!("Thanks for the notes from  yesterday.\n{}", "word ".repeat(40));
+        let rows = vec![serde_json::json!({
+            "msgId": "account1:/INBOX:m1", "subject": "Recap", "body": body, "dateMs": 1000
+        })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let rowid: i64 = conn.query_row("SELECT rowid FROM message_ids", [], |r| r.get(0)).unwrap();
+
+        let preview = preview_snippet(&fetch_message_meta(&conn, rowid).unwrap().unwrap().body, "…");
+        let tokens = config::sqlite::SEARCH_SNIPPET_TOKENS as usize;
+        assert!(preview.starts_with("Thanks for the notes from yesterday. word"));
+        assert!(preview.ends_with("word…"));
+        assert_eq!(preview.trim_end_matches('…').split(' ').count(), tokens);
+
+        assert_eq!(preview_snippet("short body", "..."), "short body");
+        assert_eq!(preview_snippet("", "…"), "");
+    }
+
+    #[test]
+    fn test_search_snippet_count() {
+        let mut conn = setup_test_db();
+        let filler = "lorem ipsum dolor sit amet ".repeat(10);
+        let body = format!("budget draft attached. {filler} please review the budget numbers. {filler} final budget due friday");
+        let rows = vec![serde_json::json!({
+            "msgId": "account1:/INBOX:m1", "subject": "Numbers", "body": body, "dateMs": 1000
+        })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+
+        // Default: single snippet, no snippets array.
+        let params = serde_json::json!({ "ignoreDate": true });
+        let (results, _) = search(&conn, "budget", &params, &synonyms, None).unwrap();
+        assert!(results[0].get("snippets").is_none());
+
+        let params = serde_json::json!({ "ignoreDate": true, "snippetCount": 3 });
+        let (results, _) = search(&conn, "budget", &params, &synonyms, None).unwrap();
+        let snippets = results[0]["snippets"].as_array().unwrap();
+        assert_eq!(snippets.len(), 3);
+        assert!(snippets[0].as_str().unwrap().starts_with("[budget] draft"));
+        assert!(snippets[1].as_str().unwrap().contains("the [budget] numbers"));
+        assert!(snippets[2].as_str().unwrap().starts_with('…'));
+        assert!(snippets[2].as_str().unwrap().contains("final [budget] due friday"));
+
+        // Capped by config.
+        let params = serde_json::json!({ "ignoreDate": true, "snippetCount": 1000 });
+        assert_eq!(ResultOptions::from_params(&params).unwrap().count, config::sqlite::SEARCH_SNIPPET_MAX_COUNT);
+        let params = serde_json::json!({ "ignoreDate": true, "snippetCount": 0 });
+        assert!(search(&conn, "budget", &params, &synonyms, None).is_err());
+    }
+
+    #[test]
+    fn test_search_include_recipients() {
+        let mut conn = setup_test_db();
+        let rows = vec![serde_json::json!({
+            "msgId": "account1:/INBOX:m1", "subject": "Offsite plan", "from": "alice@example.com",
+            "to": "team@example.com", "cc": "boss@example.com", "dateMs": 1000
+        })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+
+        let params = serde_json::json!({ "ignoreDate": true });
+        let (results, _) = search(&conn, "offsite", &params, &synonyms, None).unwrap();
+        assert!(results[0].get("to_").is_none());
+
+        let params = serde_json::json!({ "ignoreDate": true, "includeRecipients": true });
+        let (results, _) = search(&conn, "offsite", &params, &synonyms, None).unwrap();
+        assert_eq!(results[0]["to_"], "team@example.com");
+        assert_eq!(results[0]["cc"], "boss@example.com");
+        assert_eq!(results[0]["bcc"], "");
+    }
+
+    #[test]
+    fn test_stats_history() {
+        let conn = setup_test_db();
+        ensure_stats_history(&conn).unwrap();
+        insert_test_message(&conn, "account1:/INBOX:msg1", "Hello", 1000);
+
+        let dir = std::env::temp_dir().join(format!("tm_stats_history_{}", std::process::id()));
+        let snap = record_stats(&conn, &dir.join("missing.sqlite")).unwrap();
+        assert_eq!(snap["docs"], 1);
+        assert_eq!(snap["dbBytes"], 0);
+
+        // Older rows are pruned past STATS_HISTORY_MAX_ROWS.
+        for ts in 1..=config::sqlite::STATS_HISTORY_MAX_ROWS + 5 {
+            conn.execute(
+                "INSERT INTO stats_history (timestampMs, docs, vecDocs, dbBytes, walBytes) VALUES (?1, 0, 0, 0, 0)",
+                params![ts],
+            )
+            .unwrap();
+        }
+        record_stats(&conn, &dir.join("missing.sqlite")).unwrap();
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM stats_history", [], |r| r.get(0)).unwrap();
+        assert_eq!(total, config::sqlite::STATS_HISTORY_MAX_ROWS);
+
+        let recent = stats_history(&conn, 3).unwrap();
+        assert_eq!(recent.len(), 3);
+        assert!(recent[0]["timestampMs"].as_i64() < recent[2]["timestampMs"].as_i64());
+        assert_eq!(recent[2]["docs"], 1);
+    }
+
+    #[test]
+    fn test_detect_language_and_lang_filter() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({
+                "msgId": "account1:/INBOX:fr", "subject": "Réunion projet", "dateMs": 1000,
+                "body": "Bonjour à tous, la réunion du projet est déplacée à jeudi prochain. Merci de confirmer votre présence avant mercredi soir."
+            }),
+            serde_json::json!({
+                "msgId": "account1:/INBOX:en", "subject": "Projet meeting", "dateMs": 2000,
+                "body": "Hello everyone, the project meeting has been moved to next Thursday. Please confirm your attendance before Wednesday evening."
+            }),
+            serde_json::json!({ "msgId": "account1:/INBOX:short", "subject": "projet", "dateMs": 3000 }),
+        ];
+        index_batch(&mut conn, &rows, None, true, false).unwrap();
+        let lang_of = |conn: &Connection, id: &str| -> Option<String> {
+            conn.query_row(
+                "SELECT m.lang FROM message_meta m JOIN message_ids i ON i.rowid = m.rowid WHERE i.msgId = ?1",
+                params![id],
+                |r| r.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(lang_of(&conn, "account1:/INBOX:fr").as_deref(), Some("fra"));
+        assert_eq!(lang_of(&conn, "account1:/INBOX:en").as_deref(), Some("eng"));
+        assert_eq!(lang_of(&conn, "account1:/INBOX:short"), None);
+
+        let synonyms = SynonymLookup::new();
+        let params = serde_json::json!({ "ignoreDate": true, "lang": "fra" });
+        let (results, _) = search(&conn, "projet", &params, &synonyms, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["uniqueId"], "account1:/INBOX:fr");
+
+        // Detection is opt-in.
+        let rows = vec![serde_json::json!({
+            "msgId": "account1:/INBOX:fr2", "subject": "Encore", "dateMs": 4000,
+            "body": "Bonjour à tous, la réunion du projet est déplacée à jeudi prochain. Merci de confirmer votre présence."
+        })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        assert_eq!(lang_of(&conn, "account1:/INBOX:fr2"), None);
+    }
+
+    #[test]
+    fn test_tags_index_filter_and_results() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Q3 report", "dateMs": 1000, "tags": ["$label1", "todo"] }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Q3 report draft", "dateMs": 2000, "tags": "todo todo" }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Q3 report final", "dateMs": 3000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let ids = |params: Value| -> Vec<String> {
+            let (results, _) = search(&conn, "report", &params, &synonyms, None).unwrap();
+            results.iter().map(|r| r["uniqueId"].as_str().unwrap().to_string()).collect()
+        };
+
+        // Results carry tags; duplicates are collapsed at index time.
+        let (results, _) = search(&conn, "report", &serde_json::json!({ "ignoreDate": true }), &synonyms, None).unwrap();
+        assert_eq!(results[2]["tags"], serde_json::json!(["$label1", "todo"]));
+        assert_eq!(results[1]["tags"], serde_json::json!(["todo"]));
+        assert_eq!(results[0]["tags"], serde_json::json!([]));
+
+        // AND semantics across requested tags; whole-tag matching only.
+        assert_eq!(ids(serde_json::json!({ "ignoreDate": true, "tags": ["todo"] })), vec!["a:/INBOX:2", "a:/INBOX:1"]);
+        assert_eq!(ids(serde_json::json!({ "ignoreDate": true, "tags": ["todo", "$label1"] })), vec!["a:/INBOX:1"]);
+        assert!(ids(serde_json::json!({ "ignoreDate": true, "tags": ["tod"] })).is_empty());
+        assert!(ids(serde_json::json!({ "ignoreDate": true, "tags": ["to%"] })).is_empty());
+
+        // Tag names are searchable text too.
+        let (results, _) = search(&conn, "todo", &serde_json::json!({ "ignoreDate": true }), &synonyms, None).unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_tags_migration_from_legacy_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE VIRTUAL TABLE messages_fts USING fts5(msgId, subject, from_, to_, cc, bcc, body);
+            CREATE TABLE message_meta (rowid INTEGER PRIMARY KEY, dateMs INTEGER NOT NULL, hasAttachments INTEGER NOT NULL, parsedIcsAttachments TEXT);
+            CREATE TABLE message_ids (msgId TEXT PRIMARY KEY);
+            INSERT INTO message_ids (rowid, msgId) VALUES (1, 'a:/INBOX:1');
+            INSERT INTO messages_fts (rowid, msgId, subject, from_, to_, cc, bcc, body)
+                VALUES (1, 'a:/INBOX:1', 'Legacy subject', '', '', '', '', 'old body');
+            INSERT INTO message_meta (rowid, dateMs, hasAttachments, parsedIcsAttachments) VALUES (1, 1000, 0, '');
+            "#,
+        )
+        .unwrap();
+        ensure_message_content_table(&conn).unwrap();
+        ensure_tags_columns(&conn).unwrap();
+        // Idempotent.
+        ensure_tags_columns(&conn).unwrap();
+
+        let tags: String = conn.query_row("SELECT tags FROM message_meta WHERE rowid = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(tags, "");
+        let found: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'legacy'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(found, 1);
+        conn.execute("INSERT INTO messages_fts (rowid, msgId, tags) VALUES (2, 'a:/INBOX:2', 'todo')", []).unwrap();
+    }
+
+    #[test]
+    fn test_explain_search_plan_fts_only() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "account1:/INBOX:msg1", "Quarterly budget", 1000);
+        let synonyms = SynonymLookup::new();
+
+        let params = serde_json::json!({ "from": 0, "to": 2000 });
+        let res = explain_search_plan(&conn, "budget", &params, &synonyms, false).unwrap();
+        assert_eq!(res["mode"], "fts");
+        assert!(res["sql"].as_str().unwrap().contains("meta.dateMs >= ?"));
+        let plan = res["plan"].as_array().unwrap();
+        assert!(!plan.is_empty());
+        assert!(plan.iter().any(|row| row["detail"].as_str().unwrap().contains("VIRTUAL TABLE")));
+        assert!(res.get("vecStep").is_none());
+    }
+
+    #[test]
+    fn test_search_fields_projection() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "account1:/INBOX:msg1", "Quarterly budget", 1000);
+        let synonyms = SynonymLookup::new();
+
+        let params = serde_json::json!({ "ignoreDate": true, "fields": ["uniqueId", "rank"] });
+        let (results, _) = search(&conn, "budget", &params, &synonyms, None).unwrap();
+        assert_eq!(results.len(), 1);
+        let obj = results[0].as_object().unwrap();
+        assert_eq!(obj.len(), 2);
+        assert!(obj.contains_key("uniqueId") && obj.contains_key("rank"));
+
+        // Default is the full set minus the opt-in keys (`snippets`, `to_` / `cc` / `bcc`, explain's four)
+        // and `snippetKind`, which only vector-only results carry.
+        let params = serde_json::json!({ "ignoreDate": true });
+        let (results, _) = search(&conn, "budget", &params, &synonyms, None).unwrap();
+        assert_eq!(results[0].as_object().unwrap().len(), SEARCH_RESULT_FIELDS.len() - 9);
+        let params =
+            serde_json::json!({ "ignoreDate": true, "snippetCount": 2, "includeRecipients": true, "explain": true });
+        let (results, _) = search(&conn, "budget", &params, &synonyms, None).unwrap();
+        assert_eq!(results[0].as_object().unwrap().len(), SEARCH_RESULT_FIELDS.len() - 1);
+        // FTS-only: raw BM25 (negative) alongside its normalized score; no vector distance.
+        let bm25 = results[0]["bm25Rank"].as_f64().unwrap();
+        assert!(bm25 < 0.0);
+        assert_eq!(results[0]["rank"].as_f64().unwrap(), bm25);
+        assert_eq!(results[0]["score"].as_f64().unwrap(), crate::fts::hybrid::bm25_rank_to_score(bm25));
+        assert!(results[0]["distance"].is_null());
+        assert_eq!(results[0]["distanceMetric"], distance_metric().as_str());
+
+        let params = serde_json::json!({ "fields": ["bogus"] });
+        assert!(search(&conn, "budget", &params, &synonyms, None).is_err());
+
+        // A vector-only result keeps its preview marker through the projection.
+        let mut conn = setup_vec_test_db();
+        let rows = vec![serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Offsite planning", "body": "agenda", "dateMs": 1000 })];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        insert_test_vector(&conn, "a:/INBOX:1", 0.0);
+        let params = serde_json::json!({ "ignoreDate": true, "fields": ["uniqueId", "snippetKind"] });
+        let (results, _) = search_hybrid(&conn, "retreat", &params, &synonyms, &unit_vector(0.0), None, 5).unwrap();
+        let fields = parse_fields_param(&params).unwrap().unwrap();
+        let projected = project_fields(results, &fields);
+        assert_eq!(projected, vec![serde_json::json!({ "uniqueId": "a:/INBOX:1", "snippetKind": "preview" })]);
+    }
+
+    #[test]
+    fn test_update_meta() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "account1:/INBOX:msg1", "Invoice", 1000);
+
+        let params = serde_json::json!({ "hasAttachments": true, "dateMs": 5000 });
+        assert!(update_meta(&conn, "account1:/INBOX:msg1", &params).unwrap());
+        let msg = get_message_by_msgid(&conn, "account1:/INBOX:msg1").unwrap().unwrap();
+        assert_eq!(msg["hasAttachments"], 1);
+        assert_eq!(msg["dateMs"], 5000);
+        assert_eq!(msg["subject"], "Invoice");
+
+        assert!(!update_meta(&conn, "account1:/INBOX:missing", &params).unwrap());
+        assert!(update_meta(&conn, "account1:/INBOX:msg1", &serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn test_filter_new_messages_echo_rows() {
+        let conn = setup_test_db();
+        insert_test_message(&conn, "a:/INBOX:1", "Old", 1000);
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Old", "body": "seen" }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "New", "body": "fresh", "dateMs": 2000 }),
+        ];
+
+        let result = filter_new_messages(&conn, &rows, false).unwrap();
+        assert_eq!(result["newMsgIds"], serde_json::json!(["a:/INBOX:2"]));
+        assert!(result.get("newRows").is_none());
+
+        // The new rows come back untouched, ready for indexBatch.
+        let result = filter_new_messages(&conn, &rows, true).unwrap();
+        assert_eq!(result["newRows"], serde_json::json!([rows[1]]));
+        assert_eq!(result["skippedCount"], 1);
+        assert_eq!(filter_new_messages(&conn, &[], true).unwrap()["newRows"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_search_flag_filters() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget draft", "dateMs": 1000, "isRead": false, "isStarred": true }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Budget final", "dateMs": 2000, "isRead": true, "isStarred": false }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Lunch", "dateMs": 3000, "isRead": false }),
+            // Indexed without flags: unknown, matches neither value.
+            serde_json::json!({ "msgId": "a:/INBOX:4", "subject": "Budget notes", "dateMs": 4000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let hits = |params: Value| -> Vec<String> {
+            let mut ids: Vec<String> = search(&conn, "budget", &params, &synonyms, None)
+                .unwrap()
+                .0
+                .iter()
+                .map(|r| r["uniqueId"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "isRead": false })), vec!["a:/INBOX:1"]);
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "isRead": true })), vec!["a:/INBOX:2"]);
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "isStarred": false })), vec!["a:/INBOX:2"]);
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "isRead": true, "isStarred": true })), Vec::<String>::new());
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true })).len(), 3);
+
+        // Reading a message is a flag sync, not a reindex.
+        assert!(update_meta(&conn, "a:/INBOX:1", &serde_json::json!({ "isRead": true })).unwrap());
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "isRead": true })), vec!["a:/INBOX:1", "a:/INBOX:2"]);
+        let msg = get_message_by_msgid(&conn, "a:/INBOX:1").unwrap().unwrap();
+        assert_eq!((msg["isRead"].clone(), msg["isStarred"].clone()), (Value::Bool(true), Value::Bool(true)));
+        assert!(get_message_by_msgid(&conn, "a:/INBOX:4").unwrap().unwrap()["isRead"].is_null());
+        assert!(update_meta(&conn, "a:/INBOX:1", &serde_json::json!({ "isStarred": "yes" })).is_err());
+
+        // Vector-only hits go through the same check.
+        let filters = SearchFilters::lenient(&serde_json::json!({ "isStarred": true })).unwrap();
+        let rowid = |id: &str| -> i64 {
+            conn.query_row("SELECT rowid FROM message_ids WHERE msgId = ?1", params![id], |r| r.get(0)).unwrap()
+        };
+        assert!(filters.admits(&fetch_message_meta(&conn, rowid("a:/INBOX:1")).unwrap().unwrap()));
+        assert!(!filters.admits(&fetch_message_meta(&conn, rowid("a:/INBOX:4")).unwrap().unwrap()));
+    }
+
+    #[test]
+    fn test_search_address_filters() {
+        let mut conn = setup_test_db();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "Budget", "from": "Alice Smith <Alice@Example.com>", "to": "team_lead@corp.io", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Budget", "from": "alice@example.com (Alice)", "to": "\"Doe, Jo\" <jo@corp.io>, bob@corp.io", "dateMs": 2000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:3", "subject": "Budget", "from": "\"alice@example.com\" <mallory@evil.test>", "to": "teamXlead@corp.io", "dateMs": 3000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let hits = |params: Value| -> Vec<String> {
+            let mut ids: Vec<String> = search(&conn, "budget", &params, &synonyms, None)
+                .unwrap()
+                .0
+                .iter()
+                .map(|r| r["uniqueId"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        // Display names and case don't matter; a display name that looks like an address isn't one.
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "fromAddr": "ALICE@example.com" })), vec!["a:/INBOX:1", "a:/INBOX:2"]);
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "fromAddr": "Someone <mallory@evil.test>" })), vec!["a:/INBOX:3"]);
+        // Whole addresses only: `_` is literal and bob@corp.io doesn't match jo@corp.io.
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "toAddr": "team_lead@corp.io" })), vec!["a:/INBOX:1"]);
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "toAddr": "bob@corp.io" })), vec!["a:/INBOX:2"]);
+        assert_eq!(hits(serde_json::json!({ "ignoreDate": true, "toAddr": "o@corp.io" })), Vec::<String>::new());
+        // An address that doesn't parse is an error, not a silently dropped filter.
+        let err = search(&conn, "budget", &serde_json::json!({ "ignoreDate": true, "toAddr": "not an address" }), &synonyms, None);
+        assert!(err.unwrap_err().to_string().contains("Invalid toAddr"));
+        assert!(search(&conn, "budget", &serde_json::json!({ "ignoreDate": true, "fromAddr": " " }), &synonyms, None).is_ok());
+
+        let msg = get_message_by_msgid(&conn, "a:/INBOX:2").unwrap().unwrap();
+        assert_eq!(msg["fromAddr"], "alice@example.com");
+        assert_eq!(msg["toAddr"], serde_json::json!(["jo@corp.io", "bob@corp.io"]));
+
+        let filters = SearchFilters::lenient(&serde_json::json!({ "toAddr": "jo@corp.io" })).unwrap();
+        let rowid = |id: &str| -> i64 {
+            conn.query_row("SELECT rowid FROM message_ids WHERE msgId = ?1", params![id], |r| r.get(0)).unwrap()
+        };
+        assert!(filters.admits(&fetch_message_meta(&conn, rowid("a:/INBOX:2")).unwrap().unwrap()));
+        assert!(!filters.admits(&fetch_message_meta(&conn, rowid("a:/INBOX:1")).unwrap().unwrap()));
+
+        // Removing a message drops its recipient lookup rows too.
+        remove_batch(&mut conn, &[serde_json::json!("a:/INBOX:2")]).unwrap();
+        let lookups: i64 = conn.query_row("SELECT COUNT(*) FROM message_to_addrs", [], |r| r.get(0)).unwrap();
+        assert_eq!(lookups, 2);
+        // Older databases get the lookup table backfilled from toAddr.
+        conn.execute_batch("DROP TABLE message_to_addrs").unwrap();
+        ensure_meta_address_columns(&conn).unwrap();
+        let backfilled: i64 = conn.query_row("SELECT COUNT(*) FROM message_to_addrs WHERE addr = 'teamxlead@corp.io'", [], |r| r.get(0)).unwrap();
+        assert_eq!(backfilled, 1);
+    }
+
+    #[test]
+    fn test_accented_quoted_phrases_fold_like_indexer() {
+        // Use the production tokenizer (remove_diacritics 2), not the simplified test schema.
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_messages_fts(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE message_meta (rowid INTEGER PRIMARY KEY, dateMs INTEGER NOT NULL, hasAttachments INTEGER NOT NULL, parsedIcsAttachments TEXT, tags TEXT NOT NULL DEFAULT '');
+             CREATE TABLE message_ids (msgId TEXT PRIMARY KEY);",
+        ).unwrap();
+        create_message_content_table(&conn).unwrap();
+        ensure_meta_lang_column(&conn).unwrap();
+        ensure_meta_content_hash_column(&conn).unwrap();
+        ensure_meta_truncation_columns(&conn).unwrap();
+        ensure_meta_header_message_id_column(&conn).unwrap();
+        ensure_meta_flag_columns(&conn).unwrap();
+        ensure_meta_address_columns(&conn).unwrap();
+        let rows = vec![
+            serde_json::json!({ "msgId": "a:/INBOX:1", "subject": "cafe review", "dateMs": 1000 }),
+            serde_json::json!({ "msgId": "a:/INBOX:2", "subject": "Crème brûlée recipe", "dateMs": 2000 }),
+        ];
+        index_batch(&mut conn, &rows, None, false, false).unwrap();
+        let synonyms = SynonymLookup::new();
+        let params = serde_json::json!({ "ignoreDate": true });
+        let hits = |q: &str| -> Vec<String> {
+            search(&conn, q, &params, &synonyms, None)
+                .unwrap()
+                .0
+                .iter()
+                .map(|r| r["uniqueId"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        // Accented query phrase matches unaccented content, and vice versa.
+        assert_eq!(hits("\"café review\""), vec!["a:/INBOX:1"]);
+        assert_eq!(hits("\"CAFÉ REVIEW\""), vec!["a:/INBOX:1"]);
+        assert_eq!(hits("\"creme brulee\""), vec!["a:/INBOX:2"]);
+        assert_eq!(hits("subject:\"crème brûlée\""), vec!["a:/INBOX:2"]);
+        // Decomposed form (e + U+0301 combining acute) folds the same way.
+        assert_eq!(hits("\"cafe\u{301} review\""), vec!["a:/INBOX:1"]);
+    }
+
     #[test]
     fn test_get_message_by_msgid() {
         let conn = setup_test_db();
@@ -0,0 +1,487 @@
+// dump.rs — NDJSON export/import of the email index (exportDump / importDump), and
+// vector-only export for other vector stores (exportEmbeddings).
+//
+// Line 1 of a dump is a header object describing the host that wrote it; every following
+// line is one message. importDump checks the header before touching any record, so a dump
+// from a newer schema or another embedding model is refused instead of silently mixing
+// incompatible vectors into messages_vec.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config;
+use crate::fts::db::{blob_to_f32_vec, f32_vec_to_blob, index_rows, is_degenerate_embedding, table_exists, vec_count};
+
+/// First line of every dump.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpHeader {
+    pub format: String,
+    pub schema_version: u32,
+    pub host_version: String,
+    pub embedding_model: String,
+    pub embedding_dims: usize,
+    /// Whether records carry an `embedding` array. Absent in a header = assume they do.
+    #[serde(default = "default_true")]
+    pub has_embeddings: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl DumpHeader {
+    /// Header describing this host.
+    pub fn current(has_embeddings: bool) -> Self {
+        Self {
+            format: config::dump::FORMAT.to_string(),
+            schema_version: config::SCHEMA_VERSION,
+            host_version: config::HOST_VERSION.to_string(),
+            embedding_model: config::embedding::EMBEDDING_MODEL_NAME.to_string(),
+            embedding_dims: config::embedding::EMBEDDING_DIMS,
+            has_embeddings,
+        }
+    }
+
+    /// Parse the first line of a dump.
+    pub fn parse(line: &str) -> anyhow::Result<Self> {
+        let header: Self = serde_json::from_str(line)
+            .context("Dump is missing its header line (expected a format/schemaVersion object first)")?;
+        if header.format != config::dump::FORMAT {
+            bail!("Not a TabMail FTS dump (format '{}')", header.format);
+        }
+        Ok(header)
+    }
+
+    /// Whether this host can import the dump. Returns whether the records' embeddings
+    /// should be kept: with `skip_embeddings`, a model/dims mismatch is tolerated by
+    /// importing text only (rebuildEmbeddings fills the vectors in afterwards).
+    pub fn check_compatible(&self, skip_embeddings: bool) -> anyhow::Result<bool> {
+        if self.schema_version > config::SCHEMA_VERSION {
+            bail!(
+                "Dump schema version {} is newer than this host supports ({}); update the host (dump written by {})",
+                self.schema_version,
+                config::SCHEMA_VERSION,
+                self.host_version
+            );
+        }
+        if !self.has_embeddings || skip_embeddings {
+            return Ok(false);
+        }
+        let ours = (config::embedding::EMBEDDING_MODEL_NAME, config::embedding::EMBEDDING_DIMS);
+        if (self.embedding_model.as_str(), self.embedding_dims) != ours {
+            bail!(
+                "Dump embeddings are {} ({} dims) but this host uses {} ({} dims); pass skipEmbeddings to import text only",
+                self.embedding_model,
+                self.embedding_dims,
+                ours.0,
+                ours.1
+            );
+        }
+        Ok(true)
+    }
+}
+
+/// Check that `dest` is a new absolute path with room for about `estimated_bytes`, then run
+/// `write` against `<dest>.tmp` and rename it into place once synced, so a failed export
+/// (full disk, I/O error) never leaves a truncated file at `dest`. `what` names the file in
+/// errors. Returns what `write` returned.
+fn write_staged(
+    dest: &Path,
+    what: &str,
+    estimated_bytes: u64,
+    write: impl FnOnce(&mut BufWriter<File>) -> anyhow::Result<u64>,
+) -> anyhow::Result<u64> {
+    if !dest.is_absolute() {
+        bail!("{} path must be absolute: {}", what, dest.display());
+    }
+    if dest.exists() {
+        bail!("{} path already exists: {}", what, dest.display());
+    }
+    if let Some(parent) = dest.parent() {
+        crate::disk::check_free_space(parent, estimated_bytes)?;
+    }
+    let tmp = PathBuf::from(format!("{}.tmp", dest.display()));
+    let written = (|| {
+        let file = File::create(&tmp).with_context(|| format!("failed to create {}", tmp.display()))?;
+        let mut out = BufWriter::new(file);
+        let count = write(&mut out)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(count)
+    })();
+    let count = match written {
+        Ok(count) => count,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+    std::fs::rename(&tmp, dest).with_context(|| format!("failed to move {} into place", tmp.display()))?;
+    Ok(count)
+}
+
+/// Write every indexed message to `dest` as NDJSON (header line first). Embeddings are
+/// included when messages_vec has any. Returns the number of messages written.
+pub fn export_dump(conn: &Connection, dest: &Path) -> anyhow::Result<u64> {
+    let vectors = vec_count(conn);
+    let has_embeddings = vectors > 0;
+    let (text_bytes, records): (i64, i64) = conn.query_row(
+        "SELECT COALESCE(SUM(octet_length(subject) + octet_length(from_) + octet_length(to_) + octet_length(cc)
+                             + octet_length(bcc) + octet_length(body) + octet_length(tags)), 0), COUNT(*)
+         FROM message_content",
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    let vector_bytes = vectors.max(0) as u64
+        * config::embedding::EMBEDDING_DIMS as u64
+        * config::dump::NDJSON_BYTES_PER_FLOAT;
+    let estimate = text_bytes.max(0) as u64 + records.max(0) as u64 * config::dump::RECORD_OVERHEAD_BYTES + vector_bytes;
+
+    let count = write_staged(dest, "Dump", estimate, |out| write_dump(conn, out, has_embeddings))?;
+    log::info!("Exported {} messages to {} (embeddings={})", count, dest.display(), has_embeddings);
+    Ok(count)
+}
+
+fn write_dump(conn: &Connection, mut out: &mut BufWriter<File>, has_embeddings: bool) -> anyhow::Result<u64> {
+    serde_json::to_writer(&mut out, &DumpHeader::current(has_embeddings))?;
+    out.write_all(b"\n")?;
+
+    let mut vec_stmt = if has_embeddings {
+        Some(conn.prepare("SELECT embedding FROM messages_vec WHERE rowid = ?1")?)
+    } else {
+        None
+    };
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT c.rowid, c.msgId, c.subject, c.from_, c.to_, c.cc, c.bcc, c.body, c.tags,
+               m.dateMs, m.hasAttachments, m.parsedIcsAttachments, m.bodyTruncated, m.originalBodyLength,
+               m.isRead, m.isStarred, m.headerMessageId
+        FROM message_content c
+        JOIN message_meta m ON c.rowid = m.rowid
+        ORDER BY c.rowid
+        "#,
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut count: u64 = 0;
+    while let Some(r) = rows.next()? {
+        let rowid: i64 = r.get(0)?;
+        let mut record = serde_json::json!({
+            "msgId": r.get::<_, String>(1)?,
+            "subject": r.get::<_, String>(2)?,
+            "from_": r.get::<_, String>(3)?,
+            "to_": r.get::<_, String>(4)?,
+            "cc": r.get::<_, String>(5)?,
+            "bcc": r.get::<_, String>(6)?,
+            "body": r.get::<_, String>(7)?,
+            "tags": r.get::<_, String>(8)?,
+            "dateMs": r.get::<_, i64>(9)?,
+            "hasAttachments": r.get::<_, i64>(10)? != 0,
+            "parsedIcsAttachments": r.get::<_, Option<String>>(11)?.unwrap_or_default(),
+            "isTruncated": r.get::<_, bool>(12)?,
+            "originalBodyLength": r.get::<_, Option<i64>>(13)?,
+            "isRead": r.get::<_, Option<bool>>(14)?,
+            "isStarred": r.get::<_, Option<bool>>(15)?,
+            "headerMessageId": r.get::<_, Option<String>>(16)?,
+        });
+        if let Some(vec_stmt) = vec_stmt.as_mut() {
+            let blob: Option<Vec<u8>> = vec_stmt.query_row(params![rowid], |r| r.get(0)).optional()?;
+            if let Some(blob) = blob {
+                record["embedding"] = serde_json::json!(blob_to_f32_vec(&blob));
+            }
+        }
+        serde_json::to_writer(&mut out, &record)?;
+        out.write_all(b"\n")?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Body encoding of an `exportEmbeddings` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingsFormat {
+    /// One `{ "msgId", "embedding": [...] }` object per line.
+    Ndjson,
+    /// Per record: msgId byte length (u32 LE), the UTF-8 msgId, then `embeddingDims` f32 LE.
+    Binary,
+}
+
+impl EmbeddingsFormat {
+    pub fn from_param(v: Option<&Value>) -> anyhow::Result<Self> {
+        match v.and_then(|v| v.as_str()) {
+            None | Some("ndjson") => Ok(Self::Ndjson),
+            Some("binary") => Ok(Self::Binary),
+            Some(other) => bail!("Invalid format '{}' (expected \"ndjson\" or \"binary\")", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ndjson => "ndjson",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+/// Write every stored email embedding with its msgId to `dest`. Both formats start with one
+/// JSON header line (model, dims, distance metric, body encoding). Returns the number of
+/// vectors written.
+pub fn export_embeddings(conn: &Connection, dest: &Path, format: EmbeddingsFormat) -> anyhow::Result<u64> {
+    let (msg_id_bytes, vectors): (i64, i64) = conn.query_row(
+        "SELECT COALESCE(SUM(octet_length(i.msgId)), 0), COUNT(*) FROM messages_vec v JOIN message_ids i ON i.rowid = v.rowid",
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    let bytes_per_float = match format {
+        EmbeddingsFormat::Ndjson => config::dump::NDJSON_BYTES_PER_FLOAT,
+        EmbeddingsFormat::Binary => 4,
+    };
+    let estimate = msg_id_bytes.max(0) as u64
+        + vectors.max(0) as u64 * (config::embedding::EMBEDDING_DIMS as u64 * bytes_per_float + config::dump::RECORD_OVERHEAD_BYTES);
+
+    let count = write_staged(dest, "Export", estimate, |out| write_embeddings(conn, out, format))?;
+    log::info!("Exported {} embeddings to {} ({})", count, dest.display(), format.as_str());
+    Ok(count)
+}
+
+fn write_embeddings(conn: &Connection, mut out: &mut BufWriter<File>, format: EmbeddingsFormat) -> anyhow::Result<u64> {
+    let header = serde_json::json!({
+        "format": config::dump::EMBEDDINGS_FORMAT,
+        "encoding": format.as_str(),
+        "hostVersion": config::HOST_VERSION,
+        "embeddingModel": config::embedding::EMBEDDING_MODEL_NAME,
+        "embeddingDims": config::embedding::EMBEDDING_DIMS,
+        "distanceMetric": crate::fts::hybrid::distance_metric().as_str(),
+    });
+    serde_json::to_writer(&mut out, &header)?;
+    out.write_all(b"\n")?;
+
+    let mut stmt = conn.prepare(
+        "SELECT i.msgId, v.embedding FROM messages_vec v JOIN message_ids i ON i.rowid = v.rowid ORDER BY v.rowid",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut count: u64 = 0;
+    while let Some(r) = rows.next()? {
+        let msg_id: String = r.get(0)?;
+        let blob: Vec<u8> = r.get(1)?;
+        match format {
+            EmbeddingsFormat::Ndjson => {
+                let record = serde_json::json!({ "msgId": msg_id, "embedding": blob_to_f32_vec(&blob) });
+                serde_json::to_writer(&mut out, &record)?;
+                out.write_all(b"\n")?;
+            }
+            EmbeddingsFormat::Binary => {
+                // The blob is already the f32 LE array.
+                out.write_all(&(msg_id.len() as u32).to_le_bytes())?;
+                out.write_all(msg_id.as_bytes())?;
+                out.write_all(&blob)?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Import a dump written by `export_dump`. The header is validated first; records are then
+/// indexed in batches (existing msgIds are skipped) and their embeddings stored as-is, all in
+/// one transaction, so a dump that fails part-way (bad record, full disk) imports nothing.
+/// Returns (imported, skipped duplicates, embeddings stored, failed records as in `index_batch`).
+pub fn import_dump(
+    conn: &mut Connection,
+    src: &Path,
+    skip_embeddings: bool,
+    detect_lang: bool,
+) -> anyhow::Result<(i64, i64, i64, Vec<Value>)> {
+    let file = std::fs::File::open(src).with_context(|| format!("failed to open dump {}", src.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    let first = lines.next().transpose()?.unwrap_or_default();
+    let header = DumpHeader::parse(&first)?;
+    let mut keep_embeddings = header.check_compatible(skip_embeddings)?;
+    if keep_embeddings && !table_exists(conn, "messages_vec")? {
+        log::warn!("importDump: no vector table (sqlite-vec unavailable), importing text only");
+        keep_embeddings = false;
+    }
+    log::info!(
+        "Importing dump {} (schema {}, host {}, keepEmbeddings={})",
+        src.display(),
+        header.schema_version,
+        header.host_version,
+        keep_embeddings
+    );
+
+    let mut tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut totals = (0, 0, 0, Vec::new());
+    let mut batch: Vec<Value> = Vec::with_capacity(config::dump::IMPORT_BATCH_ROWS);
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Line numbers are 1-based and the header is line 1.
+        let record: Value = serde_json::from_str(&line).with_context(|| format!("invalid dump record on line {}", i + 2))?;
+        batch.push(record);
+        if batch.len() >= config::dump::IMPORT_BATCH_ROWS {
+            import_records(&mut tx, &mut batch, keep_embeddings, detect_lang, &mut totals)?;
+        }
+    }
+    import_records(&mut tx, &mut batch, keep_embeddings, detect_lang, &mut totals)?;
+    tx.commit()?;
+
+    log::info!(
+        "Imported {} messages ({} duplicates skipped, {} embeddings, {} failed)",
+        totals.0,
+        totals.1,
+        totals.2,
+        totals.3.len()
+    );
+    Ok(totals)
+}
+
+/// Index one batch of records, then store the embeddings of the newly inserted ones.
+fn import_records(
+    tx: &mut Transaction,
+    batch: &mut Vec<Value>,
+    keep_embeddings: bool,
+    detect_lang: bool,
+    totals: &mut (i64, i64, i64, Vec<Value>),
+) -> anyhow::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    // Records already in the index are skipped by index_batch; their vectors must be too.
+    let mut is_new = Vec::with_capacity(batch.len());
+    for record in batch.iter() {
+        let msg_id = record.get("msgId").and_then(|v| v.as_str()).unwrap_or("");
+        let exists: Option<i64> =
+            tx.query_row("SELECT 1 FROM message_ids WHERE msgId = ?1", params![msg_id], |r| r.get(0)).optional()?;
+        is_new.push(exists.is_none());
+    }
+
+    let (inserted, skipped, _, failed) = index_rows(tx, batch, None, detect_lang, false)?;
+    if !failed.is_empty() {
+        log::warn!("importDump: {} records could not be indexed and were skipped", failed.len());
+    }
+    totals.0 += inserted;
+    totals.1 += skipped;
+    // A failed record has no row to attach its vector to.
+    for (record, new) in batch.iter().zip(is_new.iter_mut()) {
+        *new &= !failed.iter().any(|f| f["msgId"] == record["msgId"]);
+    }
+    totals.3.extend(failed);
+
+    if keep_embeddings {
+        for (record, new) in batch.iter().zip(is_new) {
+            let Some(embedding) = record.get("embedding").and_then(|v| v.as_array()) else { continue };
+            if !new {
+                continue;
+            }
+            let msg_id = record.get("msgId").and_then(|v| v.as_str()).unwrap_or("");
+            let values: Vec<f32> = embedding.iter().filter_map(|v| v.as_f64()).map(|f| f as f32).collect();
+            if values.len() != config::embedding::EMBEDDING_DIMS {
+                bail!(
+                    "Embedding for {} has {} dims, expected {}",
+                    msg_id,
+                    values.len(),
+                    config::embedding::EMBEDDING_DIMS
+                );
+            }
+            // Same rule as indexing: a message with no text to embed stays FTS-only.
+            if is_degenerate_embedding(&values) {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO messages_vec (rowid, embedding) SELECT rowid, ?2 FROM message_ids WHERE msgId = ?1",
+                params![msg_id, f32_vec_to_blob(&values)],
+            )?;
+            totals.2 += 1;
+        }
+    }
+    batch.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_header_compatibility() {
+        let ours = DumpHeader::current(true);
+        let line = serde_json::to_string(&ours).unwrap();
+        assert!(line.contains("\"schemaVersion\"") && line.contains("\"embeddingDims\""));
+        let parsed = DumpHeader::parse(&line).unwrap();
+        assert_eq!(parsed, ours);
+        assert!(parsed.check_compatible(false).unwrap());
+        assert!(!parsed.check_compatible(true).unwrap());
+
+        // Different embedding space: refused unless embeddings are skipped.
+        let other_model = DumpHeader { embedding_model: "bge-base".into(), embedding_dims: 768, ..ours.clone() };
+        let err = other_model.check_compatible(false).unwrap_err().to_string();
+        assert!(err.contains("768 dims"), "{err}");
+        assert!(!other_model.check_compatible(true).unwrap());
+        // ...but a dump without vectors doesn't care.
+        let text_only = DumpHeader { has_embeddings: false, ..other_model };
+        assert!(!text_only.check_compatible(false).unwrap());
+
+        let newer = DumpHeader { schema_version: config::SCHEMA_VERSION + 1, ..ours };
+        assert!(newer.check_compatible(true).is_err());
+
+        assert!(DumpHeader::parse(r#"{"msgId":"a:/INBOX:1","subject":"x"}"#).is_err());
+        let foreign = r#"{"format":"other","schemaVersion":1,"hostVersion":"1","embeddingModel":"m","embeddingDims":3}"#;
+        assert!(DumpHeader::parse(foreign).is_err());
+    }
+
+    #[test]
+    fn test_export_embeddings() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Plain table standing in for the vec0 one (same columns).
+        conn.execute_batch(
+            "CREATE TABLE message_ids (rowid INTEGER PRIMARY KEY, msgId TEXT UNIQUE);
+             CREATE TABLE messages_vec (rowid INTEGER PRIMARY KEY, embedding BLOB);",
+        )
+        .unwrap();
+        let vectors = [("a:/INBOX:1", vec![0.5f32, -1.0]), ("b:/Sent:2", vec![2.0f32, 0.25])];
+        for (i, (msg_id, v)) in vectors.iter().enumerate() {
+            conn.execute("INSERT INTO message_ids (rowid, msgId) VALUES (?1, ?2)", params![i as i64 + 1, msg_id]).unwrap();
+            conn.execute("INSERT INTO messages_vec VALUES (?1, ?2)", params![i as i64 + 1, f32_vec_to_blob(v)]).unwrap();
+        }
+
+        let dir = std::env::temp_dir().join(format!("tm_export_emb_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ndjson = dir.join("emb.ndjson");
+        let binary = dir.join("emb.bin");
+        let _ = std::fs::remove_file(&ndjson);
+        let _ = std::fs::remove_file(&binary);
+
+        assert_eq!(export_embeddings(&conn, &ndjson, EmbeddingsFormat::Ndjson).unwrap(), 2);
+        let text = std::fs::read_to_string(&ndjson).unwrap();
+        let lines: Vec<Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines[0]["format"], config::dump::EMBEDDINGS_FORMAT);
+        assert_eq!(lines[0]["embeddingDims"], config::embedding::EMBEDDING_DIMS);
+        assert_eq!(lines[2], serde_json::json!({ "msgId": "b:/Sent:2", "embedding": [2.0, 0.25] }));
+        assert!(export_embeddings(&conn, &ndjson, EmbeddingsFormat::Ndjson).is_err(), "refuses to overwrite");
+        assert!(!dir.join("emb.ndjson.tmp").exists(), "written via a temp file renamed into place");
+
+        assert_eq!(export_embeddings(&conn, &binary, EmbeddingsFormat::Binary).unwrap(), 2);
+        let bytes = std::fs::read(&binary).unwrap();
+        let body = &bytes[bytes.iter().position(|&b| b == b'\n').unwrap() + 1..];
+        assert_eq!(&body[..4], &10u32.to_le_bytes());
+        assert_eq!(&body[4..14], b"a:/INBOX:1");
+        assert_eq!(blob_to_f32_vec(&body[14..22]), vec![0.5, -1.0]);
+        assert_eq!(body.len(), (4 + 10 + 8) + (4 + 9 + 8));
+
+        assert!(EmbeddingsFormat::from_param(Some(&serde_json::json!("parquet"))).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_blob_roundtrip() {
+        let v = vec![0.25f32, -1.5, 3.0];
+        assert_eq!(blob_to_f32_vec(&f32_vec_to_blob(&v)), v);
+    }
+}
@@ -0,0 +1,114 @@
+// embed_cache.rs — In-memory copy of recently used embed_cache rows (init `preloadEmbedCache`).
+//
+// embed_cache maps the hash of a message's prepared embedding text to its vector. With the
+// preload enabled, init loads the most recent rows into a process-wide map so the reader's
+// on-the-fly embeddings (rerankOnFallback) can skip the model for text it has seen before.
+// The map is bounded: it is filled by the preload and then by new embeddings until full;
+// nothing is evicted. Disabled (the default), every lookup misses and inserts are dropped.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::fts::db::blob_to_f32_vec;
+
+// None = preload disabled.
+static PRELOADED: RwLock<Option<HashMap<String, Vec<f32>>>> = RwLock::new(None);
+
+/// embed_cache key for a message's prepared embedding input (SHA256 hex of the texts, each
+/// with the document prefix it is embedded with; keys without a prefix are unchanged).
+pub fn prepared_text_hash(texts: &[String], prefix: &str) -> String {
+    let joined = texts.iter().map(|t| format!("{prefix}{t}")).collect::<Vec<_>>().join("\u{1f}");
+    let digest = Sha256::digest(joined.as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Enable the in-memory cache and fill it with the newest embed_cache rows for the current
+/// model (at most `PRELOAD_EMBED_CACHE_MAX_ENTRIES`). Returns the number loaded.
+pub fn preload(conn: &Connection) -> anyhow::Result<usize> {
+    let mut stmt = conn.prepare(
+        "SELECT content_hash, embedding FROM embed_cache WHERE model = ?1 ORDER BY created_at DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            config::embedding::EMBEDDING_MODEL_NAME,
+            config::embedding::PRELOAD_EMBED_CACHE_MAX_ENTRIES as i64
+        ],
+        |r| Ok((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?)),
+    )?;
+    let mut map = HashMap::new();
+    for row in rows {
+        let (hash, blob) = row?;
+        let embedding = blob_to_f32_vec(&blob);
+        // Skip rows written for another dimension (e.g. a model swap under the same name).
+        if embedding.len() == config::embedding::EMBEDDING_DIMS {
+            map.insert(hash, embedding);
+        }
+    }
+    let loaded = map.len();
+    *PRELOADED.write().unwrap() = Some(map);
+    log::info!("Preloaded {} embed_cache entries", loaded);
+    Ok(loaded)
+}
+
+/// Cached embedding for a `prepared_text_hash` key.
+pub fn get(hash: &str) -> Option<Vec<f32>> {
+    PRELOADED.read().unwrap().as_ref()?.get(hash).cloned()
+}
+
+/// Remember an embedding computed on the fly (no-op when disabled or full).
+pub fn insert(hash: String, embedding: Vec<f32>) {
+    if let Some(map) = PRELOADED.write().unwrap().as_mut() {
+        if map.len() < config::embedding::PRELOAD_EMBED_CACHE_MAX_ENTRIES {
+            map.insert(hash, embedding);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fts::db::f32_vec_to_blob;
+
+    #[test]
+    fn test_prepared_text_hash() {
+        let one = vec!["Subject: Budget\nbody".to_string()];
+        assert_eq!(prepared_text_hash(&one, ""), prepared_text_hash(&one.clone(), ""));
+        assert_eq!(prepared_text_hash(&one, "").len(), 64);
+        // Chunk boundaries are part of the key.
+        let split = vec!["Subject: Budget".to_string(), "body".to_string()];
+        let joined = vec!["Subject: Budget body".to_string()];
+        assert_ne!(prepared_text_hash(&split, ""), prepared_text_hash(&joined, ""));
+        // So is the document prefix: vectors embedded with another prefix aren't reused.
+        assert_ne!(prepared_text_hash(&one, ""), prepared_text_hash(&one, "passage: "));
+    }
+
+    #[test]
+    fn test_preload() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE embed_cache (content_hash TEXT PRIMARY KEY, embedding BLOB, model TEXT, created_at INTEGER)",
+        )
+        .unwrap();
+        let good = vec![0.5f32; config::embedding::EMBEDDING_DIMS];
+        let insert_row = |hash: &str, v: &[f32], model: &str| {
+            conn.execute(
+                "INSERT INTO embed_cache VALUES (?1, ?2, ?3, 1)",
+                params![hash, f32_vec_to_blob(v), model],
+            )
+            .unwrap();
+        };
+        insert_row("current", &good, config::embedding::EMBEDDING_MODEL_NAME);
+        insert_row("other-model", &good, "bge-base");
+        insert_row("wrong-dims", &[1.0, 2.0], config::embedding::EMBEDDING_MODEL_NAME);
+
+        assert_eq!(preload(&conn).unwrap(), 1);
+        assert_eq!(get("current"), Some(good.clone()));
+        assert_eq!(get("other-model"), None);
+        insert("fresh".to_string(), good.clone());
+        assert_eq!(get("fresh"), Some(good));
+    }
+}
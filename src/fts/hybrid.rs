@@ -5,9 +5,14 @@
 // 2. Normalize cosine distance to 0..1
 // 3. Union results by rowid
 // 4. Compute final score = vectorWeight * vectorScore + textWeight * textScore
+//    (+ an optional recency bonus, see `RecencyBoost`)
 // 5. Sort by final score DESC, return top N
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use anyhow::{bail, Context};
+use serde_json::Value;
 
 use crate::config;
 
@@ -17,15 +22,19 @@ pub struct HybridCandidate {
     pub rowid: i64,
     pub text_score: f64,
     pub vector_score: f64,
+    pub distance: Option<f64>,
 }
 
 /// A merged result with final combined score.
 #[derive(Debug, Clone)]
+#[allow(dead_code)] // per-engine scores are kept for debugging/explain output
 pub struct HybridResult {
     pub rowid: i64,
     pub final_score: f64,
     pub text_score: f64,
     pub vector_score: f64,
+    /// Raw distance from the vector candidate list (None = no vector match).
+    pub distance: Option<f64>,
 }
 
 /// Convert FTS5 BM25 rank to 0..1 score.
@@ -44,16 +53,117 @@ pub fn bm25_rank_to_score(rank: f64) -> f64 {
     positive_rank / (1.0 + positive_rank)
 }
 
+/// Distance metric of the vec0 tables (init `distanceMetric`, stored in db_meta).
+/// vec0 reports distances in this metric, so it also picks the distance → score conversion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    #[default]
+    Cosine,
+    L2,
+}
+
+// Process-wide: both databases must agree (checked at init).
+static DISTANCE_METRIC: AtomicU8 = AtomicU8::new(DistanceMetric::Cosine as u8);
+
+impl DistanceMetric {
+    /// `distanceMetric` init param (None when absent).
+    pub fn from_param(v: Option<&Value>) -> anyhow::Result<Option<Self>> {
+        match v {
+            None | Some(Value::Null) => Ok(None),
+            Some(v) => match v.as_str() {
+                Some(s) => Self::parse(s).map(Some),
+                None => bail!("distanceMetric must be a string (\"cosine\" or \"l2\")"),
+            },
+        }
+    }
+
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "cosine" => Ok(Self::Cosine),
+            "l2" => Ok(Self::L2),
+            other => bail!("Invalid distanceMetric '{}' (expected \"cosine\" or \"l2\")", other),
+        }
+    }
+
+    /// Name as used in vec0 DDL (`distance_metric=...`) and db_meta.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cosine => "cosine",
+            Self::L2 => "l2",
+        }
+    }
+
+    pub fn distance_to_score(self, distance: f64) -> f64 {
+        match self {
+            Self::Cosine => cosine_distance_to_score(distance),
+            Self::L2 => l2_distance_to_score(distance),
+        }
+    }
+
+    /// Distance between two embeddings computed in Rust, matching what vec0 would report.
+    pub fn distance(self, a: &[f32], b: &[f32]) -> f64 {
+        match self {
+            Self::Cosine => cosine_distance(a, b),
+            Self::L2 => l2_distance(a, b),
+        }
+    }
+}
+
+/// Metric in effect for this process.
+pub fn distance_metric() -> DistanceMetric {
+    match DISTANCE_METRIC.load(Ordering::Relaxed) {
+        1 => DistanceMetric::L2,
+        _ => DistanceMetric::Cosine,
+    }
+}
+
+pub fn set_distance_metric(metric: DistanceMetric) {
+    DISTANCE_METRIC.store(metric as u8, Ordering::Relaxed);
+}
+
 /// Convert cosine distance to 0..1 score.
 /// distance=0 → 1.0 (identical vectors), distance=1 → 0.0 (orthogonal).
 pub fn cosine_distance_to_score(distance: f64) -> f64 {
     (1.0 - distance).max(0.0)
 }
 
+/// Convert L2 distance to 0..1 score. For unit-length embeddings (which the engine produces)
+/// d² = 2 · cosine distance, so this scores exactly like `cosine_distance_to_score` and the
+/// hybrid weights keep their meaning: d=0 → 1.0, d=√2 (orthogonal) → 0.0.
+pub fn l2_distance_to_score(distance: f64) -> f64 {
+    (1.0 - distance * distance / 2.0).max(0.0)
+}
+
+/// Euclidean distance between two embeddings, computed in Rust (vec0 `distance_metric=l2`).
+pub fn l2_distance(a: &[f32], b: &[f32]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (f64::from(*x) - f64::from(*y)).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Cosine distance (1 - cosine similarity) between two embeddings, computed in Rust.
+/// Matches vec0's `distance_metric=cosine`; zero vectors are treated as orthogonal.
+pub fn cosine_distance(a: &[f32], b: &[f32]) -> f64 {
+    let mut dot = 0.0f64;
+    let mut norm_a = 0.0f64;
+    let mut norm_b = 0.0f64;
+    for (x, y) in a.iter().zip(b) {
+        dot += f64::from(*x) * f64::from(*y);
+        norm_a += f64::from(*x) * f64::from(*x);
+        norm_b += f64::from(*y) * f64::from(*y);
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
 /// Merge FTS5 and vector search results into a single ranked list.
 ///
 /// `text_results`: (rowid, bm25_rank) from FTS5 search
-/// `vector_results`: (rowid, cosine_distance) from vector search
+/// `vector_results`: (rowid, distance) from vector search
 /// `vector_weight`: weight for semantic score (0.0..1.0)
 /// `text_weight`: weight for keyword score (0.0..1.0)
 /// `limit`: maximum number of results to return
@@ -63,6 +173,29 @@ pub fn merge_results(
     vector_weight: f64,
     text_weight: f64,
     limit: usize,
+) -> Vec<HybridResult> {
+    merge_results_weighted(text_results, vector_results, |_| (vector_weight, text_weight), limit)
+}
+
+/// `merge_results` with (vector_weight, text_weight) chosen per rowid (`accountWeights`).
+pub fn merge_results_weighted(
+    text_results: &[(i64, f64)],
+    vector_results: &[(i64, f64)],
+    weights: impl Fn(i64) -> (f64, f64),
+    limit: usize,
+) -> Vec<HybridResult> {
+    merge_results_boosted(text_results, vector_results, weights, |_| 0.0, limit)
+}
+
+/// `merge_results_weighted` plus a per-rowid `bonus` (the recency boost) added to each
+/// final score. `MIN_SCORE` still applies to relevance alone, so the bonus reorders
+/// relevant results but never lets an irrelevant one through.
+pub fn merge_results_boosted(
+    text_results: &[(i64, f64)],
+    vector_results: &[(i64, f64)],
+    weights: impl Fn(i64) -> (f64, f64),
+    bonus: impl Fn(i64) -> f64,
+    limit: usize,
 ) -> Vec<HybridResult> {
     let mut candidates: HashMap<i64, HybridCandidate> = HashMap::new();
 
@@ -76,35 +209,42 @@ pub fn merge_results(
                 rowid,
                 text_score: score,
                 vector_score: 0.0,
+                distance: None,
             });
     }
 
     // Add vector results
+    let metric = distance_metric();
     for &(rowid, distance) in vector_results {
-        let score = cosine_distance_to_score(distance);
+        let score = metric.distance_to_score(distance);
         candidates
             .entry(rowid)
-            .and_modify(|c| c.vector_score = score)
+            .and_modify(|c| {
+                c.vector_score = score;
+                c.distance = Some(distance);
+            })
             .or_insert(HybridCandidate {
                 rowid,
                 text_score: 0.0,
                 vector_score: score,
+                distance: Some(distance),
             });
     }
 
     // Compute final scores and filter
     let mut results: Vec<HybridResult> = candidates
         .into_values()
-        .map(|c| {
-            let final_score = vector_weight * c.vector_score + text_weight * c.text_score;
-            HybridResult {
+        .filter_map(|c| {
+            let (vector_weight, text_weight) = weights(c.rowid);
+            let relevance = vector_weight * c.vector_score + text_weight * c.text_score;
+            (relevance >= config::hybrid::MIN_SCORE).then(|| HybridResult {
                 rowid: c.rowid,
-                final_score,
+                final_score: relevance + bonus(c.rowid),
                 text_score: c.text_score,
                 vector_score: c.vector_score,
-            }
+                distance: c.distance,
+            })
         })
-        .filter(|r| r.final_score >= config::hybrid::MIN_SCORE)
         .collect();
 
     // Sort by final score DESC
@@ -116,6 +256,114 @@ pub fn merge_results(
     results
 }
 
+/// Opt-in recency boost (`recencyWeight` / `recencyHalfLifeDays` search params): a merged
+/// result gains `weight * recency_score(dateMs)`.
+#[derive(Debug, Clone, Copy)]
+pub struct RecencyBoost {
+    weight: f64,
+    half_life_ms: f64,
+    now_ms: i64,
+}
+
+impl RecencyBoost {
+    /// None when `recencyWeight` is absent or 0 (the merge then needs no dates).
+    pub fn from_params(params: &Value, now_ms: i64) -> anyhow::Result<Option<Self>> {
+        let number = |key: &str| -> anyhow::Result<Option<f64>> {
+            match params.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(v) => match v.as_f64() {
+                    Some(x) if x.is_finite() && x >= 0.0 => Ok(Some(x)),
+                    _ => bail!("{key} must be a non-negative number"),
+                },
+            }
+        };
+        let weight = number("recencyWeight")?.unwrap_or(0.0).min(config::hybrid::RECENCY_WEIGHT_MAX);
+        if weight == 0.0 {
+            return Ok(None);
+        }
+        let half_life_days = number("recencyHalfLifeDays")?
+            .filter(|d| *d > 0.0)
+            .unwrap_or(config::hybrid::RECENCY_HALF_LIFE_DAYS);
+        Ok(Some(Self { weight, half_life_ms: half_life_days * 86_400_000.0, now_ms }))
+    }
+
+    pub fn bonus(&self, date_ms: i64) -> f64 {
+        self.weight * recency_score(date_ms, self.now_ms, self.half_life_ms)
+    }
+}
+
+/// Bounded decay of a message's age: 1.0 at `now_ms` (or in the future), 0.5 one half-life
+/// earlier, towards 0 beyond. Unknown dates (0) score 0.
+pub fn recency_score(date_ms: i64, now_ms: i64, half_life_ms: f64) -> f64 {
+    if date_ms <= 0 {
+        return 0.0;
+    }
+    let age_ms = now_ms.saturating_sub(date_ms).max(0) as f64;
+    0.5f64.powf(age_ms / half_life_ms)
+}
+
+/// Collapse results sharing the same `key` value (`uniqueId` / `memId`), keeping the
+/// best-ranked (lowest `rank`) instance at the position of the first occurrence.
+/// Defensive: duplicate msgIds in the FTS table (buggy reindex) must not show twice.
+pub fn dedup_results(results: Vec<Value>, key: &str) -> Vec<Value> {
+    let rank = |v: &Value| v.get("rank").and_then(|r| r.as_f64()).unwrap_or(f64::INFINITY);
+    let mut out: Vec<Value> = Vec::with_capacity(results.len());
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for r in results {
+        let Some(id) = r.get(key).and_then(|v| v.as_str()).map(str::to_string) else {
+            out.push(r);
+            continue;
+        };
+        match index.get(&id) {
+            Some(&i) => {
+                log::warn!("Dropping duplicate search result {}={}", key, id);
+                if rank(&r) < rank(&out[i]) {
+                    out[i] = r;
+                }
+            }
+            None => {
+                index.insert(id, out.len());
+                out.push(r);
+            }
+        }
+    }
+    out
+}
+
+/// Per-account (vector, text) weight overrides from the `accountWeights` search param:
+/// `{ "<accountId>": { "vectorWeight": 0.2, "textWeight": 0.8 }, ... }`. A missing field
+/// keeps the default weight; accounts not listed use the defaults.
+#[derive(Debug, Default)]
+pub struct AccountWeights {
+    overrides: HashMap<String, (f64, f64)>,
+}
+
+impl AccountWeights {
+    /// None when the param is absent or empty (the merge then needs no account lookup).
+    pub fn from_params(params: &Value, defaults: (f64, f64)) -> anyhow::Result<Option<Self>> {
+        let Some(map) = params.get("accountWeights") else { return Ok(None) };
+        let map = map.as_object().context("accountWeights must be an object keyed by accountId")?;
+        let mut overrides = HashMap::new();
+        for (account, w) in map {
+            let weight = |key: &str, default: f64| -> anyhow::Result<f64> {
+                match w.get(key) {
+                    None => Ok(default),
+                    Some(v) => match v.as_f64() {
+                        Some(x) if x.is_finite() && x >= 0.0 => Ok(x),
+                        _ => bail!("accountWeights.{account}.{key} must be a non-negative number"),
+                    },
+                }
+            };
+            overrides.insert(account.clone(), (weight("vectorWeight", defaults.0)?, weight("textWeight", defaults.1)?));
+        }
+        Ok((!overrides.is_empty()).then_some(Self { overrides }))
+    }
+
+    pub fn get(&self, account: &str) -> Option<(f64, f64)> {
+        self.overrides.get(account).copied()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,6 +390,28 @@ mod tests {
         assert!((cosine_distance_to_score(1.5) - 0.0).abs() < 1e-10);
     }
 
+    #[test]
+    fn test_l2_distance_to_score() {
+        assert!((l2_distance_to_score(0.0) - 1.0).abs() < 1e-10);
+        assert!(l2_distance_to_score(2f64.sqrt()).abs() < 1e-10);
+        // Opposite unit vectors (d=2) clamp to 0.
+        assert_eq!(l2_distance_to_score(2.0), 0.0);
+        // Unit vectors at 60°: cosine distance 0.5, L2 distance 1.0 → same score.
+        let a = [1.0f32, 0.0];
+        let b = [0.5f32, 3f32.sqrt() / 2.0];
+        let (cos, l2) = (cosine_distance(&a, &b), l2_distance(&a, &b));
+        assert!((l2 - 1.0).abs() < 1e-6);
+        assert!((DistanceMetric::L2.distance_to_score(l2) - DistanceMetric::Cosine.distance_to_score(cos)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_distance_metric_param() {
+        assert_eq!(DistanceMetric::from_param(None).unwrap(), None);
+        assert_eq!(DistanceMetric::from_param(Some(&serde_json::json!("l2"))).unwrap(), Some(DistanceMetric::L2));
+        assert!(DistanceMetric::from_param(Some(&serde_json::json!("dot"))).is_err());
+        assert_eq!(DistanceMetric::parse(DistanceMetric::Cosine.as_str()).unwrap(), DistanceMetric::Cosine);
+    }
+
     #[test]
     fn test_merge_results_basic() {
         let text = vec![(1, -10.0), (2, -5.0)];
@@ -156,5 +426,86 @@ mod tests {
         if !merged.is_empty() {
             assert_eq!(merged[0].rowid, 1);
         }
+
+        // Raw distances are carried through; text-only rows have none.
+        let by_rowid = |id: i64| merged.iter().find(|r| r.rowid == id);
+        assert_eq!(by_rowid(1).and_then(|r| r.distance), Some(0.2));
+        assert_eq!(by_rowid(3).and_then(|r| r.distance), Some(0.1));
+        assert_eq!(by_rowid(2).expect("text-only row is merged").distance, None);
+    }
+
+    #[test]
+    fn test_account_weights_override_merge_order() {
+        // rowid 1: mailing-list account, strong keyword hit, weak semantic match.
+        // rowid 2: personal account, weak keyword hit, strong semantic match.
+        let text = vec![(1, -20.0), (2, -0.5)];
+        let vector = vec![(1, 0.7), (2, 0.1)];
+        let account = |rowid: i64| if rowid == 1 { "lists" } else { "personal" };
+
+        let defaults = merge_results(&text, &vector, 0.7, 0.3, 10);
+        assert_eq!(defaults[0].rowid, 2);
+
+        let params = serde_json::json!({ "accountWeights": { "lists": { "vectorWeight": 0.1, "textWeight": 0.9 } } });
+        let weights = AccountWeights::from_params(&params, (0.7, 0.3)).unwrap().unwrap();
+        let merged = merge_results_weighted(&text, &vector, |r| weights.get(account(r)).unwrap_or((0.7, 0.3)), 10);
+        assert_eq!(merged[0].rowid, 1);
+        // The personal account kept the defaults.
+        assert_eq!(merged[1].final_score, defaults[0].final_score);
+
+        // Partial overrides keep the other default; bad values are rejected.
+        let partial = serde_json::json!({ "accountWeights": { "a": { "textWeight": 1.0 } } });
+        let weights = AccountWeights::from_params(&partial, (0.7, 0.3)).unwrap().unwrap();
+        assert_eq!(weights.get("a"), Some((0.7, 1.0)));
+        assert!(AccountWeights::from_params(&serde_json::json!({ "accountWeights": { "a": { "textWeight": -1 } } }), (0.7, 0.3)).is_err());
+        assert!(AccountWeights::from_params(&serde_json::json!({ "accountWeights": {} }), (0.7, 0.3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_recency_boost_orders_equal_relevance_newest_first() {
+        let day = 86_400_000;
+        let now = 1_000 * day;
+        let dates: HashMap<i64, i64> = [(1, now - 300 * day), (2, now - day), (3, now - 40 * day)].into();
+        // Identical relevance on both engines.
+        let text = vec![(1, -5.0), (2, -5.0), (3, -5.0)];
+        let vector = vec![(1, 0.3), (2, 0.3), (3, 0.3)];
+
+        let boost = RecencyBoost::from_params(&serde_json::json!({ "recencyWeight": 0.1 }), now).unwrap().unwrap();
+        let merged = merge_results_boosted(&text, &vector, |_| (0.7, 0.3), |r| boost.bonus(dates[&r]), 10);
+        assert_eq!(merged.iter().map(|r| r.rowid).collect::<Vec<_>>(), vec![2, 3, 1]);
+
+        // Off by default; the bonus never rescues a result below MIN_SCORE.
+        assert!(RecencyBoost::from_params(&serde_json::json!({}), now).unwrap().is_none());
+        assert!(RecencyBoost::from_params(&serde_json::json!({ "recencyWeight": -1 }), now).is_err());
+        let weak = merge_results_boosted(&[], &[(1, 0.95)], |_| (0.7, 0.3), |_| 1.0, 10);
+        assert!(weak.is_empty());
+
+        let half_life = 30.0 * day as f64;
+        assert_eq!(recency_score(now, now, half_life), 1.0);
+        assert_eq!(recency_score(now + day, now, half_life), 1.0);
+        assert!((recency_score(now - 30 * day, now, half_life) - 0.5).abs() < 1e-12);
+        assert_eq!(recency_score(0, now, half_life), 0.0);
+    }
+
+    #[test]
+    fn test_dedup_results_keeps_best_rank() {
+        let results = vec![
+            serde_json::json!({ "uniqueId": "a", "rank": -0.5, "subject": "first" }),
+            serde_json::json!({ "uniqueId": "b", "rank": -0.4 }),
+            serde_json::json!({ "uniqueId": "a", "rank": -0.9, "subject": "better" }),
+        ];
+        let out = dedup_results(results, "uniqueId");
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0]["subject"], "better");
+        assert_eq!(out[1]["uniqueId"], "b");
+    }
+
+    #[test]
+    fn test_cosine_distance() {
+        let a = [1.0f32, 0.0, 0.0];
+        assert!(cosine_distance(&a, &a).abs() < 1e-9);
+        assert!((cosine_distance(&a, &[0.0, 1.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!((cosine_distance(&a, &[-2.0, 0.0, 0.0]) - 2.0).abs() < 1e-9);
+        // Zero vectors count as orthogonal rather than producing NaN.
+        assert!((cosine_distance(&a, &[0.0, 0.0, 0.0]) - 1.0).abs() < 1e-9);
     }
 }
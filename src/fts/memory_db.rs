@@ -8,8 +8,9 @@ use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
-use crate::embeddings::engine::EmbeddingEngine;
-use crate::fts::query::build_fts_match;
+use crate::embeddings::engine::{EmbedRole, EmbeddingEngine};
+use crate::fts::db::{preview_snippet, DateInclusivity};
+use crate::fts::query::{auto_wildcard_min_len, build_fts_match};
 use crate::fts::synonyms::SynonymLookup;
 use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
 use serde_json::Value;
@@ -43,17 +44,12 @@ PRAGMA wal_autocheckpoint = {wal_autocheckpoint};\n\
     // - role: "user" or "assistant" (or "kb" for knowledge base entries)
     // - content: the actual text content
     // - sessionId: chat session identifier (for grouping turns)
-    conn.execute_batch(&format!(
-        r#"
-        CREATE VIRTUAL TABLE IF NOT EXISTS memory_fts USING fts5(
-            memId,
-            role,
-            content,
-            sessionId,
-            tokenize = "{tokenize}",
-            prefix = '{prefix}'
-        );
+    create_memory_fts(conn)?;
 
+    // memory_content keeps the raw text outside FTS so the index can be rebuilt
+    // (e.g. after a tokenizer change) without losing data.
+    conn.execute_batch(
+        r#"
         CREATE TABLE IF NOT EXISTS memory_meta (
             rowid INTEGER PRIMARY KEY,
             dateMs INTEGER NOT NULL,
@@ -65,6 +61,34 @@ PRAGMA wal_autocheckpoint = {wal_autocheckpoint};\n\
             memId TEXT PRIMARY KEY
         );
         "#,
+    )?;
+    create_memory_content_table(conn)?;
+    ensure_memory_session_index(conn)?;
+    super::db::ensure_db_meta(conn)?;
+    super::db::record_distance_metric(conn)?;
+
+    // Vector tables for semantic search (sqlite-vec).
+    // memory_vec rowids match memory_fts rowids for joining.
+    super::db::create_vec_table(conn, "memory_vec")?;
+    super::db::create_embed_cache_table(conn)?;
+
+    log::info!("Memory database schema initialized (7 tables: memory_fts, memory_meta, memory_ids, memory_content, db_meta, memory_vec, embed_cache)");
+    Ok(())
+}
+
+/// Create the memory_fts virtual table with the current tokenizer config and merge settings.
+fn create_memory_fts(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(&format!(
+        r#"
+        CREATE VIRTUAL TABLE IF NOT EXISTS memory_fts USING fts5(
+            memId,
+            role,
+            content,
+            sessionId,
+            tokenize = "{tokenize}",
+            prefix = '{prefix}'
+        );
+        "#,
         tokenize = config::sqlite::FTS_TOKENIZE,
         prefix = config::sqlite::FTS_PREFIXES
     ))?;
@@ -78,26 +102,55 @@ PRAGMA wal_autocheckpoint = {wal_autocheckpoint};\n\
         "INSERT INTO memory_fts(memory_fts, rank) VALUES('usermerge', 2)",
         [],
     )?;
+    Ok(())
+}
 
-    // Vector tables for semantic search (sqlite-vec).
-    // memory_vec rowids match memory_fts rowids for joining.
-    conn.execute_batch(&format!(
+fn create_memory_content_table(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
         r#"
-        CREATE VIRTUAL TABLE IF NOT EXISTS memory_vec USING vec0(
-            embedding FLOAT[{dims}] distance_metric=cosine
-        );
-
-        CREATE TABLE IF NOT EXISTS embed_cache (
-            content_hash TEXT PRIMARY KEY,
-            embedding BLOB NOT NULL,
-            model TEXT NOT NULL,
-            created_at INTEGER NOT NULL
+        CREATE TABLE IF NOT EXISTS memory_content (
+            rowid INTEGER PRIMARY KEY,
+            memId TEXT NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            sessionId TEXT NOT NULL
         );
         "#,
-        dims = config::embedding::EMBEDDING_DIMS,
-    ))?;
+    )?;
+    Ok(())
+}
+
+/// Add memory_content to an existing memory database and backfill it from memory_fts
+/// (migration for databases created before content was stored outside FTS).
+fn ensure_memory_content_table(conn: &Connection) -> anyhow::Result<()> {
+    let exists: Option<String> = conn
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name='memory_content'",
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+    if exists.is_some() {
+        return Ok(());
+    }
+
+    log::info!("Migrating memory DB: adding memory_content table");
+    let tx = conn.unchecked_transaction()?;
+    create_memory_content_table(&tx)?;
+    let copied = tx.execute(
+        "INSERT INTO memory_content (rowid, memId, role, content, sessionId)
+         SELECT rowid, memId, role, content, sessionId FROM memory_fts",
+        [],
+    )?;
+    tx.commit()?;
+    log::info!("memory_content backfilled with {} entries", copied);
+    Ok(())
+}
 
-    log::info!("Memory database schema initialized (5 tables: memory_fts, memory_meta, memory_ids, memory_vec, embed_cache)");
+/// Index memory_meta by session so per-session lookups (next turnIndex, digest, forget)
+/// don't scan every turn. Idempotent; also migrates databases created without it.
+fn ensure_memory_session_index(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_memory_meta_session ON memory_meta(sessionId, turnIndex);")?;
     Ok(())
 }
 
@@ -130,6 +183,9 @@ pub fn open_or_create_memory_db(fts_dir: &Path) -> anyhow::Result<(PathBuf, Conn
         log::info!("Using existing memory database schema");
         // Migrate: add vector tables if missing (pre-v0.7.0 databases)
         ensure_memory_vector_tables(&conn)?;
+        ensure_memory_content_table(&conn)?;
+        ensure_memory_session_index(&conn)?;
+        super::db::ensure_db_meta(&conn)?;
     }
 
     let count: i64 = conn.query_row("SELECT COUNT(*) FROM memory_fts", [], |r| r.get(0))?;
@@ -151,24 +207,15 @@ fn ensure_memory_vector_tables(conn: &Connection) -> anyhow::Result<()> {
 
     if vec_exists.is_none() {
         log::info!("Migrating memory DB: adding vector tables (memory_vec, embed_cache)");
-        conn.execute_batch(&format!(
-            r#"
-            CREATE VIRTUAL TABLE IF NOT EXISTS memory_vec USING vec0(
-                embedding FLOAT[{dims}] distance_metric=cosine
-            );
-            CREATE TABLE IF NOT EXISTS embed_cache (
-                content_hash TEXT PRIMARY KEY,
-                embedding BLOB NOT NULL,
-                model TEXT NOT NULL,
-                created_at INTEGER NOT NULL
-            );
-            "#,
-            dims = config::embedding::EMBEDDING_DIMS,
-        ))?;
+        super::db::create_vec_table(conn, "memory_vec")?;
+        super::db::create_embed_cache_table(conn)?;
         log::info!("Vector tables added to memory database");
     } else {
         // Check if existing vec0 table uses cosine distance (v0.7.0-dev had L2 by mistake).
-        let needs_cosine_migration = super::db::needs_vec_cosine_migration(conn, "memory_vec")?;
+        let needs_cosine_migration = super::db::needs_vec_cosine_migration(conn, "memory_vec")?
+            && super::db::read_db_meta(conn, "distanceMetric")?.is_none()
+            && crate::fts::hybrid::distance_metric() == crate::fts::hybrid::DistanceMetric::Cosine
+            && super::db::vec_available(conn);
         if needs_cosine_migration {
             log::info!("Migrating memory_vec: L2 → cosine distance metric (dropping and recreating)");
             conn.execute_batch(&format!(
@@ -226,6 +273,8 @@ pub fn memory_vec_count(conn: &Connection) -> i64 {
 
 /// Index a batch of memory entries
 /// Each row should have: memId, role, content, sessionId, dateMs, turnIndex
+/// turnIndex is optional: when absent it is MAX(turnIndex)+1 for the row's sessionId (0 for
+/// a new session). An explicit turnIndex always takes precedence.
 pub fn memory_index_batch(conn: &mut Connection, rows: &[Value], engine: Option<&EmbeddingEngine>) -> anyhow::Result<(i64, i64)> {
     log::info!("Indexing batch of {} memory entries (embeddings={})", rows.len(), engine.is_some());
 
@@ -233,7 +282,8 @@ pub fn memory_index_batch(conn: &mut Connection, rows: &[Value], engine: Option<
 
     let mut inserted: i64 = 0;
     let mut skipped_duplicates: i64 = 0;
-    let mut embedded: i64 = 0;
+    // (rowid, memId, prepared text) of new entries awaiting an embedding
+    let mut pending: Vec<(i64, &str, String)> = Vec::new();
 
     for row in rows {
         let Some(mem_id_val) = row.get("memId").and_then(|v| v.as_str()) else { continue };
@@ -269,9 +319,25 @@ pub fn memory_index_batch(conn: &mut Connection, rows: &[Value], engine: Option<
             "#,
             params![row_id, mem_id_val, role, content, session_id],
         )?;
+        tx.execute(
+            r#"
+            INSERT INTO memory_content (rowid, memId, role, content, sessionId)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            params![row_id, mem_id_val, role, content, session_id],
+        )?;
 
         let date_ms = row.get("dateMs").and_then(|v| v.as_i64()).unwrap_or(0);
-        let turn_index = row.get("turnIndex").and_then(|v| v.as_i64()).unwrap_or(0);
+        // An explicit turnIndex wins; otherwise continue the session's sequence
+        // (rows inserted earlier in this batch are visible inside the transaction).
+        let turn_index = match row.get("turnIndex").and_then(|v| v.as_i64()) {
+            Some(t) => t,
+            None => tx.query_row(
+                "SELECT COALESCE(MAX(turnIndex) + 1, 0) FROM memory_meta WHERE sessionId = ?1",
+                params![session_id],
+                |r| r.get(0),
+            )?,
+        };
 
         tx.execute(
             r#"
@@ -281,27 +347,19 @@ pub fn memory_index_batch(conn: &mut Connection, rows: &[Value], engine: Option<
             params![row_id, date_ms, session_id, turn_index],
         )?;
 
-        // Generate and store embedding if engine is available
-        if let Some(engine) = engine {
-            let embed_text = crate::embeddings::text_prep::prepare_memory_text(role, content);
-            match engine.embed(&embed_text) {
-                Ok(embedding) => {
-                    let blob = super::db::f32_vec_to_blob(&embedding);
-                    tx.execute(
-                        "INSERT INTO memory_vec (rowid, embedding) VALUES (?1, ?2)",
-                        params![row_id, blob],
-                    )?;
-                    embedded += 1;
-                }
-                Err(e) => {
-                    log::warn!("Failed to embed memory {}: {}", truncate_for_log(mem_id_val), e);
-                }
-            }
+        // Embedded together after the loop (bursty ingestion sends whole conversations).
+        if engine.is_some() {
+            pending.push((row_id, mem_id_val, crate::embeddings::text_prep::prepare_memory_text(role, content)));
         }
 
         inserted += 1;
     }
 
+    let embedded = match engine {
+        Some(engine) => store_memory_embeddings(&tx, engine, &pending)?,
+        None => 0,
+    };
+
     tx.commit()?;
     if engine.is_some() {
         log::info!(
@@ -321,6 +379,45 @@ pub fn memory_index_batch(conn: &mut Connection, rows: &[Value], engine: Option<
     Ok((inserted, skipped_duplicates))
 }
 
+/// Embed the batch's new entries in one `embed_batch` call and store the vectors. If the
+/// batch call fails, each entry is retried alone so one bad input only loses its own vector.
+/// Returns the number of embeddings stored.
+fn store_memory_embeddings(
+    tx: &rusqlite::Transaction,
+    engine: &EmbeddingEngine,
+    pending: &[(i64, &str, String)],
+) -> anyhow::Result<i64> {
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    let texts: Vec<String> = pending.iter().map(|(_, _, text)| text.clone()).collect();
+    let max_tokens = Some(config::embedding::MEMORY_MAX_TOKENS);
+    let results: Vec<anyhow::Result<Vec<f32>>> = match engine.embed_batch(&texts, EmbedRole::Document, max_tokens) {
+        Ok(vectors) => vectors.into_iter().map(Ok).collect(),
+        Err(e) => {
+            log::warn!("Batch embedding of {} memory entries failed, retrying one by one: {}", texts.len(), e);
+            texts.iter().map(|t| engine.embed_with_max_tokens(t, EmbedRole::Document, max_tokens)).collect()
+        }
+    };
+
+    let mut embedded = 0;
+    for ((row_id, mem_id, _), result) in pending.iter().zip(results) {
+        match result {
+            Ok(embedding) => {
+                tx.execute(
+                    "INSERT INTO memory_vec (rowid, embedding) VALUES (?1, ?2)",
+                    params![row_id, super::db::f32_vec_to_blob(&embedding)],
+                )?;
+                embedded += 1;
+            }
+            Err(e) => {
+                log::warn!("Failed to embed memory {}: {}", truncate_for_log(mem_id), e);
+            }
+        }
+    }
+    Ok(embedded)
+}
+
 // Internal struct for memory FTS candidate data during hybrid merge.
 struct MemoryFtsCandidate {
     rowid: i64,
@@ -350,6 +447,17 @@ pub fn memory_search(
     params: &Value,
     synonyms: &SynonymLookup,
     engine: Option<&EmbeddingEngine>,
+) -> anyhow::Result<Vec<Value>> {
+    let results = memory_search_ranked(conn, q, params, synonyms, engine)?;
+    Ok(crate::fts::hybrid::dedup_results(results, "memId"))
+}
+
+fn memory_search_ranked(
+    conn: &Connection,
+    q: &str,
+    params: &Value,
+    synonyms: &SynonymLookup,
+    engine: Option<&EmbeddingEngine>,
 ) -> anyhow::Result<Vec<Value>> {
     let query = q.trim();
     let ignore_date = params.get("ignoreDate").and_then(|v| v.as_bool()).unwrap_or(false);
@@ -380,25 +488,28 @@ pub fn memory_search(
         None
     };
 
-    let candidate_limit = limit * config::hybrid::CANDIDATE_MULTIPLIER;
+    let (fts_candidate_limit, vec_candidate_limit) = super::db::candidate_limits(params, limit);
+    let vec_candidate_limit =
+        super::db::date_scoped_vec_k(params, vec_candidate_limit, from_ts.is_some() || to_ts.is_some());
+    let inclusive = DateInclusivity::from_params(params)?;
 
     // --- FTS5 candidates ---
-    let fts_query = build_fts_match(Some(query), true, synonyms);
+    let fts_query = build_fts_match(Some(query), true, synonyms, auto_wildcard_min_len(params));
     log::info!(
         "Memory hybrid search: \"{}\" -> FTS \"{}\"",
         query,
         fts_query
     );
     let fts_candidates = if !fts_query.is_empty() {
-        memory_search_fts_candidates(conn, &fts_query, from_ts, to_ts, candidate_limit)?
+        memory_search_fts_candidates(conn, &fts_query, from_ts, to_ts, inclusive, fts_candidate_limit)?
     } else {
         vec![]
     };
 
     // --- Vector candidates ---
-    let query_embedding = engine.embed(query)?;
+    let query_embedding = engine.embed_with_max_tokens(query, EmbedRole::Query, Some(config::embedding::MEMORY_MAX_TOKENS))?;
     let query_blob = super::db::f32_vec_to_blob(&query_embedding);
-    let vec_candidates = super::db::search_vec_candidates(conn, "memory_vec", &query_blob, candidate_limit)
+    let mut vec_candidates = super::db::search_vec_candidates(conn, "memory_vec", &query_blob, vec_candidate_limit)
         .unwrap_or_default(); // empty vec table during rebuild → graceful empty
 
     // Fall back to FTS-only when vec table is empty (e.g., during embedding rebuild).
@@ -406,6 +517,7 @@ pub fn memory_search(
         log::info!("No memory vector candidates (vec table may be empty), falling back to FTS-only search");
         return memory_search_fts_only(conn, query, params, synonyms, ignore_date, limit);
     }
+    super::db::retain_in_date_range(conn, "memory_meta", &mut vec_candidates, from_ts, to_ts, inclusive)?;
 
     // --- Merge ---
     let text_pairs: Vec<(i64, f64)> = fts_candidates.iter().map(|c| (c.rowid, c.rank)).collect();
@@ -436,15 +548,8 @@ pub fn memory_search(
         } else {
             // Vector-only result
             if let Some(meta) = fetch_memory_meta(conn, hr.rowid)? {
-                if let Some(from) = from_ts {
-                    if meta.date_ms < from {
-                        continue;
-                    }
-                }
-                if let Some(to) = to_ts {
-                    if meta.date_ms > to {
-                        continue;
-                    }
+                if !inclusive.admits(meta.date_ms, from_ts, to_ts) {
+                    continue;
                 }
                 results.push(serde_json::json!({
                     "memId": meta.mem_id,
@@ -452,7 +557,8 @@ pub fn memory_search(
                     "content": meta.content,
                     "sessionId": meta.session_id,
                     "dateMs": meta.date_ms,
-                    "snippet": "",
+                    "snippet": preview_snippet(&meta.content, "…"),
+                    "snippetKind": "preview",
                     "rank": -hr.final_score
                 }));
             }
@@ -488,21 +594,24 @@ fn memory_list_all(
     let mut bind: Vec<rusqlite::types::Value> = vec![];
 
     if !ignore_date {
+        let inclusive = DateInclusivity::from_params(params)?;
         if let Some(from_v) = params.get("from") {
             if let Some(ts) = super::db::parse_date_param(from_v)? {
-                sql.push_str(" AND meta.dateMs >= ?");
+                sql.push_str(&format!(" AND meta.dateMs {} ?", inclusive.lower_op()));
                 bind.push(rusqlite::types::Value::from(ts));
             }
         }
         if let Some(to_v) = params.get("to") {
             if let Some(ts) = super::db::parse_date_param(to_v)? {
-                sql.push_str(" AND meta.dateMs <= ?");
+                sql.push_str(&format!(" AND meta.dateMs {} ?", inclusive.upper_op()));
                 bind.push(rusqlite::types::Value::from(ts));
             }
         }
     }
 
-    sql.push_str(" ORDER BY meta.dateMs DESC LIMIT ?");
+    // turnIndex/rowid break dateMs ties (a conversation ingested at once shares one
+    // timestamp) so pagination over the listing is stable.
+    sql.push_str(" ORDER BY meta.dateMs DESC, meta.turnIndex ASC, meta.rowid ASC LIMIT ?");
     bind.push(rusqlite::types::Value::from(limit));
 
     let mut stmt = conn.prepare(&sql)?;
@@ -541,7 +650,7 @@ fn memory_search_fts_only(
     ignore_date: bool,
     limit: i64,
 ) -> anyhow::Result<Vec<Value>> {
-    let fts_query = build_fts_match(Some(query), true, synonyms);
+    let fts_query = build_fts_match(Some(query), true, synonyms, auto_wildcard_min_len(params));
     log::info!(
         "Memory query transformation (with synonyms): \"{}\" -> \"{}\"",
         query,
@@ -569,15 +678,16 @@ fn memory_search_fts_only(
         vec![rusqlite::types::Value::from(fts_query.clone())];
 
     if !ignore_date {
+        let inclusive = DateInclusivity::from_params(params)?;
         if let Some(from_v) = params.get("from") {
             if let Some(ts) = super::db::parse_date_param(from_v)? {
-                sql.push_str(" AND meta.dateMs >= ?");
+                sql.push_str(&format!(" AND meta.dateMs {} ?", inclusive.lower_op()));
                 bind.push(rusqlite::types::Value::from(ts));
             }
         }
         if let Some(to_v) = params.get("to") {
             if let Some(ts) = super::db::parse_date_param(to_v)? {
-                sql.push_str(" AND meta.dateMs <= ?");
+                sql.push_str(&format!(" AND meta.dateMs {} ?", inclusive.upper_op()));
                 bind.push(rusqlite::types::Value::from(ts));
             }
         }
@@ -625,6 +735,7 @@ fn memory_search_fts_candidates(
     fts_query: &str,
     from_ts: Option<i64>,
     to_ts: Option<i64>,
+    inclusive: DateInclusivity,
     limit: i64,
 ) -> anyhow::Result<Vec<MemoryFtsCandidate>> {
     let mut sql = format!(
@@ -645,11 +756,11 @@ fn memory_search_fts_candidates(
         vec![rusqlite::types::Value::from(fts_query.to_string())];
 
     if let Some(from) = from_ts {
-        sql.push_str(" AND meta.dateMs >= ?");
+        sql.push_str(&format!(" AND meta.dateMs {} ?", inclusive.lower_op()));
         bind.push(rusqlite::types::Value::from(from));
     }
     if let Some(to) = to_ts {
-        sql.push_str(" AND meta.dateMs <= ?");
+        sql.push_str(&format!(" AND meta.dateMs {} ?", inclusive.upper_op()));
         bind.push(rusqlite::types::Value::from(to));
     }
 
@@ -697,10 +808,11 @@ fn fetch_memory_meta(conn: &Connection, rowid: i64) -> anyhow::Result<Option<Mem
     .map_err(Into::into)
 }
 
-/// Remove entries from memory database by memId
-pub fn memory_remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Result<i64> {
+/// Remove entries from memory database by memId.
+/// Returns (removed ids, requested ids that weren't indexed).
+pub fn memory_remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Result<(Vec<String>, Vec<String>)> {
     if ids.is_empty() {
-        return Ok(0);
+        return Ok((vec![], vec![]));
     }
 
     let ids: Vec<String> = ids
@@ -711,7 +823,9 @@ pub fn memory_remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Resu
     log::info!("Removing {} entries from memory index", ids.len());
 
     let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
-    let mut removed: i64 = 0;
+    let has_vec = super::db::table_exists(&tx, "memory_vec")?;
+    let mut removed: Vec<String> = vec![];
+    let mut not_found: Vec<String> = vec![];
 
     for mem_id_val in ids {
         if mem_id_val.is_empty() {
@@ -727,22 +841,55 @@ pub fn memory_remove_batch(conn: &mut Connection, ids: &[Value]) -> anyhow::Resu
         if let Some(row_id) = row_id {
             tx.execute("DELETE FROM memory_fts WHERE rowid = ?1", params![row_id])?;
             tx.execute("DELETE FROM memory_meta WHERE rowid = ?1", params![row_id])?;
-            tx.execute("DELETE FROM memory_vec WHERE rowid = ?1", params![row_id])?;
+            tx.execute("DELETE FROM memory_content WHERE rowid = ?1", params![row_id])?;
+            if has_vec {
+                tx.execute("DELETE FROM memory_vec WHERE rowid = ?1", params![row_id])?;
+            }
             tx.execute("DELETE FROM memory_ids WHERE memId = ?1", params![mem_id_val])?;
-            removed += 1;
+            removed.push(mem_id_val);
+        } else {
+            not_found.push(mem_id_val);
         }
     }
 
     tx.commit()?;
-    log::info!("Removed {} memory entries", removed);
-    Ok(removed)
+    log::info!("Removed {} memory entries ({} not found)", removed.len(), not_found.len());
+    Ok((removed, not_found))
+}
+
+/// Forget one conversation: delete every entry of `session_id` from all memory tables in a
+/// single transaction. Returns the number of entries removed.
+pub fn memory_clear_session(conn: &mut Connection, session_id: &str) -> anyhow::Result<usize> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let has_vec = super::db::table_exists(&tx, "memory_vec")?;
+    let row_ids: Vec<i64> = {
+        let mut stmt = tx.prepare("SELECT rowid FROM memory_meta WHERE sessionId = ?1")?;
+        let rows = stmt.query_map(params![session_id], |r| r.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+
+    for row_id in &row_ids {
+        tx.execute("DELETE FROM memory_fts WHERE rowid = ?1", params![row_id])?;
+        tx.execute("DELETE FROM memory_meta WHERE rowid = ?1", params![row_id])?;
+        tx.execute("DELETE FROM memory_content WHERE rowid = ?1", params![row_id])?;
+        if has_vec {
+            tx.execute("DELETE FROM memory_vec WHERE rowid = ?1", params![row_id])?;
+        }
+        tx.execute("DELETE FROM memory_ids WHERE rowid = ?1", params![row_id])?;
+    }
+
+    tx.commit()?;
+    log::info!("Cleared memory session {}: {} entries removed", session_id, row_ids.len());
+    Ok(row_ids.len())
 }
 
 /// Start rebuilding memory vector embeddings: clear vec tables and return total count.
 /// Call this once, then call `rebuild_memory_embeddings_batch` repeatedly until done.
 pub fn rebuild_memory_embeddings_start(conn: &mut Connection) -> anyhow::Result<i64> {
     log::info!("Starting memory embedding rebuild — clearing vector tables");
-    conn.execute("DELETE FROM memory_vec", [])?;
+    if super::db::table_exists(conn, "memory_vec")? {
+        conn.execute("DELETE FROM memory_vec", [])?;
+    }
     conn.execute("DELETE FROM embed_cache", []).ok(); // ok() in case embed_cache doesn't exist
     let total: i64 = conn.query_row("SELECT COUNT(*) FROM memory_fts", [], |r| r.get(0))?;
     log::info!("Cleared memory_vec and embed_cache, {} entries to embed", total);
@@ -779,7 +926,7 @@ pub fn rebuild_memory_embeddings_batch(
     let tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
     for (rowid, role, content) in &batch {
         let embed_text = crate::embeddings::text_prep::prepare_memory_text(role, content);
-        match engine.embed(&embed_text) {
+        match engine.embed_with_max_tokens(&embed_text, EmbedRole::Document, Some(config::embedding::MEMORY_MAX_TOKENS)) {
             Ok(embedding) => {
                 let blob = super::db::f32_vec_to_blob(&embedding);
                 // vec0 virtual tables don't support INSERT OR REPLACE,
@@ -802,6 +949,51 @@ pub fn rebuild_memory_embeddings_batch(
     Ok((new_last_rowid, processed, embedded, done))
 }
 
+/// Drop and recreate memory_fts from memory_content, leaving memory_meta and
+/// memory_vec untouched. Returns the number of entries re-inserted.
+pub fn memory_reindex_fts(conn: &mut Connection) -> anyhow::Result<i64> {
+    log::info!("Rebuilding memory_fts from memory_content (embeddings preserved)");
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    tx.execute_batch("DROP TABLE IF EXISTS memory_fts;")?;
+    create_memory_fts(&tx)?;
+    let reinserted = tx.execute(
+        "INSERT INTO memory_fts (rowid, memId, role, content, sessionId)
+         SELECT rowid, memId, role, content, sessionId FROM memory_content ORDER BY rowid",
+        [],
+    )?;
+    tx.commit()?;
+    log::info!("memory_fts rebuilt: {} entries re-indexed", reinserted);
+    Ok(reinserted as i64)
+}
+
+/// Restore the memory DB from a backup produced by `vacuum_into`, pointing `conn` at the
+/// restored file (see `restore_backup`). Returns the restored entry count.
+pub fn memory_restore_backup(db_path: &Path, conn: &mut Connection, backup_path: &Path) -> anyhow::Result<i64> {
+    super::db::validate_backup(backup_path, &["memory_fts", "memory_meta", "memory_ids"])?;
+    let old_conn = std::mem::replace(conn, Connection::open_in_memory()?);
+    let result = super::db::replace_db_file(db_path, old_conn, backup_path);
+
+    // Reopen even if the swap failed, so the writer always has a live connection.
+    *conn = reopen_memory_db(db_path)?;
+    result?;
+
+    let count = memory_db_count(conn)?;
+    log::info!("Memory restore complete: {} entries", count);
+    Ok(count)
+}
+
+/// Open a fresh writer connection to an existing memory DB (pragmas + migrations applied).
+pub fn reopen_memory_db(db_path: &Path) -> anyhow::Result<Connection> {
+    let conn = Connection::open(db_path).with_context(|| format!("reopen memory db {}", db_path.display()))?;
+    super::db::ensure_fts5_available(&conn)?;
+    super::db::apply_pragmas(&conn)?;
+    ensure_memory_vector_tables(&conn)?;
+    ensure_memory_content_table(&conn)?;
+    ensure_memory_session_index(&conn)?;
+    super::db::ensure_db_meta(&conn)?;
+    Ok(conn)
+}
+
 /// Clear and rebuild memory database.
 /// Takes ownership of the connection, returns a new one after rebuild.
 /// Caller must signal the reader thread to reopen its read-only connection.
@@ -823,12 +1015,14 @@ pub fn memory_clear_rebuild_standalone(db_path: &Path, conn: Connection) -> anyh
 }
 
 /// Read memory entries around a given timestamp (±tolerance_ms)
-/// Returns full conversation content for entries within the time window
+/// Returns full conversation content for up to `limit` entries within the time window
+/// (oldest first), and whether the window held more than that.
 pub fn memory_read_by_timestamp(
     conn: &Connection,
     timestamp_ms: i64,
     tolerance_ms: i64,
-) -> anyhow::Result<Vec<Value>> {
+    limit: i64,
+) -> anyhow::Result<(Vec<Value>, bool)> {
     let from_ms = timestamp_ms - tolerance_ms;
     let to_ms = timestamp_ms + tolerance_ms;
 
@@ -847,11 +1041,12 @@ pub fn memory_read_by_timestamp(
         JOIN memory_meta meta ON fts.rowid = meta.rowid
         WHERE meta.dateMs >= ?1 AND meta.dateMs <= ?2
         ORDER BY meta.dateMs ASC
-        LIMIT 50
+        LIMIT ?3
         "#,
     )?;
 
-    let rows = stmt.query_map(params![from_ms, to_ms], |r| {
+    // One extra row tells us whether the window was truncated.
+    let rows = stmt.query_map(params![from_ms, to_ms, limit + 1], |r| {
         let mem_id: String = r.get(0)?;
         let role: String = r.get(1)?;
         let content: String = r.get(2)?;
@@ -871,12 +1066,105 @@ pub fn memory_read_by_timestamp(
     for r in rows {
         results.push(r?);
     }
+    let truncated = results.len() as i64 > limit;
+    results.truncate(limit.max(0) as usize);
 
     log::info!(
-        "Memory read by timestamp: found {} entries in time window",
-        results.len()
+        "Memory read by timestamp: found {} entries in time window (truncated={})",
+        results.len(),
+        truncated
     );
-    Ok(results)
+    Ok((results, truncated))
+}
+
+/// Overview of one chat session: turn count, distinct roles (in order of first appearance),
+/// date span, and the first and last turns. With `central_turns` > 0, also the turns whose
+/// stored embeddings are closest to the session's mean embedding (`None` if the session has
+/// no embeddings); callers only ask for that when an embedding engine is loaded.
+pub fn memory_session_digest(conn: &Connection, session_id: &str, central_turns: usize) -> anyhow::Result<Value> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT meta.rowid, fts.memId, fts.role, fts.content, meta.dateMs
+        FROM memory_meta meta
+        JOIN memory_fts fts ON fts.rowid = meta.rowid
+        WHERE meta.sessionId = ?1
+        ORDER BY meta.dateMs ASC, meta.turnIndex ASC, meta.rowid ASC
+        "#,
+    )?;
+    let turns: Vec<(i64, Value)> = stmt
+        .query_map(params![session_id], |r| {
+            Ok((
+                r.get(0)?,
+                serde_json::json!({
+                    "memId": r.get::<_, String>(1)?,
+                    "role": r.get::<_, String>(2)?,
+                    "content": r.get::<_, String>(3)?,
+                    "dateMs": r.get::<_, i64>(4)?
+                }),
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut roles: Vec<&str> = Vec::new();
+    for (_, t) in &turns {
+        let role = t["role"].as_str().unwrap_or("");
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    let mut digest = serde_json::json!({
+        "sessionId": session_id,
+        "turns": turns.len(),
+        "roles": roles,
+        "fromMs": turns.first().map(|(_, t)| t["dateMs"].clone()),
+        "toMs": turns.last().map(|(_, t)| t["dateMs"].clone()),
+        "first": turns.first().map(|(_, t)| t.clone()),
+        "last": turns.last().map(|(_, t)| t.clone())
+    });
+
+    if central_turns > 0 && super::db::table_exists(conn, "memory_vec")? {
+        let mut vec_stmt = conn.prepare("SELECT embedding FROM memory_vec WHERE rowid = ?1")?;
+        let mut vectors = Vec::new();
+        for (i, (rowid, _)) in turns.iter().enumerate() {
+            let blob: Option<Vec<u8>> = vec_stmt.query_row(params![rowid], |r| r.get(0)).optional()?;
+            if let Some(blob) = blob {
+                vectors.push((i, super::db::blob_to_f32_vec(&blob)));
+            }
+        }
+        let central = rank_by_centrality(&vectors, central_turns).map(|ranked| {
+            ranked
+                .into_iter()
+                .map(|(i, similarity)| {
+                    let mut t = turns[i].1.clone();
+                    t["similarity"] = serde_json::json!(similarity);
+                    t
+                })
+                .collect::<Vec<_>>()
+        });
+        digest["central"] = serde_json::json!(central);
+    }
+
+    log::info!("Memory session digest for {}: {} turns", session_id, turns.len());
+    Ok(digest)
+}
+
+/// Rank `(key, embedding)` pairs by cosine similarity to their mean embedding, best first,
+/// keeping at most `k`. `None` when there are no embeddings to average.
+fn rank_by_centrality<K: Copy>(vectors: &[(K, Vec<f32>)], k: usize) -> Option<Vec<(K, f64)>> {
+    let dims = vectors.first()?.1.len();
+    let mut mean = vec![0.0f32; dims];
+    for (_, v) in vectors {
+        for (m, x) in mean.iter_mut().zip(v) {
+            *m += x / vectors.len() as f32;
+        }
+    }
+    let mut ranked: Vec<(K, f64)> = vectors
+        .iter()
+        .map(|(key, v)| (*key, 1.0 - crate::fts::hybrid::cosine_distance(v, &mean)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(k);
+    Some(ranked)
 }
 
 /// Get debug sample from memory database
@@ -926,3 +1214,223 @@ fn truncate_for_log(s: &str) -> String {
     }
     s.chars().take(max).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory memory DB without the vec0 table (sqlite-vec isn't registered in unit tests).
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_memory_fts(&conn).unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE memory_meta (
+                rowid INTEGER PRIMARY KEY,
+                dateMs INTEGER NOT NULL,
+                sessionId TEXT,
+                turnIndex INTEGER
+            );
+            CREATE TABLE memory_ids (memId TEXT PRIMARY KEY);
+            "#,
+        )
+        .unwrap();
+        create_memory_content_table(&conn).unwrap();
+        ensure_memory_session_index(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_session_lookups_use_session_index() {
+        let conn = setup_test_db();
+        let plan: Vec<String> = conn
+            .prepare("EXPLAIN QUERY PLAN SELECT COALESCE(MAX(turnIndex)+1,0) FROM memory_meta WHERE sessionId = ?1")
+            .unwrap()
+            .query_map(["s1"], |r| r.get(3))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(plan.iter().any(|d| d.contains("idx_memory_meta_session")), "{plan:?}");
+    }
+
+    #[test]
+    fn test_memory_session_digest() {
+        let mut conn = setup_test_db();
+        conn.execute_batch("CREATE TABLE memory_vec (rowid INTEGER PRIMARY KEY, embedding BLOB)").unwrap();
+        let turns = [("user", "plan a trip to Lisbon"), ("assistant", "here is an itinerary"), ("user", "thanks"), ("user", "other chat")];
+        let rows: Vec<Value> = turns
+            .iter()
+            .enumerate()
+            .map(|(i, (role, c))| {
+                let session = if i < 3 { "s1" } else { "s2" };
+                serde_json::json!({ "memId": format!("m{i}"), "role": role, "content": c, "sessionId": session, "dateMs": 1_000 + i as i64 })
+            })
+            .collect();
+        memory_index_batch(&mut conn, &rows, None).unwrap();
+
+        let digest = memory_session_digest(&conn, "s1", 0).unwrap();
+        assert_eq!(digest["turns"], 3);
+        assert_eq!(digest["roles"], serde_json::json!(["user", "assistant"]));
+        assert_eq!((digest["fromMs"].as_i64(), digest["toMs"].as_i64()), (Some(1_000), Some(1_002)));
+        assert_eq!(digest["first"]["content"], "plan a trip to Lisbon");
+        assert_eq!(digest["last"]["memId"], "m2");
+        assert!(digest.get("central").is_none());
+
+        // No embeddings stored yet: centrality can't be computed.
+        assert!(memory_session_digest(&conn, "s1", 2).unwrap()["central"].is_null());
+
+        // m0 and m1 point the same way, m2 off to the side: m2 is the least central.
+        let vectors = [(0, [1.0f32, 0.0]), (1, [0.9, 0.1]), (2, [0.0, 1.0])];
+        for (i, v) in vectors {
+            conn.execute(
+                "INSERT INTO memory_vec (rowid, embedding) SELECT rowid, ?2 FROM memory_ids WHERE memId = ?1",
+                params![format!("m{i}"), super::super::db::f32_vec_to_blob(&v)],
+            )
+            .unwrap();
+        }
+        let central = memory_session_digest(&conn, "s1", 2).unwrap()["central"].clone();
+        let ids: Vec<&str> = central.as_array().unwrap().iter().map(|t| t["memId"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["m1", "m0"]);
+
+        assert_eq!(memory_session_digest(&conn, "missing", 0).unwrap()["turns"], 0);
+    }
+
+    #[test]
+    fn test_memory_clear_session() {
+        let mut conn = setup_test_db();
+        // Plain stand-in for the vec0 table, so the per-row vector delete has something to hit.
+        conn.execute_batch("CREATE TABLE memory_vec (rowid INTEGER PRIMARY KEY, embedding BLOB)").unwrap();
+        let rows: Vec<Value> = ["s1", "s1", "s2"]
+            .iter()
+            .enumerate()
+            .map(|(i, s)| serde_json::json!({ "memId": format!("m{i}"), "role": "user", "content": "trip notes", "sessionId": s, "dateMs": 1_000 + i as i64 }))
+            .collect();
+        memory_index_batch(&mut conn, &rows, None).unwrap();
+        conn.execute("INSERT INTO memory_vec (rowid) SELECT rowid FROM memory_ids", []).unwrap();
+
+        assert_eq!(memory_clear_session(&mut conn, "s1").unwrap(), 2);
+        assert_eq!(memory_clear_session(&mut conn, "s1").unwrap(), 0);
+        for table in ["memory_fts", "memory_meta", "memory_content", "memory_vec", "memory_ids"] {
+            let n: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0)).unwrap();
+            assert_eq!(n, 1, "{table}");
+        }
+        let left: String = conn.query_row("SELECT memId FROM memory_ids", [], |r| r.get(0)).unwrap();
+        assert_eq!(left, "m2");
+    }
+
+    #[test]
+    fn test_memory_read_by_timestamp_limit_and_truncated() {
+        let mut conn = setup_test_db();
+        let rows: Vec<Value> = (0..5)
+            .map(|i| serde_json::json!({ "memId": format!("m{i}"), "role": "user", "content": "x", "sessionId": "s", "dateMs": 10_000 + i }))
+            .collect();
+        memory_index_batch(&mut conn, &rows, None).unwrap();
+
+        let (entries, truncated) = memory_read_by_timestamp(&conn, 10_002, 100, 3).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["memId"], "m0");
+        assert!(truncated);
+
+        let (entries, truncated) = memory_read_by_timestamp(&conn, 10_002, 100, 5).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn test_memory_turn_index_auto_increment() {
+        let mut conn = setup_test_db();
+        let entry = |id: &str, session: &str, turn: Option<i64>| {
+            let mut v = serde_json::json!({ "memId": id, "role": "user", "content": id, "sessionId": session, "dateMs": 1000 });
+            if let Some(t) = turn {
+                v["turnIndex"] = serde_json::json!(t);
+            }
+            v
+        };
+        memory_index_batch(&mut conn, &[entry("a0", "s1", None), entry("a1", "s1", None), entry("b0", "s2", None)], None)
+            .unwrap();
+        // Later batches continue the sequence; an explicit turnIndex takes precedence.
+        memory_index_batch(&mut conn, &[entry("a2", "s1", None), entry("a9", "s1", Some(9)), entry("a10", "s1", None)], None)
+            .unwrap();
+
+        let turns: Vec<(String, i64)> = conn
+            .prepare(
+                "SELECT c.memId, m.turnIndex FROM memory_content c JOIN memory_meta m ON c.rowid = m.rowid
+                 WHERE m.sessionId = 's1' ORDER BY m.turnIndex",
+            )
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let expected = [("a0", 0), ("a1", 1), ("a2", 2), ("a9", 9), ("a10", 10)];
+        assert_eq!(turns, expected.map(|(id, t)| (id.to_string(), t)));
+        let s2: i64 = conn.query_row("SELECT turnIndex FROM memory_meta WHERE sessionId = 's2'", [], |r| r.get(0)).unwrap();
+        assert_eq!(s2, 0);
+    }
+
+    #[test]
+    fn test_memory_list_all_stable_order_for_equal_dates() {
+        let mut conn = setup_test_db();
+        // Same dateMs for the whole conversation, inserted out of turn order.
+        let rows: Vec<Value> = [3, 0, 4, 1, 2]
+            .iter()
+            .map(|turn| {
+                serde_json::json!({
+                    "memId": format!("chat:s1:{turn}"),
+                    "role": if turn % 2 == 0 { "user" } else { "assistant" },
+                    "content": format!("turn {turn}"),
+                    "sessionId": "s1",
+                    "dateMs": 1000,
+                    "turnIndex": turn
+                })
+            })
+            .collect();
+        memory_index_batch(&mut conn, &rows, None).unwrap();
+        // An older entry sorts after all of them.
+        let older = vec![serde_json::json!({
+            "memId": "chat:s0:0", "role": "user", "content": "older", "sessionId": "s0", "dateMs": 500, "turnIndex": 0
+        })];
+        memory_index_batch(&mut conn, &older, None).unwrap();
+
+        let ids = |results: &[Value]| -> Vec<String> {
+            results.iter().map(|r| r["memId"].as_str().unwrap().to_string()).collect()
+        };
+        let params = serde_json::json!({});
+        let first = ids(&memory_list_all(&conn, &params, true, 10).unwrap());
+        assert_eq!(
+            first,
+            vec!["chat:s1:0", "chat:s1:1", "chat:s1:2", "chat:s1:3", "chat:s1:4", "chat:s0:0"]
+        );
+        for _ in 0..3 {
+            assert_eq!(ids(&memory_list_all(&conn, &params, true, 10).unwrap()), first);
+        }
+        // A page boundary inside the tie is stable too.
+        assert_eq!(ids(&memory_list_all(&conn, &params, true, 2).unwrap()), first[..2]);
+    }
+
+    #[test]
+    fn test_memory_reindex_fts_preserves_vectors() {
+        let mut conn = setup_test_db();
+        conn.execute_batch("CREATE TABLE memory_vec (rowid INTEGER PRIMARY KEY, embedding BLOB)").unwrap();
+        let rows: Vec<Value> = ["flight to Lisbon", "hotel booking", "dinner plans"]
+            .iter()
+            .enumerate()
+            .map(|(i, c)| serde_json::json!({ "memId": format!("m{i}"), "role": "user", "content": c, "sessionId": "s", "dateMs": 1_000 + i as i64 }))
+            .collect();
+        memory_index_batch(&mut conn, &rows, None).unwrap();
+        conn.execute("INSERT INTO memory_vec (rowid, embedding) SELECT rowid, x'00' FROM memory_ids", []).unwrap();
+        // Simulate an FTS index that lost rows (or was built with an old tokenizer).
+        conn.execute("DELETE FROM memory_fts WHERE memId = 'm1'", []).unwrap();
+
+        assert_eq!(memory_reindex_fts(&mut conn).unwrap(), 3);
+        let hit: String = conn
+            .query_row("SELECT memId FROM memory_fts WHERE memory_fts MATCH 'hotel'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(hit, "m1");
+        // rowids line up again with the untouched vectors.
+        let joined: i64 = conn
+            .query_row("SELECT COUNT(*) FROM memory_fts f JOIN memory_vec v ON f.rowid = v.rowid", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(joined, 3);
+    }
+}
@@ -1,4 +1,7 @@
+pub mod address;
 pub mod db;
+pub mod dump;
+pub mod embed_cache;
 pub mod hybrid;
 pub mod memory_db;
 pub mod query;
@@ -1,11 +1,140 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use serde_json::Value;
+
+use crate::config;
 use crate::fts::synonyms::SynonymLookup;
 
+// Process-wide auto-wildcard threshold (config default, overridable at init).
+static AUTO_WILDCARD_MIN_LEN: AtomicUsize = AtomicUsize::new(config::sqlite::AUTO_WILDCARD_MIN_LEN);
+
+/// Override the auto-wildcard threshold for this process (init `autoWildcardMinLen`).
+pub fn set_auto_wildcard_min_len(min_len: usize) {
+    AUTO_WILDCARD_MIN_LEN.store(min_len.max(1), Ordering::Relaxed);
+}
+
+/// Auto-wildcard threshold for a query: None when the request sets `autoWildcard: false`.
+pub fn auto_wildcard_min_len(params: &Value) -> Option<usize> {
+    let enabled = params.get("autoWildcard").and_then(|v| v.as_bool()).unwrap_or(true);
+    enabled.then(|| AUTO_WILDCARD_MIN_LEN.load(Ordering::Relaxed))
+}
+
+// Process-wide stop-word override (None = config::sqlite::STOP_WORDS; empty = disabled).
+static STOP_WORDS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+/// Replace the stop-word list for this process (init `stopWords`). Words are matched
+/// case-insensitively; an empty list disables stop-word removal.
+pub fn set_stop_words(words: Vec<String>) {
+    let words = words.into_iter().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()).collect();
+    *STOP_WORDS.write().unwrap() = Some(words);
+}
+
+pub(crate) fn is_stop_word(tok: &str) -> bool {
+    let lower = tok.to_lowercase();
+    match &*STOP_WORDS.read().unwrap() {
+        Some(words) => words.contains(&lower),
+        None => config::sqlite::STOP_WORDS.contains(&lower.as_str()),
+    }
+}
+
+fn is_operator(tok: &str) -> bool {
+    matches!(tok, "AND" | "OR" | "NOT")
+}
+
+/// Drop bare stop-word tokens. Only plain words qualify: field-scoped (`subject:the`),
+/// quoted, wildcarded and placeholder tokens are kept, as are the upper-case FTS5
+/// operators. A dropped word takes its operator with it (`budget AND the` -> `budget`),
+/// preferring the one before it, so the match never ends up with a dangling operator.
+/// If every word is a stop word, nothing is dropped (the user meant it).
+fn drop_stop_words(tokens: Vec<&str>, is_stop: impl Fn(&str) -> bool) -> Vec<&str> {
+    let droppable = |tok: &str| {
+        let core = trim_trailing_slash_question(tok);
+        !is_operator(tok)
+            && parse_placeholder(tok).is_none()
+            && !core.is_empty()
+            && core.chars().all(|c| c.is_alphanumeric() || c == '\'')
+            && is_stop(&core)
+    };
+    if tokens.iter().all(|t| is_operator(t) || droppable(t)) {
+        return tokens;
+    }
+    let mut keep = vec![true; tokens.len()];
+    for i in 0..tokens.len() {
+        if !droppable(tokens[i]) {
+            continue;
+        }
+        keep[i] = false;
+        if i > 0 && keep[i - 1] && is_operator(tokens[i - 1]) {
+            keep[i - 1] = false;
+        } else if i + 1 < tokens.len() && is_operator(tokens[i + 1]) {
+            keep[i + 1] = false;
+        }
+    }
+    tokens.into_iter().zip(keep).filter_map(|(t, k)| k.then_some(t)).collect()
+}
+
 // FTS5 query builder with email-specific syntax handling.
-pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLookup) -> String {
-    let Some(q) = q else { return String::new() };
+// Bare tokens of at least `auto_wildcard_min_len` chars get a trailing `*` (None = never).
+pub fn build_fts_match(
+    q: Option<&str>,
+    use_synonyms: bool,
+    synonyms: &SynonymLookup,
+    auto_wildcard_min_len: Option<usize>,
+) -> String {
+    build_fts_match_plan(q, use_synonyms, synonyms, auto_wildcard_min_len).fts_match
+}
+
+/// What `build_fts_match` did to a query, for `validateQuery`.
+#[derive(Debug, Default)]
+pub struct FtsMatchPlan {
+    pub fts_match: String,
+    /// FTS5 terms in order (field-scoped terms, phrases, wildcards, OR groups), before AND-joining.
+    pub tokens: Vec<String>,
+    /// (token, OR group it was expanded to) for each synonym expansion kept.
+    pub expanded_groups: Vec<(String, String)>,
+    pub dropped_stop_words: Vec<String>,
+    /// True when some part of the query was made only of stop words (searched literally).
+    pub only_stop_words: bool,
+    /// True when the expansion breadth cap removed some synonym groups.
+    pub expansion_capped: bool,
+}
+
+impl FtsMatchPlan {
+    /// Human-readable notes on how the query was interpreted.
+    pub fn warnings(&self, q: &str) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.fts_match.is_empty() {
+            warnings.push("Query is empty after normalization; search will return no results".to_string());
+        }
+        if q.matches('"').count() % 2 == 1 {
+            warnings.push("Unbalanced double quote; the rest of the query is treated as a phrase".to_string());
+        }
+        if !self.dropped_stop_words.is_empty() {
+            warnings.push(format!("Stop words ignored: {}", self.dropped_stop_words.join(", ")));
+        }
+        if self.only_stop_words {
+            warnings.push("Query is only stop words; they are searched as-is".to_string());
+        }
+        if self.expansion_capped {
+            warnings.push("Too many synonym groups; only the most specific token was expanded".to_string());
+        }
+        warnings
+    }
+}
+
+/// `build_fts_match`, keeping the intermediate steps.
+pub fn build_fts_match_plan(
+    q: Option<&str>,
+    use_synonyms: bool,
+    synonyms: &SynonymLookup,
+    auto_wildcard_min_len: Option<usize>,
+) -> FtsMatchPlan {
+    let mut plan = FtsMatchPlan::default();
+    let Some(q) = q else { return plan };
     let q = q.trim();
     if q.is_empty() {
-        return String::new();
+        return plan;
     }
 
     // First translate field aliases in raw query (from: -> from_:, to: -> to_:).
@@ -23,11 +152,27 @@ pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLo
         let is_quoted = idx % 2 == 1;
         if is_quoted {
             out.push(format!("\"{}\"", part));
+            plan.tokens.push(format!("\"{}\"", part));
             continue;
         }
 
-        let tokens: Vec<&str> = part.split_whitespace().filter(|t| !t.is_empty()).collect();
+        let raw_tokens: Vec<&str> = part.split_whitespace().filter(|t| !t.is_empty()).collect();
+        // Before the OR-group check, so a dropped word can't suppress auto-wildcarding.
+        let tokens = drop_stop_words(raw_tokens.clone(), is_stop_word);
+        let mut kept = tokens.iter().peekable();
+        for tok in &raw_tokens {
+            if kept.peek() == Some(&tok) {
+                kept.next();
+            } else if !is_operator(tok) {
+                plan.dropped_stop_words.push(tok.to_string());
+            }
+        }
+        plan.only_stop_words |= raw_tokens.iter().any(|t| !is_operator(t))
+            && tokens.len() == raw_tokens.len()
+            && raw_tokens.iter().all(|t| is_operator(t) || is_stop_word(&trim_trailing_slash_question(t)));
         let mut mapped: Vec<String> = vec![];
+        // (index in `mapped`, unexpanded token, OR-group size) per synonym expansion.
+        let mut expansions: Vec<(usize, String, usize)> = vec![];
 
         let will_have_or_groups = if use_synonyms {
             tokens.iter().any(|tok| will_expand_to_or_group(tok, synonyms))
@@ -61,8 +206,8 @@ pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLo
 
             // Remove trailing punctuation like '?' or '/' before optional wildcard.
             let has_wildcard = value.ends_with('*');
-            if has_wildcard {
-                value = &value[..value.len() - 1];
+            if let Some(stripped) = value.strip_suffix('*') {
+                value = stripped;
             }
             let core = trim_trailing_slash_question(value);
 
@@ -79,13 +224,13 @@ pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLo
             let final_token = if needs_quote {
                 format!("\"{}\"", escaped_core.replace('"', "\"\""))
             } else {
-                // Auto-add wildcard for tokens >= 4 chars, but avoid if OR groups exist.
-                if !has_wildcard
-                    && escaped_core.len() >= 4
+                // Auto-add wildcard for long enough tokens, but avoid if OR groups exist.
+                // A bare field name ("email from bob") is a word, not a prefix to complete.
+                let auto_wildcard = auto_wildcard_min_len
+                    .is_some_and(|min| escaped_core.chars().count() >= min)
                     && !will_have_or_groups
-                {
-                    format!("{escaped_core}*")
-                } else if has_wildcard {
+                    && !(field.is_none() && is_field_alias(&escaped_core));
+                if has_wildcard || auto_wildcard {
                     format!("{escaped_core}*")
                 } else {
                     escaped_core
@@ -97,6 +242,7 @@ pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLo
             } else if use_synonyms && !has_wildcard && !needs_quote && !final_token.is_empty() {
                 let expanded = synonyms.expand(&final_token);
                 if expanded != final_token {
+                    expansions.push((mapped.len(), final_token, expanded.matches(" OR ").count() + 1));
                     mapped.push(expanded);
                 } else {
                     mapped.push(final_token);
@@ -106,6 +252,14 @@ pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLo
             }
         }
 
+        plan.expansion_capped |= cap_expansion_breadth(&mut mapped, &expansions);
+        for (idx, tok, _) in &expansions {
+            if mapped[*idx] != *tok {
+                plan.expanded_groups.push((tok.clone(), mapped[*idx].clone()));
+            }
+        }
+        plan.tokens.extend(mapped.iter().cloned());
+
         if !mapped.is_empty() {
             let has_or_groups = mapped.iter().any(|t| t.contains("(") && t.contains(" OR "));
             if has_or_groups {
@@ -116,45 +270,90 @@ pub fn build_fts_match(q: Option<&str>, use_synonyms: bool, synonyms: &SynonymLo
         }
     }
 
-    out.join(" ").trim().to_string()
+    plan.fts_match = out.join(" ").trim().to_string();
+    plan
+}
+
+/// Keep AND-ed synonym groups from multiplying into a pathological FTS5 query: past
+/// `SYNONYM_MAX_EXPANSION_BREADTH` combinations, only the most specific token stays expanded.
+/// Returns whether the cap triggered.
+fn cap_expansion_breadth(mapped: &mut [String], expansions: &[(usize, String, usize)]) -> bool {
+    let breadth = expansions.iter().fold(1usize, |acc, (_, _, size)| acc.saturating_mul(*size));
+    if breadth <= config::sqlite::SYNONYM_MAX_EXPANSION_BREADTH {
+        return false;
+    }
+    let keep = expansions
+        .iter()
+        .min_by_key(|(_, tok, size)| (*size, std::cmp::Reverse(tok.chars().count())))
+        .map(|(idx, _, _)| *idx);
+    for (idx, tok, _) in expansions {
+        if Some(*idx) != keep {
+            mapped[*idx] = tok.clone();
+        }
+    }
+    log::info!(
+        "Synonym expansion capped: {} groups ({} combinations) -> 1 kept",
+        expansions.len(),
+        breadth
+    );
+    true
+}
+
+/// User-facing field prefixes and the messages_fts columns they target.
+/// `from`/`to` are renamed (the columns carry a trailing `_`); the rest are
+/// listed so they get the same case/whitespace normalization (`CC :x` -> `cc:x`).
+pub(crate) const FIELD_ALIASES: &[(&str, &str)] = &[
+    ("from", "from_"),
+    ("to", "to_"),
+    ("cc", "cc"),
+    ("bcc", "bcc"),
+    ("subject", "subject"),
+    ("body", "body"),
+];
+
+fn is_field_alias(word: &str) -> bool {
+    FIELD_ALIASES.iter().any(|(alias, _)| alias.eq_ignore_ascii_case(word))
 }
 
 fn translate_aliases(q: &str) -> String {
-    // Equivalent to Python regex: r'\b(from|to)\s*:' -> from_:/to_:
+    // Equivalent to Python regex: r'\b(from|to|cc|bcc|subject|body)\s*:' -> column:
     // We'll do a small manual scanner to avoid regex deps.
     let mut out = String::with_capacity(q.len());
     let bytes = q.as_bytes();
     let mut i = 0usize;
 
-    while i < bytes.len() {
-        // check word boundary for "from" or "to" then optional spaces then ':'
-        if starts_word_at(bytes, i, b"from") {
-            let end = i + 4;
-            let mut j = end;
-            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
-                j += 1;
-            }
-            if j < bytes.len() && bytes[j] == b':' {
-                out.push_str("from_:");
-                i = j + 1;
-                continue;
-            }
-        }
-        if starts_word_at(bytes, i, b"to") {
-            let end = i + 2;
-            let mut j = end;
-            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
-                j += 1;
-            }
-            if j < bytes.len() && bytes[j] == b':' {
-                out.push_str("to_:");
-                i = j + 1;
-                continue;
+    'scan: while i < bytes.len() {
+        // check word boundary for an alias then optional spaces then ':'
+        for (alias, column) in FIELD_ALIASES {
+            if starts_word_at(bytes, i, alias.as_bytes()) {
+                let mut j = i + alias.len();
+                while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j] == b':' {
+                    // `from: alice` filters like `from:alice`. A prefix with nothing after it
+                    // stays the plain word (a bare `from_:` is an FTS5 syntax error).
+                    let mut k = j + 1;
+                    while k < bytes.len() && bytes[k].is_ascii_whitespace() {
+                        k += 1;
+                    }
+                    if k == bytes.len() {
+                        out.push_str(alias);
+                    } else {
+                        out.push_str(column);
+                        out.push(':');
+                    }
+                    i = k;
+                    continue 'scan;
+                }
             }
         }
 
-        out.push(bytes[i] as char);
-        i += 1;
+        // Copy the whole (possibly multi-byte) char; pushing single bytes as chars
+        // would mangle UTF-8 (e.g. "café" -> "cafÃ©") and break accent folding.
+        let ch = q[i..].chars().next().unwrap_or_default();
+        out.push(ch);
+        i += ch.len_utf8().max(1);
     }
 
     out
@@ -173,7 +372,7 @@ fn starts_word_at(haystack: &[u8], i: usize, needle: &[u8]) -> bool {
     }
     for (k, nb) in needle.iter().enumerate() {
         let hb = haystack[i + k];
-        if hb.to_ascii_lowercase() != nb.to_ascii_lowercase() {
+        if !hb.eq_ignore_ascii_case(nb) {
             return false;
         }
     }
@@ -213,8 +412,11 @@ fn extract_field_quoted(q: &str, store: &mut Vec<(String, String)>) -> String {
             }
         }
 
-        out.push(bytes[i] as char);
-        i += 1;
+        // Copy the whole (possibly multi-byte) char; pushing single bytes as chars
+        // would mangle UTF-8 (e.g. "café" -> "cafÃ©") and break accent folding.
+        let ch = q[i..].chars().next().unwrap_or_default();
+        out.push(ch);
+        i += ch.len_utf8().max(1);
     }
 
     out
@@ -296,8 +498,8 @@ fn will_expand_to_or_group(tok: &str, synonyms: &SynonymLookup) -> bool {
     }
 
     let has_wildcard = value.ends_with('*');
-    if has_wildcard {
-        value = &value[..value.len() - 1];
+    if let Some(stripped) = value.strip_suffix('*') {
+        value = stripped;
     }
     let core = trim_trailing_slash_question(value);
     let escaped = core.replace('\'', "");
@@ -314,4 +516,137 @@ fn will_expand_to_or_group(tok: &str, synonyms: &SynonymLookup) -> bool {
     expanded != escaped
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_ascii_preserved_in_quoted_phrases() {
+        let synonyms = SynonymLookup::new();
+        assert_eq!(build_fts_match(Some("\"café review\""), false, &synonyms, Some(4)), "\"café review\"");
+        assert_eq!(
+            build_fts_match(Some("subject:\"Übersicht März\""), false, &synonyms, Some(4)),
+            "subject:\"Übersicht März\""
+        );
+        assert_eq!(build_fts_match(Some("from:josé"), false, &synonyms, Some(4)), "from_:josé*");
+    }
+
+    #[test]
+    fn test_field_aliases() {
+        let synonyms = SynonymLookup::new();
+        let m = |q: &str| build_fts_match(Some(q), false, &synonyms, None);
+        assert_eq!(m("from:alice"), "from_:alice");
+        assert_eq!(m("to:bob"), "to_:bob");
+        assert_eq!(m("cc:carol"), "cc:carol");
+        assert_eq!(m("bcc:dave"), "bcc:dave");
+        assert_eq!(m("subject:budget"), "subject:budget");
+        assert_eq!(m("body:invoice"), "body:invoice");
+        // Case and whitespace before ':' are normalized.
+        assert_eq!(m("BCC :dave"), "bcc:dave");
+        assert_eq!(m("Subject:\"Q3 plan\""), "subject:\"Q3 plan\"");
+        // Aliases only match whole words.
+        assert_eq!(m("abcc:dave"), "abcc:dave");
+        // Space after ':' still filters; a trailing prefix with no value is the plain word.
+        assert_eq!(m("from: alice"), "from_:alice");
+        assert_eq!(m("from:"), "from");
+        assert_eq!(m("budget cc :"), "budget cc");
+    }
+
+    #[test]
+    fn test_bare_field_names_are_words() {
+        let synonyms = SynonymLookup::new();
+        let m = |q: &str| build_fts_match(Some(q), false, &synonyms, Some(3));
+        // Without ':' a field name is an ordinary term: never a filter, never auto-wildcarded.
+        assert_eq!(m("from"), "from");
+        assert_eq!(m("email from bob"), "email* from bob*");
+        assert_eq!(m("Subject"), "Subject");
+        assert_eq!(m("from:alice"), "from_:alice*");
+    }
+
+    #[test]
+    fn test_auto_wildcard_threshold() {
+        let synonyms = SynonymLookup::new();
+        assert_eq!(build_fts_match(Some("pla"), false, &synonyms, Some(4)), "pla");
+        assert_eq!(build_fts_match(Some("plan"), false, &synonyms, Some(4)), "plan*");
+        assert_eq!(build_fts_match(Some("plan"), false, &synonyms, Some(5)), "plan");
+        assert_eq!(build_fts_match(Some("plans"), false, &synonyms, Some(5)), "plans*");
+        // Threshold counts chars, not bytes.
+        assert_eq!(build_fts_match(Some("néo"), false, &synonyms, Some(4)), "néo");
+        // Explicit wildcards are kept even when auto-wildcarding is off.
+        assert_eq!(build_fts_match(Some("plan budget*"), false, &synonyms, None), "plan budget*");
+    }
+
+    #[test]
+    fn test_stop_words_dropped_from_bare_tokens() {
+        let synonyms = SynonymLookup::new();
+        assert_eq!(build_fts_match(Some("the meeting"), false, &synonyms, Some(4)), "meeting*");
+        assert_eq!(build_fts_match(Some("The Budget?"), false, &synonyms, Some(4)), "Budget*");
+        // Field-scoped, quoted and wildcarded stop words are kept.
+        assert_eq!(build_fts_match(Some("subject:the notes"), false, &synonyms, Some(4)), "subject:the notes*");
+        assert_eq!(build_fts_match(Some("\"the plan\" of notes"), false, &synonyms, Some(4)), "\"the plan\" notes*");
+        assert_eq!(build_fts_match(Some("to* review"), false, &synonyms, Some(4)), "to* review*");
+        // FTS5 operators and all-stop-word queries survive.
+        assert_eq!(build_fts_match(Some("cats OR dogs"), false, &synonyms, None), "cats OR dogs");
+        assert_eq!(build_fts_match(Some("to be"), false, &synonyms, None), "to be");
+        assert_eq!(build_fts_match(Some("in OR on"), false, &synonyms, None), "in OR on");
+    }
+
+    #[test]
+    fn test_stop_word_takes_its_operator_with_it() {
+        let synonyms = SynonymLookup::new();
+        assert_eq!(build_fts_match(Some("budget AND the"), false, &synonyms, Some(4)), "budget*");
+        assert_eq!(build_fts_match(Some("meeting OR the"), false, &synonyms, Some(4)), "meeting*");
+        assert_eq!(build_fts_match(Some("cats NOT the"), false, &synonyms, None), "cats");
+        assert_eq!(build_fts_match(Some("the OR meeting"), false, &synonyms, None), "meeting");
+        assert_eq!(build_fts_match(Some("cats AND the OR dogs"), false, &synonyms, None), "cats OR dogs");
+        let plan = build_fts_match_plan(Some("budget AND the"), false, &synonyms, None);
+        assert_eq!(plan.dropped_stop_words, vec!["the"]);
+    }
+
+    #[test]
+    fn test_synonym_expansion_breadth_cap() {
+        let synonyms = SynonymLookup::new();
+        // Two groups stay within the cap and are both expanded.
+        let two = build_fts_match(Some("urgent meeting"), true, &synonyms, None);
+        assert_eq!(two, format!("{} AND {}", synonyms.expand("urgent"), synonyms.expand("meeting")));
+
+        // A third multiplies past it: only the smallest group survives.
+        let three = build_fts_match(Some("urgent meeting deadline"), true, &synonyms, None);
+        assert_eq!(three, format!("urgent AND meeting AND {}", synonyms.expand("deadline")));
+    }
+
+    #[test]
+    fn test_build_fts_match_plan() {
+        let synonyms = SynonymLookup::new();
+        let q = "the urgent subject:budget \"Q3 plan\"";
+        let plan = build_fts_match_plan(Some(q), true, &synonyms, Some(4));
+        assert_eq!(plan.fts_match, build_fts_match(Some(q), true, &synonyms, Some(4)));
+        assert_eq!(plan.tokens, vec![synonyms.expand("urgent"), "subject:budget".to_string(), "\"Q3 plan\"".to_string()]);
+        assert_eq!(plan.expanded_groups, vec![("urgent".to_string(), synonyms.expand("urgent"))]);
+        assert_eq!(plan.warnings(q), vec!["Stop words ignored: the"]);
+
+        let empty = build_fts_match_plan(Some("  ?? "), true, &synonyms, Some(4));
+        assert!(empty.warnings("  ?? ")[0].contains("empty after normalization"));
+        let stop = build_fts_match_plan(Some("to be"), true, &synonyms, Some(4));
+        assert!(stop.only_stop_words && stop.fts_match == "to be");
+        let unbalanced = build_fts_match_plan(Some("\"open phrase"), true, &synonyms, Some(4));
+        assert!(unbalanced.warnings("\"open phrase")[0].contains("Unbalanced"));
+        let capped = build_fts_match_plan(Some("urgent meeting deadline"), true, &synonyms, None);
+        assert!(capped.expansion_capped && capped.expanded_groups.len() == 1);
+    }
+
+    #[test]
+    fn test_drop_stop_words_custom_list() {
+        let custom = |t: &str| t.eq_ignore_ascii_case("bitte");
+        assert_eq!(drop_stop_words(vec!["bitte", "Rechnung", "the"], custom), vec!["Rechnung", "the"]);
+        assert_eq!(drop_stop_words(vec!["the", "plan"], |_: &str| false), vec!["the", "plan"]);
+    }
 
+    #[test]
+    fn test_auto_wildcard_min_len_from_params() {
+        let default = config::sqlite::AUTO_WILDCARD_MIN_LEN;
+        assert_eq!(auto_wildcard_min_len(&serde_json::json!({})), Some(default));
+        assert_eq!(auto_wildcard_min_len(&serde_json::json!({ "autoWildcard": true })), Some(default));
+        assert_eq!(auto_wildcard_min_len(&serde_json::json!({ "autoWildcard": false })), None);
+    }
+}
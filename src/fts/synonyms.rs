@@ -1,7 +1,12 @@
 use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
 
 // Email-specific synonym groups for search expansion.
-// Intentionally not loaded from JSON to avoid runtime dependencies / file I/O surprises.
+// The built-in groups are compiled in; users can layer extra groups from a JSON file
+// (init `synonymsPath`, re-read by `reloadSynonyms`).
 
 fn email_synonyms() -> Vec<(&'static str, &'static [&'static str])> {
     vec![
@@ -143,6 +148,10 @@ fn email_synonyms() -> Vec<(&'static str, &'static [&'static str])> {
     ]
 }
 
+/// The lookup the reader searches with. `reloadSynonyms` swaps the inner Arc; in-flight
+/// searches keep the lookup they started with.
+pub type SharedSynonyms = Arc<Mutex<Arc<SynonymLookup>>>;
+
 #[derive(Clone)]
 pub struct SynonymLookup {
     map: HashMap<String, BTreeSet<String>>,
@@ -155,16 +164,43 @@ impl SynonymLookup {
         for (_canonical, group) in email_synonyms() {
             let normalized_group: BTreeSet<String> = group.iter().map(|s| s.to_lowercase()).collect();
             for w in group.iter() {
-                let key = w.to_lowercase();
-                if !map.contains_key(&key) {
-                    map.insert(key, normalized_group.clone());
-                }
+                map.entry(w.to_lowercase())
+                    .or_insert_with(|| normalized_group.clone());
             }
         }
 
         Self { map }
     }
 
+    /// Built-in groups plus custom groups from a JSON file of string arrays
+    /// (`[["invoice", "factura", "rechnung"], ...]`). A word in a custom group uses that
+    /// group instead of its built-in one. Returns the lookup and the number of custom groups.
+    pub fn with_custom_file(path: &Path) -> anyhow::Result<(Self, usize)> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read synonym file {}", path.display()))?;
+        let groups: Vec<Vec<String>> = serde_json::from_str(&raw)
+            .with_context(|| format!("{} must be a JSON array of string arrays", path.display()))?;
+
+        let mut lookup = Self::new();
+        let mut loaded = 0usize;
+        for group in groups {
+            let normalized: BTreeSet<String> = group
+                .iter()
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            if normalized.len() < 2 {
+                continue;
+            }
+            for w in &normalized {
+                lookup.map.insert(w.clone(), normalized.clone());
+            }
+            loaded += 1;
+        }
+        log::info!("Loaded {} custom synonym groups from {}", loaded, path.display());
+        Ok((lookup, loaded))
+    }
+
     pub fn expand(&self, word: &str) -> String {
         let key = word.to_lowercase();
         if let Some(group) = self.map.get(&key) {
@@ -177,4 +213,26 @@ impl SynonymLookup {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_custom_file() {
+        let dir = std::env::temp_dir().join(format!("tm_synonyms_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("synonyms.json");
+        std::fs::write(&path, r#"[["invoice", "Factura"], ["solo"], ["kickoff", "launch"]]"#).unwrap();
 
+        let (lookup, loaded) = SynonymLookup::with_custom_file(&path).unwrap();
+        assert_eq!(loaded, 2);
+        assert_eq!(lookup.expand("factura"), "(factura OR invoice)");
+        assert_eq!(lookup.expand("kickoff"), "(kickoff OR launch)");
+        // Built-ins for other words are kept.
+        assert_eq!(lookup.expand("budget"), SynonymLookup::new().expand("budget"));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(SynonymLookup::with_custom_file(&path).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,104 @@
+// ics.rs — Structured event fields from ICS (iCalendar) text.
+//
+// message_meta.parsedIcsAttachments holds the raw ICS text of a message's calendar
+// attachments. `parseIcs` turns it into { summary, location, start, end, attendees,
+// organizer } per VEVENT so callers don't each need an ICS parser.
+
+use std::io::BufReader;
+
+use anyhow::Context;
+use ical::parser::ical::component::IcalEvent;
+use serde_json::Value;
+
+/// Parse every VEVENT in `text` (one or more concatenated VCALENDARs).
+/// start/end are the raw DTSTART/DTEND values (e.g. `20240315T140000Z`), with the
+/// TZID parameter, if any, in startTzid / endTzid.
+pub fn parse_events(text: &str) -> anyhow::Result<Vec<Value>> {
+    let mut events = Vec::new();
+    for calendar in ical::IcalParser::new(BufReader::new(text.as_bytes())) {
+        let calendar = calendar.context("invalid ICS")?;
+        events.extend(calendar.events.iter().map(event_to_json));
+    }
+    Ok(events)
+}
+
+fn event_to_json(event: &IcalEvent) -> Value {
+    let prop = |name: &str| event.properties.iter().find(|p| p.name == name);
+    let value = |name: &str| prop(name).and_then(|p| p.value.clone()).map(|v| unescape_text(&v));
+    let tzid = |name: &str| {
+        prop(name)
+            .and_then(|p| p.params.as_ref())
+            .and_then(|params| params.iter().find(|(k, _)| k == "TZID"))
+            .and_then(|(_, v)| v.first().cloned())
+    };
+    let attendees: Vec<String> = event
+        .properties
+        .iter()
+        .filter(|p| p.name == "ATTENDEE")
+        .filter_map(|p| p.value.as_deref().map(strip_mailto))
+        .collect();
+
+    serde_json::json!({
+        "uid": value("UID"),
+        "summary": value("SUMMARY"),
+        "location": value("LOCATION"),
+        "start": value("DTSTART"),
+        "startTzid": tzid("DTSTART"),
+        "end": value("DTEND"),
+        "endTzid": tzid("DTEND"),
+        "attendees": attendees,
+        "organizer": prop("ORGANIZER").and_then(|p| p.value.as_deref()).map(strip_mailto)
+    })
+}
+
+fn strip_mailto(v: &str) -> String {
+    let v = v.trim();
+    match v.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("mailto:") => v[7..].to_string(),
+        _ => v.to_string(),
+    }
+}
+
+/// Undo RFC 5545 TEXT escaping (`\,` `\;` `\\` `\n`).
+fn unescape_text(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    let mut chars = v.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:abc-1\r\nSUMMARY:Budget review\\, Q3\r\n\
+LOCATION:Room 4\r\nDTSTART;TZID=Europe/Berlin:20240315T140000\r\nDTEND;TZID=Europe/Berlin:20240315T150000\r\n\
+ORGANIZER;CN=Alice:mailto:alice@example.com\r\nATTENDEE;CN=Bob:MAILTO:bob@example.com\r\n\
+ATTENDEE:mailto:carol@example.com\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let events = parse_events(ics).unwrap();
+        assert_eq!(events.len(), 1);
+        let e = &events[0];
+        assert_eq!(e["summary"], "Budget review, Q3");
+        assert_eq!(e["location"], "Room 4");
+        assert_eq!(e["start"], "20240315T140000");
+        assert_eq!(e["startTzid"], "Europe/Berlin");
+        assert_eq!(e["end"], "20240315T150000");
+        assert_eq!(e["organizer"], "alice@example.com");
+        assert_eq!(e["attendees"], serde_json::json!(["bob@example.com", "carol@example.com"]));
+
+        assert!(parse_events("").unwrap().is_empty());
+        assert!(parse_events("BEGIN:VCALENDAR\nBEGIN:VEVENT\n").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
@@ -21,6 +21,7 @@ pub fn get_user_install_dir() -> anyhow::Result<PathBuf> {
     }
 }
 
+#[allow(dead_code)] // public helper kept for callers wanting the primary system dir
 pub fn get_system_install_dir() -> anyhow::Result<PathBuf> {
     match std::env::consts::OS {
         "macos" => Ok(PathBuf::from("/Applications/TabMail.app/Contents/Resources")),
@@ -71,7 +72,7 @@ pub fn get_system_install_dirs() -> Vec<PathBuf> {
 
 /// Check if a path is within any known system install directory.
 /// Used to detect if we're running from a system install (requiring migration to user-local).
-pub fn is_in_system_install_dir(path: &PathBuf) -> bool {
+pub fn is_in_system_install_dir(path: &Path) -> bool {
     for system_dir in get_system_install_dirs() {
         if is_relative_to(path, &system_dir) {
             return true;
@@ -91,7 +92,7 @@ pub fn native_manifest_dirs_user() -> anyhow::Result<Vec<PathBuf>> {
     }
 }
 
-pub fn is_relative_to(path: &PathBuf, base: &PathBuf) -> bool {
+pub fn is_relative_to(path: &Path, base: &Path) -> bool {
     // Stable path prefix comparison without unstable Path::is_relative_to.
     let p = path.to_string_lossy();
     let b = base.to_string_lossy();
@@ -120,7 +121,7 @@ pub fn exe_file_name() -> &'static str {
     }
 }
 
-pub fn can_write_dir(dir: &PathBuf) -> bool {
+pub fn can_write_dir(dir: &Path) -> bool {
     // Best-effort: attempt create a temp file.
     let probe = dir.join(".tabmail_write_probe.tmp");
     match std::fs::write(&probe, b"probe") {
@@ -132,7 +133,7 @@ pub fn can_write_dir(dir: &PathBuf) -> bool {
     }
 }
 
-pub fn ensure_dir(dir: &PathBuf) -> anyhow::Result<()> {
+pub fn ensure_dir(dir: &Path) -> anyhow::Result<()> {
     std::fs::create_dir_all(dir).with_context(|| format!("failed to create dir {}", dir.display()))
 }
 
@@ -1,26 +1,37 @@
 mod config;
+mod disk;
 mod embeddings;
 mod fts;
+mod ics;
 mod install_paths;
 mod logging;
+mod methods;
 mod native_messaging;
+mod profile;
 mod protocol;
+mod result_cache;
+mod runtime_stats;
 mod self_update;
+mod thread_priority;
 mod update_signature;
 
+use std::collections::HashMap;
 use std::io::{stdin, stdout, Stdin, Stdout};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
 
 use anyhow::{bail, Context};
 use rusqlite::Connection;
 use serde_json::Value;
 
 use crate::embeddings::engine::EmbeddingEngine;
-use crate::fts::db::{DbState, open_or_create_db};
+use crate::fts::db::{AutoOptimize, DbState, SearchMode, WalShutdownMode, open_or_create_db};
+use crate::fts::hybrid::DistanceMetric;
 use crate::fts::memory_db;
-use crate::fts::synonyms::SynonymLookup;
+use crate::fts::synonyms::{SharedSynonyms, SynonymLookup};
+use crate::result_cache::{ResultCache, WriteGenerations};
+use crate::runtime_stats::RuntimeStats;
 
 fn main() {
     if let Err(e) = real_main() {
@@ -42,15 +53,30 @@ fn real_main() -> anyhow::Result<()> {
         let staged = read_arg_value(&args, "--staged").context("missing --staged")?;
         return self_update::apply_update_mode(Path::new(&target), Path::new(&staged));
     }
+    // Install diagnostics for support: print a JSON report and exit (non-zero if broken).
+    if args.len() >= 2 && args[1] == "--verify-install" {
+        let report = self_update::verify_install()?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        if report["ok"] != true {
+            bail!("install verification failed: invalid native-messaging manifest");
+        }
+        return Ok(());
+    }
 
     // Register sqlite-vec as an auto-extension before any DB connections are opened.
     // This makes vec0 virtual tables available in all connections.
+    type VecInitFn = unsafe extern "C" fn(
+        *mut rusqlite::ffi::sqlite3,
+        *mut *mut std::os::raw::c_char,
+        *const rusqlite::ffi::sqlite3_api_routines,
+    ) -> std::os::raw::c_int;
     unsafe {
-        rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
+        rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute::<*const (), VecInitFn>(
             sqlite_vec::sqlite3_vec_init as *const (),
         )));
     }
 
+    let runtime_stats = Arc::new(RuntimeStats::new());
     log::info!("=== TabMail FTS Helper Started ===");
     log::info!("Waiting for messages from Thunderbird extension...");
 
@@ -86,15 +112,27 @@ fn real_main() -> anyhow::Result<()> {
 
         match req.method.as_str() {
             "hello" => {
-                let resp = handle_hello(&req.id, &req.params)?;
+                let resp = handle_hello(&req.id, &req.params)
+                    .with_context(|| format!("{} failed (id: {})", req.method, req.id))?;
                 native_messaging::write_json(&mut out_stream, &resp)?;
             }
             "updateCheck" => {
-                let resp = handle_update_check(&req.id, &req.params)?;
+                let resp = handle_update_check(&req.id, &req.params)
+                    .with_context(|| format!("{} failed (id: {})", req.method, req.id))?;
+                native_messaging::write_json(&mut out_stream, &resp)?;
+            }
+            "purgeStaleFiles" => {
+                let resp = handle_purge_stale_files(&req.id)
+                    .with_context(|| format!("{} failed (id: {})", req.method, req.id))?;
+                native_messaging::write_json(&mut out_stream, &resp)?;
+            }
+            "listMethods" => {
+                let resp = serde_json::json!({ "id": req.id, "result": methods::catalog(method_target_name) });
                 native_messaging::write_json(&mut out_stream, &resp)?;
             }
             "updateRequest" => {
-                let resp = handle_update_request(&req.id, &req.params)?;
+                let resp = handle_update_request(&req.id, &req.params)
+                    .with_context(|| format!("{} failed (id: {})", req.method, req.id))?;
                 native_messaging::write_json(&mut out_stream, &resp)?;
                 // updateRequest with success means process should exit for restart
                 if resp.get("result").and_then(|r| r.get("success")).and_then(|v| v.as_bool()).unwrap_or(false) {
@@ -103,11 +141,28 @@ fn real_main() -> anyhow::Result<()> {
                 }
             }
             "init" => {
-                let resp = handle_init(&mut state, &req.id, &req.params)?;
-                native_messaging::write_json(&mut out_stream, &resp)?;
+                let resp = handle_init(&mut state, &req.id, &req.params)
+                    .with_context(|| format!("{} failed (id: {})", req.method, req.id))?;
+                native_messaging::write_json(&mut out_stream, &protocol::shape_response(resp))?;
                 // init done — transition to Phase B (multi-threaded)
                 break;
             }
+            "health" => {
+                // Answerable before init so watchdogs can tell "not ready" from "dead".
+                let resp = serde_json::json!({
+                    "id": req.id,
+                    "result": {
+                        "ok": false,
+                        "initialized": false,
+                        "hasEmbeddings": false,
+                        "dbWritable": false,
+                        "walBytes": 0,
+                        "integrityOk": null,
+                        "timezoneInfo": crate::fts::db::timezone_info()
+                    }
+                });
+                native_messaging::write_json(&mut out_stream, &resp)?;
+            }
             other => {
                 let err = serde_json::json!({ "id": req.id, "error": format!("Must call 'init' first, got '{other}'") });
                 native_messaging::write_json(&mut out_stream, &err)?;
@@ -119,7 +174,7 @@ fn real_main() -> anyhow::Result<()> {
     // Phase B: Multi-threaded operation
     // Reader thread (read-only ops) + Writer thread (write ops) + Main (stdin dispatch)
     // ========================================================================
-    run_multi_threaded(state, in_stream, out_stream, message_count)
+    run_multi_threaded(state, in_stream, out_stream, message_count, runtime_stats)
 }
 
 // ============================================================================
@@ -131,11 +186,18 @@ struct ThreadMessage {
     method: String,
     id: String,
     params: Value,
+    /// Writer requests dispatched before this one (read-your-writes target for the reader).
+    write_seq: u64,
 }
 
 enum MethodTarget {
     Reader,
     Writer,
+    Main,
+    /// A repeated `init` after Phase B started (client bug); answered with `alreadyInitialized`.
+    Init,
+    /// `searchStream`: acked with a streamId, results follow as `searchResult` notifications.
+    Stream,
     Unknown,
 }
 
@@ -143,27 +205,55 @@ fn classify_method(method: &str) -> MethodTarget {
     match method {
         // Read-only email operations
         "search" | "stats" | "filterNewMessages" | "getMessageByMsgId"
-        | "findByHeaderMessageId" | "queryByDateRange" | "debugSample" => MethodTarget::Reader,
+        | "findByHeaderMessageId" | "queryByDateRange" | "debugSample" | "backup"
+        | "searchExplainPlan" | "statsHistory" | "searchSuggestQueries" | "verifyIntegrity" | "parseIcs"
+        | "subjectSearch" | "exportDump" | "exportEmbeddings" | "searchMulti" | "getSchema" => MethodTarget::Reader,
 
         // Read-only memory operations
-        "memorySearch" | "memoryStats" | "memoryDebugSample" | "memoryRead" => MethodTarget::Reader,
+        "memorySearch" | "memoryStats" | "memoryDebugSample" | "memoryRead"
+        | "memorySessionDigest" => MethodTarget::Reader,
 
         // Write email operations
         "indexBatch" | "removeBatch" | "optimize" | "clear"
-        | "rebuildEmbeddingsStart" | "rebuildEmbeddingsBatch" => MethodTarget::Writer,
+        | "rebuildEmbeddingsStart" | "rebuildEmbeddingsBatch" | "reembedMessage" | "reembedAllStart" | "reembedAllBatch"
+        | "reindexFtsFromContent" | "restoreBackup" | "updateMeta" | "recordStats"
+        | "reopenConnections" | "importDump" => MethodTarget::Writer,
 
         // Write memory operations
-        "memoryIndexBatch" | "memoryRemoveBatch" | "memoryClear" => MethodTarget::Writer,
+        "memoryIndexBatch" | "memoryRemoveBatch" | "memoryClear" | "memoryClearSession"
+        | "memoryReindex" | "memoryReindexFts" => MethodTarget::Writer,
+
+        // Handled inline on the main thread (no shared DB connection)
+        "benchmarkEmbedding" | "runtimeStats" | "reloadSynonyms" | "health" | "warmCache" | "validateQuery"
+        | "cancelStream" | "purgeStaleFiles" | "listMethods" => MethodTarget::Main,
+
+        // Answered on the main thread, then paged out by a dedicated thread
+        "searchStream" => MethodTarget::Stream,
+
+        "init" => MethodTarget::Init,
 
         _ => MethodTarget::Unknown,
     }
 }
 
+/// Post-init handler of `method` as reported by `listMethods` (None if not routed after init).
+fn method_target_name(method: &str) -> Option<&'static str> {
+    match classify_method(method) {
+        MethodTarget::Reader => Some("reader"),
+        MethodTarget::Writer => Some("writer"),
+        MethodTarget::Main => Some("main"),
+        MethodTarget::Init => Some("init"),
+        MethodTarget::Stream => Some("stream"),
+        MethodTarget::Unknown => None,
+    }
+}
+
 fn run_multi_threaded(
     state: DbState,
     mut in_stream: Stdin,
     out_stream: Stdout,
     mut message_count: u64,
+    runtime_stats: Arc<RuntimeStats>,
 ) -> anyhow::Result<()> {
     // Extract resources from init state
     let email_db_path = state.db_path.context("email DB path missing after init")?;
@@ -171,7 +261,17 @@ fn run_multi_threaded(
     let writer_email_conn = state.conn.context("email conn missing after init")?;
     let writer_memory_conn = state.memory_conn.context("memory conn missing after init")?;
     let engine: Option<Arc<EmbeddingEngine>> = state.embedding_engine.map(Arc::new);
-    let synonyms = Arc::new(state.synonyms);
+    let synonyms: SharedSynonyms = Arc::new(Mutex::new(Arc::new(state.synonyms)));
+    let synonyms_path = state.synonyms_path;
+    let detect_language = state.detect_language;
+    let wal_checkpoint_rows = state.wal_checkpoint_rows;
+    let low_priority_rebuilds = state.low_priority_rebuilds;
+    let auto_optimize = state.auto_optimize;
+    let idle_maintenance_secs = state.idle_maintenance_secs;
+    let idle_optimize = state.idle_optimize;
+    let method_timeouts = state.method_timeouts;
+    let wal_shutdown_mode = state.wal_shutdown_mode;
+    let result_cache_size = state.result_cache_size;
 
     // Open read-only connections for reader thread
     let reader_email_conn = crate::fts::db::open_read_only_connection(&email_db_path)?;
@@ -184,6 +284,15 @@ fn run_multi_threaded(
     let email_reopen = Arc::new(AtomicBool::new(false));
     let memory_reopen = Arc::new(AtomicBool::new(false));
 
+    // Writer completion count, for reads that ask for `freshness: "latest"`
+    let write_progress = Arc::new(WriteProgress::default());
+    // Per-database write counters that invalidate the reader's result cache
+    let write_generations = Arc::new(WriteGenerations::default());
+
+    // Time of the last request of any kind, for the writer's idle maintenance
+    let activity = Arc::new(ActivityClock::new());
+    let streams = Arc::new(SearchStreams::default());
+
     // Channels: main → reader, main → writer
     let (reader_tx, reader_rx) = mpsc::channel::<ThreadMessage>();
     let (writer_tx, writer_rx) = mpsc::channel::<ThreadMessage>();
@@ -197,6 +306,10 @@ fn run_multi_threaded(
         let memory_path = memory_db_path.clone();
         let email_reopen = Arc::clone(&email_reopen);
         let memory_reopen = Arc::clone(&memory_reopen);
+        let runtime_stats = Arc::clone(&runtime_stats);
+        let write_progress = Arc::clone(&write_progress);
+        let write_generations = Arc::clone(&write_generations);
+        let method_timeouts = method_timeouts.clone();
 
         std::thread::Builder::new()
             .name("fts-reader".to_string())
@@ -212,6 +325,11 @@ fn run_multi_threaded(
                     memory_path,
                     email_reopen,
                     memory_reopen,
+                    runtime_stats,
+                    write_progress,
+                    write_generations,
+                    method_timeouts,
+                    result_cache_size,
                 );
             })?
     };
@@ -224,6 +342,10 @@ fn run_multi_threaded(
         let memory_path = memory_db_path.clone();
         let email_reopen = Arc::clone(&email_reopen);
         let memory_reopen = Arc::clone(&memory_reopen);
+        let runtime_stats = Arc::clone(&runtime_stats);
+        let write_progress = Arc::clone(&write_progress);
+        let write_generations = Arc::clone(&write_generations);
+        let activity = Arc::clone(&activity);
 
         std::thread::Builder::new()
             .name("fts-writer".to_string())
@@ -233,11 +355,23 @@ fn run_multi_threaded(
                     writer_email_conn,
                     writer_memory_conn,
                     engine,
+                    detect_language,
+                    wal_checkpoint_rows,
+                    low_priority_rebuilds,
+                    auto_optimize,
+                    idle_maintenance_secs,
+                    idle_optimize,
                     stdout,
                     email_path,
                     memory_path,
                     email_reopen,
                     memory_reopen,
+                    runtime_stats,
+                    write_progress,
+                    write_generations,
+                    activity,
+                    method_timeouts,
+                    wal_shutdown_mode,
                 );
             })?
     };
@@ -245,6 +379,7 @@ fn run_multi_threaded(
     log::info!("Multi-threaded mode active: reader + writer threads spawned");
 
     // Main thread: stdin dispatch loop
+    let mut writes_dispatched: u64 = 0;
     loop {
         let req = match native_messaging::read_message(&mut in_stream) {
             Ok(Some(r)) => r,
@@ -259,6 +394,7 @@ fn run_multi_threaded(
         };
 
         message_count += 1;
+        activity.touch();
         log::info!(
             "Dispatching message #{}: {} (id: {})",
             message_count,
@@ -270,6 +406,7 @@ fn run_multi_threaded(
             method: req.method.clone(),
             id: req.id.clone(),
             params: req.params,
+            write_seq: writes_dispatched,
         };
 
         match classify_method(&req.method) {
@@ -280,16 +417,58 @@ fn run_multi_threaded(
                 }
             }
             MethodTarget::Writer => {
+                if is_deferred_ack(&msg.method, &msg.params) {
+                    // Ack before handing off, so a later indexError can never overtake it.
+                    let queued = serde_json::json!({ "id": msg.id, "result": { "queued": true } });
+                    write_response(&shared_stdout, "main", &msg.method, &msg.id, Ok(queued));
+                }
+                write_progress.mark_dispatched();
                 if writer_tx.send(msg).is_err() {
                     log::error!("Writer thread channel closed");
                     break;
                 }
+                writes_dispatched += 1;
+            }
+            MethodTarget::Main => {
+                let resp = handle_main_request(
+                    engine.as_deref(),
+                    &email_db_path,
+                    &runtime_stats,
+                    &synonyms,
+                    &streams,
+                    synonyms_path.as_deref(),
+                    &msg.method,
+                    &msg.id,
+                    &msg.params,
+                );
+                if resp.is_err() {
+                    RuntimeStats::add(&runtime_stats.errors, 1);
+                }
+                write_response(&shared_stdout, "main", &msg.method, &msg.id, resp);
+            }
+            MethodTarget::Stream => {
+                start_search_stream(&streams, &email_db_path, &synonyms, &shared_stdout, &runtime_stats, &msg);
+            }
+            MethodTarget::Init => {
+                log::warn!("Ignoring repeated init (id: {}): already initialized", req.id);
+                let err = serde_json::json!({
+                    "id": req.id,
+                    "error": {
+                        "code": "alreadyInitialized",
+                        "message": "init was already called for this session; restart the host to re-initialize",
+                        "dbPath": email_db_path.to_string_lossy(),
+                        "memoryDbPath": memory_db_path.to_string_lossy()
+                    }
+                });
+                let mut out = shared_stdout.lock().unwrap();
+                let _ = native_messaging::write_json(&mut *out, &protocol::shape_response(err));
             }
             MethodTarget::Unknown => {
+                log::warn!("Unknown method {} (id: {})", req.method, req.id);
                 let err =
                     serde_json::json!({ "id": req.id, "error": format!("Unknown method: {}", req.method) });
                 let mut out = shared_stdout.lock().unwrap();
-                let _ = native_messaging::write_json(&mut *out, &err);
+                let _ = native_messaging::write_json(&mut *out, &protocol::shape_response(err));
             }
         }
     }
@@ -308,56 +487,151 @@ fn run_multi_threaded(
 // Reader thread
 // ============================================================================
 
+#[allow(clippy::too_many_arguments)]
 fn reader_thread_main(
     rx: mpsc::Receiver<ThreadMessage>,
     mut email_conn: Connection,
     mut memory_conn: Connection,
     engine: Option<Arc<EmbeddingEngine>>,
-    synonyms: Arc<SynonymLookup>,
+    synonyms: SharedSynonyms,
     stdout: Arc<Mutex<Stdout>>,
     email_db_path: PathBuf,
     memory_db_path: PathBuf,
     email_reopen: Arc<AtomicBool>,
     memory_reopen: Arc<AtomicBool>,
+    runtime_stats: Arc<RuntimeStats>,
+    write_progress: Arc<WriteProgress>,
+    write_generations: Arc<WriteGenerations>,
+    method_timeouts: methods::MethodTimeouts,
+    result_cache_size: usize,
 ) {
     log::info!("[reader] Thread started");
 
+    // Search mode last announced to the extension. Starts at the mode the session was
+    // initialized with, so only a later change (e.g. vec table emptied by a rebuild) is sent.
+    let mut announced_mode = if engine.is_some() { SearchMode::Hybrid } else { SearchMode::Fts };
+    let mut result_cache = ResultCache::new(result_cache_size);
+    // Lookup the cached results were computed with; a reloadSynonyms replaces it.
+    let mut cached_synonyms: Arc<SynonymLookup> = Arc::clone(&synonyms.lock().unwrap());
+
     while let Ok(msg) = rx.recv() {
+        log::debug!("[reader] Handling {} (id: {})", msg.method, msg.id);
+
+        match msg.params.get("freshness").and_then(|v| v.as_str()) {
+            None | Some("any") => {}
+            Some("latest") => {
+                // Read-your-writes: wait until every write dispatched before this request has
+                // finished. Costs up to one writer batch of latency (capped), so it is opt-in.
+                let wait = std::time::Duration::from_millis(config::dispatch::FRESHNESS_WAIT_MAX_MS);
+                if !write_progress.wait_for(msg.write_seq, wait) {
+                    log::warn!(
+                        "[reader] freshness=latest: writes still pending after {}ms, serving {} (id: {}) anyway",
+                        config::dispatch::FRESHNESS_WAIT_MAX_MS,
+                        msg.method,
+                        msg.id
+                    );
+                }
+            }
+            Some(other) => {
+                let err = anyhow::anyhow!("Invalid freshness '{}' (expected \"latest\" or \"any\")", other);
+                RuntimeStats::add(&runtime_stats.errors, 1);
+                write_response(&stdout, "reader", &msg.method, &msg.id, Err(err));
+                continue;
+            }
+        }
+
         // Check if writer signaled us to reopen after clear
         if email_reopen.compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
-            log::info!("[reader] Reopening email read-only connection after clear");
+            log::info!("[reader] Reopening email read-only connection after clear (before {})", msg.method);
             match crate::fts::db::open_read_only_connection(&email_db_path) {
                 Ok(new_conn) => email_conn = new_conn,
-                Err(e) => log::error!("[reader] Failed to reopen email conn: {:?}", e),
+                Err(e) => log::error!("[reader] Failed to reopen email conn (before {}): {:?}", msg.method, e),
             }
         }
         if memory_reopen.compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
-            log::info!("[reader] Reopening memory read-only connection after clear");
+            log::info!("[reader] Reopening memory read-only connection after clear (before {})", msg.method);
             match memory_db::open_read_only_memory_connection(&memory_db_path) {
                 Ok(new_conn) => memory_conn = new_conn,
-                Err(e) => log::error!("[reader] Failed to reopen memory conn: {:?}", e),
+                Err(e) => log::error!("[reader] Failed to reopen memory conn (before {}): {:?}", msg.method, e),
+            }
+        }
+
+        // Backstop for a missed reopen flag: reopen if our view keeps disagreeing with the writer's.
+        if let Some(generation) = runtime_stats.reader_consistency.pending() {
+            let mark = crate::fts::db::high_water_mark(&email_conn).unwrap_or(-1);
+            if runtime_stats.reader_consistency.check(generation, mark) {
+                log::warn!("[reader] Read-only email conn diverged from the writer, reopening (before {})", msg.method);
+                match crate::fts::db::open_read_only_connection(&email_db_path) {
+                    Ok(new_conn) => {
+                        email_conn = new_conn;
+                        runtime_stats.reader_consistency.reopened();
+                    }
+                    Err(e) => log::error!("[reader] Failed to reopen email conn (before {}): {:?}", msg.method, e),
+                }
             }
         }
 
         let engine_ref = engine.as_deref();
-        let resp = handle_read_request(
-            &email_conn,
-            &memory_conn,
-            &email_db_path,
-            &memory_db_path,
-            engine_ref,
-            &synonyms,
-            &msg.method,
-            &msg.id,
-            &msg.params,
-        );
+        // Snapshot the current lookup; a concurrent reloadSynonyms applies from the next request.
+        let synonyms_now: Arc<SynonymLookup> = Arc::clone(&synonyms.lock().unwrap());
+        if !Arc::ptr_eq(&synonyms_now, &cached_synonyms) {
+            result_cache.clear();
+            cached_synonyms = Arc::clone(&synonyms_now);
+        }
+        let mut search_mode: Option<SearchMode> = None;
+        let resp = match result_cache.get(&write_generations, &msg.method, &msg.params) {
+            Some(Ok(result)) => {
+                log::debug!("[reader] {} (id: {}) served from result cache", msg.method, msg.id);
+                RuntimeStats::add(&runtime_stats.result_cache_hits, 1);
+                Ok(serde_json::json!({ "id": msg.id, "result": result }))
+            }
+            lookup => {
+                let interrupts = vec![email_conn.get_interrupt_handle(), memory_conn.get_interrupt_handle()];
+                let resp = run_with_budget(&method_timeouts, interrupts, "reader", &msg, &runtime_stats, || {
+                    handle_read_request(
+                        &email_conn,
+                        &memory_conn,
+                        &email_db_path,
+                        &memory_db_path,
+                        engine_ref,
+                        &synonyms_now,
+                        &mut search_mode,
+                        &runtime_stats,
+                        &msg.method,
+                        &msg.id,
+                        &msg.params,
+                    )
+                });
+                if let Some(Err(lookup)) = lookup {
+                    RuntimeStats::add(&runtime_stats.result_cache_misses, 1);
+                    // Only real results; a search timeout is an Ok response carrying `error`.
+                    if let Some(result) = resp.as_ref().ok().and_then(|r| r.get("result")) {
+                        result_cache.store(lookup, result.clone());
+                    }
+                }
+                resp
+            }
+        };
 
-        write_response(&stdout, &msg.id, resp);
+        match &resp {
+            Ok(_) if matches!(msg.method.as_str(), "search" | "memorySearch") => {
+                RuntimeStats::add(&runtime_stats.searches, 1)
+            }
+            Ok(_) => {}
+            Err(_) => RuntimeStats::add(&runtime_stats.errors, 1),
+        }
+        if let Some(mode) = search_mode.filter(|m| *m != announced_mode) {
+            log::info!("[reader] Search mode changed: {} -> {}", announced_mode.as_str(), mode.as_str());
+            announced_mode = mode;
+            write_notification(&stdout, "searchModeChanged", serde_json::json!({ "mode": mode.as_str() }));
+        }
+        write_response(&stdout, "reader", &msg.method, &msg.id, resp);
     }
 
     log::info!("[reader] Thread stopped (channel closed)");
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_read_request(
     email_conn: &Connection,
     memory_conn: &Connection,
@@ -365,6 +639,8 @@ fn handle_read_request(
     memory_db_path: &Path,
     engine: Option<&EmbeddingEngine>,
     synonyms: &SynonymLookup,
+    search_mode: &mut Option<SearchMode>,
+    runtime_stats: &RuntimeStats,
     method: &str,
     msg_id: &str,
     params: &Value,
@@ -376,8 +652,56 @@ fn handle_read_request(
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
                 .to_string();
-            let results = crate::fts::db::search(email_conn, &q, params, synonyms, engine)?;
-            Ok(serde_json::json!({ "id": msg_id, "result": results }))
+            // Optional per-query budget; absent or 0 means no timeout.
+            let timeout_ms = params
+                .get("timeoutMs")
+                .and_then(|v| v.as_u64())
+                .filter(|&ms| ms > 0);
+            let Some(timeout_ms) = timeout_ms else {
+                let (results, mode) = crate::fts::db::search(email_conn, &q, params, synonyms, engine)?;
+                *search_mode = mode;
+                return Ok(serde_json::json!({ "id": msg_id, "result": results }));
+            };
+
+            let watchdog = QueryWatchdog::start(email_conn, timeout_ms);
+            let outcome = crate::fts::db::search(email_conn, &q, params, synonyms, engine);
+            let fired = watchdog.finish();
+            match outcome {
+                Ok((results, mode)) => {
+                    *search_mode = mode;
+                    Ok(serde_json::json!({ "id": msg_id, "result": results }))
+                }
+                Err(e) if fired => {
+                    log::warn!("Search timed out after {}ms (q={:?}): {}", timeout_ms, q, e);
+                    Ok(serde_json::json!({
+                        "id": msg_id,
+                        "error": {
+                            "code": "timeout",
+                            "message": format!("Search exceeded timeoutMs={timeout_ms}")
+                        }
+                    }))
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "searchMulti" => {
+            let queries: Vec<String> = params
+                .get("queries")
+                .and_then(|v| v.as_array())
+                .context("queries parameter is required and must be an array of strings")?
+                .iter()
+                .map(|q| q.as_str().map(str::to_string).context("queries must be an array of strings"))
+                .collect::<anyhow::Result<_>>()?;
+            let (result, mode) = crate::fts::db::search_multi(email_conn, &queries, params, synonyms, engine)?;
+            if mode.is_some() {
+                *search_mode = mode;
+            }
+            Ok(serde_json::json!({ "id": msg_id, "result": result }))
+        }
+        "searchExplainPlan" => {
+            let q = params.get("q").and_then(|v| v.as_str()).unwrap_or("");
+            let res = crate::fts::db::explain_search_plan(email_conn, q, params, synonyms, engine.is_some())?;
+            Ok(serde_json::json!({ "id": msg_id, "result": res }))
         }
         "stats" => {
             let docs = crate::fts::db::db_count(email_conn)?;
@@ -391,13 +715,71 @@ fn handle_read_request(
                 "result": { "ok": true, "docs": docs, "vecDocs": vec_docs, "dbBytes": db_bytes }
             }))
         }
+        "getSchema" => {
+            let schema = crate::fts::db::get_schema(email_conn)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": schema }))
+        }
+        "statsHistory" => {
+            let limit = params
+                .get("limit")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(config::sqlite::STATS_HISTORY_DEFAULT_LIMIT);
+            let snapshots = crate::fts::db::stats_history(email_conn, limit)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "snapshots": snapshots } }))
+        }
+        "subjectSearch" => {
+            let q = params.get("q").and_then(|v| v.as_str()).unwrap_or("");
+            let limit = params
+                .get("limit")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(config::sqlite::SEARCH_DEFAULT_LIMIT)
+                .clamp(1, config::sqlite::SUBJECT_SEARCH_MAX_LIMIT);
+            let results = crate::fts::db::subject_search(email_conn, q, params, limit)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": results }))
+        }
+        "searchSuggestQueries" => {
+            let count = params
+                .get("count")
+                .and_then(|v| v.as_u64())
+                .map(|n| (n as usize).min(config::suggest::MAX_COUNT))
+                .unwrap_or(config::suggest::DEFAULT_COUNT);
+            let suggestions = crate::fts::db::suggest_queries(email_conn, count)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "suggestions": suggestions } }))
+        }
+        "parseIcs" => {
+            // Raw ICS text, or the stored parsedIcsAttachments of an indexed message.
+            let text = match (params.get("ics").and_then(|v| v.as_str()), params.get("msgId").and_then(|v| v.as_str())) {
+                (Some(ics), _) => ics.to_string(),
+                (None, Some(target)) => crate::fts::db::get_parsed_ics(email_conn, target)?
+                    .with_context(|| format!("Message not found: {target}"))?,
+                (None, None) => anyhow::bail!("parseIcs requires msgId or ics"),
+            };
+            let events = crate::ics::parse_events(&text)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "events": events } }))
+        }
+        "verifyIntegrity" => {
+            // Problems are prefixed with the database they were found in.
+            let mut problems = Vec::new();
+            for (name, conn) in [("email", email_conn), ("memory", memory_conn)] {
+                problems.extend(crate::fts::db::integrity_check(conn)?.into_iter().map(|p| format!("{name}: {p}")));
+            }
+            runtime_stats.set_integrity_ok(problems.is_empty());
+            if !problems.is_empty() {
+                log::error!("Integrity check found {} problems: {:?}", problems.len(), problems);
+            }
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "integrityOk": problems.is_empty(), "problems": problems }
+            }))
+        }
         "filterNewMessages" => {
             let rows = params
                 .get("rows")
                 .and_then(|v| v.as_array())
                 .cloned()
                 .unwrap_or_default();
-            let result = crate::fts::db::filter_new_messages(email_conn, &rows)?;
+            let echo_rows = params.get("echoNewRows").and_then(|v| v.as_bool()).unwrap_or(false);
+            let result = crate::fts::db::filter_new_messages(email_conn, &rows, echo_rows)?;
             Ok(serde_json::json!({ "id": msg_id, "result": result }))
         }
         "getMessageByMsgId" => {
@@ -410,15 +792,21 @@ fn handle_read_request(
             Ok(serde_json::json!({ "id": msg_id, "result": res }))
         }
         "findByHeaderMessageId" => {
-            let account_id = params
-                .get("accountId")
-                .and_then(|v| v.as_str())
-                .context("accountId parameter is required")?;
+            let any_account = params.get("anyAccount").and_then(|v| v.as_bool()).unwrap_or(false);
+            let account_id = if any_account {
+                None
+            } else {
+                Some(
+                    params
+                        .get("accountId")
+                        .and_then(|v| v.as_str())
+                        .context("accountId parameter is required (or pass anyAccount: true)")?,
+                )
+            };
             let header_message_id = params
                 .get("headerMessageId")
                 .and_then(|v| v.as_str())
                 .context("headerMessageId parameter is required")?;
-            log::info!("Finding by headerMessageId: {} (account={})", header_message_id, account_id);
             let res = crate::fts::db::find_by_header_message_id(email_conn, account_id, header_message_id)?;
             Ok(serde_json::json!({ "id": msg_id, "result": res }))
         }
@@ -429,11 +817,13 @@ fn handle_read_request(
                 .get("limit")
                 .and_then(|v| v.as_i64())
                 .unwrap_or(config::sqlite::QUERY_BY_DATE_RANGE_DEFAULT_LIMIT);
-            let res = crate::fts::db::query_by_date_range(email_conn, from_v, to_v, limit)?;
+            let inclusive = crate::fts::db::DateInclusivity::from_params(params)?;
+            let tz = crate::fts::db::DateTz::from_params(params)?;
+            let res = crate::fts::db::query_by_date_range(email_conn, from_v, to_v, limit, inclusive, tz)?;
             Ok(serde_json::json!({ "id": msg_id, "result": res }))
         }
         "debugSample" => {
-            let res = crate::fts::db::debug_sample(email_conn)?;
+            let res = crate::fts::db::debug_sample(email_conn, params)?;
             Ok(serde_json::json!({ "id": msg_id, "result": res }))
         }
         "memorySearch" => {
@@ -445,6 +835,43 @@ fn handle_read_request(
             let results = memory_db::memory_search(memory_conn, &q, params, synonyms, engine)?;
             Ok(serde_json::json!({ "id": msg_id, "result": results }))
         }
+        "exportDump" => {
+            let path = params
+                .get("path")
+                .and_then(|v| v.as_str())
+                .context("path parameter is required and must be a string")?;
+            let count = crate::fts::dump::export_dump(email_conn, Path::new(path))?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "path": path, "count": count } }))
+        }
+        "exportEmbeddings" => {
+            let path = params
+                .get("path")
+                .and_then(|v| v.as_str())
+                .context("path parameter is required and must be a string")?;
+            let format = crate::fts::dump::EmbeddingsFormat::from_param(params.get("format"))?;
+            let count = crate::fts::dump::export_embeddings(email_conn, Path::new(path), format)?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "path": path, "format": format.as_str(), "count": count }
+            }))
+        }
+        "backup" => {
+            let path = params
+                .get("path")
+                .and_then(|v| v.as_str())
+                .context("path parameter is required and must be a string")?;
+            let target = params.get("target").and_then(|v| v.as_str()).unwrap_or("email");
+            let conn = match target {
+                "email" => email_conn,
+                "memory" => memory_conn,
+                other => bail!("Invalid backup target '{}' (expected \"email\" or \"memory\")", other),
+            };
+            let bytes = crate::fts::db::vacuum_into(conn, Path::new(path))?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "target": target, "path": path, "bytes": bytes }
+            }))
+        }
         "memoryStats" => {
             let docs = memory_db::memory_db_count(memory_conn)?;
             let vec_docs = memory_db::memory_vec_count(memory_conn);
@@ -473,8 +900,33 @@ fn handle_read_request(
                     serde_json::json!({ "id": msg_id, "error": "Missing or invalid timestampMs parameter" }),
                 );
             }
-            let results = memory_db::memory_read_by_timestamp(memory_conn, timestamp_ms, tolerance_ms)?;
-            Ok(serde_json::json!({ "id": msg_id, "result": results }))
+            let limit = params
+                .get("limit")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(config::sqlite::MEMORY_READ_DEFAULT_LIMIT)
+                .clamp(1, config::sqlite::MEMORY_READ_MAX_LIMIT);
+            let (results, truncated) =
+                memory_db::memory_read_by_timestamp(memory_conn, timestamp_ms, tolerance_ms, limit)?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "entries": results, "truncated": truncated }
+            }))
+        }
+        "memorySessionDigest" => {
+            let session_id = params
+                .get("sessionId")
+                .and_then(|v| v.as_str())
+                .context("Missing sessionId parameter")?;
+            // Centrality averages stored embeddings; only meaningful when the engine keeps them current.
+            let central_turns = match engine {
+                Some(_) => params
+                    .get("centralTurns")
+                    .and_then(|v| v.as_u64())
+                    .map_or(0, |n| (n as usize).min(config::sqlite::MEMORY_DIGEST_MAX_CENTRAL_TURNS)),
+                None => 0,
+            };
+            let digest = memory_db::memory_session_digest(memory_conn, session_id, central_turns)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": digest }))
         }
         _ => Ok(serde_json::json!({ "id": msg_id, "error": format!("Unknown reader method: {method}") })),
     }
@@ -484,52 +936,223 @@ fn handle_read_request(
 // Writer thread
 // ============================================================================
 
+#[allow(clippy::too_many_arguments)]
 fn writer_thread_main(
     rx: mpsc::Receiver<ThreadMessage>,
     mut email_conn: Connection,
     mut memory_conn: Connection,
     engine: Option<Arc<EmbeddingEngine>>,
+    detect_language: bool,
+    wal_checkpoint_rows: u64,
+    low_priority_rebuilds: bool,
+    auto_optimize: AutoOptimize,
+    idle_maintenance_secs: u64,
+    idle_optimize: bool,
     stdout: Arc<Mutex<Stdout>>,
     email_db_path: PathBuf,
     memory_db_path: PathBuf,
     email_reopen: Arc<AtomicBool>,
     memory_reopen: Arc<AtomicBool>,
+    runtime_stats: Arc<RuntimeStats>,
+    write_progress: Arc<WriteProgress>,
+    write_generations: Arc<WriteGenerations>,
+    activity: Arc<ActivityClock>,
+    method_timeouts: methods::MethodTimeouts,
+    wal_shutdown_mode: WalShutdownMode,
 ) {
     log::info!("[writer] Thread started");
+    // Rows committed by indexBatch since the last writer-driven WAL checkpoint
+    let mut rows_since_checkpoint: u64 = 0;
+    // Rows inserted + removed since the FTS index was last optimized
+    let mut rows_since_optimize: u64 = 0;
+    // Writes handled since idle maintenance last ran (nothing to tidy otherwise)
+    let mut dirty = false;
+    let idle_after = (idle_maintenance_secs > 0).then(|| std::time::Duration::from_secs(idle_maintenance_secs));
 
-    while let Ok(msg) = rx.recv() {
+    loop {
+        let msg = match idle_after {
+            None => match rx.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+            Some(idle_after) => match rx.recv_timeout(idle_after) {
+                Ok(msg) => msg,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    // Reads count as activity too: wait until the whole helper has been quiet.
+                    if dirty && activity.idle_for() >= idle_after {
+                        run_idle_maintenance(&email_conn, &memory_conn, idle_optimize && rows_since_optimize > 0);
+                        if idle_optimize {
+                            rows_since_optimize = 0;
+                        }
+                        dirty = false;
+                    }
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            },
+        };
+        dirty = true;
+        log::debug!("[writer] Handling {} (id: {})", msg.method, msg.id);
         let engine_ref = engine.as_deref();
-        let resp = handle_write_request(
-            &mut email_conn,
-            &mut memory_conn,
-            &email_db_path,
-            &memory_db_path,
-            engine_ref,
-            &email_reopen,
-            &memory_reopen,
-            &msg.method,
-            &msg.id,
-            &msg.params,
-        );
+        // Restored when the request finishes, so indexBatch between rebuild batches runs at normal priority.
+        let _priority = (low_priority_rebuilds && is_rebuild_method(&msg.method))
+            .then(thread_priority::BackgroundPriority::enter);
+        let interrupts = vec![email_conn.get_interrupt_handle(), memory_conn.get_interrupt_handle()];
+        let mut resp = run_with_budget(&method_timeouts, interrupts, "writer", &msg, &runtime_stats, || {
+            handle_write_request(
+                &mut email_conn,
+                &mut memory_conn,
+                &email_db_path,
+                &memory_db_path,
+                engine_ref,
+                detect_language,
+                &email_reopen,
+                &memory_reopen,
+                &runtime_stats,
+                &write_progress,
+                &msg.method,
+                &msg.id,
+                &msg.params,
+            )
+        });
+
+        if resp.is_err() {
+            RuntimeStats::add(&runtime_stats.errors, 1);
+        } else if !msg.method.starts_with("memory") {
+            match crate::fts::db::high_water_mark(&email_conn) {
+                Ok(mark) => runtime_stats.reader_consistency.publish(mark),
+                Err(e) => log::warn!("[writer] Failed to read high-water mark after {}: {:?}", msg.method, e),
+            }
+        }
+        if wal_checkpoint_rows > 0 && msg.method == "indexBatch" {
+            if let Ok(r) = &resp {
+                rows_since_checkpoint += r["result"]["count"].as_u64().unwrap_or(0);
+            }
+            if rows_since_checkpoint >= wal_checkpoint_rows {
+                match crate::fts::db::wal_checkpoint_passive(&email_conn) {
+                    Ok((busy, log_frames, done)) => log::info!(
+                        "[writer] WAL checkpoint after {} rows: {}/{} frames checkpointed (busy={})",
+                        rows_since_checkpoint,
+                        done,
+                        log_frames,
+                        busy
+                    ),
+                    Err(e) => log::warn!("[writer] WAL checkpoint failed: {:?}", e),
+                }
+                rows_since_checkpoint = 0;
+            }
+        }
+        match (msg.method.as_str(), &mut resp) {
+            ("indexBatch" | "removeBatch", Ok(r)) => {
+                rows_since_optimize += r["result"]["count"].as_u64().unwrap_or(0);
+                if auto_optimize == AutoOptimize::Advisory
+                    && rows_since_optimize >= config::sqlite::AUTO_OPTIMIZE_EVERY_ROWS
+                {
+                    r["result"]["optimizeRecommended"] = Value::Bool(true);
+                }
+            }
+            ("optimize" | "clear" | "restoreBackup", Ok(_)) => rows_since_optimize = 0,
+            _ => {}
+        }
+        // Committed (or failed) either way: release readers waiting on this write, and drop
+        // cached results computed before it.
+        write_generations.bump(&msg.method);
+        write_progress.mark_done();
+        if is_deferred_ack(&msg.method, &msg.params) {
+            // Already acked with { queued: true }; only failures are reported, as a notification:
+            // the whole batch (`error`) or the rows it skipped (`failed`).
+            match resp {
+                Err(e) => {
+                    log::error!("[writer] Deferred indexBatch failed (id: {}): {:?}", msg.id, e);
+                    write_notification(&stdout, "indexError", serde_json::json!({ "id": msg.id, "error": error_value(&e) }));
+                }
+                Ok(r) => {
+                    let failed = &r["result"]["failed"];
+                    if failed.as_array().is_some_and(|f| !f.is_empty()) {
+                        log::warn!("[writer] Deferred indexBatch skipped failed rows (id: {})", msg.id);
+                        write_notification(&stdout, "indexError", serde_json::json!({ "id": msg.id, "failed": failed }));
+                    }
+                }
+            }
+        } else {
+            write_response(&stdout, "writer", &msg.method, &msg.id, resp);
+        }
 
-        write_response(&stdout, &msg.id, resp);
+        // After responding, so the batch that crosses the threshold isn't held up by the merge.
+        if auto_optimize == AutoOptimize::Auto && rows_since_optimize >= config::sqlite::AUTO_OPTIMIZE_EVERY_ROWS {
+            let start = std::time::Instant::now();
+            match crate::fts::db::optimize(&email_conn) {
+                Ok(()) => log::info!(
+                    "[writer] Auto-optimized FTS index after {} rows in {}ms",
+                    rows_since_optimize,
+                    start.elapsed().as_millis()
+                ),
+                Err(e) => log::warn!("[writer] Auto-optimize failed: {:?}", e),
+            }
+            rows_since_optimize = 0;
+        }
+    }
+
+    for (name, conn) in [("email", &email_conn), ("memory", &memory_conn)] {
+        let start = std::time::Instant::now();
+        match crate::fts::db::shutdown_checkpoint(conn, wal_shutdown_mode) {
+            Ok(Some((busy, log_frames, checkpointed))) => log::info!(
+                "[writer] Shutdown {:?} checkpoint of {} WAL: {}/{} frames (busy={}) in {}ms",
+                wal_shutdown_mode,
+                name,
+                checkpointed,
+                log_frames,
+                busy,
+                start.elapsed().as_millis()
+            ),
+            Ok(None) => log::info!("[writer] {} WAL left as is on shutdown", name),
+            Err(e) => log::warn!("[writer] Shutdown checkpoint of {} WAL failed: {:?}", name, e),
+        }
     }
 
     log::info!("[writer] Thread stopped (channel closed)");
 }
 
+/// Quiet-period housekeeping: PASSIVE-checkpoint both WALs and optionally optimize the email
+/// FTS index. Failures are logged; the next idle period retries.
+fn run_idle_maintenance(email_conn: &Connection, memory_conn: &Connection, optimize: bool) {
+    let start = std::time::Instant::now();
+    for (name, conn) in [("email", email_conn), ("memory", memory_conn)] {
+        if let Err(e) = crate::fts::db::wal_checkpoint_passive(conn) {
+            log::warn!("[writer] Idle {} WAL checkpoint failed: {:?}", name, e);
+        }
+    }
+    if optimize {
+        if let Err(e) = crate::fts::db::optimize(email_conn) {
+            log::warn!("[writer] Idle optimize failed: {:?}", e);
+        }
+    }
+    log::info!(
+        "[writer] Idle maintenance done in {}ms (checkpoint{})",
+        start.elapsed().as_millis(),
+        if optimize { " + optimize" } else { "" }
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_write_request(
     email_conn: &mut Connection,
     memory_conn: &mut Connection,
     email_db_path: &Path,
     memory_db_path: &Path,
     engine: Option<&EmbeddingEngine>,
+    detect_language: bool,
     email_reopen: &AtomicBool,
     memory_reopen: &AtomicBool,
+    runtime_stats: &RuntimeStats,
+    write_progress: &WriteProgress,
     method: &str,
     msg_id: &str,
     params: &Value,
 ) -> anyhow::Result<Value> {
+    // Rebuild batches give way to writes queued behind them (see `run_yielding`).
+    let yield_to_writes = params.get("yieldToWrites").and_then(|v| v.as_bool()).unwrap_or(true);
+    let writes_waiting = || yield_to_writes && write_progress.queued_behind_current() > 0;
     match method {
         "indexBatch" => {
             let rows = params
@@ -537,11 +1160,23 @@ fn handle_write_request(
                 .and_then(|v| v.as_array())
                 .cloned()
                 .unwrap_or_default();
-            let (count, skipped) = crate::fts::db::index_batch(email_conn, &rows, engine)?;
-            Ok(serde_json::json!({
-                "id": msg_id,
-                "result": { "ok": true, "count": count, "skippedDuplicates": skipped }
-            }))
+            let dedupe_content = params.get("dedupeContent").and_then(|v| v.as_bool()).unwrap_or(false);
+            let (count, skipped, deduped, failed) =
+                crate::fts::db::index_batch(email_conn, &rows, engine, detect_language, dedupe_content)?;
+            RuntimeStats::add(&runtime_stats.messages_indexed, count.max(0) as u64);
+            let mut result = serde_json::json!({
+                "ok": true, "count": count, "skippedDuplicates": skipped, "dedupedByContent": deduped, "failed": failed
+            });
+            if params.get("validateEncoding").and_then(|v| v.as_bool()).unwrap_or(false) {
+                // Rows are indexed either way; these are reported so the extension can re-fetch them.
+                let suspect = crate::fts::db::suspect_encoding_ids(&rows);
+                if !suspect.is_empty() {
+                    log::warn!("indexBatch: {} rows look mis-decoded (U+FFFD-heavy)", suspect.len());
+                }
+                result["suspectEncoding"] = serde_json::json!(suspect.len());
+                result["suspectEncodingIds"] = serde_json::json!(suspect);
+            }
+            Ok(serde_json::json!({ "id": msg_id, "result": result }))
         }
         "removeBatch" => {
             let ids = params
@@ -549,13 +1184,55 @@ fn handle_write_request(
                 .and_then(|v| v.as_array())
                 .cloned()
                 .unwrap_or_default();
-            let removed = crate::fts::db::remove_batch(email_conn, &ids)?;
-            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "count": removed } }))
+            let (removed, not_found) = crate::fts::db::remove_batch(email_conn, &ids)?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "count": removed.len(), "removedIds": removed, "notFoundIds": not_found }
+            }))
         }
         "optimize" => {
             crate::fts::db::optimize(email_conn)?;
+            // Snapshot after optimize so stats_history tracks growth without a separate schedule.
+            if let Err(e) = crate::fts::db::record_stats(email_conn, email_db_path) {
+                log::warn!("Failed to record stats snapshot after optimize: {}", e);
+            }
             Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true } }))
         }
+        "importDump" => {
+            let path = params
+                .get("path")
+                .and_then(|v| v.as_str())
+                .context("path parameter is required and must be a string")?;
+            let skip_embeddings = params.get("skipEmbeddings").and_then(|v| v.as_bool()).unwrap_or(false);
+            let (count, skipped, embeddings, failed) =
+                crate::fts::dump::import_dump(email_conn, Path::new(path), skip_embeddings, detect_language)?;
+            RuntimeStats::add(&runtime_stats.messages_indexed, count.max(0) as u64);
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "count": count, "skippedDuplicates": skipped, "embeddings": embeddings, "failed": failed }
+            }))
+        }
+        "reopenConnections" => {
+            // Manual recovery after out-of-band changes to the DB files. Open the new
+            // connections first so a failure leaves the current ones in place.
+            let new_email = crate::fts::db::reopen_db(email_db_path)?;
+            let new_memory = memory_db::reopen_memory_db(memory_db_path)?;
+            *email_conn = new_email;
+            *memory_conn = new_memory;
+            email_reopen.store(true, Ordering::SeqCst);
+            memory_reopen.store(true, Ordering::SeqCst);
+            let docs = crate::fts::db::db_count(email_conn)?;
+            let memory_docs = memory_db::memory_db_count(memory_conn)?;
+            log::info!("Reopened connections: {} email docs, {} memory entries", docs, memory_docs);
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "docs": docs, "memoryDocs": memory_docs }
+            }))
+        }
+        "recordStats" => {
+            let snapshot = crate::fts::db::record_stats(email_conn, email_db_path)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "snapshot": snapshot } }))
+        }
         "clear" => {
             // Swap connection with a temporary in-memory one, clear + rebuild, swap back
             let old_conn = std::mem::replace(email_conn, Connection::open_in_memory()?);
@@ -579,21 +1256,105 @@ fn handle_write_request(
             let last_rowid = params.get("lastRowid").and_then(|v| v.as_i64()).unwrap_or(0);
             let batch_size = params.get("batchSize").and_then(|v| v.as_i64()).unwrap_or(500);
             let eng = engine.context("Embedding engine not available — cannot rebuild embeddings")?;
-            let (new_last, processed, embedded, done) = match target {
-                "memory" => {
-                    memory_db::rebuild_memory_embeddings_batch(memory_conn, eng, last_rowid, batch_size)?
-                }
-                _ => crate::fts::db::rebuild_embeddings_batch(email_conn, eng, last_rowid, batch_size)?,
-            };
+            let mut embedded = 0;
+            let (new_last, processed, done, yielded) =
+                run_yielding(last_rowid, batch_size, writes_waiting, |last, rows| {
+                    let (new_last, processed, chunk_embedded, done) = match target {
+                        "memory" => memory_db::rebuild_memory_embeddings_batch(memory_conn, eng, last, rows)?,
+                        _ => crate::fts::db::rebuild_embeddings_batch(email_conn, eng, last, rows)?,
+                    };
+                    embedded += chunk_embedded;
+                    Ok((new_last, processed, done))
+                })?;
             Ok(serde_json::json!({
                 "id": msg_id,
                 "result": {
                     "ok": true, "target": target,
                     "lastRowid": new_last, "processed": processed,
-                    "embedded": embedded, "done": done
+                    "embedded": embedded, "done": done, "yielded": yielded
+                }
+            }))
+        }
+        "reembedAllStart" => {
+            engine.context("Embedding engine not available — cannot re-embed")?;
+            let total = crate::fts::db::reembed_all_start(email_conn)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "total": total } }))
+        }
+        "reembedAllBatch" => {
+            let last_rowid = params.get("lastRowid").and_then(|v| v.as_i64()).unwrap_or(0);
+            let batch_size = params.get("batchSize").and_then(|v| v.as_i64()).unwrap_or(500);
+            let eng = engine.context("Embedding engine not available — cannot re-embed")?;
+            let (mut embedded, mut cache_hits, mut failed, mut remaining) = (0, 0, 0, 0);
+            let (new_last, processed, done, yielded) =
+                run_yielding(last_rowid, batch_size, writes_waiting, |last, rows| {
+                    let p = crate::fts::db::reembed_all_batch(email_conn, eng, last, rows)?;
+                    embedded += p.embedded;
+                    cache_hits += p.cache_hits;
+                    failed += p.failed;
+                    remaining = p.remaining;
+                    Ok((p.last_rowid, p.processed, p.done))
+                })?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": {
+                    "ok": true,
+                    "lastRowid": new_last, "processed": processed,
+                    "embedded": embedded, "cacheHits": cache_hits, "failed": failed,
+                    "remaining": remaining, "done": done, "yielded": yielded
+                }
+            }))
+        }
+        "reembedMessage" => {
+            let target = params
+                .get("msgId")
+                .and_then(|v| v.as_str())
+                .context("msgId parameter is required and must be a string")?;
+            let eng = engine.context("Embedding engine not available — cannot re-embed message")?;
+            let rowid = crate::fts::db::reembed_message(email_conn, eng, target)?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "msgId": target, "rowid": rowid }
+            }))
+        }
+        "updateMeta" => {
+            let target = params
+                .get("msgId")
+                .and_then(|v| v.as_str())
+                .context("msgId parameter is required and must be a string")?;
+            let found = crate::fts::db::update_meta(email_conn, target, params)?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "msgId": target, "found": found }
+            }))
+        }
+        "restoreBackup" => {
+            let path = params
+                .get("path")
+                .and_then(|v| v.as_str())
+                .context("path parameter is required and must be a string")?;
+            let target = params.get("target").and_then(|v| v.as_str()).unwrap_or("email");
+            let docs = match target {
+                "email" => {
+                    let docs = crate::fts::db::restore_backup(email_db_path, email_conn, Path::new(path))?;
+                    email_reopen.store(true, Ordering::SeqCst);
+                    docs
                 }
+                "memory" => {
+                    let docs = memory_db::memory_restore_backup(memory_db_path, memory_conn, Path::new(path))?;
+                    memory_reopen.store(true, Ordering::SeqCst);
+                    docs
+                }
+                other => bail!("Invalid restore target '{}' (expected \"email\" or \"memory\")", other),
+            };
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "target": target, "docs": docs }
             }))
         }
+        "reindexFtsFromContent" => {
+            let count = crate::fts::db::reindex_fts_from_content(email_conn)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "count": count } }))
+        }
         "memoryIndexBatch" => {
             let rows = params
                 .get("rows")
@@ -612,8 +1373,11 @@ fn handle_write_request(
                 .and_then(|v| v.as_array())
                 .cloned()
                 .unwrap_or_default();
-            let removed = memory_db::memory_remove_batch(memory_conn, &ids)?;
-            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "count": removed } }))
+            let (removed, not_found) = memory_db::memory_remove_batch(memory_conn, &ids)?;
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": { "ok": true, "count": removed.len(), "removedIds": removed, "notFoundIds": not_found }
+            }))
         }
         "memoryClear" => {
             let old_conn = std::mem::replace(memory_conn, Connection::open_in_memory()?);
@@ -622,26 +1386,542 @@ fn handle_write_request(
             memory_reopen.store(true, Ordering::SeqCst);
             Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true } }))
         }
+        "memoryClearSession" => {
+            let session_id = params
+                .get("sessionId")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .context("sessionId parameter is required")?;
+            let count = memory_db::memory_clear_session(memory_conn, session_id)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "count": count } }))
+        }
+        // memoryReindexFts is the explicit name (pairs with reindexFtsFromContent).
+        "memoryReindex" | "memoryReindexFts" => {
+            let count = memory_db::memory_reindex_fts(memory_conn)?;
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "count": count } }))
+        }
         _ => Ok(serde_json::json!({ "id": msg_id, "error": format!("Unknown writer method: {method}") })),
     }
 }
 
+// ============================================================================
+// Main-thread handlers
+// ============================================================================
+
+#[allow(clippy::too_many_arguments)]
+fn handle_main_request(
+    engine: Option<&EmbeddingEngine>,
+    email_db_path: &Path,
+    runtime_stats: &RuntimeStats,
+    synonyms: &SharedSynonyms,
+    streams: &SearchStreams,
+    synonyms_path: Option<&Path>,
+    method: &str,
+    msg_id: &str,
+    params: &Value,
+) -> anyhow::Result<Value> {
+    match method {
+        "purgeStaleFiles" => handle_purge_stale_files(msg_id),
+        "listMethods" => Ok(serde_json::json!({ "id": msg_id, "result": methods::catalog(method_target_name) })),
+        "cancelStream" => {
+            let stream_id = params
+                .get("streamId")
+                .and_then(|v| v.as_str())
+                .context("streamId parameter is required")?;
+            // false = unknown or already finished (its final chunk has been or is being sent).
+            let cancelled = streams.cancel(stream_id);
+            log::info!("cancelStream {}: cancelled={}", stream_id, cancelled);
+            Ok(serde_json::json!({ "id": msg_id, "result": { "cancelled": cancelled } }))
+        }
+        "benchmarkEmbedding" => {
+            let eng = engine.context("Embedding engine not available — cannot benchmark")?;
+            let iterations = params
+                .get("iterations")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(config::embedding::BENCHMARK_DEFAULT_ITERATIONS)
+                .clamp(1, config::embedding::BENCHMARK_MAX_ITERATIONS);
+            let (count, total_ms) = eng.benchmark(iterations)?;
+            let per_embed_ms = if count > 0 { total_ms / count as f64 } else { 0.0 };
+            log::info!(
+                "Embedding benchmark: {} embeddings in {:.1}ms ({:.2}ms each, device={})",
+                count,
+                total_ms,
+                per_embed_ms,
+                eng.device_name()
+            );
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": {
+                    "count": count,
+                    "totalMs": total_ms,
+                    "perEmbedMs": per_embed_ms,
+                    "device": eng.device_name(),
+                    "model": config::embedding::EMBEDDING_MODEL_NAME
+                }
+            }))
+        }
+        "runtimeStats" => {
+            let embeddings = engine.map(|e| e.embed_count()).unwrap_or(0);
+            Ok(serde_json::json!({ "id": msg_id, "result": runtime_stats.snapshot(embeddings) }))
+        }
+        "health" => {
+            // Cheap by design: file metadata only, no DB queries (integrity is the cached result).
+            let db_writable = std::fs::metadata(email_db_path)
+                .map(|m| !m.permissions().readonly())
+                .unwrap_or(false);
+            // Not checked yet (None) doesn't count against readiness; a failed check does.
+            let integrity_ok = runtime_stats.integrity_ok();
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": {
+                    "ok": db_writable && integrity_ok != Some(false),
+                    "initialized": true,
+                    "hasEmbeddings": engine.is_some(),
+                    "dbWritable": db_writable,
+                    "walBytes": crate::fts::db::wal_bytes(email_db_path),
+                    "integrityOk": integrity_ok,
+                    "timezoneInfo": crate::fts::db::timezone_info()
+                }
+            }))
+        }
+        "warmCache" => {
+            // Own short-lived connection: the OS cache it fills is shared with the reader.
+            let start = std::time::Instant::now();
+            let conn = crate::fts::db::open_read_only_connection(email_db_path)?;
+            let tables = crate::fts::db::warm_cache(&conn)?;
+            let elapsed_ms = start.elapsed().as_millis() as u64;
+            log::info!("Cache warmed in {}ms: {}", elapsed_ms, tables);
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "ms": elapsed_ms, "tables": tables } }))
+        }
+        "validateQuery" => {
+            let q = params.get("q").and_then(|v| v.as_str()).unwrap_or("");
+            let use_synonyms = params.get("synonyms").and_then(|v| v.as_bool()).unwrap_or(true);
+            let lookup = synonyms.lock().unwrap().clone();
+            let plan = crate::fts::query::build_fts_match_plan(
+                Some(q),
+                use_synonyms,
+                &lookup,
+                crate::fts::query::auto_wildcard_min_len(params),
+            );
+            let expanded: Vec<_> = plan
+                .expanded_groups
+                .iter()
+                .map(|(token, group)| serde_json::json!({ "token": token, "group": group }))
+                .collect();
+            Ok(serde_json::json!({
+                "id": msg_id,
+                "result": {
+                    "ftsMatch": plan.fts_match,
+                    "tokens": plan.tokens,
+                    "expandedGroups": expanded,
+                    "warnings": plan.warnings(q)
+                }
+            }))
+        }
+        "reloadSynonyms" => {
+            let path = synonyms_path.context("No synonym file configured (pass synonymsPath to init)")?;
+            // Parse fully before swapping, so a broken file leaves the current lookup in place.
+            let (lookup, groups) = SynonymLookup::with_custom_file(path)?;
+            *synonyms.lock().unwrap() = Arc::new(lookup);
+            log::info!("Synonyms reloaded from {}: {} custom groups", path.display(), groups);
+            Ok(serde_json::json!({ "id": msg_id, "result": { "ok": true, "groups": groups } }))
+        }
+        _ => Ok(serde_json::json!({ "id": msg_id, "error": format!("Unknown main-thread method: {method}") })),
+    }
+}
+
 // ============================================================================
 // Shared helpers
 // ============================================================================
 
-fn write_response(stdout: &Arc<Mutex<Stdout>>, msg_id: &str, result: anyhow::Result<Value>) {
+/// Count of writer requests finished so far. The reader waits on it for
+/// `freshness: "latest"`; each read snapshot starts after the wait, so it sees those commits.
+/// Also counts dispatched writes, so a long rebuild can tell when others are queued behind it.
+#[derive(Default)]
+struct WriteProgress {
+    completed: Mutex<u64>,
+    changed: Condvar,
+    dispatched: AtomicU64,
+}
+
+impl WriteProgress {
+    fn mark_dispatched(&self) {
+        self.dispatched.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Writes queued behind the one the writer is handling now.
+    fn queued_behind_current(&self) -> u64 {
+        let done = *self.completed.lock().unwrap();
+        self.dispatched.load(Ordering::SeqCst).saturating_sub(done + 1)
+    }
+
+    fn mark_done(&self) {
+        *self.completed.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+
+    /// Block until at least `seq` writes have finished. Returns false on timeout.
+    fn wait_for(&self, seq: u64, timeout: std::time::Duration) -> bool {
+        let done = self.completed.lock().unwrap();
+        let (done, _) = self.changed.wait_timeout_while(done, timeout, |n| *n < seq).unwrap();
+        *done >= seq
+    }
+}
+
+/// Running `searchStream`s: id allocation and each stream's cancel flag.
+#[derive(Default)]
+struct SearchStreams {
+    next_id: AtomicU64,
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// A `searchStream` refused because `SEARCH_STREAM_MAX_ACTIVE` streams are already running.
+/// Reported as `{ "error": { "code": "tooManyStreams", ... } }`.
+#[derive(Debug)]
+struct TooManyStreams {
+    limit: usize,
+}
+
+impl std::fmt::Display for TooManyStreams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} search streams are already running; cancel one or wait for it to finish", self.limit)
+    }
+}
+
+impl std::error::Error for TooManyStreams {}
+
+impl SearchStreams {
+    fn register(&self) -> anyhow::Result<(String, Arc<AtomicBool>)> {
+        let mut active = self.active.lock().unwrap();
+        let limit = config::sqlite::SEARCH_STREAM_MAX_ACTIVE;
+        if active.len() >= limit {
+            return Err(TooManyStreams { limit }.into());
+        }
+        let id = format!("stream-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let cancel = Arc::new(AtomicBool::new(false));
+        active.insert(id.clone(), Arc::clone(&cancel));
+        Ok((id, cancel))
+    }
+
+    /// Ask a running stream to stop after its current chunk. False if it isn't running.
+    fn cancel(&self, id: &str) -> bool {
+        match self.active.lock().unwrap().get(id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn finish(&self, id: &str) {
+        self.active.lock().unwrap().remove(id);
+    }
+}
+
+/// Handle `searchStream`: validate, answer `{ streamId }`, then page the matches out on a
+/// dedicated thread with its own read-only connection, so the reader stays free for other
+/// requests. The response is written before the thread starts, so no chunk can overtake it.
+fn start_search_stream(
+    streams: &Arc<SearchStreams>,
+    email_db_path: &Path,
+    synonyms: &SharedSynonyms,
+    stdout: &Arc<Mutex<Stdout>>,
+    runtime_stats: &RuntimeStats,
+    msg: &ThreadMessage,
+) {
+    let prepared = (|| -> anyhow::Result<_> {
+        let q = msg.params.get("q").and_then(|v| v.as_str()).context("q parameter is required")?;
+        let chunk_size = match msg.params.get("chunkSize").filter(|v| !v.is_null()) {
+            Some(v) => v
+                .as_i64()
+                .filter(|&n| n >= 1)
+                .context("chunkSize must be a positive integer")?
+                .min(config::sqlite::SEARCH_STREAM_MAX_CHUNK_SIZE),
+            None => config::sqlite::SEARCH_STREAM_CHUNK_SIZE,
+        };
+        let synonyms_now: Arc<SynonymLookup> = Arc::clone(&synonyms.lock().unwrap());
+        let stream = crate::fts::db::SearchStream::new(q, &msg.params, &synonyms_now)?;
+        let conn = crate::fts::db::open_read_only_connection(email_db_path)?;
+        // Last, so a stream is only registered once it will actually run.
+        let (stream_id, cancel) = streams.register()?;
+        Ok((stream, conn, chunk_size, stream_id, cancel))
+    })();
+    let (stream, conn, chunk_size, stream_id, cancel) = match prepared {
+        Ok(p) => p,
+        Err(e) => {
+            RuntimeStats::add(&runtime_stats.errors, 1);
+            write_response(stdout, "main", &msg.method, &msg.id, Err(e));
+            return;
+        }
+    };
+
+    log::info!("searchStream {} started (id: {}, chunkSize={})", stream_id, msg.id, chunk_size);
+    let resp = serde_json::json!({ "id": msg.id, "result": { "streamId": stream_id } });
+    write_response(stdout, "main", &msg.method, &msg.id, Ok(resp));
+
+    let spawned = std::thread::Builder::new().name("search-stream".into()).spawn({
+        let streams = Arc::clone(streams);
+        let stdout = Arc::clone(stdout);
+        let stream_id = stream_id.clone();
+        move || {
+            run_search_stream(stream, &conn, chunk_size, &stream_id, &cancel, &stdout);
+            streams.finish(&stream_id);
+        }
+    });
+    if let Err(e) = spawned {
+        log::error!("Failed to spawn searchStream thread: {:?}", e);
+        streams.finish(&stream_id);
+        let last = serde_json::json!({ "streamId": stream_id, "chunk": [], "done": true, "error": format!("{e}") });
+        write_notification(stdout, "searchResult", last);
+    }
+}
+
+/// Emit `searchResult` notifications until the stream is exhausted, fails or is cancelled.
+/// The last notification always has `done: true` (plus `cancelled` or `error` when relevant).
+fn run_search_stream(
+    mut stream: Option<crate::fts::db::SearchStream>,
+    conn: &Connection,
+    chunk_size: i64,
+    stream_id: &str,
+    cancel: &AtomicBool,
+    stdout: &Arc<Mutex<Stdout>>,
+) {
+    let mut sent: usize = 0;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            log::info!("searchStream {} cancelled after {} results", stream_id, sent);
+            let last = serde_json::json!({ "streamId": stream_id, "chunk": [], "done": true, "cancelled": true });
+            write_notification(stdout, "searchResult", last);
+            return;
+        }
+        // None = the query can't match anything; finish with one empty chunk.
+        let page = match stream.as_mut() {
+            Some(s) => s.next_page(conn, chunk_size),
+            None => Ok(vec![]),
+        };
+        match page {
+            Ok(chunk) => {
+                let done = (chunk.len() as i64) < chunk_size;
+                sent += chunk.len();
+                let chunk = protocol::shape_results(Value::Array(chunk));
+                write_notification(
+                    stdout,
+                    "searchResult",
+                    serde_json::json!({ "streamId": stream_id, "chunk": chunk, "done": done }),
+                );
+                if done {
+                    log::info!("searchStream {} done: {} results", stream_id, sent);
+                    return;
+                }
+            }
+            Err(e) => {
+                log::error!("searchStream {} failed after {} results: {:?}", stream_id, sent, e);
+                let last = serde_json::json!({ "streamId": stream_id, "chunk": [], "done": true, "error": error_value(&e) });
+                write_notification(stdout, "searchResult", last);
+                return;
+            }
+        }
+    }
+}
+
+/// When the main loop last received a request (any kind), as millis since the clock started.
+struct ActivityClock {
+    start: std::time::Instant,
+    last_ms: AtomicU64,
+}
+
+impl ActivityClock {
+    fn new() -> Self {
+        Self { start: std::time::Instant::now(), last_ms: AtomicU64::new(0) }
+    }
+
+    fn touch(&self) {
+        self.last_ms.store(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn idle_for(&self) -> std::time::Duration {
+        let now = self.start.elapsed().as_millis() as u64;
+        std::time::Duration::from_millis(now.saturating_sub(self.last_ms.load(Ordering::Relaxed)))
+    }
+}
+
+/// Interrupts in-flight SQL on a connection once a deadline passes.
+/// Only SQLite work is interruptible; the query-embedding step runs to completion.
+struct QueryWatchdog {
+    cancel: mpsc::Sender<()>,
+    handle: std::thread::JoinHandle<bool>,
+}
+
+impl QueryWatchdog {
+    fn start(conn: &Connection, timeout_ms: u64) -> Self {
+        Self::start_all(vec![conn.get_interrupt_handle()], std::time::Duration::from_millis(timeout_ms))
+    }
+
+    /// Watch several connections at once (a request may use either database).
+    fn start_all(interrupts: Vec<rusqlite::InterruptHandle>, timeout: std::time::Duration) -> Self {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            match cancelled.recv_timeout(timeout) {
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    interrupts.iter().for_each(|i| i.interrupt());
+                    true
+                }
+                _ => false,
+            }
+        });
+        Self { cancel, handle }
+    }
+
+    /// Stop the watchdog. Returns true if it fired (the query was interrupted).
+    fn finish(self) -> bool {
+        let _ = self.cancel.send(());
+        self.handle.join().unwrap_or(false)
+    }
+}
+
+/// Run a reader / writer request under its method's budget (see `MethodTimeouts`). An
+/// overrun is logged and counted; with `cancelOnTimeout` the watchdog also interrupts the
+/// request's SQL, and a request that failed because of it becomes a `MethodTimeout` error.
+/// Requests carrying their own `timeoutMs` (search) are left to that.
+fn run_with_budget(
+    timeouts: &methods::MethodTimeouts,
+    interrupts: Vec<rusqlite::InterruptHandle>,
+    thread: &str,
+    msg: &ThreadMessage,
+    runtime_stats: &RuntimeStats,
+    run: impl FnOnce() -> anyhow::Result<Value>,
+) -> anyhow::Result<Value> {
+    let Some(budget) = timeouts.budget(&msg.method).filter(|_| msg.params.get("timeoutMs").is_none()) else {
+        return run();
+    };
+    let watchdog = timeouts.cancel.then(|| QueryWatchdog::start_all(interrupts, budget));
+    let start = std::time::Instant::now();
+    let resp = run();
+    let fired = watchdog.is_some_and(QueryWatchdog::finish);
+    let elapsed = start.elapsed();
+    if elapsed <= budget && !fired {
+        return resp;
+    }
+    RuntimeStats::add(&runtime_stats.timeouts, 1);
+    log::warn!(
+        "[{}] {} (id: {}) took {}ms, over its {}ms budget{}",
+        thread,
+        msg.method,
+        msg.id,
+        elapsed.as_millis(),
+        budget.as_millis(),
+        if fired { " (interrupted)" } else { "" }
+    );
+    match resp {
+        Err(e) if fired => {
+            log::debug!("[{}] {} interrupted: {:?}", thread, msg.method, e);
+            Err(anyhow::Error::new(methods::MethodTimeout { method: msg.method.clone(), budget }))
+        }
+        other => other,
+    }
+}
+
+/// Serialize a handler result to stdout. Every error log line carries the thread tag,
+/// method, and request id so a wire-level error can be traced back to its handler.
+fn write_response(
+    stdout: &Arc<Mutex<Stdout>>,
+    thread: &str,
+    method: &str,
+    msg_id: &str,
+    result: anyhow::Result<Value>,
+) {
     let value = match result {
         Ok(v) => v,
         Err(e) => {
-            log::error!("Handler error for {}: {:?}", msg_id, e);
-            serde_json::json!({ "id": msg_id, "error": format!("{e}") })
+            log::error!("[{}] Handler error for {} (id: {}): {:?}", thread, method, msg_id, e);
+            error_response(msg_id, &e)
         }
     };
+    let value = protocol::shape_response(value);
 
     let mut out = stdout.lock().unwrap();
     if let Err(e) = native_messaging::write_json(&mut *out, &value) {
-        log::error!("Error writing response for {}: {:?}", msg_id, e);
+        log::error!("[{}] Error writing response for {} (id: {}): {:?}", thread, method, msg_id, e);
+    }
+}
+
+/// The `{ id, error }` envelope for a failed request: structured for the error types the
+/// extension acts on (insufficient disk, timeouts, too many streams), the message string otherwise.
+fn error_response(msg_id: &str, e: &anyhow::Error) -> Value {
+    serde_json::json!({ "id": msg_id, "error": error_value(e) })
+}
+
+/// The `error` value of a response or notification (see `error_response`).
+fn error_value(e: &anyhow::Error) -> Value {
+    if let Some(d) = e.downcast_ref::<disk::InsufficientDisk>() {
+        serde_json::json!({
+            "code": "insufficientDisk",
+            "message": d.to_string(),
+            "availableBytes": d.available,
+            "requiredBytes": d.required
+        })
+    } else if let Some(t) = e.downcast_ref::<methods::MethodTimeout>() {
+        serde_json::json!({ "code": "timeout", "message": t.to_string(), "budgetMs": t.budget.as_millis() as u64 })
+    } else if let Some(s) = e.downcast_ref::<TooManyStreams>() {
+        serde_json::json!({ "code": "tooManyStreams", "message": s.to_string(), "limit": s.limit })
+    } else {
+        Value::String(format!("{e}"))
+    }
+}
+
+/// Process up to `batch_size` rows after `last_rowid` as sub-batches of
+/// `REBUILD_YIELD_CHUNK_ROWS`, each committed by `step(last_rowid, rows)` (returning the new
+/// last rowid, rows processed and whether the rebuild is done). Stops early, with `yielded`
+/// set, when `writes_waiting` reports a write queued behind this one: the writer handles it
+/// next and the caller's next batch call resumes from the returned rowid.
+/// Returns (last_rowid, processed, done, yielded).
+fn run_yielding(
+    last_rowid: i64,
+    batch_size: i64,
+    writes_waiting: impl Fn() -> bool,
+    mut step: impl FnMut(i64, i64) -> anyhow::Result<(i64, i64, bool)>,
+) -> anyhow::Result<(i64, i64, bool, bool)> {
+    let (mut last, mut processed) = (last_rowid, 0);
+    loop {
+        let rows = (batch_size - processed).min(config::dispatch::REBUILD_YIELD_CHUNK_ROWS);
+        let (new_last, n, done) = step(last, rows)?;
+        last = new_last;
+        processed += n;
+        if done || processed >= batch_size {
+            return Ok((last, processed, done, false));
+        }
+        if writes_waiting() {
+            log::info!("Rebuild batch yielding after {} rows: writes queued behind it", processed);
+            return Ok((last, processed, false, true));
+        }
+    }
+}
+
+/// Long CPU-bound writer requests (re-embedding / re-tokenizing the whole index) that run
+/// at lowered thread priority with init `rebuildPriority: "low"`.
+fn is_rebuild_method(method: &str) -> bool {
+    matches!(
+        method,
+        "rebuildEmbeddingsStart" | "rebuildEmbeddingsBatch" | "reembedAllBatch" | "reindexFtsFromContent"
+            | "memoryReindex"
+            | "memoryReindexFts"
+    )
+}
+
+/// `indexBatch` with `ack: "deferred"`: acked with `{ queued: true }` on dispatch, inserted
+/// later by the writer. Failures arrive only as an `indexError` notification carrying the
+/// request id, so the caller can no longer tie an error to the await that sent the batch.
+fn is_deferred_ack(method: &str, params: &Value) -> bool {
+    method == "indexBatch" && params.get("ack").and_then(|v| v.as_str()) == Some("deferred")
+}
+
+/// Write an unsolicited notification (`{ method, params }`, no id) to stdout.
+fn write_notification(stdout: &Arc<Mutex<Stdout>>, method: &str, params: Value) {
+    let value = protocol::shape_notification(method, params);
+    let mut out = stdout.lock().unwrap();
+    if let Err(e) = native_messaging::write_json(&mut *out, &value) {
+        log::error!("Error writing {} notification: {:?}", method, e);
     }
 }
 
@@ -661,7 +1941,11 @@ fn handle_hello(msg_id: &str, params: &Value) -> anyhow::Result<Value> {
     );
 
     // Auto-migrate to user-local if running from system install (non-blocking; parity with Python helper).
-    let migrated = match self_update::auto_migrate_to_user_local() {
+    // Managed installs opt out with TM_DISABLE_AUTO_MIGRATE (wins over the param) or `autoMigrate: false`.
+    let disabled_by_env = std::env::var("TM_DISABLE_AUTO_MIGRATE")
+        .is_ok_and(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "" | "0" | "false"));
+    let auto_migrate = !disabled_by_env && params.get("autoMigrate").and_then(|v| v.as_bool()).unwrap_or(true);
+    let migrated = match self_update::auto_migrate_to_user_local(auto_migrate) {
         Ok(v) => v,
         Err(e) => {
             log::error!("Auto-migration failed (non-fatal): {:?}", e);
@@ -676,7 +1960,7 @@ fn handle_hello(msg_id: &str, params: &Value) -> anyhow::Result<Value> {
     let is_system_install = install_paths::is_in_system_install_dir(&current_path);
     let can_self_update = current_path
         .parent()
-        .map(|p| install_paths::can_write_dir(&p.to_path_buf()))
+        .map(install_paths::can_write_dir)
         .unwrap_or(false);
 
     Ok(serde_json::json!({
@@ -691,11 +1975,25 @@ fn handle_hello(msg_id: &str, params: &Value) -> anyhow::Result<Value> {
             "isSystemInstall": is_system_install,
             "canSelfUpdate": can_self_update,
             "userLocalReady": migrated,
+            "autoMigrate": auto_migrate,
             "addonVersion": addon_version
         }
     }))
 }
 
+/// Remove leftover update backups, staged binaries and partial model downloads.
+/// Answerable before and after init (it touches no database).
+fn handle_purge_stale_files(msg_id: &str) -> anyhow::Result<Value> {
+    let (deleted, bytes_freed) = self_update::purge_stale_files()?;
+    Ok(serde_json::json!({
+        "id": msg_id,
+        "result": {
+            "deleted": deleted.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
+            "bytesFreed": bytes_freed
+        }
+    }))
+}
+
 fn handle_update_check(msg_id: &str, params: &Value) -> anyhow::Result<Value> {
     let target_version = params
         .get("targetVersion")
@@ -743,6 +2041,12 @@ fn handle_update_request(msg_id: &str, params: &Value) -> anyhow::Result<Value>
         sha256_hex,
         platform,
         signature_base64: signature,
+        download_timeout: download_timeout(
+            params,
+            "downloadTimeoutSecs",
+            "TM_UPDATE_DOWNLOAD_TIMEOUT_SECS",
+            config::update::DOWNLOAD_TIMEOUT_SECS,
+        ),
     })?;
 
     Ok(serde_json::json!({
@@ -766,15 +2070,15 @@ fn handle_init(state: &mut DbState, msg_id: &str, params: &Value) -> anyhow::Res
         .unwrap_or("thunderbird@tabmail.ai");
 
     // profilePath override (for testing): use the provided path directly, skip auto-detection
-    let (tb_profile, new_fts_parent) =
+    let (tb_profile, new_fts_parent, profile_reason) =
         if let Some(override_path) = params.get("profilePath").and_then(|v| v.as_str()) {
             let p = PathBuf::from(override_path);
             log::info!("Using explicit profilePath: {}", p.display());
             std::fs::create_dir_all(&p)?;
-            (p.clone(), p)
+            (p.clone(), p, "explicit profilePath".to_string())
         } else {
             // Auto-detect Thunderbird profile
-            let tb_profile = find_thunderbird_profile_dir()?;
+            let (tb_profile, profile_reason) = profile::find_thunderbird_profile_dir()?;
             log::info!("Detected TB profile: {}", tb_profile.display());
 
             // Old location: <profile>/tabmail_fts/
@@ -798,11 +2102,18 @@ fn handle_init(state: &mut DbState, msg_id: &str, params: &Value) -> anyhow::Res
                 log::warn!("Could not create addon data dir: {}", e);
             }
 
-            (tb_profile, new_fts_parent)
+            (tb_profile, new_fts_parent, profile_reason)
         };
 
+    // New vec tables are created with the requested metric; existing databases keep theirs
+    // (a conflicting request fails init rather than mixing metrics).
+    let requested_metric = DistanceMetric::from_param(params.get("distanceMetric"))?;
+    crate::fts::hybrid::set_distance_metric(requested_metric.unwrap_or_default());
+
     // Initialize email FTS DB
     let (db_path, conn) = open_or_create_db(&new_fts_parent)?;
+    let distance_metric = crate::fts::db::resolve_distance_metric(&conn, "messages_vec", requested_metric)?;
+    crate::fts::hybrid::set_distance_metric(distance_metric);
     state.db_path = Some(db_path.clone());
     state.conn = Some(conn);
 
@@ -818,6 +2129,7 @@ fn handle_init(state: &mut DbState, msg_id: &str, params: &Value) -> anyhow::Res
     let fts_subdir = new_fts_parent.join("tabmail_fts");
     std::fs::create_dir_all(&fts_subdir)?;
     let (memory_db_path, memory_conn) = memory_db::open_or_create_memory_db(&fts_subdir)?;
+    crate::fts::db::resolve_distance_metric(&memory_conn, "memory_vec", Some(distance_metric))?;
     state.memory_db_path = Some(memory_db_path.clone());
     state.memory_conn = Some(memory_conn);
 
@@ -835,26 +2147,134 @@ fn handle_init(state: &mut DbState, msg_id: &str, params: &Value) -> anyhow::Res
         memory_docs
     );
 
+    // Opt-in: costs a table scan and up to PRELOAD_EMBED_CACHE_MAX_ENTRIES vectors of memory.
+    let embed_cache_preloaded = if params.get("preloadEmbedCache").and_then(|v| v.as_bool()).unwrap_or(false) {
+        let conn = state.conn.as_ref().context("db connection missing after init")?;
+        match crate::fts::embed_cache::preload(conn) {
+            Ok(n) => n,
+            Err(e) => {
+                log::warn!("embed_cache preload failed (continuing without it): {:?}", e);
+                0
+            }
+        }
+    } else {
+        0
+    };
+
+    // Per-message language tagging is opt-in: it adds detection cost to every indexed row.
+    state.detect_language = params.get("detectLanguage").and_then(|v| v.as_bool()).unwrap_or(false);
+    log::info!("Language detection: {}", if state.detect_language { "enabled" } else { "disabled" });
+
+    if let Some(path) = params.get("synonymsPath").and_then(|v| v.as_str()) {
+        let path = PathBuf::from(path);
+        // A bad file is not fatal at init: keep the built-in groups and let reloadSynonyms retry.
+        match SynonymLookup::with_custom_file(&path) {
+            Ok((lookup, _)) => state.synonyms = lookup,
+            Err(e) => log::warn!("Custom synonyms not loaded (built-ins only): {:?}", e),
+        }
+        state.synonyms_path = Some(path);
+    }
+
+    state.auto_optimize = AutoOptimize::from_param(params.get("autoOptimize"))?;
+    state.wal_shutdown_mode = WalShutdownMode::from_param(params.get("walShutdownMode"))?;
+    if let Some(n) = params.get("resultCacheSize").and_then(|v| v.as_u64()) {
+        state.result_cache_size = n as usize;
+    }
+
+    if let Some(secs) = params.get("idleMaintenanceSecs").and_then(|v| v.as_u64()) {
+        state.idle_maintenance_secs = secs;
+    }
+    state.idle_optimize = params.get("idleOptimize").and_then(|v| v.as_bool()).unwrap_or(true);
+    log::info!(
+        "Idle maintenance after {}s quiet (0 = off), optimize={}",
+        state.idle_maintenance_secs,
+        state.idle_optimize
+    );
+    state.method_timeouts = methods::MethodTimeouts::from_params(params)?;
+
+    match params.get("rebuildPriority").and_then(|v| v.as_str()) {
+        Some("low") => state.low_priority_rebuilds = true,
+        None | Some("normal") => state.low_priority_rebuilds = false,
+        Some(other) => bail!("Invalid rebuildPriority '{}' (expected \"low\" or \"normal\")", other),
+    }
+
+    if let Some(rows) = params.get("walCheckpointRows").and_then(|v| v.as_u64()) {
+        state.wal_checkpoint_rows = rows;
+    }
+    log::info!("Writer WAL checkpoint every {} indexed rows (0 = off)", state.wal_checkpoint_rows);
+
+    // Chunked embedding multiplies embedding cost for long emails, so it's opt-in.
+    let chunk_long_bodies = params.get("chunkLongBodies").and_then(|v| v.as_bool()).unwrap_or(false);
+    crate::embeddings::text_prep::set_chunk_long_bodies(chunk_long_bodies);
+    log::info!("Chunked embedding of long bodies: {}", if chunk_long_bodies { "enabled" } else { "disabled" });
+
+    // cc / bcc in the embedding input. Existing vectors don't change until re-embedded.
+    let embed_cc = params.get("embedCc").and_then(|v| v.as_bool()).unwrap_or(false);
+    let embed_bcc = params.get("embedBcc").and_then(|v| v.as_bool()).unwrap_or(false);
+    crate::embeddings::text_prep::set_embed_recipients(embed_cc, embed_bcc);
+    log::info!("Embedding recipients: cc={} bcc={}", embed_cc, embed_bcc);
+
+    match params.get("stopWords") {
+        Some(Value::Array(words)) => {
+            let words: Vec<String> = words.iter().filter_map(|w| w.as_str().map(str::to_string)).collect();
+            log::info!("Stop words overridden: {} words", words.len());
+            crate::fts::query::set_stop_words(words);
+        }
+        Some(Value::Bool(false)) => {
+            log::info!("Stop-word removal disabled");
+            crate::fts::query::set_stop_words(Vec::new());
+        }
+        _ => {}
+    }
+
+    if let Some(min_len) = params.get("autoWildcardMinLen").and_then(|v| v.as_u64()) {
+        crate::fts::query::set_auto_wildcard_min_len(min_len as usize);
+        log::info!("Auto-wildcard min token length overridden: {}", min_len);
+    }
+
+    // Without sqlite-vec there is nowhere to store or search vectors: skip the engine
+    // entirely so indexing and search take their FTS-only paths.
+    let vec_available = crate::fts::db::vec_available(state.conn.as_ref().context("db connection missing after init")?);
+
     // Initialize embedding engine (lazy model download on first init).
     // If download or load fails, we continue in FTS-only mode (graceful degradation).
-    let has_embeddings = match crate::embeddings::download::ensure_model_files() {
-        Ok(model_dir) => match crate::embeddings::engine::EmbeddingEngine::load(&model_dir) {
-            Ok(engine) => {
-                log::info!("Embedding engine loaded successfully");
-                state.embedding_engine = Some(engine);
-                true
-            }
+    let model_timeout = download_timeout(
+        params,
+        "modelDownloadTimeoutSecs",
+        "TM_MODEL_DOWNLOAD_TIMEOUT_SECS",
+        config::embedding::MODEL_DOWNLOAD_TIMEOUT_SECS,
+    );
+    let has_embeddings = if !vec_available {
+        log::warn!("sqlite-vec unavailable: embedding model not loaded (FTS-only mode)");
+        false
+    } else {
+        match crate::embeddings::download::ensure_model_files(model_timeout) {
+            Ok(model_dir) => match crate::embeddings::engine::EmbeddingEngine::load(&model_dir) {
+                Ok(mut engine) => {
+                    engine.set_prefixes(
+                        params.get("queryPrefix").and_then(|v| v.as_str()),
+                        params.get("documentPrefix").and_then(|v| v.as_str()),
+                    );
+                    log::info!("Embedding engine loaded successfully");
+                    state.embedding_engine = Some(engine);
+                    true
+                }
+                Err(e) => {
+                    log::warn!("Failed to load embedding engine (FTS-only mode): {:?}", e);
+                    false
+                }
+            },
             Err(e) => {
-                log::warn!("Failed to load embedding engine (FTS-only mode): {:?}", e);
+                log::warn!("Failed to download model files (FTS-only mode): {:?}", e);
                 false
             }
-        },
-        Err(e) => {
-            log::warn!("Failed to download model files (FTS-only mode): {:?}", e);
-            false
         }
     };
 
+    // Client sends the newest envelope it understands; responses from here on use the
+    // chosen one (see protocol::shape_response).
+    let protocol_version = protocol::negotiate(params.get("protocolVersion").and_then(|v| v.as_u64()));
+
     Ok(serde_json::json!({
         "id": msg_id,
         "result": {
@@ -866,8 +2286,14 @@ fn handle_init(state: &mut DbState, msg_id: &str, params: &Value) -> anyhow::Res
             "memoryDocs": memory_docs,
             "vfs": "native",
             "tbProfile": tb_profile.to_string_lossy(),
+            "selectedProfileReason": profile_reason,
             "addonDataDir": new_fts_parent.to_string_lossy(),
-            "hasEmbeddings": has_embeddings
+            "hasEmbeddings": has_embeddings,
+            "detectLanguage": state.detect_language,
+            "embedCachePreloaded": embed_cache_preloaded,
+            "vecAvailable": vec_available,
+            "distanceMetric": distance_metric.as_str(),
+            "protocolVersion": protocol_version
         }
     }))
 }
@@ -876,6 +2302,21 @@ fn handle_init(state: &mut DbState, msg_id: &str, params: &Value) -> anyhow::Res
 // Utility functions
 // ============================================================================
 
+/// Resolve a download timeout: request param, then env var, then the config default.
+/// Overrides are clamped to 1..=DOWNLOAD_TIMEOUT_MAX_SECS.
+fn download_timeout(params: &Value, param_key: &str, env_var: &str, default_secs: u64) -> std::time::Duration {
+    let (secs, source) = if let Some(v) = params.get(param_key).and_then(|v| v.as_u64()) {
+        (v, param_key)
+    } else if let Some(v) = std::env::var(env_var).ok().and_then(|s| s.trim().parse::<u64>().ok()) {
+        (v, env_var)
+    } else {
+        (default_secs, "default")
+    };
+    let secs = secs.clamp(1, config::update::DOWNLOAD_TIMEOUT_MAX_SECS);
+    log::info!("Download timeout: {}s (from {})", secs, source);
+    std::time::Duration::from_secs(secs)
+}
+
 fn read_arg_value(args: &[String], key: &str) -> Option<String> {
     args.iter()
         .position(|a| a == key)
@@ -935,52 +2376,6 @@ fn migrate_fts_data(old_fts_dir: &Path, new_fts_dir: &Path) -> anyhow::Result<bo
     Ok(copied > 0)
 }
 
-fn find_thunderbird_profile_dir() -> anyhow::Result<PathBuf> {
-    let system = std::env::consts::OS;
-    let profiles_dir = match system {
-        "macos" => home_dir()?.join("Library/Thunderbird/Profiles"),
-        "linux" => home_dir()?.join(".thunderbird"),
-        "windows" => {
-            let appdata = std::env::var("APPDATA").unwrap_or_default();
-            PathBuf::from(appdata).join("Thunderbird/Profiles")
-        }
-        _ => {
-            log::warn!("Unknown OS: {}, using fallback", system);
-            return Ok(home_dir()?.join(".tabmail"));
-        }
-    };
-
-    if !profiles_dir.exists() {
-        log::warn!(
-            "TB profiles directory not found: {}",
-            profiles_dir.display()
-        );
-        return Ok(home_dir()?.join(".tabmail"));
-    }
-
-    let mut candidates: Vec<PathBuf> = std::fs::read_dir(&profiles_dir)?
-        .filter_map(|e| e.ok())
-        .map(|e| e.path())
-        .filter(|p| p.is_dir())
-        .filter(|p| {
-            p.file_name()
-                .and_then(|s| s.to_str())
-                .map(|s| !s.starts_with('.'))
-                .unwrap_or(false)
-        })
-        .collect();
-
-    if candidates.is_empty() {
-        log::warn!("No profiles found in {}", profiles_dir.display());
-        return Ok(home_dir()?.join(".tabmail"));
-    }
-
-    candidates.sort_by_key(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok());
-    let most_recent = candidates.last().cloned().unwrap();
-    log::info!("Found TB profile: {}", most_recent.display());
-    Ok(most_recent)
-}
-
 fn home_dir() -> anyhow::Result<PathBuf> {
     if let Ok(v) = std::env::var("HOME") {
         if !v.is_empty() {
@@ -994,3 +2389,46 @@ fn home_dir() -> anyhow::Result<PathBuf> {
     }
     bail!("Cannot determine home directory")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_streams_are_capped() {
+        let streams = SearchStreams::default();
+        let ids: Vec<String> =
+            (0..config::sqlite::SEARCH_STREAM_MAX_ACTIVE).map(|_| streams.register().unwrap().0).collect();
+        let err = streams.register().unwrap_err();
+        let resp = error_response("s1", &err);
+        assert_eq!(resp["error"]["code"], "tooManyStreams");
+        assert_eq!(resp["error"]["limit"], config::sqlite::SEARCH_STREAM_MAX_ACTIVE);
+        // A finished stream frees its slot.
+        streams.finish(&ids[0]);
+        assert!(streams.register().is_ok());
+    }
+
+    #[test]
+    fn test_run_with_budget_reports_structured_timeout() {
+        let params = serde_json::json!({ "methodTimeoutsMs": { "search": 20 }, "cancelOnTimeout": true });
+        let timeouts = methods::MethodTimeouts::from_params(&params).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        let msg = ThreadMessage { method: "search".to_string(), id: "7".to_string(), params: serde_json::json!({}), write_seq: 0 };
+        let stats = RuntimeStats::new();
+        let resp = run_with_budget(&timeouts, vec![conn.get_interrupt_handle()], "reader", &msg, &stats, || {
+            // Never finishes on its own: only the watchdog's interrupt ends it.
+            let n: i64 = conn.query_row(
+                "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c",
+                [],
+                |r| r.get(0),
+            )?;
+            Ok(serde_json::json!(n))
+        });
+
+        let envelope = error_response(&msg.id, &resp.unwrap_err());
+        assert_eq!(envelope["id"], "7");
+        assert_eq!(envelope["error"]["code"], "timeout");
+        assert_eq!(envelope["error"]["budgetMs"], 20);
+        assert_eq!(stats.timeouts.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,224 @@
+// methods.rs — Method catalog for `listMethods` (capability discovery by the extension).
+//
+// Routing stays in `classify_method`; this table only adds each method's required params and
+// a one-line description. The test below keeps the two in sync.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde_json::Value;
+
+use crate::config;
+
+pub struct MethodInfo {
+    pub name: &'static str,
+    /// Params the handler rejects the request without.
+    pub required: &'static [&'static str],
+    pub description: &'static str,
+}
+
+const fn m(name: &'static str, required: &'static [&'static str], description: &'static str) -> MethodInfo {
+    MethodInfo { name, required, description }
+}
+
+/// Methods answered before `init` (Phase A). `hello`, `updateCheck` and `updateRequest`
+/// are only available there.
+pub const PRE_INIT: &[&str] = &["hello", "updateCheck", "updateRequest", "purgeStaleFiles", "init", "health", "listMethods"];
+
+pub const METHODS: &[MethodInfo] = &[
+    // Lifecycle and self-update
+    m("hello", &[], "Version handshake; reports host version and install state"),
+    m("updateCheck", &["targetVersion"], "Whether an update to targetVersion is needed and possible"),
+    m("updateRequest", &["targetVersion", "updateUrl", "sha256", "platform", "signature"], "Download, verify and stage a new helper binary"),
+    m("init", &[], "Open the databases and load the embedding model"),
+    m("health", &[], "Cheap liveness and readiness check"),
+    m("listMethods", &[], "This catalog"),
+    m("purgeStaleFiles", &[], "Delete leftover update backups, staged binaries and partial downloads"),
+    // Email reads
+    m("search", &[], "Ranked search (hybrid or FTS-only) over indexed mail"),
+    m("searchMulti", &["queries"], "Run several searches and merge their results"),
+    m("searchStream", &["q"], "Page every match out as searchResult notifications"),
+    m("searchExplainPlan", &[], "SQLite query plan of the search a query would run"),
+    m("searchSuggestQueries", &[], "Suggested queries from frequent indexed terms"),
+    m("subjectSearch", &[], "Search message subjects only"),
+    m("stats", &[], "Document counts and database sizes"),
+    m("statsHistory", &[], "Recorded stats snapshots"),
+    m("getSchema", &[], "Tables, columns and schema version of the email database"),
+    m("filterNewMessages", &[], "Which of the given rows are not indexed yet"),
+    m("getMessageByMsgId", &["msgId"], "Stored fields of one message"),
+    m("findByHeaderMessageId", &["headerMessageId"], "msgIds of a Message-ID in one account (accountId) or all (anyAccount)"),
+    m("queryByDateRange", &["from", "to"], "Messages within a date range"),
+    m("debugSample", &[], "A few recent rows, for debugging"),
+    m("parseIcs", &[], "Parse calendar attachments of a message (msgId) or raw ics text"),
+    m("verifyIntegrity", &[], "Run integrity checks on both databases"),
+    m("backup", &["path"], "Write a consistent copy of the email or memory database"),
+    m("exportDump", &["path"], "Export all messages as an NDJSON dump"),
+    m("exportEmbeddings", &["path"], "Export stored embeddings (NDJSON or binary)"),
+    // Memory reads
+    m("memorySearch", &[], "Search chat memory"),
+    m("memoryStats", &[], "Memory database counts and size"),
+    m("memoryDebugSample", &[], "A few recent memory entries, for debugging"),
+    m("memoryRead", &["timestampMs"], "Memory entries around a timestamp"),
+    m("memorySessionDigest", &["sessionId"], "Overview of one chat session"),
+    // Email writes
+    m("indexBatch", &[], "Index message rows"),
+    m("removeBatch", &[], "Remove messages by msgId"),
+    m("updateMeta", &["msgId"], "Update a message's metadata and flags without reindexing"),
+    m("optimize", &[], "Merge FTS index segments"),
+    m("clear", &[], "Delete and recreate the email database"),
+    m("rebuildEmbeddingsStart", &[], "Clear vectors before an embedding rebuild"),
+    m("rebuildEmbeddingsBatch", &[], "Embed the next batch of messages (or memory entries)"),
+    m("reembedAllStart", &[], "Start re-embedding all messages in place"),
+    m("reembedAllBatch", &[], "Re-embed the next batch of messages"),
+    m("reembedMessage", &["msgId"], "Re-embed one message"),
+    m("reindexFtsFromContent", &[], "Rebuild the FTS index from stored content"),
+    m("restoreBackup", &["path"], "Replace a database with a backup"),
+    m("importDump", &["path"], "Import an NDJSON dump"),
+    m("recordStats", &[], "Record a stats snapshot"),
+    m("reopenConnections", &[], "Reopen all connections after out-of-band file changes"),
+    // Memory writes
+    m("memoryIndexBatch", &[], "Index memory entries"),
+    m("memoryRemoveBatch", &[], "Remove memory entries by memId"),
+    m("memoryClear", &[], "Delete and recreate the memory database"),
+    m("memoryClearSession", &["sessionId"], "Remove every entry of one chat session"),
+    m("memoryReindexFts", &[], "Rebuild the memory FTS index from stored content"),
+    m("memoryReindex", &[], "Alias of memoryReindexFts"),
+    // Main thread
+    m("runtimeStats", &[], "Counters since startup"),
+    m("benchmarkEmbedding", &[], "Time the embedding model"),
+    m("warmCache", &[], "Read the databases into the OS page cache"),
+    m("validateQuery", &[], "Show how a query is parsed, without running it"),
+    m("reloadSynonyms", &[], "Reload the synonym file"),
+    m("cancelStream", &["streamId"], "Stop a running searchStream"),
+];
+
+/// `listMethods` result. `target_of` maps a method to the thread that handles it after
+/// init (`None` for methods only answered before init).
+pub fn catalog(target_of: impl Fn(&str) -> Option<&'static str>) -> Value {
+    let methods: Vec<Value> = METHODS
+        .iter()
+        .map(|info| {
+            serde_json::json!({
+                "name": info.name,
+                "target": target_of(info.name).unwrap_or("preInit"),
+                "preInit": PRE_INIT.contains(&info.name),
+                "requiredParams": info.required,
+                "description": info.description
+            })
+        })
+        .collect();
+    serde_json::json!({ "hostVersion": crate::config::HOST_VERSION, "methods": methods })
+}
+
+/// Time budget per reader / writer method: `METHOD_TIMEOUTS_MS` with init
+/// `methodTimeoutsMs` overrides. `cancel` (init `cancelOnTimeout`) interrupts requests that
+/// overrun instead of only logging them.
+#[derive(Debug, Clone)]
+pub struct MethodTimeouts {
+    budgets_ms: HashMap<String, u64>,
+    pub cancel: bool,
+}
+
+impl Default for MethodTimeouts {
+    fn default() -> Self {
+        let budgets_ms = config::dispatch::METHOD_TIMEOUTS_MS.iter().map(|(m, ms)| (m.to_string(), *ms)).collect();
+        Self { budgets_ms, cancel: false }
+    }
+}
+
+impl MethodTimeouts {
+    pub fn from_params(params: &Value) -> anyhow::Result<Self> {
+        let mut timeouts = Self::default();
+        if let Some(overrides) = params.get("methodTimeoutsMs") {
+            let overrides = overrides.as_object().context("methodTimeoutsMs must be an object keyed by method name")?;
+            for (method, ms) in overrides {
+                if !METHODS.iter().any(|m| m.name == method) {
+                    bail!("methodTimeoutsMs: unknown method '{}'", method);
+                }
+                let ms = ms.as_u64().with_context(|| format!("methodTimeoutsMs.{method} must be a non-negative integer"))?;
+                timeouts.budgets_ms.insert(method.clone(), ms);
+            }
+        }
+        timeouts.cancel = params.get("cancelOnTimeout").and_then(|v| v.as_bool()).unwrap_or(false);
+        Ok(timeouts)
+    }
+
+    /// None when `method` has no budget (unlisted, or overridden with 0).
+    pub fn budget(&self, method: &str) -> Option<Duration> {
+        self.budgets_ms.get(method).filter(|&&ms| ms > 0).map(|&ms| Duration::from_millis(ms))
+    }
+}
+
+/// A request interrupted for overrunning its budget. Reported as
+/// `{ "error": { "code": "timeout", ... } }`.
+#[derive(Debug)]
+pub struct MethodTimeout {
+    pub method: String,
+    pub budget: Duration,
+}
+
+impl fmt::Display for MethodTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} exceeded its {}ms budget and was cancelled", self.method, self.budget.as_millis())
+    }
+}
+
+impl std::error::Error for MethodTimeout {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_timeouts() {
+        for (method, _) in config::dispatch::METHOD_TIMEOUTS_MS {
+            assert!(METHODS.iter().any(|m| m.name == *method), "{method} has a budget but isn't a method");
+        }
+        let defaults = MethodTimeouts::default();
+        assert_eq!(defaults.budget("search"), Some(Duration::from_secs(10)));
+        assert_eq!(defaults.budget("stats"), None);
+        assert!(!defaults.cancel);
+
+        let params = serde_json::json!({
+            "methodTimeoutsMs": { "search": 500, "rebuildEmbeddingsBatch": 0, "stats": 2000 },
+            "cancelOnTimeout": true
+        });
+        let t = MethodTimeouts::from_params(&params).unwrap();
+        assert_eq!(t.budget("search"), Some(Duration::from_millis(500)));
+        assert_eq!(t.budget("rebuildEmbeddingsBatch"), None);
+        assert_eq!(t.budget("stats"), Some(Duration::from_secs(2)));
+        assert_eq!(t.budget("indexBatch"), defaults.budget("indexBatch"));
+        assert!(t.cancel);
+
+        assert!(MethodTimeouts::from_params(&serde_json::json!({ "methodTimeoutsMs": { "serch": 10 } })).is_err());
+        assert!(MethodTimeouts::from_params(&serde_json::json!({ "methodTimeoutsMs": { "search": -1 } })).is_err());
+    }
+
+    #[test]
+    fn test_catalog_matches_routing() {
+        let mut names: Vec<&str> = METHODS.iter().map(|i| i.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), METHODS.len(), "duplicate catalog entry");
+
+        // Every listed method is routed after init, or answered before it.
+        for info in METHODS {
+            assert!(
+                crate::method_target_name(info.name).is_some() || PRE_INIT.contains(&info.name),
+                "{} is listed but never handled",
+                info.name
+            );
+        }
+        for name in PRE_INIT {
+            assert!(METHODS.iter().any(|i| i.name == *name), "{name} missing from catalog");
+        }
+
+        let catalog = catalog(crate::method_target_name);
+        let hello = catalog["methods"].as_array().unwrap().iter().find(|m| m["name"] == "hello").unwrap();
+        assert_eq!(hello["target"], "preInit");
+        let search = catalog["methods"].as_array().unwrap().iter().find(|m| m["name"] == "search").unwrap();
+        assert_eq!((search["target"].as_str(), search["preInit"].as_bool()), (Some("reader"), Some(false)));
+    }
+}
@@ -0,0 +1,157 @@
+// profile.rs — Thunderbird profile auto-detection for `init` (when no profilePath is given).
+//
+// The profiles directory can hold stale, empty or half-created profiles next to the real
+// one, and ~/.thunderbird also holds non-profile directories. Picking by directory mtime
+// alone indexed the wrong profile for multi-profile users, so candidates are ranked and
+// the reason for the pick is returned to the extension (`selectedProfileReason`).
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Lock files Thunderbird holds while a profile is open (`parent.lock` is a dangling
+/// symlink on Linux, a file on Windows; `.parentlock` on macOS).
+const LOCK_FILES: &[&str] = &["parent.lock", ".parentlock", "lock"];
+
+/// Files or directories that show a profile has mail in it.
+const MAIL_DATA_MARKERS: &[&str] = &["Mail", "ImapMail", "global-messages-db.sqlite"];
+
+/// Profile directory to use and a one-line explanation of the choice.
+pub fn find_thunderbird_profile_dir() -> anyhow::Result<(PathBuf, String)> {
+    let system = std::env::consts::OS;
+    let profiles_dir = match system {
+        "macos" => crate::home_dir()?.join("Library/Thunderbird/Profiles"),
+        "linux" => crate::home_dir()?.join(".thunderbird"),
+        "windows" => {
+            let appdata = std::env::var("APPDATA").unwrap_or_default();
+            PathBuf::from(appdata).join("Thunderbird/Profiles")
+        }
+        _ => {
+            log::warn!("Unknown OS: {}, using fallback", system);
+            return Ok((crate::home_dir()?.join(".tabmail"), format!("unknown OS {system}; using ~/.tabmail")));
+        }
+    };
+
+    if !profiles_dir.exists() {
+        log::warn!(
+            "TB profiles directory not found: {}",
+            profiles_dir.display()
+        );
+        return Ok((crate::home_dir()?.join(".tabmail"), "no Thunderbird profiles directory; using ~/.tabmail".into()));
+    }
+
+    match select_profile(&profiles_dir)? {
+        Some((profile, reason)) => {
+            log::info!("Found TB profile: {} ({})", profile.display(), reason);
+            Ok((profile, reason))
+        }
+        None => {
+            log::warn!("No profiles found in {}", profiles_dir.display());
+            Ok((crate::home_dir()?.join(".tabmail"), "no profiles found; using ~/.tabmail".into()))
+        }
+    }
+}
+
+struct Candidate {
+    path: PathBuf,
+    has_prefs: bool,
+    has_mail: bool,
+    locked: bool,
+    last_used: Option<SystemTime>,
+}
+
+/// Pick a profile in `profiles_dir`. Directories without `prefs.js` (not a profile, or never
+/// opened) are skipped unless nothing else is there; profiles with mail data win over
+/// empty ones; ties go to the most recently used (`prefs.js` is rewritten by Thunderbird).
+/// A held lock is only reported: the Thunderbird that started this helper holds one too.
+pub(crate) fn select_profile(profiles_dir: &Path) -> anyhow::Result<Option<(PathBuf, String)>> {
+    let mut candidates: Vec<Candidate> = std::fs::read_dir(profiles_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|s| s.to_str())
+                .map(|s| !s.starts_with('.'))
+                .unwrap_or(false)
+        })
+        .map(|path| {
+            let prefs = path.join("prefs.js");
+            let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+            let last_used = modified(&prefs).or_else(|| modified(&path));
+            Candidate {
+                has_prefs: prefs.is_file(),
+                has_mail: MAIL_DATA_MARKERS.iter().any(|m| path.join(m).exists()),
+                locked: LOCK_FILES.iter().any(|l| std::fs::symlink_metadata(path.join(l)).is_ok()),
+                last_used,
+                path,
+            }
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let total = candidates.len();
+    let with_prefs = candidates.iter().filter(|c| c.has_prefs).count();
+    if with_prefs > 0 {
+        candidates.retain(|c| c.has_prefs);
+    }
+    candidates.sort_by_key(|c| (c.has_mail, c.last_used));
+    let pick = candidates.pop().unwrap();
+
+    let mut reason = if with_prefs == 0 {
+        format!("no directory has prefs.js; most recently modified of {total}")
+    } else if pick.has_mail {
+        format!("most recently used of {} profiles with mail data", candidates.iter().filter(|c| c.has_mail).count() + 1)
+    } else {
+        format!("most recently used of {with_prefs} profiles with prefs.js (none has mail data)")
+    };
+    if with_prefs > 0 && with_prefs < total {
+        reason.push_str(&format!("; skipped {} without prefs.js", total - with_prefs));
+    }
+    reason.push_str(if pick.locked { "; in use (lock file present)" } else { "; not locked" });
+    Ok(Some((pick.path, reason)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_profile_prefers_real_profiles() {
+        let dir = std::env::temp_dir().join(format!("tm_profiles_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let make = |name: &str, files: &[&str], dirs: &[&str]| {
+            let p = dir.join(name);
+            std::fs::create_dir_all(&p).unwrap();
+            for f in files {
+                std::fs::write(p.join(f), "").unwrap();
+            }
+            for d in dirs {
+                std::fs::create_dir_all(p.join(d)).unwrap();
+            }
+        };
+        make("Crash Reports", &[], &[]);
+        make("abc.default", &["prefs.js"], &[]);
+        make("xyz.default-release", &["prefs.js", ".parentlock"], &["ImapMail"]);
+        // Newest but empty.
+        make("new.profile", &["prefs.js"], &[]);
+        let stamp = |name: &str, secs: u64| {
+            let f = std::fs::File::options().write(true).open(dir.join(name).join("prefs.js")).unwrap();
+            f.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs)).unwrap();
+        };
+        stamp("abc.default", 2_000_000_000);
+        stamp("new.profile", 2_000_000_100);
+
+        let (path, reason) = select_profile(&dir).unwrap().unwrap();
+        assert_eq!(path.file_name().unwrap(), "xyz.default-release");
+        assert!(reason.contains("mail data") && reason.contains("skipped 1") && reason.contains("in use"), "{reason}");
+
+        // Without mail data anywhere, the most recently used profile wins.
+        std::fs::remove_dir_all(dir.join("xyz.default-release")).unwrap();
+        let (path, reason) = select_profile(&dir).unwrap().unwrap();
+        assert_eq!(path.file_name().unwrap(), "new.profile");
+        assert!(reason.contains("none has mail data") && reason.ends_with("not locked"), "{reason}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,10 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::config;
+
 #[derive(Debug, Deserialize)]
 pub struct Request {
     pub id: String,
@@ -9,12 +13,15 @@ pub struct Request {
     pub params: Value,
 }
 
+// Wire shapes for reference; handlers build responses with serde_json::json!.
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct ResponseOk<T: Serialize> {
     pub id: String,
     pub result: T,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct ResponseErr {
     pub id: String,
@@ -22,3 +29,142 @@ pub struct ResponseErr {
 }
 
 
+
+// Response envelope version negotiated in init (`protocolVersion`). Process-wide: one
+// client per host process.
+static VERSION: AtomicU32 = AtomicU32::new(config::protocol::DEFAULT_VERSION);
+
+/// Pick the envelope version for a client that supports up to `requested` (absent = v1)
+/// and make it the active one. Returns the chosen version.
+pub fn negotiate(requested: Option<u64>) -> u32 {
+    let chosen = requested
+        .map(|v| v.clamp(1, config::protocol::MAX_VERSION as u64) as u32)
+        .unwrap_or(config::protocol::DEFAULT_VERSION);
+    VERSION.store(chosen, Ordering::Relaxed);
+    chosen
+}
+
+pub fn version() -> u32 {
+    VERSION.load(Ordering::Relaxed)
+}
+
+/// Rewrite a response built in the v1 shape for the negotiated version. v1 is the shape
+/// handlers produce; v2 turns `error` strings into `{ code, message }` objects and replaces
+/// each result object's `rank` (lower is better) with `score` (higher is better: the
+/// `explain` score when present, else the negated rank).
+pub fn shape_response(value: Value) -> Value {
+    shape_for(version(), value)
+}
+
+/// `shape_response` for a bare list of results (searchStream chunks).
+pub fn shape_results(mut results: Value) -> Value {
+    if version() >= 2 {
+        rank_to_score(&mut results);
+    }
+    results
+}
+
+/// A `{ method, params }` notification, with an `error` in its params shaped like a
+/// response's for the negotiated version.
+pub fn shape_notification(method: &str, params: Value) -> Value {
+    notification_for(version(), method, params)
+}
+
+fn notification_for(version: u32, method: &str, mut params: Value) -> Value {
+    if let Some(error) = params.get_mut("error") {
+        *error = shape_error(version, error.take());
+    }
+    serde_json::json!({ "method": method, "params": params })
+}
+
+fn shape_for(version: u32, mut value: Value) -> Value {
+    if version < 2 {
+        return value;
+    }
+    if let Some(obj) = value.as_object_mut() {
+        if let Some(error) = obj.get_mut("error") {
+            *error = shape_error(version, error.take());
+        }
+        if let Some(result) = obj.get_mut("result") {
+            rank_to_score(result);
+        }
+    }
+    value
+}
+
+/// v2 turns an `error` string into `{ code: "error", message }`; structured errors and
+/// v1 errors pass through.
+fn shape_error(version: u32, error: Value) -> Value {
+    match error {
+        Value::String(message) if version >= 2 => serde_json::json!({ "code": "error", "message": message }),
+        other => other,
+    }
+}
+
+fn rank_to_score(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(rank_to_score),
+        Value::Object(obj) => {
+            if let Some(rank) = obj.get("rank").and_then(|r| r.as_f64()) {
+                obj.remove("rank");
+                if !obj.contains_key("score") {
+                    obj.insert("score".to_string(), serde_json::json!(-rank));
+                }
+            }
+            obj.values_mut().for_each(rank_to_score);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape_for_versions() {
+        let err = serde_json::json!({ "id": "1", "error": "boom" });
+        assert_eq!(shape_for(1, err.clone()), err);
+        assert_eq!(shape_for(2, err)["error"], serde_json::json!({ "code": "error", "message": "boom" }));
+
+        let structured = serde_json::json!({ "id": "1", "error": { "code": "timeout", "message": "slow" } });
+        assert_eq!(shape_for(2, structured.clone()), structured);
+
+        let res = serde_json::json!({ "id": "1", "result": [
+            { "uniqueId": "a", "rank": -0.75 },
+            { "uniqueId": "b", "rank": -3.0, "score": 0.75 }
+        ] });
+        assert_eq!(shape_for(1, res.clone()), res);
+        let v2 = shape_for(2, res);
+        assert_eq!(v2["result"][0], serde_json::json!({ "uniqueId": "a", "score": 0.75 }));
+        assert_eq!(v2["result"][1], serde_json::json!({ "uniqueId": "b", "score": 0.75 }));
+
+        // searchMulti nests results per query.
+        let multi = serde_json::json!({ "id": "1", "result": [{ "query": "x", "results": [{ "rank": -1.0 }] }] });
+        assert_eq!(shape_for(2, multi)["result"][0]["results"][0], serde_json::json!({ "score": 1.0 }));
+    }
+
+    #[test]
+    fn test_notification_errors_follow_the_version() {
+        let params = serde_json::json!({ "id": "7", "error": "disk I/O error" });
+        let v1 = notification_for(1, "indexError", params.clone());
+        assert_eq!(v1, serde_json::json!({ "method": "indexError", "params": params }));
+        let v2 = notification_for(2, "indexError", params);
+        assert_eq!(v2["params"]["error"], serde_json::json!({ "code": "error", "message": "disk I/O error" }));
+        assert_eq!(v2["params"]["id"], "7");
+
+        let structured = serde_json::json!({ "streamId": "stream-1", "done": true, "error": { "code": "timeout", "message": "slow" } });
+        assert_eq!(notification_for(2, "searchResult", structured.clone())["params"], structured);
+        let ok = serde_json::json!({ "streamId": "stream-1", "chunk": [], "done": true });
+        assert_eq!(notification_for(2, "searchResult", ok.clone())["params"], ok);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None), config::protocol::DEFAULT_VERSION);
+        assert_eq!(negotiate(Some(0)), 1);
+        assert_eq!(negotiate(Some(99)), config::protocol::MAX_VERSION);
+        assert_eq!(negotiate(Some(1)), 1);
+        assert_eq!(version(), 1);
+    }
+}
@@ -0,0 +1,201 @@
+// result_cache.rs — Reader-side cache of search results for repeated identical queries.
+//
+// The extension re-issues the same search when a panel is reopened. Entries are keyed on the
+// method plus its normalized params and stamped with the write generation of the database
+// they read; the writer bumps that generation after every write, so a hit is only served
+// when nothing was written since the result was computed.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::config;
+
+/// Params that change how a request is served but not its result.
+const NON_RESULT_PARAMS: &[&str] = &["freshness", "timeoutMs"];
+
+/// Which database a method's result depends on, for cacheable reads.
+fn cached_db(method: &str) -> Option<Db> {
+    match method {
+        "search" | "searchMulti" | "subjectSearch" => Some(Db::Email),
+        "memorySearch" => Some(Db::Memory),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Db {
+    Email,
+    Memory,
+}
+
+/// Per-database write counters, bumped by the writer once a write has finished (before its
+/// response goes out, so a client that saw the response never gets a pre-write hit).
+#[derive(Default)]
+pub struct WriteGenerations {
+    email: AtomicU64,
+    memory: AtomicU64,
+}
+
+impl WriteGenerations {
+    /// Writer: `method` finished (successfully or not; a failed write may still have changed rows).
+    pub fn bump(&self, method: &str) {
+        match method {
+            "reopenConnections" => {
+                self.email.fetch_add(1, Ordering::SeqCst);
+                self.memory.fetch_add(1, Ordering::SeqCst);
+            }
+            m if m.starts_with("memory") => {
+                self.memory.fetch_add(1, Ordering::SeqCst);
+            }
+            _ => {
+                self.email.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn current(&self, db: Db) -> u64 {
+        match db {
+            Db::Email => self.email.load(Ordering::SeqCst),
+            Db::Memory => self.memory.load(Ordering::SeqCst),
+        }
+    }
+}
+
+struct Entry {
+    result: Value,
+    generation: u64,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+/// Bounded LRU of `result` values. Capacity 0 disables it.
+pub struct ResultCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<String, Entry>,
+    tick: u64,
+}
+
+/// Where a cacheable request stands before it runs: its key and the generation its result
+/// will be stamped with (read before the query, so a write that lands mid-query invalidates it).
+pub struct Lookup {
+    key: String,
+    generation: u64,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: Duration::from_secs(config::result_cache::TTL_SECS),
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// The cached result for this request, or a `Lookup` to `store` its result under.
+    /// None for methods that aren't cached, or when the cache is disabled.
+    pub fn get(&mut self, generations: &WriteGenerations, method: &str, params: &Value) -> Option<Result<Value, Lookup>> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let db = cached_db(method)?;
+        let lookup = Lookup { key: cache_key(method, params), generation: generations.current(db) };
+        self.tick += 1;
+        let valid = |e: &Entry| e.generation == lookup.generation && e.stored_at.elapsed() < self.ttl;
+        match self.entries.get(&lookup.key).map(valid) {
+            Some(true) => {
+                let e = self.entries.get_mut(&lookup.key).unwrap();
+                e.last_used = self.tick;
+                Some(Ok(e.result.clone()))
+            }
+            Some(false) => {
+                self.entries.remove(&lookup.key);
+                Some(Err(lookup))
+            }
+            None => Some(Err(lookup)),
+        }
+    }
+
+    /// Drop every entry (results computed with settings that have since changed).
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn store(&mut self, lookup: Lookup, result: Value) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&lookup.key) {
+            if let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        let entry = Entry { result, generation: lookup.generation, stored_at: Instant::now(), last_used: self.tick };
+        self.entries.insert(lookup.key, entry);
+    }
+}
+
+/// The method and its params with `q` whitespace-normalized and serving-only params
+/// dropped. serde_json maps are key-sorted, so param order doesn't matter.
+fn cache_key(method: &str, params: &Value) -> String {
+    let mut normalized = params.clone();
+    if let Some(obj) = normalized.as_object_mut() {
+        for p in NON_RESULT_PARAMS {
+            obj.remove(*p);
+        }
+        if let Some(q) = obj.get("q").and_then(|v| v.as_str()) {
+            let q = q.split_whitespace().collect::<Vec<_>>().join(" ");
+            obj.insert("q".to_string(), Value::String(q));
+        }
+    }
+    format!("{method} {normalized}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_result_cache_hits_and_invalidation() {
+        let gens = WriteGenerations::default();
+        let mut cache = ResultCache::new(2);
+        let params = json!({ "q": "quarterly  report", "limit": 10 });
+
+        let Some(Err(lookup)) = cache.get(&gens, "search", &params) else { panic!("expected a miss") };
+        cache.store(lookup, json!(["r1"]));
+        // Same query with different spacing, key order and freshness is a hit.
+        let same = json!({ "limit": 10, "q": " quarterly report ", "freshness": "latest" });
+        assert!(matches!(cache.get(&gens, "search", &same), Some(Ok(v)) if v == json!(["r1"])));
+        assert!(matches!(cache.get(&gens, "search", &json!({ "q": "quarterly report", "limit": 20 })), Some(Err(_))));
+
+        // A memory write leaves email results alone; an email write invalidates them.
+        gens.bump("memoryIndexBatch");
+        assert!(matches!(cache.get(&gens, "search", &params), Some(Ok(_))));
+        gens.bump("indexBatch");
+        assert!(matches!(cache.get(&gens, "search", &params), Some(Err(_))));
+
+        // Uncached methods and a disabled cache never answer.
+        assert!(cache.get(&gens, "stats", &json!({})).is_none());
+        assert!(ResultCache::new(0).get(&gens, "search", &params).is_none());
+    }
+
+    #[test]
+    fn test_result_cache_evicts_least_recently_used() {
+        let gens = WriteGenerations::default();
+        let mut cache = ResultCache::new(2);
+        let q = |s: &str| json!({ "q": s });
+        for s in ["a", "b"] {
+            let Some(Err(lookup)) = cache.get(&gens, "search", &q(s)) else { panic!() };
+            cache.store(lookup, json!(s));
+        }
+        assert!(matches!(cache.get(&gens, "search", &q("a")), Some(Ok(_))));
+        let Some(Err(lookup)) = cache.get(&gens, "search", &q("c")) else { panic!() };
+        cache.store(lookup, json!("c"));
+        assert!(matches!(cache.get(&gens, "search", &q("a")), Some(Ok(_))));
+        assert!(matches!(cache.get(&gens, "search", &q("b")), Some(Err(_))));
+        assert!(matches!(cache.get(&gens, "search", &q("c")), Some(Ok(_))));
+    }
+}
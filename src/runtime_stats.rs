@@ -0,0 +1,175 @@
+// runtime_stats.rs — Process-level counters since startup (exposed via `runtimeStats`).
+//
+// Complements the point-in-time `stats` method: these are cumulative and shared by the
+// main, reader, and writer threads, so everything here is lock-free atomics.
+
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicU8, Ordering};
+
+use serde_json::Value;
+
+use crate::config;
+
+pub struct RuntimeStats {
+    pub started_at_ms: i64,
+    /// `search` + `memorySearch` requests answered successfully.
+    pub searches: AtomicU64,
+    /// Email rows inserted by `indexBatch` (duplicates excluded).
+    pub messages_indexed: AtomicU64,
+    /// Handler calls that returned an error response.
+    pub errors: AtomicU64,
+    /// Reader / writer requests that overran their method budget (interrupted or not).
+    pub timeouts: AtomicU64,
+    /// Cacheable reads answered from / missing the reader's result cache.
+    pub result_cache_hits: AtomicU64,
+    pub result_cache_misses: AtomicU64,
+    /// Outcome of the last `verifyIntegrity`: 0 = never run, 1 = ok, 2 = problems found.
+    integrity: AtomicU8,
+    /// Writer/reader agreement on the email DB.
+    pub reader_consistency: ReaderConsistency,
+}
+
+impl RuntimeStats {
+    pub fn new() -> Self {
+        Self {
+            started_at_ms: chrono::Utc::now().timestamp_millis(),
+            searches: AtomicU64::new(0),
+            messages_indexed: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            result_cache_hits: AtomicU64::new(0),
+            result_cache_misses: AtomicU64::new(0),
+            integrity: AtomicU8::new(0),
+            reader_consistency: ReaderConsistency::default(),
+        }
+    }
+
+    pub fn set_integrity_ok(&self, ok: bool) {
+        self.integrity.store(if ok { 1 } else { 2 }, Ordering::Relaxed);
+    }
+
+    /// Cached `verifyIntegrity` outcome (None if it never ran), reported by `health`.
+    pub fn integrity_ok(&self) -> Option<bool> {
+        match self.integrity.load(Ordering::Relaxed) {
+            0 => None,
+            v => Some(v == 1),
+        }
+    }
+
+    pub fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Snapshot as the `runtimeStats` result object. `embeddings_computed` comes from
+    /// the embedding engine's own counter (0 in FTS-only mode).
+    pub fn snapshot(&self, embeddings_computed: u64) -> Value {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        serde_json::json!({
+            "startedAtMs": self.started_at_ms,
+            "uptimeMs": now_ms - self.started_at_ms,
+            "searches": self.searches.load(Ordering::Relaxed),
+            "messagesIndexed": self.messages_indexed.load(Ordering::Relaxed),
+            "embeddingsComputed": embeddings_computed,
+            "errors": self.errors.load(Ordering::Relaxed),
+            "timeouts": self.timeouts.load(Ordering::Relaxed),
+            "resultCache": {
+                "hits": self.result_cache_hits.load(Ordering::Relaxed),
+                "misses": self.result_cache_misses.load(Ordering::Relaxed)
+            },
+            "readerConsistency": self.reader_consistency.snapshot()
+        })
+    }
+}
+
+/// Safety net for the reader's read-only connection, on top of the writer's reopen flags.
+/// After every email write the writer publishes a cheap high-water mark of what it sees and
+/// bumps a write generation. The reader compares its own mark whenever the generation moved
+/// and reopens its connection once the two have disagreed on several reads in a row.
+#[derive(Default)]
+pub struct ReaderConsistency {
+    generation: AtomicU64,
+    writer_mark: AtomicI64,
+    /// Last generation the reader confirmed it agrees with.
+    reader_generation: AtomicU64,
+    diverged_checks: AtomicU64,
+    reopens: AtomicU64,
+}
+
+impl ReaderConsistency {
+    /// Writer: record the mark seen after a committed write.
+    pub fn publish(&self, mark: i64) {
+        self.writer_mark.store(mark, Ordering::SeqCst);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Reader: the generation to check against, or None if it is already confirmed.
+    pub fn pending(&self) -> Option<u64> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        (generation != self.reader_generation.load(Ordering::Relaxed)).then_some(generation)
+    }
+
+    /// Reader: compare its mark with the writer's as of `generation`. Returns true when
+    /// the reader should reopen its connection.
+    pub fn check(&self, generation: u64, reader_mark: i64) -> bool {
+        // A write landed while the reader was looking: inconclusive, check again next read.
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return false;
+        }
+        if reader_mark == self.writer_mark.load(Ordering::SeqCst) {
+            self.reader_generation.store(generation, Ordering::Relaxed);
+            self.diverged_checks.store(0, Ordering::Relaxed);
+            return false;
+        }
+        self.diverged_checks.fetch_add(1, Ordering::Relaxed) + 1 >= config::dispatch::READER_DIVERGENCE_REOPEN_CHECKS
+    }
+
+    /// Reader: its connection was reopened because of a divergence.
+    pub fn reopened(&self) {
+        self.reopens.fetch_add(1, Ordering::Relaxed);
+        self.diverged_checks.store(0, Ordering::Relaxed);
+    }
+
+    /// `readerConsistency` object of the `runtimeStats` result.
+    pub fn snapshot(&self) -> Value {
+        serde_json::json!({
+            "writeGeneration": self.generation.load(Ordering::SeqCst),
+            "readerGeneration": self.reader_generation.load(Ordering::Relaxed),
+            "divergedChecks": self.diverged_checks.load(Ordering::Relaxed),
+            "reopens": self.reopens.load(Ordering::Relaxed)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_consistency() {
+        let c = ReaderConsistency::default();
+        assert_eq!(c.pending(), None);
+
+        c.publish(10);
+        let generation = c.pending().unwrap();
+        assert!(!c.check(generation, 10));
+        assert_eq!(c.pending(), None);
+
+        // A stale reader (e.g. still on a cleared file) is reopened after repeated disagreement.
+        c.publish(0);
+        let generation = c.pending().unwrap();
+        for _ in 1..config::dispatch::READER_DIVERGENCE_REOPEN_CHECKS {
+            assert!(!c.check(generation, 10));
+        }
+        assert!(c.check(generation, 10));
+        c.reopened();
+        assert!(!c.check(generation, 0));
+        assert_eq!(c.snapshot()["reopens"], 1);
+        assert_eq!(c.snapshot()["divergedChecks"], 0);
+
+        // A write landing mid-check is inconclusive, not a divergence.
+        c.publish(5);
+        let generation = c.pending().unwrap();
+        c.publish(6);
+        assert!(!c.check(generation, 5));
+        assert_eq!(c.snapshot()["divergedChecks"], 0);
+    }
+}
@@ -30,7 +30,10 @@ fn parse_version(v: &str) -> Vec<u64> {
         .collect()
 }
 
-pub fn auto_migrate_to_user_local() -> anyhow::Result<bool> {
+/// Copy a system-installed helper to the user-local dir and point the user manifests at it,
+/// so it can self-update. Returns whether a user-local install is ready. With `enabled`
+/// false (managed installs) nothing is copied or written; only the current state is reported.
+pub fn auto_migrate_to_user_local(enabled: bool) -> anyhow::Result<bool> {
     let current_path = install_paths::current_exe_path();
     let user_dir = install_paths::get_user_install_dir()?;
 
@@ -45,6 +48,10 @@ pub fn auto_migrate_to_user_local() -> anyhow::Result<bool> {
         log::info!("User-local install already exists at {}", user_exe.display());
         return Ok(true);
     }
+    if !enabled {
+        log::info!("Auto-migration disabled, staying at {}", current_path.display());
+        return Ok(false);
+    }
 
     log::info!("🔄 Auto-migrating from system to user-local for auto-updates...");
     log::info!("   From: {}", current_path.display());
@@ -57,18 +64,7 @@ pub fn auto_migrate_to_user_local() -> anyhow::Result<bool> {
     remove_quarantine(&user_exe);
 
     for manifest_dir in install_paths::native_manifest_dirs_user()? {
-        install_paths::ensure_dir(&manifest_dir)?;
-        let manifest_path = manifest_dir.join("tabmail_fts.json");
-        let manifest = serde_json::json!({
-            "name": "tabmail_fts",
-            "description": "TabMail FTS Native Helper (user-local, auto-updating)",
-            "path": user_exe.to_string_lossy(),
-            "type": "stdio",
-            "allowed_extensions": ["thunderbird@tabmail.ai"]
-        });
-        std::fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)
-            .with_context(|| format!("failed writing manifest {}", manifest_path.display()))?;
-
+        let manifest_path = write_user_manifest(&manifest_dir, &user_exe)?;
         log::info!("✅ User-local manifest created: {}", manifest_path.display());
     }
 
@@ -77,10 +73,94 @@ pub fn auto_migrate_to_user_local() -> anyhow::Result<bool> {
     Ok(true)
 }
 
+/// Write the user-local native-messaging manifest pointing at `exe` into `manifest_dir`,
+/// then read it back with `validate_manifest`. Returns the manifest path.
+fn write_user_manifest(manifest_dir: &Path, exe: &Path) -> anyhow::Result<PathBuf> {
+    install_paths::ensure_dir(manifest_dir)?;
+    let manifest_path = manifest_dir.join(format!("{}.json", config::update::MANIFEST_NAME));
+    let manifest = serde_json::json!({
+        "name": config::update::MANIFEST_NAME,
+        "description": "TabMail FTS Native Helper (user-local, auto-updating)",
+        "path": exe.to_string_lossy(),
+        "type": "stdio",
+        "allowed_extensions": [config::update::MANIFEST_EXTENSION_ID]
+    });
+    std::fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)
+        .with_context(|| format!("failed writing manifest {}", manifest_path.display()))?;
+    if let Err(e) = validate_manifest(&manifest_path) {
+        log::error!("❌ Manifest written but invalid: {:?}", e);
+        return Err(e.context(format!("manifest {} is unusable", manifest_path.display())));
+    }
+    Ok(manifest_path)
+}
+
+/// Re-read a native-messaging manifest and check Thunderbird could launch the helper from
+/// it: valid JSON with our host name, `stdio` type and extension id, and a `path` to an
+/// existing executable file. Returns that path.
+pub fn validate_manifest(path: &Path) -> anyhow::Result<PathBuf> {
+    let bytes = std::fs::read(path).with_context(|| format!("failed reading manifest {}", path.display()))?;
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&bytes).with_context(|| format!("manifest {} is not valid JSON", path.display()))?;
+    if manifest["name"] != config::update::MANIFEST_NAME {
+        bail!("manifest name is {}, expected {}", manifest["name"], config::update::MANIFEST_NAME);
+    }
+    if manifest["type"] != "stdio" {
+        bail!("manifest type is {}, expected \"stdio\"", manifest["type"]);
+    }
+    let allowed = manifest["allowed_extensions"].as_array().map(|a| a.iter().any(|e| e == config::update::MANIFEST_EXTENSION_ID));
+    if allowed != Some(true) {
+        bail!("manifest allowed_extensions does not include {}", config::update::MANIFEST_EXTENSION_ID);
+    }
+    let exe = PathBuf::from(manifest["path"].as_str().context("manifest has no \"path\" string")?);
+    if !exe.is_file() {
+        bail!("manifest path {} is not an existing file", exe.display());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if std::fs::metadata(&exe)?.permissions().mode() & 0o111 == 0 {
+            bail!("manifest path {} is not executable", exe.display());
+        }
+    }
+    Ok(exe)
+}
+
+/// `--verify-install` report: the running executable and, for each user manifest location,
+/// whether a manifest is there and valid. `ok` is false if any present manifest is invalid.
+pub fn verify_install() -> anyhow::Result<serde_json::Value> {
+    let mut ok = true;
+    let mut manifests = Vec::new();
+    for manifest_dir in install_paths::native_manifest_dirs_user()? {
+        let path = manifest_dir.join(format!("{}.json", config::update::MANIFEST_NAME));
+        let entry = if !path.exists() {
+            serde_json::json!({ "path": path.to_string_lossy(), "exists": false })
+        } else {
+            match validate_manifest(&path) {
+                Ok(exe) => serde_json::json!({
+                    "path": path.to_string_lossy(), "exists": true, "valid": true, "exe": exe.to_string_lossy()
+                }),
+                Err(e) => {
+                    ok = false;
+                    serde_json::json!({
+                        "path": path.to_string_lossy(), "exists": true, "valid": false, "error": format!("{e:#}")
+                    })
+                }
+            }
+        };
+        manifests.push(entry);
+    }
+    Ok(serde_json::json!({
+        "ok": ok,
+        "exe": install_paths::current_exe_path().to_string_lossy(),
+        "hostVersion": config::HOST_VERSION,
+        "manifests": manifests
+    }))
+}
+
 pub fn update_check(target_version: &str) -> anyhow::Result<(bool, bool)> {
     let needs_update = version_less_than(config::HOST_VERSION, target_version);
     let current = install_paths::current_exe_path();
-    let can_update = current.parent().map(|p| install_paths::can_write_dir(&p.to_path_buf())).unwrap_or(false);
+    let can_update = current.parent().map(install_paths::can_write_dir).unwrap_or(false);
     Ok((needs_update, can_update))
 }
 
@@ -90,6 +170,7 @@ pub struct UpdateParams<'a> {
     pub sha256_hex: &'a str,
     pub platform: &'a str,
     pub signature_base64: &'a str,
+    pub download_timeout: std::time::Duration,
 }
 
 pub struct UpdateResult {
@@ -123,7 +204,7 @@ pub fn update_request(p: UpdateParams<'_>) -> anyhow::Result<UpdateResult> {
 
     let can_write_here = current_path
         .parent()
-        .map(|d| install_paths::can_write_dir(&d.to_path_buf()))
+        .map(install_paths::can_write_dir)
         .unwrap_or(false);
 
     let target_path = if can_write_here {
@@ -140,17 +221,7 @@ pub fn update_request(p: UpdateParams<'_>) -> anyhow::Result<UpdateResult> {
     // If current is system install and cannot write, and target is user-local, ensure manifest exists.
     if install_paths::is_in_system_install_dir(&current_path) && target_path != current_path {
         for manifest_dir in install_paths::native_manifest_dirs_user()? {
-            install_paths::ensure_dir(&manifest_dir)?;
-            let manifest_path = manifest_dir.join("tabmail_fts.json");
-            let manifest = serde_json::json!({
-                "name": "tabmail_fts",
-                "description": "TabMail FTS Native Helper (user-local, auto-updating)",
-                "path": target_path.to_string_lossy(),
-                "type": "stdio",
-                "allowed_extensions": ["thunderbird@tabmail.ai"]
-            });
-            std::fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)
-                .with_context(|| format!("failed writing manifest {}", manifest_path.display()))?;
+            let manifest_path = write_user_manifest(&manifest_dir, &target_path)?;
             log::info!("✅ User-local manifest ensured: {}", manifest_path.display());
         }
     }
@@ -168,7 +239,7 @@ pub fn update_request(p: UpdateParams<'_>) -> anyhow::Result<UpdateResult> {
 
     // Download to staged file first.
     let staged_path = PathBuf::from(format!("{}{}", target_path.display(), config::update::STAGED_SUFFIX));
-    download_to(&staged_path, p.update_url, p.sha256_hex)?;
+    download_to(&staged_path, p.update_url, p.sha256_hex, p.download_timeout)?;
     make_executable(&staged_path)?;
     remove_quarantine(&staged_path);
 
@@ -210,13 +281,18 @@ pub fn update_request(p: UpdateParams<'_>) -> anyhow::Result<UpdateResult> {
     }
 }
 
-fn download_to(dest_path: &Path, url: &str, expected_sha256_hex: &str) -> anyhow::Result<()> {
-    log::info!("Downloading {} to {}", url, dest_path.display());
+fn download_to(
+    dest_path: &Path,
+    url: &str,
+    expected_sha256_hex: &str,
+    timeout: std::time::Duration,
+) -> anyhow::Result<()> {
+    log::info!("Downloading {} to {} (timeout {}s)", url, dest_path.display(), timeout.as_secs());
 
     let resp = ureq::get(url)
-        .timeout(std::time::Duration::from_secs(config::update::DOWNLOAD_TIMEOUT_SECS))
+        .timeout(timeout)
         .call()
-        .context("download failed")?;
+        .with_context(|| format!("download failed (timeout {}s)", timeout.as_secs()))?;
 
     if resp.status() >= 400 {
         bail!("download failed with status {}", resp.status());
@@ -337,7 +413,7 @@ fn remove_quarantine(p: &Path) {
 fn spawn_apply_update_helper(target: &Path, staged: &Path) -> anyhow::Result<()> {
     // On Windows, we can't overwrite a running exe. We spawn a TEMP COPY of ourselves to perform the swap.
     let current = install_paths::current_exe_path();
-    let tmp_dir = std::env::temp_dir().join("tabmail-native-fts-update");
+    let tmp_dir = std::env::temp_dir().join(config::update::APPLY_HELPER_TMP_DIR);
     std::fs::create_dir_all(&tmp_dir)?;
     let helper = tmp_dir.join("fts_helper_apply_update.exe");
     std::fs::copy(&current, &helper).with_context(|| format!("failed copying helper to {}", helper.display()))?;
@@ -354,6 +430,90 @@ fn spawn_apply_update_helper(target: &Path, staged: &Path) -> anyhow::Result<()>
     Ok(())
 }
 
+/// Delete leftovers of past updates and model downloads: binary backups (`*backup`) and
+/// staged binaries (`*.new`) next to the installed helper, interrupted model `.tmp` files,
+/// and the Windows apply-helper temp directory. Only files named after the helper binary
+/// are considered, never the running executable, and nothing younger than
+/// `STALE_FILE_MIN_AGE_SECS`. Returns the removed paths and the bytes freed.
+pub fn purge_stale_files() -> anyhow::Result<(Vec<PathBuf>, u64)> {
+    let min_age = std::time::Duration::from_secs(config::update::STALE_FILE_MIN_AGE_SECS);
+    let current = install_paths::current_exe_path();
+    let mut dirs: Vec<PathBuf> = current.parent().map(Path::to_path_buf).into_iter().collect();
+    if let Ok(user_dir) = install_paths::get_user_install_dir() {
+        if !dirs.contains(&user_dir) {
+            dirs.push(user_dir);
+        }
+    }
+
+    let mut removed = Vec::new();
+    for dir in &dirs {
+        removed.extend(purge_matching(dir, min_age, |p| {
+            p != current.as_path() && is_update_leftover(p, install_paths::exe_file_name())
+        }));
+    }
+    removed.extend(crate::embeddings::download::purge_stale_tmp_files(min_age)?);
+
+    let helper_dir = std::env::temp_dir().join(config::update::APPLY_HELPER_TMP_DIR);
+    if helper_dir.is_dir() && is_older_than(&helper_dir, min_age) {
+        let bytes = purge_matching(&helper_dir, std::time::Duration::ZERO, |_| true)
+            .iter()
+            .map(|(_, b)| b)
+            .sum();
+        match std::fs::remove_dir(&helper_dir) {
+            Ok(()) => removed.push((helper_dir, bytes)),
+            Err(e) => log::warn!("Could not remove {}: {}", helper_dir.display(), e),
+        }
+    }
+
+    let bytes_freed = removed.iter().map(|(_, b)| b).sum();
+    log::info!("Purged {} stale files ({} bytes)", removed.len(), bytes_freed);
+    Ok((removed.into_iter().map(|(p, _)| p).collect(), bytes_freed))
+}
+
+/// A backup or staged copy of the helper binary (`fts_helper.backup`, `fts_helper.exe.backup`,
+/// `fts_helper.exebackup`, `fts_helper.new`, ...). The binary itself never matches.
+fn is_update_leftover(path: &Path, exe_name: &str) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return false };
+    let stem = exe_name.strip_suffix(".exe").unwrap_or(exe_name);
+    name != exe_name
+        && name.starts_with(&format!("{stem}."))
+        && (name.ends_with("backup") || name.ends_with(config::update::STAGED_SUFFIX))
+}
+
+fn is_older_than(path: &Path, min_age: std::time::Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age >= min_age)
+}
+
+/// Remove the regular files in `dir` accepted by `wanted` and at least `min_age` old.
+/// Failures are logged and skipped. Returns (path, size) of each removed file.
+pub(crate) fn purge_matching(
+    dir: &Path,
+    min_age: std::time::Duration,
+    wanted: impl Fn(&Path) -> bool,
+) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![] };
+    let mut removed = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else { continue };
+        if !meta.is_file() || !wanted(&path) || !is_older_than(&path, min_age) {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                log::info!("Removed stale file {} ({} bytes)", path.display(), meta.len());
+                removed.push((path, meta.len()));
+            }
+            Err(e) => log::warn!("Could not remove stale file {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
 pub fn apply_update_mode(target: &Path, staged: &Path) -> anyhow::Result<()> {
     log::info!("Apply-update mode: target={}, staged={}", target.display(), staged.display());
 
@@ -391,4 +551,56 @@ fn try_swap_files(target: &Path, staged: &Path, backup: &Path) -> anyhow::Result
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn test_purge_update_leftovers() {
+        let dir = std::env::temp_dir().join(format!("tm_purge_stale_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["fts_helper", "fts_helper.backup", "fts_helper.new", "fts_helper.exe.backup", "other.backup", "fts_helperx.new"] {
+            std::fs::write(dir.join(name), b"1234").unwrap();
+        }
+
+        // Too young: nothing goes.
+        let young = purge_matching(&dir, std::time::Duration::from_secs(3600), |p| is_update_leftover(p, "fts_helper"));
+        assert!(young.is_empty());
+
+        let mut removed: Vec<String> = purge_matching(&dir, std::time::Duration::ZERO, |p| is_update_leftover(p, "fts_helper"))
+            .into_iter()
+            .map(|(p, bytes)| {
+                assert_eq!(bytes, 4);
+                p.file_name().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        removed.sort();
+        assert_eq!(removed, vec!["fts_helper.backup", "fts_helper.exe.backup", "fts_helper.new"]);
+        assert!(dir.join("fts_helper").exists() && dir.join("other.backup").exists());
+
+        assert!(is_update_leftover(Path::new("fts_helper.exebackup"), "fts_helper.exe"));
+        assert!(!is_update_leftover(Path::new("fts_helper.exe"), "fts_helper.exe"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_manifest() {
+        let dir = std::env::temp_dir().join(format!("tm_manifest_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("fts_helper");
+        std::fs::write(&exe, b"#!/bin/sh\n").unwrap();
+        make_executable(&exe).unwrap();
+
+        let manifest_path = write_user_manifest(&dir, &exe).unwrap();
+        assert_eq!(validate_manifest(&manifest_path).unwrap(), exe);
+
+        // Half-written file (e.g. disk full mid-write).
+        let bytes = std::fs::read(&manifest_path).unwrap();
+        std::fs::write(&manifest_path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(format!("{:#}", validate_manifest(&manifest_path).unwrap_err()).contains("not valid JSON"));
+
+        // Points at a binary that is gone.
+        assert!(write_user_manifest(&dir, &dir.join("missing")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+// thread_priority.rs — Temporarily lower the calling thread's CPU priority.
+//
+// With init `rebuildPriority: "low"`, the writer thread holds a `BackgroundPriority` while it
+// runs embedding rebuilds, so a big backfill yields the CPU to Thunderbird's UI. The priority
+// is only lowered when it can be put back. Platform behaviour:
+// - Linux: the thread's nice value (setpriority on its tid). Lowering a nice value again needs
+//   CAP_SYS_NICE or an RLIMIT_NICE allowance, which desktop users usually lack; without one
+//   the thread is left alone rather than stuck at the lowered priority.
+// - macOS: the thread's QoS class (utility while lowered, default afterwards).
+// - Windows: THREAD_PRIORITY_BELOW_NORMAL, then the previous thread priority.
+// Other platforms: no-op. Failures are logged, never fatal.
+
+/// Lowers the current thread's priority until dropped. Drop it on the same thread.
+pub struct BackgroundPriority {
+    previous: Option<i32>,
+}
+
+impl BackgroundPriority {
+    pub fn enter() -> Self {
+        let previous = match sys::lower() {
+            Ok(prev) => Some(prev),
+            Err(e) => {
+                log::warn!("Could not lower thread priority: {}", e);
+                None
+            }
+        };
+        Self { previous }
+    }
+}
+
+impl Drop for BackgroundPriority {
+    fn drop(&mut self) {
+        let Some(prev) = self.previous else { return };
+        if let Err(e) = sys::restore(prev) {
+            log::warn!("Could not restore thread priority (staying lowered): {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::io;
+
+    use crate::config;
+
+    /// Returns the previous nice value. Fails without changing anything when the previous
+    /// value couldn't be restored afterwards.
+    pub fn lower() -> io::Result<i32> {
+        // SAFETY: getpriority/setpriority on the calling thread's own id; no memory involved.
+        let tid = unsafe { libc::gettid() } as libc::id_t;
+        let previous = unsafe { libc::getpriority(libc::PRIO_PROCESS, tid) };
+        // SAFETY: geteuid has no failure mode; root stands in for CAP_SYS_NICE.
+        if unsafe { libc::geteuid() } != 0 && !restorable(previous, nice_rlimit()?) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("RLIMIT_NICE does not allow restoring nice {previous}; not lowering"),
+            ));
+        }
+        set_nice(tid, previous.max(config::threads::BACKGROUND_NICE))?;
+        Ok(previous)
+    }
+
+    /// Soft RLIMIT_NICE (`None` = unlimited).
+    fn nice_rlimit() -> io::Result<Option<libc::rlim_t>> {
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        // SAFETY: getrlimit writes into the struct we own.
+        if unsafe { libc::getrlimit(libc::RLIMIT_NICE, &mut limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((limit.rlim_cur != libc::RLIM_INFINITY).then_some(limit.rlim_cur))
+    }
+
+    /// Whether an unprivileged thread may set its nice value back to `nice`: RLIMIT_NICE `n`
+    /// allows values down to `20 - n`.
+    pub(super) fn restorable(nice: i32, rlimit: Option<libc::rlim_t>) -> bool {
+        rlimit.is_none_or(|n| 20 - n.min(40) as i32 <= nice)
+    }
+
+    pub fn restore(previous: i32) -> io::Result<()> {
+        // SAFETY: as above.
+        let tid = unsafe { libc::gettid() } as libc::id_t;
+        set_nice(tid, previous)
+    }
+
+    fn set_nice(tid: libc::id_t, nice: i32) -> io::Result<()> {
+        // SAFETY: as above.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::io;
+
+    pub fn lower() -> io::Result<i32> {
+        set_qos(libc::qos_class_t::QOS_CLASS_UTILITY)?;
+        Ok(0)
+    }
+
+    pub fn restore(_previous: i32) -> io::Result<()> {
+        set_qos(libc::qos_class_t::QOS_CLASS_DEFAULT)
+    }
+
+    fn set_qos(class: libc::qos_class_t) -> io::Result<()> {
+        // SAFETY: only changes the calling thread's QoS class.
+        match unsafe { libc::pthread_set_qos_class_self_np(class, 0) } {
+            0 => Ok(()),
+            rc => Err(io::Error::from_raw_os_error(rc)),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::io;
+
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, GetThreadPriority, SetThreadPriority, THREAD_PRIORITY_BELOW_NORMAL,
+    };
+
+    pub fn lower() -> io::Result<i32> {
+        // SAFETY: GetCurrentThread returns a pseudo-handle for the calling thread; nothing to close.
+        let previous = unsafe { GetThreadPriority(GetCurrentThread()) };
+        set(THREAD_PRIORITY_BELOW_NORMAL)?;
+        Ok(previous)
+    }
+
+    pub fn restore(previous: i32) -> io::Result<()> {
+        set(previous)
+    }
+
+    fn set(priority: i32) -> io::Result<()> {
+        // SAFETY: as above.
+        if unsafe { SetThreadPriority(GetCurrentThread(), priority) } != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod sys {
+    pub fn lower() -> std::io::Result<i32> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "thread priority not supported on this platform"))
+    }
+
+    pub fn restore(_previous: i32) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn current_nice() -> i32 {
+        // SAFETY: reads the calling thread's nice value.
+        unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) }
+    }
+
+    #[test]
+    fn test_background_priority_is_restored() {
+        std::thread::spawn(|| {
+            let before = current_nice();
+            let guard = BackgroundPriority::enter();
+            if guard.previous.is_some() {
+                assert!(current_nice() >= before.max(crate::config::threads::BACKGROUND_NICE));
+            } else {
+                assert_eq!(current_nice(), before);
+            }
+            drop(guard);
+            assert_eq!(current_nice(), before);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_restorable_nice() {
+        // The common desktop default: RLIMIT_NICE 0 only allows raising nice back to 20.
+        assert!(!sys::restorable(0, Some(0)));
+        assert!(sys::restorable(0, Some(20)));
+        assert!(!sys::restorable(-5, Some(20)));
+        assert!(sys::restorable(-20, None));
+    }
+}
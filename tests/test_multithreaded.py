@@ -96,13 +96,13 @@ class TestMultiThreadedDispatch(unittest.TestCase):
             stderr=subprocess.PIPE,
         )
 
-    def _hello_and_init(self, proc):
-        """Run hello + init handshake, return hello result."""
+    def _hello_and_init(self, proc, **init_params):
+        """Run hello + init handshake (extra keyword args go into init params), return hello result."""
         _send_message(proc, {"id": "h1", "method": "hello", "params": {"addonVersion": "1.3.0"}})
         hello_resp = _read_message(proc)
         self.assertIn("result", hello_resp, f"hello failed: {hello_resp}")
 
-        _send_message(proc, {"id": "h2", "method": "init", "params": {"profilePath": self.temp_dir}})
+        _send_message(proc, {"id": "h2", "method": "init", "params": {"profilePath": self.temp_dir, **init_params}})
         init_resp = _read_message(proc)
         self.assertIn("result", init_resp, f"init failed: {init_resp}")
         self.assertTrue(init_resp["result"]["ok"])
@@ -471,6 +471,297 @@ class TestMultiThreadedDispatch(unittest.TestCase):
         finally:
             self._stop_process(proc)
 
+    # ------------------------------------------------------------------
+    # Test 9: Repeated init after transition returns alreadyInitialized
+    # ------------------------------------------------------------------
+    def test_second_init_returns_already_initialized(self):
+        """A second init in multi-threaded mode gets a structured error echoing the db paths."""
+        proc = self._start_process()
+        try:
+            self._hello_and_init(proc)
+
+            _send_message(proc, {"id": "i2", "method": "init", "params": {"profilePath": self.temp_dir}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["id"], "i2")
+            self.assertEqual(resp["error"]["code"], "alreadyInitialized")
+            self.assertTrue(resp["error"]["dbPath"])
+            self.assertTrue(resp["error"]["memoryDbPath"])
+
+            # Still serving requests afterwards
+            _send_message(proc, {"id": "i3", "method": "stats", "params": {}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["id"], "i3")
+            self.assertTrue(resp["result"]["ok"])
+
+        finally:
+            self._stop_process(proc)
+
+
+    # ------------------------------------------------------------------
+    # Test 10: reopenConnections returns fresh counts and keeps serving
+    # ------------------------------------------------------------------
+    def test_reopen_connections(self):
+        """Writer reopens both connections; reader follows and sees the same data."""
+        proc = self._start_process()
+        try:
+            self._hello_and_init(proc)
+
+            _send_message(proc, {"id": "r1", "method": "indexBatch", "params": {"rows": [
+                {"msgId": "acct:/INBOX:reopen-1", "subject": "reopen check", "from_": "a@b.com",
+                 "to_": "c@d.com", "body": "body", "dateMs": 1700000000000, "hasAttachments": False}
+            ]}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["result"]["count"], 1)
+
+            _send_message(proc, {"id": "r2", "method": "reopenConnections", "params": {}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["id"], "r2")
+            self.assertTrue(resp["result"]["ok"])
+            self.assertEqual(resp["result"]["docs"], 1)
+            self.assertEqual(resp["result"]["memoryDocs"], 0)
+
+            _send_message(proc, {"id": "r3", "method": "stats", "params": {}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["result"]["docs"], 1)
+
+        finally:
+            self._stop_process(proc)
+
+    # ------------------------------------------------------------------
+    # Test 11: freshness "latest" sees a write sent just before it
+    # ------------------------------------------------------------------
+    def test_freshness_latest_reads_own_writes(self):
+        """A search pipelined behind indexBatch with freshness=latest finds the new row."""
+        proc = self._start_process()
+        try:
+            self._hello_and_init(proc)
+
+            rows = [
+                {"msgId": f"acct:/INBOX:fresh-{i}", "subject": f"freshness probe {i}", "from_": "a@b.com",
+                 "to_": "c@d.com", "body": "zeppelin", "dateMs": 1700000000000 + i, "hasAttachments": False}
+                for i in range(50)
+            ]
+            # Sent back to back without waiting for the write to be answered
+            _send_message(proc, {"id": "f1", "method": "indexBatch", "params": {"rows": rows}})
+            _send_message(proc, {"id": "f2", "method": "search",
+                                 "params": {"q": "zeppelin", "limit": 100, "freshness": "latest"}})
+            responses = _read_all_responses(proc, 2)
+            self.assertEqual(responses["f1"]["result"]["count"], 50)
+            self.assertEqual(len(responses["f2"]["result"]), 50)
+
+            _send_message(proc, {"id": "f3", "method": "search",
+                                 "params": {"q": "zeppelin", "freshness": "eventually"}})
+            resp = _read_message(proc)
+            self.assertIn("error", resp)
+
+        finally:
+            self._stop_process(proc)
+
+    # ------------------------------------------------------------------
+    # Test 12: indexBatch with ack "deferred" is acked before the insert
+    # ------------------------------------------------------------------
+    def test_index_batch_deferred_ack(self):
+        """Deferred indexBatch answers { queued: true } and the rows land afterwards."""
+        proc = self._start_process()
+        try:
+            self._hello_and_init(proc)
+
+            rows = [
+                {"msgId": f"acct:/INBOX:deferred-{i}", "subject": f"deferred {i}", "from_": "a@b.com",
+                 "to_": "c@d.com", "body": "body", "dateMs": 1700000000000 + i, "hasAttachments": False}
+                for i in range(5)
+            ]
+            _send_message(proc, {"id": "d1", "method": "indexBatch", "params": {"rows": rows, "ack": "deferred"}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["id"], "d1")
+            self.assertEqual(resp["result"], {"queued": True})
+
+            _send_message(proc, {"id": "d2", "method": "stats", "params": {"freshness": "latest"}})
+            resp = _read_message(proc)
+            self.assertEqual(resp["id"], "d2")
+            self.assertEqual(resp["result"]["docs"], 5)
+
+        finally:
+            self._stop_process(proc)
+
+    # ------------------------------------------------------------------
+    # Test 13: health before init, after init, and after verifyIntegrity
+    # ------------------------------------------------------------------
+    def test_health(self):
+        """health answers pre-init (not ready) and caches verifyIntegrity's outcome."""
+        proc = self._start_process()
+        try:
+            _send_message(proc, {"id": "h0", "method": "health", "params": {}})
+            resp = _read_message(proc)
+            self.assertFalse(resp["result"]["initialized"])
+            self.assertFalse(resp["result"]["ok"])
+
+            self._hello_and_init(proc)
+
+            _send_message(proc, {"id": "h1", "method": "health", "params": {}})
+            resp = _read_message(proc)
+            self.assertTrue(resp["result"]["initialized"])
+            self.assertTrue(resp["result"]["dbWritable"])
+            self.assertIsNone(resp["result"]["integrityOk"])
+            self.assertIn("walBytes", resp["result"])
+
+            _send_message(proc, {"id": "h2", "method": "verifyIntegrity", "params": {}})
+            resp = _read_message(proc)
+            self.assertTrue(resp["result"]["integrityOk"])
+            self.assertEqual(resp["result"]["problems"], [])
+
+            _send_message(proc, {"id": "h3", "method": "health", "params": {}})
+            resp = _read_message(proc)
+            self.assertTrue(resp["result"]["ok"])
+            self.assertTrue(resp["result"]["integrityOk"])
+
+        finally:
+            self._stop_process(proc)
+
+
+    # ------------------------------------------------------------------
+    # Test 14: a rebuild batch yields to an indexBatch queued behind it
+    # ------------------------------------------------------------------
+    def test_rebuild_batch_yields_to_index_batch(self):
+        """rebuildEmbeddingsBatch stops early when live mail is queued, then resumes."""
+        proc = self._start_process()
+        try:
+            _send_message(proc, {"id": "h1", "method": "hello", "params": {"addonVersion": "1.3.0"}})
+            _read_message(proc)
+            _send_message(proc, {"id": "h2", "method": "init", "params": {"profilePath": self.temp_dir}})
+            init_resp = _read_message(proc, timeout_seconds=300)
+            if not init_resp["result"].get("hasEmbeddings"):
+                self.skipTest("embedding model not available")
+
+            rows = [
+                {"msgId": f"acct:/INBOX:rebuild-{i}", "subject": f"rebuild probe {i}", "from_": "a@b.com",
+                 "to_": "c@d.com", "body": "quarterly planning notes " * 20, "dateMs": 1700000000000 + i,
+                 "hasAttachments": False}
+                for i in range(400)
+            ]
+            _send_message(proc, {"id": "r0", "method": "indexBatch", "params": {"rows": rows}})
+            self.assertEqual(_read_message(proc, timeout_seconds=300)["result"]["count"], 400)
+            _send_message(proc, {"id": "r1", "method": "rebuildEmbeddingsStart", "params": {}})
+            self.assertTrue(_read_message(proc)["result"]["ok"])
+
+            # Live mail arrives while one large rebuild batch is running.
+            live = [{"msgId": "acct:/INBOX:live-1", "subject": "live mail", "from_": "x@y.com", "to_": "c@d.com",
+                     "body": "just arrived", "dateMs": 1700000001000, "hasAttachments": False}]
+            _send_message(proc, {"id": "r2", "method": "rebuildEmbeddingsBatch", "params": {"batchSize": 400}})
+            _send_message(proc, {"id": "r3", "method": "indexBatch", "params": {"rows": live}})
+            responses = _read_all_responses(proc, 2, timeout_seconds=300)
+            batch = responses["r2"]["result"]
+            self.assertTrue(batch["yielded"])
+            self.assertFalse(batch["done"])
+            self.assertLess(batch["processed"], 400)
+            self.assertEqual(responses["r3"]["result"]["count"], 1)
+
+            # The caller's usual loop resumes from lastRowid and finishes the rebuild.
+            last_rowid, processed, done = batch["lastRowid"], batch["processed"], False
+            while not done:
+                _send_message(proc, {"id": "r4", "method": "rebuildEmbeddingsBatch",
+                                     "params": {"lastRowid": last_rowid, "batchSize": 400}})
+                result = _read_message(proc, timeout_seconds=300)["result"]
+                last_rowid, done = result["lastRowid"], result["done"]
+                processed += result["processed"]
+            self.assertEqual(processed, 401)
+
+        finally:
+            self._stop_process(proc)
+
+    # ------------------------------------------------------------------
+    # Test 15: repeated searches hit the result cache until a write lands
+    # ------------------------------------------------------------------
+    def test_result_cache_invalidated_by_index_batch(self):
+        """An identical repeated search is served from cache; indexBatch invalidates it."""
+        proc = self._start_process()
+        try:
+            self._hello_and_init(proc)
+
+            def row(i):
+                return {"msgId": f"acct:/INBOX:cache-{i}", "subject": f"cache probe {i}", "from_": "a@b.com",
+                        "to_": "c@d.com", "body": "marzipan", "dateMs": 1700000000000 + i, "hasAttachments": False}
+
+            def cache_stats():
+                _send_message(proc, {"id": "rs", "method": "runtimeStats", "params": {}})
+                return _read_message(proc)["result"]["resultCache"]
+
+            _send_message(proc, {"id": "c0", "method": "indexBatch", "params": {"rows": [row(0), row(1)]}})
+            self.assertEqual(_read_message(proc)["result"]["count"], 2)
+
+            _send_message(proc, {"id": "c1", "method": "search", "params": {"q": "marzipan", "limit": 10}})
+            first = _read_message(proc)
+            _send_message(proc, {"id": "c2", "method": "search", "params": {"limit": 10, "q": " marzipan "}})
+            second = _read_message(proc)
+            self.assertEqual(second["id"], "c2")
+            self.assertEqual(second["result"], first["result"])
+            self.assertEqual(len(second["result"]), 2)
+            self.assertEqual(cache_stats(), {"hits": 1, "misses": 1})
+
+            _send_message(proc, {"id": "c3", "method": "indexBatch", "params": {"rows": [row(2)]}})
+            self.assertEqual(_read_message(proc)["result"]["count"], 1)
+            _send_message(proc, {"id": "c4", "method": "search", "params": {"q": "marzipan", "limit": 10}})
+            self.assertEqual(len(_read_message(proc)["result"]), 3)
+            self.assertEqual(cache_stats(), {"hits": 1, "misses": 2})
+
+        finally:
+            self._stop_process(proc)
+
+    # ------------------------------------------------------------------
+    # Test 16: a read pipelined right behind clear / memoryClear sees the fresh DB
+    # ------------------------------------------------------------------
+    def test_read_pipelined_after_clear_sees_fresh_db(self):
+        """The reader reopens before serving a read queued behind clear, not stale pre-clear rows."""
+        proc = self._start_process()
+        try:
+            # No result cache: an empty result must come from the reopened connection.
+            self._hello_and_init(proc, resultCacheSize=0)
+
+            rows = [
+                {"msgId": f"acct:/INBOX:stale-{i}", "subject": f"stale probe {i}", "from_": "a@b.com",
+                 "to_": "c@d.com", "body": "quokka", "dateMs": 1700000000000 + i, "hasAttachments": False}
+                for i in range(5)
+            ]
+            mem_rows = [
+                {"memId": f"stale-mem-{i}", "role": "user", "content": f"quokka memory {i}",
+                 "sessionId": "stale-session", "dateMs": 1700000000000 + i, "turnIndex": i}
+                for i in range(3)
+            ]
+            _send_message(proc, {"id": "s1", "method": "indexBatch", "params": {"rows": rows}})
+            self.assertEqual(_read_message(proc)["result"]["count"], 5)
+            _send_message(proc, {"id": "s2", "method": "memoryIndexBatch", "params": {"rows": mem_rows}})
+            self.assertEqual(_read_message(proc)["result"]["count"], 3)
+
+            # The reader's connections have served the old files before the clear.
+            _send_message(proc, {"id": "s3", "method": "search", "params": {"q": "quokka", "limit": 10}})
+            self.assertEqual(len(_read_message(proc)["result"]), 5)
+            _send_message(proc, {"id": "s4", "method": "memoryStats", "params": {}})
+            self.assertEqual(_read_message(proc)["result"]["docs"], 3)
+
+            # Sent back to back, without waiting for the clears to be answered.
+            _send_message(proc, {"id": "s5", "method": "clear", "params": {}})
+            _send_message(proc, {"id": "s6", "method": "search",
+                                 "params": {"q": "quokka", "limit": 10, "freshness": "latest"}})
+            _send_message(proc, {"id": "s7", "method": "memoryClear", "params": {}})
+            _send_message(proc, {"id": "s8", "method": "memoryStats", "params": {"freshness": "latest"}})
+            responses = _read_all_responses(proc, 4)
+            self.assertTrue(responses["s5"]["result"]["ok"], responses["s5"])
+            self.assertTrue(responses["s7"]["result"]["ok"], responses["s7"])
+            self.assertEqual(responses["s6"]["result"], [], "search served stale pre-clear rows")
+            self.assertEqual(responses["s8"]["result"]["docs"], 0, "memoryStats served the pre-clear DB")
+
+            # The reopened connections keep following the writer.
+            _send_message(proc, {"id": "s9", "method": "indexBatch", "params": {"rows": rows[:1]}})
+            self.assertEqual(_read_message(proc)["result"]["count"], 1)
+            _send_message(proc, {"id": "s10", "method": "search", "params": {"q": "quokka", "limit": 10}})
+            self.assertEqual(len(_read_message(proc)["result"]), 1)
+
+            _send_message(proc, {"id": "s11", "method": "runtimeStats", "params": {}})
+            consistency = _read_message(proc)["result"]["readerConsistency"]
+            self.assertEqual(consistency["reopens"], 0, "reopen flag missed; divergence backstop had to reopen")
+
+        finally:
+            self._stop_process(proc)
 
 if __name__ == "__main__":
     unittest.main(verbosity=2)